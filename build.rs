@@ -0,0 +1,17 @@
+// With the ffi feature, tests/ffi/roundtrip.c is compiled against include/tetrs.h and
+// linked into the tests only, for tests/ffi.rs to call.
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "ffi")]
+    {
+        println!("cargo:rerun-if-changed=tests/ffi/roundtrip.c");
+        println!("cargo:rerun-if-changed=include/tetrs.h");
+        cc::Build::new()
+            .file("tests/ffi/roundtrip.c")
+            .include("include")
+            .cargo_metadata(false)
+            .compile("tetrs_roundtrip");
+        let out_dir = std::env::var("OUT_DIR").unwrap();
+        println!("cargo:rustc-link-arg-tests={out_dir}/libtetrs_roundtrip.a");
+    }
+}
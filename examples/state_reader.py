@@ -0,0 +1,38 @@
+#!/usr/bin/env python3
+# Reads the game state served by `tetris --state-socket PATH` and prints a line of
+# stats for every snapshot. This is the contract for overlays: one JSON object per
+# line, fields as below. Readers that can't keep up just miss snapshots.
+#
+#     python3 examples/state_reader.py /tmp/tetrs.sock
+
+import json
+import socket
+import sys
+
+
+def main():
+    if len(sys.argv) != 2:
+        sys.exit(f"usage: {sys.argv[0]} SOCKET")
+    with socket.socket(socket.AF_UNIX, socket.SOCK_STREAM) as sock:
+        sock.connect(sys.argv[1])
+        for line in sock.makefile("r", encoding="utf-8"):
+            state = json.loads(line)
+            # The falling piece isn't on the board, it's in piece_cells
+            rows = state["board"].split("\n")
+            height = next(
+                (len(rows) - y for y, row in enumerate(rows) if row.strip(".")), 0
+            )
+            score = state["score"]
+            print(
+                f"{state['mode']:<8} points {score['points']:>7}  lines {score['lines']:>3}"
+                f"  level {score['level']:>2}  b2b {'yes' if score['back_to_back'] else 'no ':<3}"
+                f"  piece {state['piece']}  hold {state['hold'] or '-'}"
+                f"  next {''.join(state['next'])}  stack {height:>2}"
+                + ("  paused" if state["paused"] else "")
+                + ("  over" if state["over"] else ""),
+                flush=True,
+            )
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,152 @@
+/* The C ABI of the tetrs engine, made from src/ffi.rs by cbindgen. Don't edit. */
+
+#ifndef TETRS_H
+#define TETRS_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Actions for tetrs_game_input()
+ */
+#define TETRS_MOVE_LEFT 0
+
+#define TETRS_MOVE_RIGHT 1
+
+#define TETRS_SOFT_DROP 2
+
+#define TETRS_HARD_DROP 3
+
+#define TETRS_ROTATE_CW 4
+
+#define TETRS_ROTATE_CCW 5
+
+#define TETRS_HOLD 6
+
+/**
+ * What an input or some time did, see Outcome
+ */
+#define TETRS_BLOCKED 0
+
+#define TETRS_MOVED 1
+
+#define TETRS_LOCKED 2
+
+#define TETRS_GAME_OVER 3
+
+/**
+ * Not an action above
+ */
+#define TETRS_UNKNOWN_ACTION -1
+
+/**
+ * Modes for TetrsConfig
+ */
+#define TETRS_MARATHON 0
+
+#define TETRS_SPRINT 1
+
+#define TETRS_ULTRA 2
+
+#define TETRS_ADAPTIVE 3
+
+/**
+ * Opaque to C
+ */
+typedef struct TetrsGame TetrsGame;
+
+typedef struct TetrsConfig {
+  /**
+   * At least 4 by 4, the terminal game plays on 10 by 20
+   */
+  uint32_t width;
+  uint32_t height;
+  /**
+   * The same seed deals the same tetrominos
+   */
+  uint64_t seed;
+  /**
+   * One of TETRS_MARATHON, TETRS_SPRINT, TETRS_ULTRA or TETRS_ADAPTIVE
+   */
+  uint32_t mode;
+} TetrsConfig;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Starts a game, or returns NULL if the config is NULL or out of range. The game
+ * is the caller's to free with tetrs_game_free().
+ *
+ * # Safety
+ *
+ * `config` is NULL or points to a TetrsConfig.
+ */
+struct TetrsGame *tetrs_game_new(const struct TetrsConfig *config);
+
+/**
+ * Applies one of the TETRS_ actions, returns a TETRS_ outcome.
+ *
+ * # Safety
+ *
+ * `game` comes from tetrs_game_new() and hasn't been freed.
+ */
+int32_t tetrs_game_input(struct TetrsGame *game, uint32_t action);
+
+/**
+ * Runs gravity and the lock delay on by `ms` milliseconds, returns the last
+ * outcome that wasn't TETRS_BLOCKED, if anything happened.
+ *
+ * # Safety
+ *
+ * `game` comes from tetrs_game_new() and hasn't been freed.
+ */
+int32_t tetrs_game_tick(struct TetrsGame *game, uint32_t ms);
+
+/**
+ * Copies the board into `out`, width times height bytes row by row from the top:
+ * '.' for an empty cell, the tetromino's letter for a locked one, the lower case
+ * letter for the falling one. Returns the number of bytes the board takes, and
+ * copies nothing if `len` is less, so a NULL `out` asks for the size.
+ *
+ * # Safety
+ *
+ * `game` comes from tetrs_game_new() and hasn't been freed, `out` is NULL or has
+ * room for `len` bytes.
+ */
+size_t tetrs_game_board(const struct TetrsGame *game, uint8_t *out, size_t len);
+
+/**
+ * Board width in cells, the length of a row in tetrs_game_board().
+ *
+ * # Safety
+ *
+ * `game` comes from tetrs_game_new() and hasn't been freed.
+ */
+uint32_t tetrs_game_width(const struct TetrsGame *game);
+
+/**
+ * # Safety
+ *
+ * `game` comes from tetrs_game_new() and hasn't been freed.
+ */
+uint32_t tetrs_game_points(const struct TetrsGame *game);
+
+/**
+ * Ends the game, NULL is ignored.
+ *
+ * # Safety
+ *
+ * `game` is NULL or comes from tetrs_game_new() and hasn't been freed yet.
+ */
+void tetrs_game_free(struct TetrsGame *game);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* TETRS_H */
@@ -0,0 +1,306 @@
+// The replays saved to the replays directory, as the Replays entry of the menu lists
+// them: when each was saved, its mode, what it came to and the name it was given.
+// Only the header of each file is read for the list, the inputs are read once one
+// is picked to be played.
+//
+//     up, down       picks one, page up and page down a screenful
+//     enter          plays it back
+//     s              sorts by date, newest first, or by result, best first
+//     d              deletes it, once y confirms
+//     esc, q         back to the menu
+//
+// A file that can't be read as a replay lists as "(unreadable)", it can still go.
+
+use std::{
+    cmp::Ordering,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::{Instant, UNIX_EPOCH},
+};
+
+use crossterm::event::{Event, KeyCode, KeyEventKind};
+use tui::{backend::Backend, widgets::ListState, Terminal};
+
+use crate::{
+    replay::{self, Header, Replay},
+    ui::{self, Playfield},
+};
+
+pub struct Listing {
+    pub path: PathBuf,
+    // None when the file isn't a replay this build reads
+    pub header: Option<Header>,
+    // When it was saved, or last written for replays that don't say
+    pub date: u64,
+}
+
+impl Listing {
+    fn read(path: PathBuf) -> Self {
+        let header = Replay::header(&path).ok();
+        let modified = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |time| time.as_secs());
+        let date = header.as_ref().and_then(|header| header.saved);
+        Self {
+            date: date.unwrap_or(modified),
+            path,
+            header,
+        }
+    }
+
+    pub fn file(&self) -> String {
+        self.path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    // One line of the list
+    pub fn line(&self) -> String {
+        let Some(header) = &self.header else {
+            return format!("{} (unreadable)", self.file());
+        };
+        let result = match header.result {
+            Some(result) if header.timed() => ui::format_time(result.time()),
+            Some(result) => format!("{} pts", result.points),
+            None => "-".to_string(),
+        };
+        format!(
+            "{} {:<10.10} {:>9} {}",
+            replay::date(self.date),
+            header.mode_name(),
+            result,
+            header.name.as_deref().unwrap_or("")
+        )
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Sort {
+    #[default]
+    Date,
+    Result,
+}
+
+impl Sort {
+    fn name(self) -> &'static str {
+        match self {
+            Sort::Date => "date",
+            Sort::Result => "result",
+        }
+    }
+}
+
+// Every file in the directory, none when there's no directory yet
+pub fn list(dir: &Path) -> Vec<Listing> {
+    let Ok(files) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    files
+        .filter_map(|file| file.ok())
+        .filter(|file| file.file_type().is_ok_and(|kind| kind.is_file()))
+        .map(|file| Listing::read(file.path()))
+        .collect()
+}
+
+// Finished runs with a line goal come first fastest first, then the rest by points.
+// The unreadable ones go last whatever the order.
+pub fn sort(listings: &mut [Listing], sort: Sort) {
+    listings.sort_by(|a, b| match (&a.header, &b.header) {
+        (None, None) => a.path.cmp(&b.path),
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(first), Some(second)) => match sort {
+            Sort::Date => b.date.cmp(&a.date),
+            Sort::Result => compare_results(first, second),
+        },
+    });
+}
+
+fn compare_results(a: &Header, b: &Header) -> Ordering {
+    let (Some(first), Some(second)) = (a.result, b.result) else {
+        return b.result.is_some().cmp(&a.result.is_some());
+    };
+    match (a.timed(), b.timed()) {
+        (true, true) => first.time_ms.cmp(&second.time_ms),
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        (false, false) => second.points.cmp(&first.points),
+    }
+}
+
+// Returns the replay picked to be played back, None to go back to the menu
+pub fn run<B: Backend>(
+    terminal: &mut Terminal<B>,
+    playfield: &mut Playfield,
+    dir: Option<&Path>,
+    rx_input: &mpsc::Receiver<(Event, Instant)>,
+) -> io::Result<Option<Replay>> {
+    let mut order = Sort::default();
+    let mut listings = dir.map(list).unwrap_or_default();
+    sort(&mut listings, order);
+    let mut state = ListState::default();
+    state.select((!listings.is_empty()).then_some(0));
+    // Waiting for y to delete the selected one
+    let mut deleting = false;
+    // What went wrong with the last thing asked for
+    let mut problem: Option<String> = None;
+    loop {
+        let lines: Vec<String> = listings.iter().map(Listing::line).collect();
+        let status = match (state.selected(), deleting, &problem) {
+            (Some(selected), true, _) => {
+                format!("delete {}? [y/n]", listings[selected].file())
+            }
+            (_, _, Some(problem)) => problem.clone(),
+            (_, _, None) => format!("enter play  s sort ({})  d delete  esc back", order.name()),
+        };
+        playfield.draw_replays(terminal, &lines, &mut state, &status);
+        let Ok((event, _)) = rx_input.recv() else {
+            return Ok(None);
+        };
+        let key = match event {
+            Event::Key(key) if key.kind != KeyEventKind::Release => key,
+            Event::Resize(width, height) => {
+                crate::resize(terminal, playfield, width, height)?;
+                continue;
+            }
+            _ => continue,
+        };
+        problem = None;
+        let selected = state.selected();
+        if deleting {
+            deleting = false;
+            if let (KeyCode::Char('y'), Some(index)) = (key.code, selected) {
+                match fs::remove_file(&listings[index].path) {
+                    Ok(()) => {
+                        listings.remove(index);
+                        let last = listings.len().checked_sub(1);
+                        state.select(last.map(|last| index.min(last)));
+                    }
+                    Err(err) => problem = Some(format!("can't delete it: {err}")),
+                }
+            }
+            continue;
+        }
+        // The rows of the list, inside its border and above the status line
+        let page = usize::from(playfield.bounds().height.saturating_sub(3)).max(1);
+        let last = listings.len().saturating_sub(1);
+        let moved = |by: isize| selected.map(|index| index.saturating_add_signed(by).min(last));
+        match key.code {
+            KeyCode::Up => state.select(moved(-1)),
+            KeyCode::Down => state.select(moved(1)),
+            KeyCode::PageUp => state.select(moved(-(page as isize))),
+            KeyCode::PageDown => state.select(moved(page as isize)),
+            KeyCode::Char('s') => {
+                order = match order {
+                    Sort::Date => Sort::Result,
+                    Sort::Result => Sort::Date,
+                };
+                let path = selected.map(|index| listings[index].path.clone());
+                sort(&mut listings, order);
+                // The one picked stays picked
+                state.select(path.and_then(|path| listings.iter().position(|l| l.path == path)));
+            }
+            KeyCode::Char('d') if selected.is_some() => deleting = true,
+            KeyCode::Enter => {
+                if let Some(index) = selected {
+                    match Replay::load(&listings[index].path) {
+                        Ok(replay) => return Ok(Some(replay)),
+                        Err(err) => problem = Some(format!("can't play it: {err}")),
+                    }
+                }
+            }
+            KeyCode::Esc | KeyCode::Char('q') => return Ok(None),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process, time::Duration};
+
+    use crossterm::event::KeyEventKind;
+    use tetris::state::GameMode;
+
+    use super::*;
+    use crate::{
+        input::{Action, RepeatSettings},
+        session::Session,
+    };
+
+    // A directory of its own for every test, removed up front in case an earlier run
+    // left it
+    fn scratch_dir(test: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("tetrs-browser-{test}-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // A marathon of `drops` hard drops, saved as the game over screen would
+    fn save(dir: &Path, drops: u64, name: &str, saved: u64) {
+        let repeat = RepeatSettings::default();
+        let mut replay = Replay::new(5, 1, GameMode::Marathon.ruleset(), repeat);
+        let mut session = Session::new(replay.game(), repeat);
+        for drop in 1..=drops {
+            session.advance(Duration::from_millis(drop * 100));
+            session.key(Action::HardDrop, KeyEventKind::Press);
+            let hash = session.game().state_hash();
+            replay.record(drop * 100, Action::HardDrop, KeyEventKind::Press, hash);
+        }
+        replay.note_result(session.game());
+        replay.name = Some(name.to_string());
+        replay.saved = Some(saved);
+        replay.keep(dir, name).unwrap();
+    }
+
+    fn names(listings: &[Listing]) -> Vec<String> {
+        listings
+            .iter()
+            .map(|listing| match &listing.header {
+                Some(header) => header.name.clone().unwrap_or_default(),
+                None => listing.file(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn replays_list_by_date_or_result_with_the_unreadable_last() {
+        let dir = scratch_dir("sort");
+        save(&dir, 2, "older", 1_700_000_000);
+        save(&dir, 6, "newer", 1_717_200_000);
+        fs::write(dir.join("broken.json"), "{\"version\": 3, \"mode\"").unwrap();
+        let mut listings = list(&dir);
+        sort(&mut listings, Sort::Date);
+        assert_eq!(names(&listings), ["newer", "older", "broken.json"]);
+        sort(&mut listings, Sort::Result);
+        assert_eq!(names(&listings), ["newer", "older", "broken.json"]);
+        assert_eq!(listings[2].line(), "broken.json (unreadable)");
+        assert!(listings[1].line().starts_with("2023-11-14 Marathon  "));
+        assert!(listings[1].line().ends_with(" older"));
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(list(&dir).is_empty());
+    }
+
+    #[test]
+    fn the_header_is_read_without_the_inputs() {
+        let dir = scratch_dir("header");
+        // Whatever is in place of the inputs, the list doesn't look at them
+        fs::write(
+            dir.join("odd.json"),
+            "{\"version\": 3, \"mode\": \"sprint\", \"name\": \"odd\", \"inputs\": {\"x\": 1}}",
+        )
+        .unwrap();
+        let listing = &list(&dir)[0];
+        let header = listing.header.as_ref().unwrap();
+        assert_eq!(header.mode_name(), "Sprint");
+        assert_eq!(header.name.as_deref(), Some("odd"));
+        assert!(Replay::load(&listing.path).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,219 @@
+// Challenge codes: the mode, the seed and the bag count of a game in a couple dozen
+// characters, shown on the results screen. Whoever gets one plays the same
+// tetrominos with
+//
+//     tetris --challenge 2NEpo7TZRRrLZSi2U
+//
+// and the times or scores can be compared. The code also holds a hash of the handling
+// it was played with and a check on all of it, so that a mistyped code is turned
+// down rather than dealing other tetrominos. Other handling is allowed, the results
+// screen just says so. Base58 leaves out 0, O, I and l, which get misread.
+
+use std::fmt;
+
+use tetris::state::GameMode;
+
+use crate::replay::Handling;
+
+const ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+const VERSION: u8 = 1;
+// Version, mode, bag count, seed, handling hash, check
+const LEN: usize = 1 + 1 + 1 + 8 + 4 + 2;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Challenge {
+    pub mode: GameMode,
+    pub seed: u64,
+    pub bag_count: usize,
+    handling: u32,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChallengeError {
+    NotBase58(char),
+    // Wrong length or failed check, most likely a typo
+    Mistyped,
+    UnknownVersion(u8),
+}
+
+impl fmt::Display for ChallengeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChallengeError::NotBase58(c) => write!(f, "'{c}' can't be part of a challenge code"),
+            ChallengeError::Mistyped => write!(f, "not a challenge code, is it copied in full?"),
+            ChallengeError::UnknownVersion(version) => {
+                write!(f, "the code is from a newer tetrs (version {version})")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ChallengeError {}
+
+impl Challenge {
+    pub fn new(mode: GameMode, seed: u64, bag_count: usize, handling: Handling) -> Self {
+        Self {
+            mode,
+            seed,
+            bag_count,
+            handling: handling_hash(handling),
+        }
+    }
+
+    // Whether the keys repeat the way they did for whoever made the code
+    pub fn same_handling(&self, handling: Handling) -> bool {
+        self.handling == handling_hash(handling)
+    }
+
+    pub fn code(&self) -> String {
+        let mut bytes = vec![
+            VERSION,
+            GameMode::ALL
+                .iter()
+                .position(|mode| *mode == self.mode)
+                .unwrap() as u8,
+            self.bag_count.min(u8::MAX as usize) as u8,
+        ];
+        bytes.extend(self.seed.to_be_bytes());
+        bytes.extend(self.handling.to_be_bytes());
+        let check = fnv1a(&bytes) as u16;
+        bytes.extend(check.to_be_bytes());
+        encode(&bytes)
+    }
+
+    pub fn parse(code: &str) -> Result<Challenge, ChallengeError> {
+        let bytes = decode(code.trim())?;
+        if bytes.len() != LEN {
+            return Err(ChallengeError::Mistyped);
+        }
+        let (data, check) = bytes.split_at(LEN - 2);
+        if (fnv1a(data) as u16).to_be_bytes() != check {
+            return Err(ChallengeError::Mistyped);
+        }
+        if data[0] != VERSION {
+            return Err(ChallengeError::UnknownVersion(data[0]));
+        }
+        Ok(Challenge {
+            mode: *GameMode::ALL
+                .get(data[1] as usize)
+                .ok_or(ChallengeError::Mistyped)?,
+            bag_count: data[2].max(1) as usize,
+            seed: u64::from_be_bytes(data[3..11].try_into().unwrap()),
+            handling: u32::from_be_bytes(data[11..15].try_into().unwrap()),
+        })
+    }
+}
+
+fn handling_hash(handling: Handling) -> u32 {
+    // Through JSON, the factor is an enum and this is the one stable form there is
+    fnv1a(&serde_json::to_vec(&handling).unwrap()) as u32
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+// Big-endian base 58, every leading zero byte is a leading '1'
+fn encode(bytes: &[u8]) -> String {
+    let zeros = bytes.iter().take_while(|byte| **byte == 0).count();
+    // Little-endian base 58 digits
+    let mut digits: Vec<u8> = Vec::new();
+    for byte in &bytes[zeros..] {
+        let mut carry = *byte as u32;
+        for digit in &mut digits {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    std::iter::repeat_n('1', zeros)
+        .chain(
+            digits
+                .iter()
+                .rev()
+                .map(|digit| ALPHABET[*digit as usize] as char),
+        )
+        .collect()
+}
+
+fn decode(code: &str) -> Result<Vec<u8>, ChallengeError> {
+    let zeros = code.chars().take_while(|c| *c == '1').count();
+    // Little-endian bytes
+    let mut bytes: Vec<u8> = Vec::new();
+    for c in code.chars().skip(zeros) {
+        let mut carry = ALPHABET
+            .iter()
+            .position(|digit| *digit as char == c)
+            .ok_or(ChallengeError::NotBase58(c))? as u32;
+        for byte in &mut bytes {
+            carry += *byte as u32 * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+    Ok(std::iter::repeat_n(0, zeros)
+        .chain(bytes.into_iter().rev())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::input::RepeatSettings;
+
+    fn challenge(seed: u64) -> Challenge {
+        Challenge::new(
+            GameMode::Sprint,
+            seed,
+            2,
+            Handling::from(RepeatSettings::default()),
+        )
+    }
+
+    #[test]
+    fn a_code_starts_the_same_game() {
+        for seed in [0, 42, u64::MAX] {
+            let challenge = challenge(seed);
+            let code = challenge.code();
+            assert!(code.len() <= 24, "{code}");
+            assert_eq!(Challenge::parse(&code), Ok(challenge));
+        }
+    }
+
+    #[test]
+    fn other_handling_is_noticed() {
+        let challenge = challenge(42);
+        let mut handling = Handling::from(RepeatSettings::default());
+        assert!(challenge.same_handling(handling));
+        handling.das = 100;
+        assert!(!challenge.same_handling(handling));
+    }
+
+    #[test]
+    fn a_mistyped_code_is_turned_down() {
+        let code = challenge(42).code();
+        let mut chars: Vec<char> = code.chars().collect();
+        chars[5] = if chars[5] == 'x' { 'y' } else { 'x' };
+        let typo: String = chars.iter().collect();
+        assert_eq!(Challenge::parse(&typo), Err(ChallengeError::Mistyped));
+        assert_eq!(
+            Challenge::parse(&code[..code.len() - 1]),
+            Err(ChallengeError::Mistyped)
+        );
+        assert_eq!(
+            Challenge::parse(&code.replace(chars[0], "0")),
+            Err(ChallengeError::NotBase58('0'))
+        );
+    }
+}
@@ -0,0 +1,1132 @@
+// Settings read from $XDG_CONFIG_HOME/tetrs/config.toml. Every setting has a default
+// compiled in, a missing file just means all of them are used. Key bindings live in
+// a [keys] table, each action takes one key name or a list of them:
+//
+//     [keys]
+//     move_left = ["h", "Left"]
+//     hard_drop = "Space"
+//
+// held keys are tuned in [handling], in milliseconds:
+//
+//     [handling]
+//     das = 170
+//     arr = 40
+//     soft_drop = 30
+//
+// soft_drop_factor makes the soft drop that many times as fast as gravity instead,
+// "infinite" takes the tetromino down to the stack right away without locking it.
+// soft_drop_lock is what a soft drop does once the tetromino is on the stack: with
+// "tap", the default, pressing it again locks it but holding it down never does, with
+// "hold" it locks either way, and with "never" only the lock delay or a hard drop do.
+//
+// [game] turns the ghost and hold on or off, picks how gravity speeds up, with
+// always_save_replays saves the replay of every game without asking and with
+// idle_pause pauses a game after that many seconds without a key, 0 for never, and
+// [display] picks the frame drawn around the boxes, whether the window title follows
+// the game and the colors, and with hold_hint shows the letter of the held tetromino
+// faintly over the next one whenever holding would swap it in:
+//
+//     [game]
+//     ghost = true
+//     hold = false
+//     gravity = "linear"
+//     always_save_replays = true
+//     idle_pause = 30
+//
+//     [display]
+//     frame = "double"
+//     padding = true
+//     floor = true
+//     title = false
+//     hold_hint = true
+//     background = "light"
+//     skin = "green"
+//     hud = ["next", "hold", "time"]
+//     sidebar = "right"
+//
+// The background is taken from the terminal's COLORFGBG when it's left out, and
+// assumed to be dark when that isn't set either. A skin is a directory, relative to
+// this file's, see skin.rs. Its frame style goes over the one in [display].
+//
+// The hud lists the panels beside the board out of next, hold, score, level, lines,
+// time and clear, the name of the last line clear. What's left out isn't shown, and
+// when the terminal is too small for all of them the last ones are left out first.
+// The sidebar is "left" or "right" to put them all on that side, by default hold and
+// clear go on the left and the rest on the right.
+//
+// Modes of one's own go in [modes.<id>] tables and show up in the menu after the
+// built-in ones. They start out as a marathon, lines and time end the game once
+// reached, and level_up, hold, ghost and scoring can be turned off. Hold can also be
+// a number, the holds allowed in the whole game:
+//
+//     [modes.zen]
+//     name = "Zen"
+//     description = "20 lines, no hurry"
+//     lines = 20
+//     time = 300
+//     level_up = false
+//     hold = 5
+//     scoring = false
+//
+// clear_buffer = true keeps the keys pressed while lines clear for the next tetromino
+// (a rotation or hold as it spawns, movement keys already charging their repeat), and
+// zangi = true lets the tetromino that cleared them still slide along the stack.
+// adaptive = true makes the speed follow the play as in the built-in Adaptive mode, a
+// level or more faster for clean play and slower while the stack is near the top.
+// spin_b2b = true has a T-spin that clears no lines start or carry on a back-to-back
+// chain, rather than only leaving it be.
+//
+// They take a gravity too, which [game] goes over.
+//
+// Each one has a high score table of its own, kept under its id.
+//
+// The file is read over a preset, chosen with --preset, and the command line has the
+// last word: preset < config file < command line flags.
+//
+// The Settings entry of the menu writes what it covers to settings.toml, next to this
+// file, in the same form. It's read after this one and so goes over it: preset <
+// config file < settings.toml < command line flags. What it doesn't cover, like the
+// frame's padding, the hud or the modes, stays with this file.
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    process,
+    time::Duration,
+};
+
+use crossterm::event::KeyCode;
+use tui::widgets::BorderType;
+
+use tetris::{
+    score::GravityCurve,
+    state::{GameMode, Hold, Ruleset, SoftDropLock},
+};
+
+use crate::{
+    dirs,
+    input::{Action, Keymap, RepeatSettings, SoftDropFactor},
+    skin::{Skin, SkinError},
+    ui::{self, Background, Framing, Hud, Panel, Sidebar},
+};
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub keymap: Keymap,
+    pub repeat: RepeatSettings,
+    pub ghost: bool,
+    pub hold: bool,
+    // "stepped", the default, changes the speed at each level up, "linear" and
+    // "exponential" get there a little with every line. None keeps the mode's own.
+    pub gravity: Option<GravityCurve>,
+    // Also --always-save-replays
+    pub always_save_replays: bool,
+    // None never pauses a game by itself
+    pub idle_pause: Option<Duration>,
+    pub border_type: BorderType,
+    pub framing: Framing,
+    pub title: bool,
+    pub hold_hint: bool,
+    // None leaves it to the terminal to say
+    pub background: Option<Background>,
+    pub skin: Skin,
+    // The directory the skin came from, as written in [display]
+    pub skin_dir: Option<String>,
+    pub hud: Hud,
+    // Custom modes from [modes], in the order they're listed
+    pub modes: Vec<Ruleset>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            keymap: Keymap::default(),
+            repeat: RepeatSettings::default(),
+            ghost: true,
+            hold: true,
+            gravity: None,
+            always_save_replays: false,
+            idle_pause: Some(Duration::from_secs(30)),
+            border_type: BorderType::Plain,
+            framing: Framing::default(),
+            title: true,
+            hold_hint: false,
+            background: None,
+            skin: Skin::default(),
+            skin_dir: None,
+            hud: Hud::default(),
+            modes: Vec::new(),
+        }
+    }
+}
+
+// Sets of settings to start from, written and read like a config file. They only
+// cover what the config does, the rules themselves (SRS kicks, the 7-bag, one preview,
+// the lock delay and the scoring) are the same whichever one is picked.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Preset {
+    // The defaults: guideline key repeats, with the ghost and hold on
+    Guideline,
+    // Slower key repeats, rotation on x and z, and neither the ghost nor hold
+    Classic,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 2] = [Preset::Guideline, Preset::Classic];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Guideline => "guideline",
+            Preset::Classic => "classic",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Preset> {
+        Preset::ALL.into_iter().find(|preset| preset.name() == name)
+    }
+
+    fn text(self) -> &'static str {
+        match self {
+            Preset::Guideline => {
+                "[handling]\n\
+                 das = 170\n\
+                 arr = 40\n\
+                 soft_drop = 30\n\
+                 [game]\n\
+                 ghost = true\n\
+                 hold = true\n"
+            }
+            Preset::Classic => {
+                "[keys]\n\
+                 rotate_cw = [\"x\", \"Up\"]\n\
+                 rotate_ccw = \"z\"\n\
+                 hold = []\n\
+                 [handling]\n\
+                 das = 267\n\
+                 arr = 100\n\
+                 soft_drop = 33\n\
+                 [game]\n\
+                 ghost = false\n\
+                 hold = false\n"
+            }
+        }
+    }
+}
+
+// Errors don't mention the file, file() says which one it was
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(io::Error),
+    Syntax(toml::de::Error),
+    UnknownSetting(String),
+    UnknownAction(String),
+    UnknownKey {
+        action: String,
+        key: String,
+    },
+    NotAKeyList(String),
+    NotADuration(String),
+    NotSeconds(String),
+    NotASoftDropFactor,
+    UnknownSoftDropLock(String),
+    NotABool(String),
+    NotAString(String),
+    UnknownFrame(String),
+    UnknownBackground(String),
+    NotACount(String),
+    Skin(SkinError),
+    UnknownPanel(String),
+    NotAPanelList,
+    UnknownSidebar(String),
+    NotAHold(String),
+    // The table's name for it and the value
+    UnknownGravity(String, String),
+    // A custom mode can't take the id of a built-in one
+    TakenModeId(String),
+    // Only from validate(), files can have what they like
+    TooLong(String),
+    KeyConflict {
+        key: String,
+        first: Action,
+        second: Action,
+    },
+    // In settings.toml rather than the config file
+    InSettings(Box<ConfigError>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Read(err) => write!(f, "can't be read: {err}"),
+            ConfigError::Syntax(err) => write!(f, "{err}"),
+            ConfigError::UnknownSetting(name) => write!(f, "unknown setting '{name}'"),
+            ConfigError::UnknownAction(name) => write!(
+                f,
+                "unknown action '{name}' in [keys] (expected one of {})",
+                Action::ALL.map(Action::name).join(", ")
+            ),
+            ConfigError::UnknownKey { action, key } => {
+                write!(f, "unknown key '{key}' bound to {action}")
+            }
+            ConfigError::NotAKeyList(action) => write!(
+                f,
+                "{action} must be a key name or a list of key names, like \"Left\" or [\"h\", \"Left\"]"
+            ),
+            ConfigError::NotADuration(name) => {
+                write!(f, "{name} must be a whole number of milliseconds")
+            }
+            ConfigError::NotSeconds(name) => {
+                write!(f, "{name} must be a whole number of seconds, 0 for never")
+            }
+            ConfigError::NotASoftDropFactor => write!(
+                f,
+                "handling.soft_drop_factor must be a whole number above 0 or \"infinite\""
+            ),
+            ConfigError::UnknownSoftDropLock(name) => write!(
+                f,
+                "unknown soft_drop_lock '{name}' in [handling] (expected tap, hold or never)"
+            ),
+            ConfigError::NotABool(name) => write!(f, "{name} must be true or false"),
+            ConfigError::NotAString(name) => write!(f, "{name} must be a quoted name"),
+            ConfigError::UnknownFrame(name) => write!(
+                f,
+                "unknown frame '{name}' (expected plain, rounded, double or thick)"
+            ),
+            ConfigError::UnknownBackground(name) => {
+                write!(f, "unknown background '{name}' (expected dark or light)")
+            }
+            ConfigError::Skin(err) => write!(f, "skin {err}"),
+            ConfigError::UnknownPanel(name) => write!(
+                f,
+                "unknown panel '{name}' in display.hud (expected one of {})",
+                Panel::ALL.map(Panel::name).join(", ")
+            ),
+            ConfigError::NotAPanelList => write!(
+                f,
+                "display.hud must be a list of panel names, like [\"next\", \"hold\"]"
+            ),
+            ConfigError::UnknownSidebar(name) => {
+                write!(f, "unknown sidebar '{name}' (expected both, left or right)")
+            }
+            ConfigError::UnknownGravity(name, gravity) => write!(
+                f,
+                "unknown {name} '{gravity}' (expected stepped, linear or exponential)"
+            ),
+            ConfigError::NotAHold(name) => write!(
+                f,
+                "{name} must be true, false or how many holds the game allows"
+            ),
+            ConfigError::NotACount(name) => write!(f, "{name} must be a whole number above 0"),
+            ConfigError::TakenModeId(id) => {
+                write!(f, "[modes.{id}] is taken by a built-in mode, pick another id")
+            }
+            ConfigError::TooLong(name) => write!(
+                f,
+                "{name} must be {} milliseconds or less",
+                Config::MAX_HANDLING.as_millis()
+            ),
+            ConfigError::KeyConflict { key, first, second } => write!(
+                f,
+                "{key} is bound to both {} and {}",
+                first.name(),
+                second.name()
+            ),
+            ConfigError::InSettings(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl ConfigError {
+    pub fn file(&self) -> Option<PathBuf> {
+        match self {
+            ConfigError::InSettings(_) => Config::settings_path(),
+            _ => Config::path(),
+        }
+    }
+
+    // The setting a validate() error is about, as the config file names it
+    pub fn setting(&self) -> Option<String> {
+        match self {
+            ConfigError::TooLong(name) => Some(name.clone()),
+            ConfigError::KeyConflict { second, .. } => Some(format!("keys.{}", second.name())),
+            ConfigError::Skin(_) => Some("display.skin".to_string()),
+            _ => None,
+        }
+    }
+}
+
+impl Config {
+    // No key is held that long on purpose
+    pub const MAX_HANDLING: Duration = Duration::from_secs(1);
+
+    pub fn path() -> Option<PathBuf> {
+        Some(dirs::config_dir()?.join("config.toml"))
+    }
+
+    // Written by the settings screen
+    pub fn settings_path() -> Option<PathBuf> {
+        Some(dirs::config_dir()?.join("settings.toml"))
+    }
+
+    // The preset with the config file and then the settings over it, the command line
+    // comes later
+    pub fn load(preset: Preset) -> Result<Config, ConfigError> {
+        let mut config = Self::preset(preset);
+        let Some(path) = Self::path() else {
+            return Ok(config);
+        };
+        config.merge_file(&path)?;
+        if let Some(path) = Self::settings_path() {
+            config
+                .merge_file(&path)
+                .map_err(|err| ConfigError::InSettings(Box::new(err)))?;
+        }
+        Ok(config)
+    }
+
+    fn merge_file(&mut self, path: &Path) -> Result<(), ConfigError> {
+        match fs::read_to_string(path) {
+            Ok(text) => self.merge(&text),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(ConfigError::Read(err)),
+        }
+    }
+
+    pub fn preset(preset: Preset) -> Config {
+        let mut config = Config::default();
+        config
+            .merge(preset.text())
+            .expect("presets are valid config");
+        config
+    }
+
+    // Settings in the text replace the ones already there, anything it leaves out is
+    // kept. A key binding replaces all the keys of its action.
+    pub fn merge(&mut self, text: &str) -> Result<(), ConfigError> {
+        let table: toml::Table = text.parse().map_err(ConfigError::Syntax)?;
+        let config = self;
+        for (name, value) in table {
+            match (name.as_str(), value) {
+                ("keys", toml::Value::Table(keys)) => {
+                    for (action_name, keys) in keys {
+                        let action = Action::from_name(&action_name)
+                            .ok_or(ConfigError::UnknownAction(action_name.clone()))?;
+                        config.keymap.bind(action, parse_keys(&action_name, keys)?);
+                    }
+                }
+                ("handling", toml::Value::Table(handling)) => {
+                    for (setting, value) in handling {
+                        if setting == "soft_drop_factor" {
+                            config.repeat.soft_drop_factor = Some(match value {
+                                toml::Value::Integer(factor @ 1..) => {
+                                    SoftDropFactor::Times(factor.min(u32::MAX as i64) as u32)
+                                }
+                                toml::Value::String(name) if name == "infinite" => {
+                                    SoftDropFactor::Infinite
+                                }
+                                _ => return Err(ConfigError::NotASoftDropFactor),
+                            });
+                            continue;
+                        }
+                        if setting == "soft_drop_lock" {
+                            let toml::Value::String(name) = value else {
+                                return Err(ConfigError::NotAString(
+                                    "handling.soft_drop_lock".to_string(),
+                                ));
+                            };
+                            config.repeat.soft_drop_lock = SoftDropLock::from_name(&name)
+                                .ok_or(ConfigError::UnknownSoftDropLock(name))?;
+                            continue;
+                        }
+                        let duration = match value {
+                            toml::Value::Integer(ms) if ms >= 0 => Duration::from_millis(ms as u64),
+                            _ => return Err(ConfigError::NotADuration(setting)),
+                        };
+                        match setting.as_str() {
+                            "das" => config.repeat.delay = duration,
+                            "arr" => config.repeat.interval = duration,
+                            "soft_drop" => config.repeat.soft_drop_interval = duration,
+                            _ => {
+                                return Err(ConfigError::UnknownSetting(format!(
+                                    "handling.{setting}"
+                                )))
+                            }
+                        }
+                    }
+                }
+                ("game", toml::Value::Table(game)) => {
+                    for (setting, value) in game {
+                        let name = format!("game.{setting}");
+                        if setting == "gravity" {
+                            config.gravity = Some(parse_gravity(name, value)?);
+                            continue;
+                        }
+                        if setting == "idle_pause" {
+                            config.idle_pause = match value {
+                                toml::Value::Integer(0) => None,
+                                toml::Value::Integer(seconds @ 1..) => {
+                                    Some(Duration::from_secs(seconds as u64))
+                                }
+                                _ => return Err(ConfigError::NotSeconds(name)),
+                            };
+                            continue;
+                        }
+                        let enabled = value.as_bool().ok_or(ConfigError::NotABool(name.clone()))?;
+                        match setting.as_str() {
+                            "ghost" => config.ghost = enabled,
+                            "hold" => config.hold = enabled,
+                            "always_save_replays" => config.always_save_replays = enabled,
+                            _ => return Err(ConfigError::UnknownSetting(name)),
+                        }
+                    }
+                }
+                ("display", toml::Value::Table(display)) => {
+                    for (setting, value) in display {
+                        let name = format!("display.{setting}");
+                        match (setting.as_str(), value) {
+                            ("frame", toml::Value::String(frame)) => {
+                                config.border_type = ui::parse_border_type(&frame)
+                                    .ok_or(ConfigError::UnknownFrame(frame))?;
+                            }
+                            ("background", toml::Value::String(background)) => {
+                                config.background = Some(
+                                    Background::from_name(&background)
+                                        .ok_or(ConfigError::UnknownBackground(background))?,
+                                );
+                            }
+                            ("skin", toml::Value::String(dir)) => {
+                                config.skin =
+                                    Skin::load(skin_path(&dir)).map_err(ConfigError::Skin)?;
+                                config.skin_dir = Some(dir);
+                                if let Some(border_type) = config.skin.border_type {
+                                    config.border_type = border_type;
+                                }
+                            }
+                            ("sidebar", toml::Value::String(sidebar)) => {
+                                config.hud.sidebar = Sidebar::from_name(&sidebar)
+                                    .ok_or(ConfigError::UnknownSidebar(sidebar))?;
+                            }
+                            ("hud", toml::Value::Array(panels)) => {
+                                config.hud.panels = panels
+                                    .into_iter()
+                                    .map(|panel| match panel {
+                                        toml::Value::String(name) => Panel::from_name(&name)
+                                            .ok_or(ConfigError::UnknownPanel(name)),
+                                        _ => Err(ConfigError::NotAPanelList),
+                                    })
+                                    .collect::<Result<_, _>>()?;
+                            }
+                            ("hud", _) => return Err(ConfigError::NotAPanelList),
+                            ("frame" | "background" | "skin" | "sidebar", _) => {
+                                return Err(ConfigError::NotAString(name))
+                            }
+                            ("padding", toml::Value::Boolean(padding)) => {
+                                config.framing.padding = padding;
+                            }
+                            ("floor", toml::Value::Boolean(floor)) => config.framing.floor = floor,
+                            ("title", toml::Value::Boolean(title)) => config.title = title,
+                            ("hold_hint", toml::Value::Boolean(hint)) => config.hold_hint = hint,
+                            ("padding" | "floor" | "title" | "hold_hint", _) => {
+                                return Err(ConfigError::NotABool(name))
+                            }
+                            _ => return Err(ConfigError::UnknownSetting(name)),
+                        }
+                    }
+                }
+                ("modes", toml::Value::Table(modes)) => {
+                    for (id, settings) in modes {
+                        let toml::Value::Table(settings) = settings else {
+                            return Err(ConfigError::UnknownSetting(format!("modes.{id}")));
+                        };
+                        let index = match config.modes.iter().position(|mode| mode.id == id) {
+                            Some(index) => index,
+                            None => {
+                                config.modes.push(custom_mode(&id)?);
+                                config.modes.len() - 1
+                            }
+                        };
+                        merge_mode(&mut config.modes[index], settings)?;
+                    }
+                }
+                _ => return Err(ConfigError::UnknownSetting(name)),
+            }
+        }
+        Ok(())
+    }
+
+    // The flags that override the file: --frame NAME, --frame-padding, --frame-floor and
+    // --always-save-replays
+    pub fn apply_args(&mut self, args: impl IntoIterator<Item = String>) -> Result<(), String> {
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--frame" => {
+                    let name = args.next().unwrap_or_default();
+                    self.border_type = ui::parse_border_type(&name).ok_or(format!(
+                        "invalid --frame value: {name} (expected plain, rounded, double or thick)"
+                    ))?;
+                }
+                "--frame-padding" => self.framing.padding = true,
+                "--frame-floor" => self.framing.floor = true,
+                "--always-save-replays" => self.always_save_replays = true,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // What the settings screen checks before its draft goes live: handling that can
+    // be played with, no key on two actions and a skin that's still there, which is
+    // loaded again on the way. The error's setting() says what it's about.
+    pub fn validate(&mut self) -> Result<(), ConfigError> {
+        let handling = [
+            ("handling.das", self.repeat.delay),
+            ("handling.arr", self.repeat.interval),
+            ("handling.soft_drop", self.repeat.soft_drop_interval),
+        ];
+        for (name, duration) in handling {
+            if duration > Self::MAX_HANDLING {
+                return Err(ConfigError::TooLong(name.to_string()));
+            }
+        }
+        if let Some((code, first, second)) = self.keymap.conflict() {
+            let key = key_name(code);
+            return Err(ConfigError::KeyConflict { key, first, second });
+        }
+        self.skin = match &self.skin_dir {
+            Some(dir) => Skin::load(skin_path(dir)).map_err(ConfigError::Skin)?,
+            None => Skin::default(),
+        };
+        if let Some(border_type) = self.skin.border_type {
+            self.border_type = border_type;
+        }
+        Ok(())
+    }
+
+    // The settings the settings screen covers, as settings.toml has them
+    pub fn settings_text(&self) -> String {
+        let millis = |duration: Duration| duration.as_millis();
+        let mut text = format!(
+            "[handling]\ndas = {}\narr = {}\nsoft_drop = {}\n",
+            millis(self.repeat.delay),
+            millis(self.repeat.interval),
+            millis(self.repeat.soft_drop_interval)
+        );
+        text.push_str(&format!(
+            "\n[game]\nghost = {}\nhold = {}\nidle_pause = {}\n",
+            self.ghost,
+            self.hold,
+            self.idle_pause.map_or(0, |after| after.as_secs())
+        ));
+        text.push_str(&format!(
+            "\n[display]\nframe = \"{}\"\ntitle = {}\nhold_hint = {}\n",
+            ui::border_type_name(self.border_type),
+            self.title,
+            self.hold_hint
+        ));
+        if let Some(dir) = &self.skin_dir {
+            text.push_str(&format!("skin = {}\n", quoted(dir)));
+        }
+        text.push_str("\n[keys]\n");
+        for action in Action::ALL {
+            let keys: Vec<String> = self
+                .keymap
+                .keys(action)
+                .into_iter()
+                .map(|code| quoted(&key_name(code)))
+                .collect();
+            text.push_str(&format!("{} = [{}]\n", action.name(), keys.join(", ")));
+        }
+        text
+    }
+
+    // Written next to the file and renamed over it, like the high scores
+    pub fn save_settings(&self) -> io::Result<()> {
+        let path = Self::settings_path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(format!(".{}.tmp", process::id()));
+        fs::write(&temporary, self.settings_text())?;
+        fs::rename(&temporary, &path).inspect_err(|_| {
+            let _ = fs::remove_file(&temporary);
+        })
+    }
+}
+
+// Skins are named by their directory, relative to the config file's
+fn skin_path(dir: &str) -> PathBuf {
+    dirs::config_dir().unwrap_or_default().join(dir)
+}
+
+// The directories next to the config file, any of them could be a skin
+pub fn skin_dirs() -> Vec<String> {
+    let Some(Ok(entries)) = dirs::config_dir().map(fs::read_dir) else {
+        return Vec::new();
+    };
+    let mut dirs: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+// A TOML string
+fn quoted(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// A marathon by another name, until its settings say otherwise
+fn custom_mode(id: &str) -> Result<Ruleset, ConfigError> {
+    if GameMode::ALL
+        .into_iter()
+        .chain([GameMode::Custom])
+        .any(|mode| mode.id() == id)
+    {
+        return Err(ConfigError::TakenModeId(id.to_string()));
+    }
+    Ok(Ruleset {
+        id: id.to_string(),
+        name: id.to_string(),
+        description: String::new(),
+        ..GameMode::Marathon.ruleset()
+    })
+}
+
+fn merge_mode(mode: &mut Ruleset, settings: toml::Table) -> Result<(), ConfigError> {
+    for (setting, value) in settings {
+        let name = format!("modes.{}.{setting}", mode.id);
+        match (setting.as_str(), value) {
+            ("name", toml::Value::String(text)) => mode.name = text,
+            ("description", toml::Value::String(text)) => mode.description = text,
+            ("name" | "description", _) => return Err(ConfigError::NotAString(name)),
+            ("lines", toml::Value::Integer(lines @ 1..)) => {
+                mode.line_goal = Some(lines.min(u32::MAX.into()) as u32);
+            }
+            ("time", toml::Value::Integer(seconds @ 1..)) => mode.time_limit = Some(seconds as u64),
+            ("lines" | "time", _) => return Err(ConfigError::NotACount(name)),
+            ("level_up", toml::Value::Boolean(enabled)) => mode.level_up = enabled,
+            ("hold", toml::Value::Boolean(false)) => mode.hold = Hold::Off,
+            ("hold", toml::Value::Boolean(true)) => mode.hold = Hold::Standard,
+            ("hold", toml::Value::Integer(holds @ 1..)) => {
+                mode.hold = Hold::Limited(holds.min(u32::MAX.into()) as u32);
+            }
+            ("hold", _) => return Err(ConfigError::NotAHold(name)),
+            ("ghost", toml::Value::Boolean(enabled)) => mode.ghost = enabled,
+            ("scoring", toml::Value::Boolean(enabled)) => mode.scoring = enabled,
+            ("clear_buffer", toml::Value::Boolean(enabled)) => mode.clear_buffer = enabled,
+            ("zangi", toml::Value::Boolean(enabled)) => mode.zangi = enabled,
+            ("adaptive", toml::Value::Boolean(enabled)) => mode.adaptive = enabled,
+            ("spin_b2b", toml::Value::Boolean(enabled)) => mode.spin_b2b = enabled,
+            ("gravity", value) => mode.gravity = parse_gravity(name, value)?,
+            (
+                "level_up" | "ghost" | "scoring" | "clear_buffer" | "zangi" | "adaptive"
+                | "spin_b2b",
+                _,
+            ) => return Err(ConfigError::NotABool(name)),
+            _ => return Err(ConfigError::UnknownSetting(name)),
+        }
+    }
+    Ok(())
+}
+
+fn parse_gravity(name: String, value: toml::Value) -> Result<GravityCurve, ConfigError> {
+    match value {
+        toml::Value::String(gravity) => {
+            GravityCurve::from_name(&gravity).ok_or(ConfigError::UnknownGravity(name, gravity))
+        }
+        _ => Err(ConfigError::NotAString(name)),
+    }
+}
+
+fn parse_keys(action: &str, value: toml::Value) -> Result<Vec<KeyCode>, ConfigError> {
+    let names = match value {
+        toml::Value::String(name) => vec![toml::Value::String(name)],
+        toml::Value::Array(names) => names,
+        _ => return Err(ConfigError::NotAKeyList(action.to_string())),
+    };
+    names
+        .into_iter()
+        .map(|name| {
+            let toml::Value::String(name) = name else {
+                return Err(ConfigError::NotAKeyList(action.to_string()));
+            };
+            parse_key(&name).ok_or(ConfigError::UnknownKey {
+                action: action.to_string(),
+                key: name,
+            })
+        })
+        .collect()
+}
+
+// The name parse_key() reads back as the same key
+pub fn key_name(code: KeyCode) -> String {
+    let name = match code {
+        KeyCode::Char(' ') => "Space",
+        KeyCode::Char(c) => return c.to_string(),
+        KeyCode::F(number) => return format!("F{number}"),
+        KeyCode::Left => "Left",
+        KeyCode::Right => "Right",
+        KeyCode::Up => "Up",
+        KeyCode::Down => "Down",
+        KeyCode::Enter => "Enter",
+        KeyCode::Esc => "Esc",
+        KeyCode::Tab => "Tab",
+        KeyCode::Backspace => "Backspace",
+        KeyCode::Insert => "Insert",
+        KeyCode::Delete => "Delete",
+        KeyCode::Home => "Home",
+        KeyCode::End => "End",
+        KeyCode::PageUp => "PageUp",
+        KeyCode::PageDown => "PageDown",
+        other => return format!("{other:?}"),
+    };
+    name.to_string()
+}
+
+// Single characters stand for themselves and are case sensitive, so "r" and "R"
+// are different keys. Named keys are matched regardless of case.
+pub fn parse_key(name: &str) -> Option<KeyCode> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(c));
+    }
+    let code = match name.to_ascii_lowercase().as_str() {
+        "space" => KeyCode::Char(' '),
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "enter" => KeyCode::Enter,
+        "esc" | "escape" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "backspace" => KeyCode::Backspace,
+        "insert" => KeyCode::Insert,
+        "delete" => KeyCode::Delete,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        function => KeyCode::F(function.strip_prefix('f')?.parse().ok()?),
+    };
+    Some(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(config: &Config, action: Action) -> Vec<KeyCode> {
+        config
+            .keymap
+            .bindings()
+            .iter()
+            .filter(|(_, bound)| *bound == action)
+            .map(|&(code, _)| code)
+            .collect()
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn every_preset_is_valid_config() {
+        for preset in Preset::ALL {
+            assert_eq!(Preset::from_name(preset.name()), Some(preset));
+            Config::default().merge(preset.text()).unwrap();
+        }
+    }
+
+    #[test]
+    fn the_classic_preset_turns_off_the_modern_helpers() {
+        let config = Config::preset(Preset::Classic);
+        assert!(!config.ghost);
+        assert!(!config.hold);
+        assert_eq!(keys(&config, Action::Hold), vec![]);
+        assert_eq!(config.repeat.delay, Duration::from_millis(267));
+        assert_eq!(config.repeat.interval, Duration::from_millis(100));
+        assert!(Config::preset(Preset::Guideline).ghost);
+    }
+
+    #[test]
+    fn the_config_file_goes_over_the_preset() {
+        let mut config = Config::preset(Preset::Classic);
+        config
+            .merge("[keys]\nrotate_cw = \"k\"\n[game]\nghost = true\n")
+            .unwrap();
+        assert_eq!(keys(&config, Action::RotateCw), vec![KeyCode::Char('k')]);
+        assert!(config.ghost);
+        // Whatever the file leaves out stays as the preset has it
+        assert!(!config.hold);
+        assert_eq!(keys(&config, Action::RotateCcw), vec![KeyCode::Char('z')]);
+        assert_eq!(config.repeat.delay, Duration::from_millis(267));
+    }
+
+    #[test]
+    fn the_command_line_goes_over_the_config_file() {
+        let mut config = Config::default();
+        config
+            .merge("[display]\nframe = \"double\"\npadding = false\n")
+            .unwrap();
+        config
+            .apply_args(args(&["tetris", "--frame", "thick", "--frame-padding"]))
+            .unwrap();
+        assert_eq!(config.border_type, BorderType::Thick);
+        assert!(config.framing.padding);
+        assert!(!config.framing.floor);
+        assert!(config
+            .apply_args(args(&["tetris", "--frame", "wavy"]))
+            .is_err());
+    }
+
+    #[test]
+    fn replays_are_saved_without_asking_from_the_file_or_the_command_line() {
+        let mut config = Config::default();
+        assert!(!config.always_save_replays);
+        config
+            .merge("[game]\nalways_save_replays = true\n")
+            .unwrap();
+        assert!(config.always_save_replays);
+        let mut config = Config::default();
+        config
+            .apply_args(args(&["tetris", "--always-save-replays"]))
+            .unwrap();
+        assert!(config.always_save_replays);
+    }
+
+    #[test]
+    fn the_settings_read_back_as_they_were_written() {
+        let mut written = Config::preset(Preset::Classic);
+        written.repeat.delay = Duration::from_millis(120);
+        written.idle_pause = None;
+        written.border_type = BorderType::Double;
+        written.hold_hint = true;
+        written
+            .keymap
+            .bind(Action::Hold, vec![KeyCode::Char('"'), KeyCode::F(2)]);
+        let mut read = Config::default();
+        read.merge(&written.settings_text()).unwrap();
+        assert_eq!(read.repeat, written.repeat);
+        assert_eq!(read.idle_pause, None);
+        assert!(!read.ghost && !read.hold && read.hold_hint);
+        assert_eq!(read.border_type, BorderType::Double);
+        for action in Action::ALL {
+            assert_eq!(read.keymap.keys(action), written.keymap.keys(action));
+        }
+    }
+
+    #[test]
+    fn validation_finds_what_a_file_could_never_say() {
+        let mut config = Config::default();
+        assert!(config.validate().is_ok());
+        config.repeat.interval = Duration::from_millis(1001);
+        let err = config.validate().unwrap_err();
+        assert_eq!(err.setting().as_deref(), Some("handling.arr"));
+        config.repeat.interval = Duration::from_millis(1000);
+        config.keymap.add(Action::Pause, KeyCode::Char('q'));
+        let err = config.validate().unwrap_err();
+        assert_eq!(err.setting().as_deref(), Some("keys.pause"));
+        assert_eq!(err.to_string(), "q is bound to both quit and pause");
+    }
+
+    #[test]
+    fn games_pause_after_30_idle_seconds_unless_told_otherwise() {
+        let mut config = Config::default();
+        assert_eq!(config.idle_pause, Some(Duration::from_secs(30)));
+        config.merge("[game]\nidle_pause = 90\n").unwrap();
+        assert_eq!(config.idle_pause, Some(Duration::from_secs(90)));
+        config.merge("[game]\nidle_pause = 0\n").unwrap();
+        assert_eq!(config.idle_pause, None);
+        assert_eq!(
+            config
+                .merge("[game]\nidle_pause = -1\n")
+                .unwrap_err()
+                .to_string(),
+            "game.idle_pause must be a whole number of seconds, 0 for never"
+        );
+    }
+
+    #[test]
+    fn the_soft_drop_factor_is_a_number_or_infinite() {
+        let mut config = Config::default();
+        assert_eq!(config.repeat.soft_drop_factor, None);
+        config.merge("[handling]\nsoft_drop_factor = 20\n").unwrap();
+        assert_eq!(
+            config.repeat.soft_drop_factor,
+            Some(SoftDropFactor::Times(20))
+        );
+        config
+            .merge("[handling]\nsoft_drop_factor = \"infinite\"\n")
+            .unwrap();
+        assert_eq!(
+            config.repeat.soft_drop_factor,
+            Some(SoftDropFactor::Infinite)
+        );
+        for bad in ["0", "\"fast\"", "1.5"] {
+            assert_eq!(
+                Config::default()
+                    .merge(&format!("[handling]\nsoft_drop_factor = {bad}\n"))
+                    .unwrap_err()
+                    .to_string(),
+                "handling.soft_drop_factor must be a whole number above 0 or \"infinite\""
+            );
+        }
+    }
+
+    #[test]
+    fn the_soft_drop_lock_is_tap_hold_or_never() {
+        let mut config = Config::default();
+        assert_eq!(config.repeat.soft_drop_lock, SoftDropLock::Tap);
+        config
+            .merge("[handling]\nsoft_drop_lock = \"hold\"\n")
+            .unwrap();
+        assert_eq!(config.repeat.soft_drop_lock, SoftDropLock::Hold);
+        config
+            .merge("[handling]\nsoft_drop_lock = \"never\"\n")
+            .unwrap();
+        assert_eq!(config.repeat.soft_drop_lock, SoftDropLock::Never);
+        let error = |text| Config::default().merge(text).unwrap_err().to_string();
+        assert_eq!(
+            error("[handling]\nsoft_drop_lock = \"always\"\n"),
+            "unknown soft_drop_lock 'always' in [handling] (expected tap, hold or never)"
+        );
+        assert_eq!(
+            error("[handling]\nsoft_drop_lock = true\n"),
+            "handling.soft_drop_lock must be a quoted name"
+        );
+    }
+
+    #[test]
+    fn bad_game_and_display_settings_are_reported() {
+        let error = |text| Config::default().merge(text).unwrap_err().to_string();
+        assert_eq!(
+            error("[game]\nghost = 1\n"),
+            "game.ghost must be true or false"
+        );
+        assert_eq!(
+            error("[game]\nspeed = true\n"),
+            "unknown setting 'game.speed'"
+        );
+        assert_eq!(
+            error("[display]\nframe = \"wavy\"\n"),
+            "unknown frame 'wavy' (expected plain, rounded, double or thick)"
+        );
+        assert_eq!(
+            error("[display]\nframe = 2\n"),
+            "display.frame must be a quoted name"
+        );
+        assert_eq!(
+            error("[display]\nbackground = \"grey\"\n"),
+            "unknown background 'grey' (expected dark or light)"
+        );
+        assert_eq!(
+            error("[display]\ntitle = 0\n"),
+            "display.title must be true or false"
+        );
+        assert_eq!(
+            error("[display]\nfloor = \"yes\"\n"),
+            "display.floor must be true or false"
+        );
+        assert_eq!(
+            error("[display]\nhold_hint = \"on\"\n"),
+            "display.hold_hint must be true or false"
+        );
+    }
+
+    #[test]
+    fn the_hud_is_a_list_of_panels() {
+        let mut config = Config::default();
+        config
+            .merge("[display]\nhud = [\"time\", \"next\"]\nsidebar = \"left\"\n")
+            .unwrap();
+        assert_eq!(config.hud.panels, vec![Panel::Time, Panel::Next]);
+        assert_eq!(config.hud.sidebar, Sidebar::Left);
+        let error = |text| Config::default().merge(text).unwrap_err().to_string();
+        assert_eq!(
+            error("[display]\nhud = [\"next\", \"stats\"]\n"),
+            "unknown panel 'stats' in display.hud \
+             (expected one of next, hold, score, level, lines, time, clear)"
+        );
+        assert_eq!(
+            error("[display]\nhud = \"next\"\n"),
+            "display.hud must be a list of panel names, like [\"next\", \"hold\"]"
+        );
+        assert_eq!(
+            error("[display]\nsidebar = \"top\"\n"),
+            "unknown sidebar 'top' (expected both, left or right)"
+        );
+    }
+
+    #[test]
+    fn custom_modes_start_out_as_a_marathon() {
+        let mut config = Config::default();
+        config
+            .merge("[modes.zen]\nname = \"Zen\"\nlines = 20\nscoring = false\n")
+            .unwrap();
+        let zen = &config.modes[0];
+        assert_eq!(zen.id, "zen");
+        assert_eq!(zen.name, "Zen");
+        assert_eq!(zen.line_goal, Some(20));
+        assert!(!zen.scoring);
+        assert!(zen.level_up);
+        assert_eq!(zen.hold, Hold::Standard);
+        assert_eq!(zen.mode(), GameMode::Custom);
+        // A later file changes the mode rather than adding another one
+        config.merge("[modes.zen]\ntime = 60\n").unwrap();
+        assert_eq!(config.modes.len(), 1);
+        assert_eq!(config.modes[0].line_goal, Some(20));
+        assert_eq!(config.modes[0].time_limit, Some(60));
+        assert!(!config.modes[0].spin_b2b);
+        config.merge("[modes.zen]\nspin_b2b = true\n").unwrap();
+        assert!(config.modes[0].spin_b2b);
+    }
+
+    #[test]
+    fn bad_custom_modes_are_reported() {
+        let error = |text| Config::default().merge(text).unwrap_err().to_string();
+        assert_eq!(
+            error("[modes.sprint]\nlines = 20\n"),
+            "[modes.sprint] is taken by a built-in mode, pick another id"
+        );
+        assert_eq!(
+            error("[modes.zen]\nlines = 0\n"),
+            "modes.zen.lines must be a whole number above 0"
+        );
+        assert_eq!(
+            error("[modes.zen]\nhold = \"no\"\n"),
+            "modes.zen.hold must be true, false or how many holds the game allows"
+        );
+        assert_eq!(
+            error("[modes.zen]\nhold = 0\n"),
+            "modes.zen.hold must be true, false or how many holds the game allows"
+        );
+        assert_eq!(
+            error("[modes.zen]\nspeed = 2\n"),
+            "unknown setting 'modes.zen.speed'"
+        );
+        assert_eq!(
+            error("[modes.zen]\ngravity = \"smooth\"\n"),
+            "unknown modes.zen.gravity 'smooth' (expected stepped, linear or exponential)"
+        );
+        assert_eq!(
+            error("[modes.zen]\nspin_b2b = 1\n"),
+            "modes.zen.spin_b2b must be true or false"
+        );
+    }
+
+    #[test]
+    fn the_gravity_curve_is_left_to_the_mode_unless_set() {
+        let mut config = Config::default();
+        assert_eq!(config.gravity, None);
+        config.merge("[game]\ngravity = \"exponential\"\n").unwrap();
+        assert_eq!(config.gravity, Some(GravityCurve::Exponential));
+        assert!(config.hold);
+    }
+}
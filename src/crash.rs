@@ -0,0 +1,264 @@
+// Panic handling: the terminal is put back in a usable state and a crash bundle with
+// everything needed to reproduce the problem is written to the data directory, unless
+// the game runs without persistence.
+
+use std::{
+    backtrace::Backtrace,
+    collections::VecDeque,
+    fmt::{self, Write as _},
+    fs, io,
+    panic::{self, PanicHookInfo},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crossterm::{
+    event::PopKeyboardEnhancementFlags,
+    execute,
+    terminal::{disable_raw_mode, is_raw_mode_enabled, LeaveAlternateScreen},
+};
+
+use crate::dirs::Persistence;
+
+const RECENT_EVENTS: usize = 200;
+
+// Always on, so it has to stay cheap: a bounded list of short strings and the board
+// as of the last lock, plus what the game was started with
+struct CrashLog {
+    events: VecDeque<String>,
+    board: Option<String>,
+    seed: Option<u64>,
+    config: Option<String>,
+}
+
+#[cfg(not(test))]
+static LOG: Mutex<CrashLog> = Mutex::new(CrashLog::new());
+
+// Tests run side by side on threads of their own, each one gets a log of its own
+#[cfg(test)]
+thread_local! {
+    static LOG: Mutex<CrashLog> = const { Mutex::new(CrashLog::new()) };
+}
+
+// Never waits, a panic can come while the log is in use
+fn with_log<R>(f: impl FnOnce(&mut CrashLog) -> R) -> Option<R> {
+    #[cfg(not(test))]
+    return LOG.try_lock().ok().map(|mut log| f(&mut log));
+    #[cfg(test)]
+    return LOG.with(|log| log.try_lock().ok().map(|mut log| f(&mut log)));
+}
+
+pub fn record(event: String) {
+    with_log(|log| {
+        if log.events.len() == RECENT_EVENTS {
+            log.events.pop_front();
+        }
+        log.events.push_back(event);
+    });
+}
+
+pub fn set_board(board: String) {
+    with_log(|log| log.board = Some(board));
+}
+
+// The game can be played again with --seed
+pub fn set_seed(seed: u64) {
+    with_log(|log| log.seed = Some(seed));
+}
+
+pub fn set_config(config: String) {
+    with_log(|log| log.config = Some(config));
+}
+
+pub fn install_hook(persistence: Persistence) {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        // Left alone when the game never set it up, or already put it back
+        if is_raw_mode_enabled().unwrap_or(true) {
+            let _ = disable_raw_mode();
+            let _ = execute!(
+                io::stdout(),
+                PopKeyboardEnhancementFlags,
+                LeaveAlternateScreen
+            );
+        }
+        default_hook(info);
+        eprintln!("{}", saved_message(write_bundle(info, &persistence)));
+    }));
+}
+
+fn saved_message(saved: io::Result<PathBuf>) -> String {
+    match saved {
+        Ok(path) => format!(
+            "A crash report was written to {}\nPlease attach that file when reporting this bug.",
+            path.display()
+        ),
+        Err(err) => format!("Couldn't write a crash report: {err}"),
+    }
+}
+
+impl CrashLog {
+    const fn new() -> Self {
+        Self {
+            events: VecDeque::new(),
+            board: None,
+            seed: None,
+            config: None,
+        }
+    }
+
+    fn bundle(&self, panic: &dyn fmt::Display) -> String {
+        let mut bundle = String::new();
+        let _ = writeln!(bundle, "tetris {}", env!("CARGO_PKG_VERSION"));
+        let _ = writeln!(bundle, "\n== panic\n{panic}");
+        let _ = writeln!(bundle, "\n== backtrace\n{}", Backtrace::force_capture());
+        let _ = writeln!(bundle, "\n== seed");
+        match self.seed {
+            Some(seed) => {
+                let _ = writeln!(bundle, "{seed} (play it again with --seed {seed})");
+            }
+            None => bundle.push_str("(no game started)\n"),
+        }
+        let _ = writeln!(bundle, "\n== config");
+        match &self.config {
+            Some(config) => {
+                let _ = writeln!(bundle, "{config}");
+            }
+            None => bundle.push_str("(not loaded)\n"),
+        }
+        let _ = writeln!(bundle, "\n== recent events (oldest first)");
+        for event in &self.events {
+            let _ = writeln!(bundle, "{event}");
+        }
+        let _ = writeln!(bundle, "\n== board at the last lock");
+        match &self.board {
+            Some(board) => bundle.push_str(board),
+            None => bundle.push_str("(no piece locked yet)"),
+        }
+        bundle.push('\n');
+        bundle
+    }
+}
+
+fn write_bundle(info: &PanicHookInfo, persistence: &Persistence) -> io::Result<PathBuf> {
+    let dir = persistence.dir("crashes")?;
+    // Whatever was logged is lost if the log is in use, the panic is still worth keeping
+    let bundle = with_log(|log| log.bundle(info)).unwrap_or_else(|| CrashLog::new().bundle(info));
+    save_bundle(&dir, &bundle)
+}
+
+fn save_bundle(dir: &Path, bundle: &str) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+    let path = dir.join(format!("crash-{timestamp}.txt"));
+    fs::write(&path, bundle)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use crossterm::event::KeyEventKind;
+    use tetris::{game::TetrominosBag, state::Game};
+
+    use super::*;
+    use crate::{
+        input::{Action, RepeatSettings},
+        session::Session,
+    };
+
+    // There's only one hook for every thread, so the tests that panic take turns
+    static PANICS: Mutex<()> = Mutex::new(());
+
+    fn one_panic_at_a_time() -> std::sync::MutexGuard<'static, ()> {
+        PANICS
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    #[test]
+    fn a_panic_mid_game_leaves_the_board_in_the_bundle() {
+        let _panics = one_panic_at_a_time();
+        set_seed(7);
+        set_config(String::from("Config { .. }"));
+        let mut session = Session::new(
+            Game::with_bag(10, 20, TetrominosBag::new()),
+            RepeatSettings::default(),
+        );
+        session.key(Action::MoveLeft, KeyEventKind::Press);
+        session.key(Action::HardDrop, KeyEventKind::Press);
+        let board = session.game().board().to_text();
+
+        let panic = panic::catch_unwind(|| panic!("controlled")).unwrap_err();
+        let message = panic.downcast_ref::<&str>().unwrap();
+        let bundle = with_log(|log| log.bundle(message)).unwrap();
+        let dir = env::temp_dir().join(format!("tetrs-crash-{}", process::id()));
+        let path = save_bundle(&dir, &bundle).unwrap();
+        let saved = fs::read_to_string(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(saved.contains("\n== panic\ncontrolled\n"));
+        assert!(saved.contains("\n== seed\n7 (play it again with --seed 7)\n"));
+        assert!(saved.contains("\n== config\nConfig { .. }\n"));
+        assert!(saved.contains("\nspawned O\nMoveLeft\nHardDrop\nlocked O\nspawned I\n"));
+        assert!(board.ends_with("...OO.....\n...OO....."));
+        assert!(saved.ends_with(&format!("\n== board at the last lock\n{board}\n")));
+    }
+
+    #[test]
+    fn the_installed_hook_writes_the_bundle_and_says_where_it_is() {
+        let _panics = one_panic_at_a_time();
+        let data = env::temp_dir().join(format!("tetrs-hook-{}", process::id()));
+        set_seed(3);
+        record(String::from("HardDrop"));
+        install_hook(Persistence::at(data.join("tetrs")));
+        let panicked = panic::catch_unwind(|| panic!("through the hook"));
+        // Back to the default hook
+        let _ = panic::take_hook();
+        assert!(panicked.is_err());
+
+        let bundles: Vec<PathBuf> = fs::read_dir(data.join("tetrs/crashes"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        let saved = fs::read_to_string(&bundles[0]).unwrap();
+        fs::remove_dir_all(&data).unwrap();
+        assert_eq!(bundles.len(), 1);
+        assert!(saved.contains("through the hook"));
+        assert!(saved.contains("\n== seed\n3 (play it again with --seed 3)\n"));
+        assert!(saved.contains("\nHardDrop\n"));
+        let message = saved_message(Ok(bundles[0].clone()));
+        assert_eq!(
+            message,
+            format!(
+                "A crash report was written to {}\nPlease attach that file when reporting this bug.",
+                bundles[0].display()
+            )
+        );
+    }
+
+    #[test]
+    fn without_persistence_the_message_says_why_there_is_no_bundle() {
+        let dir = Persistence::off("--no-persist").dir("crashes");
+        assert_eq!(
+            saved_message(dir),
+            "Couldn't write a crash report: running without persistence (--no-persist)"
+        );
+    }
+
+    #[test]
+    fn only_the_most_recent_events_are_kept() {
+        for event in 0..RECENT_EVENTS + 50 {
+            record(event.to_string());
+        }
+        let events = with_log(|log| log.events.clone()).unwrap();
+        assert_eq!(events.len(), RECENT_EVENTS);
+        assert_eq!(events.front().map(String::as_str), Some("50"));
+        let bundle = with_log(|log| log.bundle(&"panic")).unwrap();
+        assert!(bundle.contains("(no piece locked yet)"));
+    }
+}
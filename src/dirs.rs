@@ -0,0 +1,124 @@
+// Where the game keeps its files, following the XDG base directory spec, and whether
+// it writes any at all
+
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    process,
+};
+
+// $XDG_DATA_HOME/tetrs, falling back to ~/.local/share/tetrs
+pub fn data_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".local/share"),
+    };
+    Some(base.join("tetrs"))
+}
+
+// $XDG_CONFIG_HOME/tetrs, falling back to ~/.config/tetrs
+pub fn config_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("tetrs"))
+}
+
+// The one place that says whether the game writes to the data directory. High scores,
+// splits, replays and crash bundles all get their directory from here, so a data
+// directory that can't be written, or --no-persist, leaves every one of them off
+// rather than failing at the end of every game.
+#[derive(Clone, Debug)]
+pub struct Persistence {
+    // Why nothing is written when there's no directory
+    data_dir: Result<PathBuf, String>,
+}
+
+impl Persistence {
+    // Writes a file into the data directory, once, at startup, to see if it can
+    pub fn detect(wanted: bool) -> Self {
+        if !wanted {
+            return Self::off("--no-persist");
+        }
+        match data_dir() {
+            Some(dir) => Self::at(dir),
+            None => Self::off("no data directory, HOME isn't set"),
+        }
+    }
+
+    pub fn at(dir: PathBuf) -> Self {
+        match probe(&dir) {
+            Ok(()) => Self { data_dir: Ok(dir) },
+            Err(err) => Self::off(format!("can't write {}: {err}", dir.display())),
+        }
+    }
+
+    pub fn off(reason: impl Into<String>) -> Self {
+        Self {
+            data_dir: Err(reason.into()),
+        }
+    }
+
+    // None when nothing is to be written
+    pub fn data_dir(&self) -> Option<&Path> {
+        self.data_dir.as_deref().ok()
+    }
+
+    // For the banner on the menu, and the one error every writer gets
+    pub fn off_reason(&self) -> Option<&str> {
+        self.data_dir.as_ref().err().map(String::as_str)
+    }
+
+    // A directory in the data directory, or why there isn't one
+    pub fn dir(&self, name: &str) -> io::Result<PathBuf> {
+        match &self.data_dir {
+            Ok(dir) => Ok(dir.join(name)),
+            Err(reason) => Err(io::Error::other(format!(
+                "running without persistence ({reason})"
+            ))),
+        }
+    }
+}
+
+fn probe(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let probe = dir.join(format!(".probe-{}", process::id()));
+    fs::write(&probe, "")?;
+    fs::remove_file(&probe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_data_directory_that_cant_be_written_turns_persistence_off() {
+        let base = env::temp_dir().join(format!("tetrs-persist-{}", process::id()));
+        let persistence = Persistence::at(base.clone());
+        assert_eq!(persistence.data_dir(), Some(base.as_path()));
+        assert_eq!(persistence.dir("replays").unwrap(), base.join("replays"));
+        // Nothing left behind by the probe
+        assert_eq!(fs::read_dir(&base).unwrap().count(), 0);
+
+        // Not even root can make a directory inside a file
+        let file = base.join("file");
+        fs::write(&file, "").unwrap();
+        let persistence = Persistence::at(file.join("tetrs"));
+        fs::remove_dir_all(&base).unwrap();
+        assert_eq!(persistence.data_dir(), None);
+        let reason = persistence.off_reason().unwrap();
+        assert!(reason.starts_with(&format!("can't write {}: ", file.join("tetrs").display())));
+        assert_eq!(
+            persistence.dir("crashes").unwrap_err().to_string(),
+            format!("running without persistence ({reason})")
+        );
+    }
+
+    #[test]
+    fn no_persist_writes_nothing() {
+        let persistence = Persistence::detect(false);
+        assert_eq!(persistence.data_dir(), None);
+        assert_eq!(persistence.off_reason(), Some("--no-persist"));
+    }
+}
@@ -0,0 +1,250 @@
+// `tetris doctor`: checks the environment without starting a game and prints a
+// report that can be pasted into a bug report as is.
+
+use std::{error::Error, io, path::Path};
+
+use crossterm::{style::available_color_count, terminal};
+
+use crate::{
+    config::Config,
+    dirs, preset,
+    ui::{self, Playfield},
+    PLAYFIELD_COLS, PLAYFIELD_ROWS, X_SCALING, Y_SCALING,
+};
+
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+fn report(status: Status, check: &str, remedy: Option<&str>) {
+    let status = match status {
+        Status::Pass => "pass",
+        Status::Warn => "warn",
+        Status::Fail => "FAIL",
+    };
+    match remedy {
+        Some(remedy) => println!("[{status}] {check}\n       -> {remedy}"),
+        None => println!("[{status}] {check}"),
+    }
+}
+
+// Whether the game could save into `dir`, found out without writing anything, the
+// doctor only looks. A directory that isn't there yet gets created by the first save,
+// so then it's the nearest one that is there that has to be writable, and that one
+// is returned.
+fn check_writable(dir: &Path) -> io::Result<Option<&Path>> {
+    let existing = dir
+        .ancestors()
+        .find(|ancestor| ancestor.exists())
+        .ok_or(io::ErrorKind::NotFound)?;
+    if !existing.is_dir() {
+        return Err(io::Error::other(format!(
+            "{} isn't a directory",
+            existing.display()
+        )));
+    }
+    if !writable(existing) {
+        return Err(io::ErrorKind::PermissionDenied.into());
+    }
+    Ok((existing != dir).then_some(existing))
+}
+
+// access() goes by the real user and all of its groups, the way creating a file would
+#[cfg(unix)]
+fn writable(dir: &Path) -> bool {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+    let Ok(path) = CString::new(dir.as_os_str().as_bytes()) else {
+        return false;
+    };
+    unsafe { libc::access(path.as_ptr(), libc::W_OK | libc::X_OK) == 0 }
+}
+
+#[cfg(not(unix))]
+fn writable(dir: &Path) -> bool {
+    std::fs::metadata(dir).is_ok_and(|metadata| !metadata.permissions().readonly())
+}
+
+pub fn run() -> Result<(), Box<dyn Error>> {
+    // Loaded first since the frame flags change the size needed, but reported
+    // after the terminal checks
+    let preset = preset()?;
+    let (mut config, load_error) = match Config::load(preset) {
+        Ok(config) => (config, None),
+        Err(err) => (Config::preset(preset), Some(err)),
+    };
+    config.apply_args(std::env::args())?;
+
+    let (width, height) = terminal::size()?;
+    let (min_width, min_height) = Playfield::required_terminal_size(
+        PLAYFIELD_COLS,
+        PLAYFIELD_ROWS,
+        X_SCALING,
+        Y_SCALING,
+        config.framing,
+        &config.hud,
+    );
+    let mut playfield = Playfield::new(
+        width,
+        height,
+        PLAYFIELD_COLS,
+        PLAYFIELD_ROWS,
+        X_SCALING,
+        Y_SCALING,
+        config.framing,
+    );
+    playfield.hud = config.hud.clone();
+    if width >= min_width && height >= min_height {
+        report(
+            Status::Pass,
+            &format!("terminal size {width}x{height} fits the {min_width}x{min_height} layout"),
+            None,
+        );
+    } else if playfield.fits() {
+        report(
+            Status::Warn,
+            &format!(
+                "terminal size {width}x{height} is below {min_width}x{min_height}, \
+                 some panels are left out"
+            ),
+            Some("enlarge the window, or take panels out of the hud setting"),
+        );
+    } else {
+        report(
+            Status::Fail,
+            &format!("terminal size {width}x{height} is below {min_width}x{min_height}"),
+            Some("enlarge the window or reduce the font size"),
+        );
+    }
+
+    if ui::color_supported() {
+        report(
+            Status::Pass,
+            &format!("{} colors available", available_color_count()),
+            None,
+        );
+    } else {
+        report(
+            Status::Warn,
+            "no color support detected, pieces are drawn as letters",
+            Some("unset NO_COLOR or set TERM to your terminal's real type"),
+        );
+    }
+
+    // Worth knowing on its own, the palette follows it
+    match (config.background, ui::Background::detect()) {
+        (Some(background), _) => report(
+            Status::Pass,
+            &format!("{} background, set in the config", background.name()),
+            None,
+        ),
+        (None, Some(background)) => report(
+            Status::Pass,
+            &format!("{} background, from COLORFGBG", background.name()),
+            None,
+        ),
+        (None, None) => report(
+            Status::Warn,
+            "background unknown, the colors assume a dark one",
+            Some("set background = \"light\" under [display] in the config for light terminals"),
+        ),
+    }
+
+    // crossterm can't ask the terminal which protocol it speaks, the game only finds out
+    // when the first key release comes in
+    report(
+        Status::Warn,
+        "keyboard enhancement protocol: unknown / not probed. Without the kitty keyboard \
+         protocol, held keys start repeating after the terminal's own repeat delay",
+        Some("lower your terminal's key repeat delay if holding a key feels sluggish"),
+    );
+
+    match dirs::data_dir() {
+        Some(dir) => match check_writable(&dir) {
+            Ok(None) => report(
+                Status::Pass,
+                &format!("data directory {} is writable", dir.display()),
+                None,
+            ),
+            Ok(Some(parent)) => report(
+                Status::Pass,
+                &format!(
+                    "data directory {} would be created, {} is writable",
+                    dir.display(),
+                    parent.display()
+                ),
+                None,
+            ),
+            Err(err) => report(
+                Status::Fail,
+                &format!("data directory {} can't be written: {err}", dir.display()),
+                Some("fix its permissions or point XDG_DATA_HOME somewhere writable"),
+            ),
+        },
+        None => report(
+            Status::Fail,
+            "no data directory, high scores won't be saved",
+            Some("set HOME or XDG_DATA_HOME"),
+        ),
+    }
+
+    let path = Config::path().map_or(String::from("(no home directory)"), |path| {
+        path.display().to_string()
+    });
+    match load_error {
+        None => {
+            let check = if Config::path().is_some_and(|path| path.exists()) {
+                format!("config {path} loaded over the {} preset", preset.name())
+            } else {
+                format!("no config at {path}, using the {} preset", preset.name())
+            };
+            report(Status::Pass, &check, None);
+            if let Some(settings) = Config::settings_path().filter(|path| path.exists()) {
+                let check = format!("settings from the menu {} read over it", settings.display());
+                report(Status::Pass, &check, None);
+            }
+        }
+        Some(err) => report(
+            Status::Fail,
+            &format!("config {}: {err}", err.file().unwrap_or_default().display()),
+            Some("fix the file or move it away to use the preset"),
+        ),
+    }
+
+    println!("\nkey bindings:");
+    for (code, action) in config.keymap.bindings() {
+        println!("  {:<12} {}", format!("{code:?}"), action.name());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::*;
+
+    #[test]
+    fn a_missing_data_directory_is_checked_without_creating_it() {
+        let base = env::temp_dir().join(format!("tetrs-doctor-{}", process::id()));
+        fs::create_dir_all(&base).unwrap();
+        let dir = base.join("share/tetrs");
+        let checked = check_writable(&dir).map_err(|err| err.to_string());
+        let created = dir.exists() || base.join("share").exists();
+        assert!(fs::read_dir(&base).unwrap().next().is_none());
+        fs::remove_dir(&base).unwrap();
+        assert_eq!(checked, Ok(Some(base.as_path())));
+        assert!(!created);
+    }
+
+    #[test]
+    fn a_file_in_the_way_isnt_a_data_directory() {
+        let file = env::temp_dir().join(format!("tetrs-doctor-file-{}", process::id()));
+        fs::write(&file, "").unwrap();
+        let dir = file.join("tetrs");
+        let checked = check_writable(&dir).map_err(|err| err.to_string());
+        fs::remove_file(&file).unwrap();
+        assert!(checked.unwrap_err().ends_with("isn't a directory"));
+    }
+}
@@ -0,0 +1,111 @@
+// The game with gravity and the lock delay running on a clock it's given, for
+// frontends that only have time and keys to pass on, like the browser demo and the C
+// ABI. Unlike the terminal game there's no pause after a lock: full rows go and the
+// next tetromino comes right away. Nothing in here needs threads or the system's
+// randomness, the seed comes from the caller.
+
+use std::time::Duration;
+
+use crate::{
+    game::TetrominosBag,
+    state::{Game, GameMode, Input, Outcome},
+};
+
+const STEP: Duration = Duration::from_millis(1);
+
+pub struct Engine {
+    game: Game,
+    // Since gravity last moved the tetromino, or it spawned
+    since_gravity: Duration,
+}
+
+impl Engine {
+    pub fn new(width: usize, height: usize, seed: u64, mode: GameMode) -> Self {
+        Self::with_game(Game::with_mode(
+            width,
+            height,
+            TetrominosBag::shuffled(seed),
+            mode,
+        ))
+    }
+
+    // A game set up some other way, with gravity starting now
+    pub fn with_game(mut game: Game) -> Self {
+        game.take_events();
+        Self {
+            game,
+            since_gravity: Duration::ZERO,
+        }
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    pub fn apply(&mut self, input: Input) -> Outcome {
+        let outcome = self.game.apply(input);
+        if outcome == Outcome::Moved && matches!(input, Input::SoftDrop | Input::Hold) {
+            self.since_gravity = Duration::ZERO;
+        }
+        self.settle(outcome)
+    }
+
+    // Runs the game on by `elapsed`, a millisecond at a time. Returns the last outcome
+    // that wasn't Blocked, if anything happened at all.
+    pub fn tick(&mut self, elapsed: Duration) -> Outcome {
+        let mut last = Outcome::Blocked;
+        let mut left = elapsed;
+        while left >= STEP && !self.game.is_over() {
+            left -= STEP;
+            self.since_gravity += STEP;
+            if self.since_gravity >= self.game.score().gravity_interval() {
+                self.since_gravity = Duration::ZERO;
+                let outcome = self.game.apply(Input::Tick);
+                last = self.keep(last, outcome);
+            }
+            let outcome = self.game.elapse(STEP);
+            last = self.keep(last, outcome);
+        }
+        last
+    }
+
+    // Row by row from the top, a byte per cell: '.' when empty, the letter of the
+    // tetromino for a locked cell and the lower case letter for the falling one
+    pub fn cells(&self) -> Vec<u8> {
+        self.game
+            .board()
+            .tiles
+            .iter()
+            .flatten()
+            .map(|cell| match cell {
+                None => b'.',
+                Some(cell) if cell.is_active => cell.shape.to_ascii_lowercase() as u8,
+                Some(cell) => cell.shape as u8,
+            })
+            .collect()
+    }
+
+    fn keep(&mut self, last: Outcome, outcome: Outcome) -> Outcome {
+        match self.settle(outcome) {
+            Outcome::Blocked => last,
+            outcome => outcome,
+        }
+    }
+
+    // Clears the rows of a lock on the spot, and starts gravity over for the next one
+    fn settle(&mut self, outcome: Outcome) -> Outcome {
+        let outcome = match outcome {
+            Outcome::Locked { lines } if lines > 0 => match self.game.finish_clear() {
+                Outcome::GameOver => Outcome::GameOver,
+                _ => outcome,
+            },
+            outcome => outcome,
+        };
+        if matches!(outcome, Outcome::Locked { .. }) {
+            self.since_gravity = Duration::ZERO;
+        }
+        // Nobody here reads them, they'd only pile up
+        self.game.take_events();
+        outcome
+    }
+}
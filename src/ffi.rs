@@ -0,0 +1,178 @@
+// A C ABI around Engine, for frontends that aren't written in Rust. Only plain
+// integers and bytes cross it, the game itself stays behind an opaque pointer. The
+// header is include/tetrs.h, made with cbindgen (see cbindgen.toml):
+//
+//     cbindgen --config cbindgen.toml --output include/tetrs.h src/ffi.rs
+//
+// and the library with `cargo build --release --no-default-features --features ffi`,
+// which leaves target/release/libtetris.so (or .dylib, .dll) to link against.
+
+use std::{ptr, slice, time::Duration};
+
+use crate::{
+    engine::Engine,
+    state::{GameMode, Input, Outcome},
+};
+
+/// Actions for tetrs_game_input()
+pub const TETRS_MOVE_LEFT: u32 = 0;
+pub const TETRS_MOVE_RIGHT: u32 = 1;
+pub const TETRS_SOFT_DROP: u32 = 2;
+pub const TETRS_HARD_DROP: u32 = 3;
+pub const TETRS_ROTATE_CW: u32 = 4;
+pub const TETRS_ROTATE_CCW: u32 = 5;
+pub const TETRS_HOLD: u32 = 6;
+
+/// What an input or some time did, see Outcome
+pub const TETRS_BLOCKED: i32 = 0;
+pub const TETRS_MOVED: i32 = 1;
+pub const TETRS_LOCKED: i32 = 2;
+pub const TETRS_GAME_OVER: i32 = 3;
+/// Not an action above
+pub const TETRS_UNKNOWN_ACTION: i32 = -1;
+
+/// Modes for TetrsConfig
+pub const TETRS_MARATHON: u32 = 0;
+pub const TETRS_SPRINT: u32 = 1;
+pub const TETRS_ULTRA: u32 = 2;
+pub const TETRS_ADAPTIVE: u32 = 3;
+
+#[repr(C)]
+pub struct TetrsConfig {
+    /// At least 4 by 4, the terminal game plays on 10 by 20
+    pub width: u32,
+    pub height: u32,
+    /// The same seed deals the same tetrominos
+    pub seed: u64,
+    /// One of TETRS_MARATHON, TETRS_SPRINT, TETRS_ULTRA or TETRS_ADAPTIVE
+    pub mode: u32,
+}
+
+/// Opaque to C
+pub struct TetrsGame {
+    engine: Engine,
+    width: usize,
+}
+
+fn outcome(outcome: Outcome) -> i32 {
+    match outcome {
+        Outcome::Blocked => TETRS_BLOCKED,
+        Outcome::Moved => TETRS_MOVED,
+        Outcome::Locked { .. } => TETRS_LOCKED,
+        Outcome::GameOver => TETRS_GAME_OVER,
+    }
+}
+
+/// Starts a game, or returns NULL if the config is NULL or out of range. The game
+/// is the caller's to free with tetrs_game_free().
+///
+/// # Safety
+///
+/// `config` is NULL or points to a TetrsConfig.
+#[no_mangle]
+pub unsafe extern "C" fn tetrs_game_new(config: *const TetrsConfig) -> *mut TetrsGame {
+    let Some(config) = config.as_ref() else {
+        return ptr::null_mut();
+    };
+    let mode = match config.mode {
+        TETRS_MARATHON => GameMode::Marathon,
+        TETRS_SPRINT => GameMode::Sprint,
+        TETRS_ULTRA => GameMode::Ultra,
+        TETRS_ADAPTIVE => GameMode::Adaptive,
+        _ => return ptr::null_mut(),
+    };
+    let (width, height) = (config.width as usize, config.height as usize);
+    if !(4..=255).contains(&width) || !(4..=255).contains(&height) {
+        return ptr::null_mut();
+    }
+    Box::into_raw(Box::new(TetrsGame {
+        engine: Engine::new(width, height, config.seed, mode),
+        width,
+    }))
+}
+
+/// Applies one of the TETRS_ actions, returns a TETRS_ outcome.
+///
+/// # Safety
+///
+/// `game` comes from tetrs_game_new() and hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn tetrs_game_input(game: *mut TetrsGame, action: u32) -> i32 {
+    let game = &mut *game;
+    let input = match action {
+        TETRS_MOVE_LEFT => Input::MoveLeft,
+        TETRS_MOVE_RIGHT => Input::MoveRight,
+        TETRS_SOFT_DROP => Input::SoftDrop,
+        TETRS_HARD_DROP => Input::HardDrop,
+        TETRS_ROTATE_CW => Input::RotateCw,
+        TETRS_ROTATE_CCW => Input::RotateCcw,
+        TETRS_HOLD => Input::Hold,
+        _ => return TETRS_UNKNOWN_ACTION,
+    };
+    outcome(game.engine.apply(input))
+}
+
+/// Runs gravity and the lock delay on by `ms` milliseconds, returns the last
+/// outcome that wasn't TETRS_BLOCKED, if anything happened.
+///
+/// # Safety
+///
+/// `game` comes from tetrs_game_new() and hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn tetrs_game_tick(game: *mut TetrsGame, ms: u32) -> i32 {
+    let game = &mut *game;
+    outcome(game.engine.tick(Duration::from_millis(ms.into())))
+}
+
+/// Copies the board into `out`, width times height bytes row by row from the top:
+/// '.' for an empty cell, the tetromino's letter for a locked one, the lower case
+/// letter for the falling one. Returns the number of bytes the board takes, and
+/// copies nothing if `len` is less, so a NULL `out` asks for the size.
+///
+/// # Safety
+///
+/// `game` comes from tetrs_game_new() and hasn't been freed, `out` is NULL or has
+/// room for `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn tetrs_game_board(
+    game: *const TetrsGame,
+    out: *mut u8,
+    len: usize,
+) -> usize {
+    let game = &*game;
+    let cells = game.engine.cells();
+    if !out.is_null() && len >= cells.len() {
+        slice::from_raw_parts_mut(out, cells.len()).copy_from_slice(&cells);
+    }
+    cells.len()
+}
+
+/// Board width in cells, the length of a row in tetrs_game_board().
+///
+/// # Safety
+///
+/// `game` comes from tetrs_game_new() and hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn tetrs_game_width(game: *const TetrsGame) -> u32 {
+    (*game).width as u32
+}
+
+/// # Safety
+///
+/// `game` comes from tetrs_game_new() and hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn tetrs_game_points(game: *const TetrsGame) -> u32 {
+    (*game).engine.game().score().points
+}
+
+/// Ends the game, NULL is ignored.
+///
+/// # Safety
+///
+/// `game` is NULL or comes from tetrs_game_new() and hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn tetrs_game_free(game: *mut TetrsGame) {
+    if !game.is_null() {
+        drop(Box::from_raw(game));
+    }
+}
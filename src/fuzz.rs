@@ -0,0 +1,354 @@
+// `tetris fuzz [--seconds N] [--seed N]`: plays random games as fast as they go for N
+// seconds (30 by default) and checks the game after every tick:
+//
+// - the falling tetromino is on the board, on cells nothing is locked in, and there
+//   are no other falling cells
+// - no row holds more locked cells than it has columns, and the row counters agree
+//   with the cells
+// - the points never go down
+// - every 7 tetrominos out of the bag are the 7 shapes once each
+//
+// Keys come at random ticks, pressed, repeated and released, the way Session gets them
+// from the terminal. The first game that breaks a rule has its inputs cut down to as
+// few as still break it, and is saved as a replay to the current directory, which
+// `tetris --replay` plays back. The seed printed with it gives the same run of games
+// again.
+
+use std::{
+    error::Error,
+    fmt,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use tetris::state::GameMode;
+
+use crate::{
+    arg_value,
+    input::{Action, RepeatSettings},
+    replay::{KeyKind, RecordedInput, Replay},
+    session::{Session, TICK},
+};
+
+const DEFAULT_SECONDS: u64 = 30;
+const INPUTS: usize = 400;
+// Pausing stops the clock and quitting is up to main, neither reaches the game
+const ACTIONS: [Action; 7] = [
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::SoftDrop,
+    Action::HardDrop,
+    Action::RotateCw,
+    Action::RotateCcw,
+    Action::Hold,
+];
+// Time the game runs on by itself after the last input
+const RUN_OUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug)]
+struct Violation {
+    // Index of the last input played before it, None if it was before the first one
+    after: Option<usize>,
+    tick: u64,
+    rule: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.after {
+            Some(input) => write!(f, "after input {input}, ")?,
+            None => write!(f, "before any input, ")?,
+        }
+        write!(f, "at tick {}: {}", self.tick, self.rule)
+    }
+}
+
+pub fn run() -> Result<(), Box<dyn Error>> {
+    let seconds = match arg_value("--seconds") {
+        Some(value) => value
+            .parse()
+            .ok()
+            .filter(|seconds| *seconds > 0)
+            .ok_or(format!("invalid --seconds value: {value}"))?,
+        None => DEFAULT_SECONDS,
+    };
+    let seed = match arg_value("--seed") {
+        Some(value) => value
+            .parse::<u64>()
+            .map_err(|_| format!("invalid --seed value: {value}"))?,
+        None => rand::random(),
+    };
+    let mut seeds = StdRng::seed_from_u64(seed);
+    let start = Instant::now();
+    let mut games = 0;
+    while start.elapsed() < Duration::from_secs(seconds) {
+        let replay = random_game(seeds.gen());
+        let Err(violation) = check(&replay) else {
+            games += 1;
+            continue;
+        };
+        println!("game {} of seed {seed} broke a rule {violation}", games + 1);
+        // Nothing after the input that broke it can have played a part
+        let mut replay = replay;
+        replay
+            .inputs
+            .truncate(violation.after.map_or(0, |input| input + 1));
+        let replay = minimize(replay, |replay| check(replay).is_err());
+        let violation = check(&replay).expect_err("only kept while it fails");
+        let replay = with_hashes(replay);
+        let path = PathBuf::from(format!("fuzz-{}.json", replay.seed));
+        replay.save(&path)?;
+        println!(
+            "cut down to {} inputs, breaking it {violation}:",
+            replay.inputs.len()
+        );
+        for (index, input) in replay.inputs.iter().enumerate() {
+            println!(
+                "  {index:>3}  tick {:>6}  {:?} {:?}",
+                input.tick, input.action, input.kind
+            );
+        }
+        println!("saved as {}, play it with --replay", path.display());
+        return Err("an invariant was broken".into());
+    }
+    println!("{games} games in {seconds}s, every rule held (seed {seed})");
+    Ok(())
+}
+
+// Game `seed` of a run: the seed goes to the bag as well as to the inputs
+fn random_game(seed: u64) -> Replay {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mode = *GameMode::ALL.choose(&mut rng).unwrap();
+    let mut replay = Replay::new(seed, 1, mode.ruleset(), RepeatSettings::default());
+    let mut tick = 0;
+    let mut held = Vec::new();
+    for _ in 0..INPUTS {
+        // Mostly quick keys, now and then a wait long enough for gravity and the lock
+        // delay to do something
+        tick += if rng.gen_ratio(1, 10) {
+            rng.gen_range(500..3000)
+        } else {
+            rng.gen_range(0..150)
+        };
+        let action = *ACTIONS.choose(&mut rng).unwrap();
+        let kind = if !action.is_movement() {
+            KeyKind::Press
+        } else if held.contains(&action) {
+            if rng.gen_bool(0.5) {
+                held.retain(|key| *key != action);
+                KeyKind::Release
+            } else {
+                KeyKind::Repeat
+            }
+        } else {
+            held.push(action);
+            KeyKind::Press
+        };
+        replay.inputs.push(RecordedInput {
+            tick,
+            action,
+            kind,
+            hash: None,
+        });
+    }
+    replay
+}
+
+// Plays the game one tick at a time and checks every rule after each of them
+fn check(replay: &Replay) -> Result<(), Violation> {
+    let mut session = Session::new(replay.game(), replay.repeat());
+    let mut watch = Watch::default();
+    let mut after = None;
+    let ends = replay
+        .inputs
+        .iter()
+        .map(|input| Duration::from_millis(input.tick))
+        .chain([
+            Duration::from_millis(replay.inputs.last().map_or(0, |input| input.tick)) + RUN_OUT,
+        ]);
+    for (index, end) in ends.enumerate() {
+        loop {
+            if let Some(rule) = watch.broken_rule(&session) {
+                return Err(Violation {
+                    after,
+                    tick: session.tick_count(),
+                    rule,
+                });
+            }
+            if session.clock() >= end || session.game().is_over() {
+                break;
+            }
+            session.advance(session.clock() + TICK);
+        }
+        if let Some(input) = replay.inputs.get(index) {
+            session.key(input.action, input.kind.into());
+            after = Some(index);
+        }
+    }
+    Ok(())
+}
+
+// What earlier ticks left to compare with
+#[derive(Default)]
+struct Watch {
+    points: u32,
+    // Whole bags already checked
+    bags: usize,
+}
+
+impl Watch {
+    fn broken_rule(&mut self, session: &Session) -> Option<String> {
+        let game = session.game();
+        let board = game.board();
+        let (columns, rows) = (board.tiles[0].len(), board.tiles.len());
+
+        // A locked tetromino stays the current one while its rows flash, and the one a
+        // top out couldn't spawn overlaps the stack
+        if !game.is_clearing() && !game.is_over() {
+            let body = game.tetromino().body();
+            for (x, y) in body {
+                if x >= columns || y >= rows {
+                    return Some(format!("the tetromino is off the board at ({x}, {y})"));
+                }
+                if !matches!(board.tiles[y][x], Some(cell) if cell.is_active) {
+                    return Some(format!(
+                        "the tetromino overlaps a locked cell at ({x}, {y})"
+                    ));
+                }
+            }
+            for (y, row) in board.tiles.iter().enumerate() {
+                for (x, cell) in row.iter().enumerate() {
+                    if matches!(cell, Some(cell) if cell.is_active) && !body.contains(&(x, y)) {
+                        return Some(format!(
+                            "a falling cell at ({x}, {y}) isn't the tetromino's"
+                        ));
+                    }
+                }
+            }
+        }
+
+        let counted = board.row_fill_counts();
+        for (y, row) in board.tiles.iter().enumerate() {
+            let locked = row
+                .iter()
+                .filter(|cell| matches!(cell, Some(cell) if !cell.is_active))
+                .count();
+            if usize::from(counted[y]) > columns {
+                return Some(format!("row {y} holds {} cells", counted[y]));
+            }
+            if usize::from(counted[y]) != locked {
+                return Some(format!(
+                    "row {y} is counted as {} cells but holds {locked}",
+                    counted[y]
+                ));
+            }
+        }
+
+        let points = game.score().points;
+        if points < self.points {
+            return Some(format!(
+                "the points went down from {} to {points}",
+                self.points
+            ));
+        }
+        self.points = points;
+
+        // The last bag can still be dealing, it only has to be without repeats so far
+        for bag in session.dealt()[self.bags * 7..].chunks(7) {
+            let mut shapes = bag.to_vec();
+            shapes.sort_unstable();
+            shapes.dedup();
+            if shapes.len() != bag.len() {
+                let bag: String = bag.iter().collect();
+                return Some(format!("the bag dealt {bag}"));
+            }
+            if bag.len() == 7 {
+                self.bags += 1;
+            }
+        }
+        None
+    }
+}
+
+// Drops every input the game still fails without, one at a time from the last
+fn minimize(mut replay: Replay, fails: impl Fn(&Replay) -> bool) -> Replay {
+    for index in (0..replay.inputs.len()).rev() {
+        let input = replay.inputs.remove(index);
+        if !fails(&replay) {
+            replay.inputs.insert(index, input);
+        }
+    }
+    replay
+}
+
+// The state after each input, so that playing the saved game back tells right away
+// if it goes another way
+fn with_hashes(mut replay: Replay) -> Replay {
+    let mut session = Session::new(replay.game(), replay.repeat());
+    for input in &mut replay.inputs {
+        session.advance(Duration::from_millis(input.tick));
+        session.key(input.action, input.kind.into());
+        input.hash = Some(session.game().state_hash());
+    }
+    replay
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn random_games_keep_to_the_rules() {
+        let mut seeds = StdRng::seed_from_u64(7);
+        for _ in 0..3 {
+            let replay = random_game(seeds.gen());
+            if let Err(violation) = check(&replay) {
+                panic!("game {} broke a rule {violation}", replay.seed);
+            }
+        }
+    }
+
+    #[test]
+    fn the_same_seed_makes_the_same_game() {
+        let inputs = |replay: Replay| -> Vec<(u64, Action)> {
+            replay
+                .inputs
+                .iter()
+                .map(|input| (input.tick, input.action))
+                .collect()
+        };
+        assert_eq!(inputs(random_game(3)), inputs(random_game(3)));
+        assert_ne!(inputs(random_game(3)), inputs(random_game(4)));
+    }
+
+    #[test]
+    fn a_failing_game_is_cut_down_to_the_inputs_it_needs() {
+        let hard_drops = |replay: &Replay| {
+            replay
+                .inputs
+                .iter()
+                .filter(|input| input.action == Action::HardDrop)
+                .count()
+        };
+        let replay = random_game(11);
+        assert!(hard_drops(&replay) > 2);
+        let minimized = minimize(replay, |replay| hard_drops(replay) >= 2);
+        assert_eq!(minimized.inputs.len(), 2);
+        assert_eq!(hard_drops(&minimized), 2);
+    }
+
+    #[test]
+    fn the_saved_game_plays_back_the_same_way() {
+        let mut replay = random_game(5);
+        replay.inputs.truncate(50);
+        let replay = with_hashes(replay);
+        let mut session = Session::new(replay.game(), replay.repeat());
+        for (index, input) in replay.inputs.iter().enumerate() {
+            session.advance(Duration::from_millis(input.tick));
+            session.key(input.action, input.kind.into());
+            input.check(index, session.game().state_hash()).unwrap();
+        }
+    }
+}
@@ -1,21 +1,56 @@
 #![allow(dead_code)]
 
-use std::{collections::HashMap, mem::swap};
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fmt,
+    mem::{swap, take},
+};
 
-use tui::style::Color;
-
-use rand::{seq::SliceRandom, thread_rng};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use serde::{Deserialize, Serialize};
 
 type Coordinates = (usize, usize);
 
+// The grid the game is played on, with the falling tetromino written into it as
+// active cells. This is the source of truth, the ui only ever reads it.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Board {
+    pub tiles: Vec<Vec<Option<Playcell>>>,
+    // Locked cells in each row
+    pub(crate) row_fill: Vec<u8>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Playcell {
+    pub is_active: bool,
+    // Letter of the tetromino the cell belongs to, 'G' for garbage. Colors are up to
+    // whatever draws the board.
+    pub shape: char,
+}
+
+impl Playcell {
+    pub fn new(is_active: bool, shape: char) -> Self {
+        Self { is_active, shape }
+    }
+
+    // Character drawn for the cell when colors aren't available
+    pub fn glyph(&self) -> char {
+        match self.shape {
+            'G' => '#',
+            shape => shape,
+        }
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct Tetromino {
     shape: char,
     body: [Coordinates; 4],
-    color: Color,
     rotation: RotationState,
 }
 
+#[derive(Clone, Copy)]
 pub enum Direction {
     Up,
     Down,
@@ -28,8 +63,85 @@ pub enum ShiftError {
     BottomCollision,
 }
 
-use crate::ui::{Playcell, Playfield};
+pub enum SpawnError {
+    Blocked,
+}
+
+// Why a kick didn't fit
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum KickFailure {
+    // A cell would have gone past a wall, the floor or the top
+    OutOfBounds,
+    // A cell would have overlapped the stack
+    Blocked,
+}
+
+// One kick tried during a rotation, with the cells the tetromino would have taken,
+// some of them possibly off the board
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct KickAttempt {
+    pub index: usize,
+    pub cells: [(i32, i32); 4],
+    // None for the kick that was taken
+    pub failure: Option<KickFailure>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TSpin {
+    Mini,
+    Full,
+}
+
+#[derive(Debug)]
+pub enum BoardTextError {
+    Empty,
+    TooManyRows {
+        rows: usize,
+        max: usize,
+    },
+    WrongWidth {
+        row: usize,
+        width: usize,
+        expected: usize,
+    },
+    UnknownCell {
+        row: usize,
+        column: usize,
+        cell: char,
+    },
+}
+
+impl fmt::Display for BoardTextError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BoardTextError::Empty => write!(f, "board has no cells"),
+            BoardTextError::TooManyRows { rows, max } => {
+                write!(f, "board has {rows} rows but at most {max} fit")
+            }
+            BoardTextError::WrongWidth {
+                row,
+                width,
+                expected,
+            } => write!(f, "row {row} is {width} cells wide, expected {expected}"),
+            BoardTextError::UnknownCell { row, column, cell } => write!(
+                f,
+                "unknown cell '{cell}' at row {row}, column {column} (expected '.', 'G' or a piece letter)"
+            ),
+        }
+    }
+}
+
+impl Error for BoardTextError {}
+
 impl Tetromino {
+    pub fn shape(&self) -> char {
+        self.shape
+    }
+
+    pub fn body(&self) -> [(usize, usize); 4] {
+        self.body
+    }
+
     fn get_length(&self) -> usize {
         let mut max = 0;
         let mut min = 0;
@@ -40,29 +152,69 @@ impl Tetromino {
         max + min + 1
     }
     // Place the tetromino lowest point in the middle of the playfield's 22nd line
-    pub fn spawn(&mut self, playfield: &mut Playfield) {
-        let middle_point: usize = playfield.get_x_midpoint() - (self.get_length() / 2);
+    // Nothing is written to the playfield if any of the target cells is already taken
+    pub fn spawn(&mut self, board: &mut Board) -> Result<(), SpawnError> {
+        let middle_point: usize = board.get_x_midpoint() - (self.get_length() / 2);
+        let mut new_body = self.body;
         // y remains unchanged, default coordinates already accommodate for it
-        for (x, y) in &mut self.body {
+        for (x, _) in &mut new_body {
             *x += middle_point;
-            playfield.tiles[*y][*x] = Some(Playcell::new(true, self.color));
         }
+        self.collides(&new_body, board, Direction::Down)
+            .map_err(|_| SpawnError::Blocked)?;
+        for (x, y) in new_body {
+            board.tiles[y][x] = Some(Playcell::new(true, self.shape));
+        }
+        self.body = new_body;
+        Ok(())
     }
 
-    pub fn place_in_playfield(self, playfield: &mut Playfield) {
+    pub fn place_in_playfield(self, board: &mut Board) {
         for (x, y) in self.body {
-            if let Some(cell) = &mut playfield.tiles[y][x] {
+            if let Some(cell) = &mut board.tiles[y][x] {
                 cell.is_active = false;
+                board.row_fill[y] += 1;
+            }
+        }
+        board.check_row_fill();
+    }
+
+    // Undoes place_in_playfield(), the tetromino can move again
+    pub fn lift_from_playfield(&self, board: &mut Board) {
+        for (x, y) in self.body {
+            if let Some(cell) = &mut board.tiles[y][x] {
+                cell.is_active = true;
+                board.row_fill[y] -= 1;
             }
         }
+        board.check_row_fill();
+    }
+
+    // Rows covered by the tetromino, from top to bottom
+    pub fn rows(&self) -> Vec<usize> {
+        let mut rows: Vec<usize> = self.body.iter().map(|(_, y)| *y).collect();
+        rows.sort_unstable();
+        rows.dedup();
+        rows
+    }
+
+    // Clockwise from spawn, 0 to 3
+    pub fn quarter_turns(&self) -> u8 {
+        self.rotation as u8
+    }
+
+    pub fn remove_from_playfield(&self, board: &mut Board) {
+        for (x, y) in self.body {
+            board.tiles[y][x] = None;
+        }
     }
 
-    pub fn change_position(&mut self, new_body: &[Coordinates], playfield: &mut Playfield) {
+    pub fn change_position(&mut self, new_body: &[Coordinates], board: &mut Board) {
         for (x, y) in &self.body {
-            playfield.tiles[*y][*x] = None;
+            board.tiles[*y][*x] = None;
         }
         for (x, y) in new_body {
-            playfield.tiles[*y][*x] = Some(Playcell::new(true, self.color));
+            board.tiles[*y][*x] = Some(Playcell::new(true, self.shape));
         }
         self.body = new_body.try_into().unwrap();
     }
@@ -70,11 +222,11 @@ impl Tetromino {
     pub fn collides(
         &self,
         new_body: &[Coordinates],
-        playfield: &mut Playfield,
+        board: &Board,
         direction: Direction,
     ) -> Result<(), ShiftError> {
         for (x, y) in new_body {
-            if let Some(playcell) = playfield
+            if let Some(playcell) = board
                 .tiles
                 .get(*y)
                 .ok_or(ShiftError::BottomCollision)?
@@ -92,11 +244,7 @@ impl Tetromino {
         Ok(())
     }
 
-    pub fn shift(
-        &mut self,
-        playfield: &mut Playfield,
-        direction: Direction,
-    ) -> Result<(), ShiftError> {
+    pub fn shift(&mut self, board: &mut Board, direction: Direction) -> Result<(), ShiftError> {
         let mut new_body = self.body;
         match direction {
             Direction::Up => {
@@ -109,55 +257,84 @@ impl Tetromino {
                     *y += 1;
                 }
             }
-            Direction::Right => {
+            Direction::Left => {
                 for (x, _) in &mut new_body {
                     *x = x.checked_sub(1).ok_or(ShiftError::BorderCollision)?;
                 }
             }
-            Direction::Left => {
+            Direction::Right => {
                 for (x, _) in &mut new_body {
                     *x += 1;
                 }
             }
         };
-        self.collides(&new_body, playfield, direction)?;
-        self.change_position(&new_body, playfield);
+        self.collides(&new_body, board, direction)?;
+        self.change_position(&new_body, board);
         Ok(())
     }
 
-    pub fn hard_drop(&mut self, playfield: &mut Playfield) -> ShiftError {
+    // Where the tetromino would land if hard dropped, nothing gets moved. One that
+    // doesn't fit where it is, like the one left over after a blocked spawn, stays put.
+    pub fn ghost_body(&self, board: &Board) -> [Coordinates; 4] {
+        if self.collides(&self.body, board, Direction::Down).is_err() {
+            return self.body;
+        }
         let mut new_body = self.body;
-        while self.collides(&new_body, playfield, Direction::Down).is_ok() {
-                for (_, y) in &mut new_body {
-                    *y += 1;
-                }
+        while self.collides(&new_body, board, Direction::Down).is_ok() {
+            for (_, y) in &mut new_body {
+                *y += 1;
+            }
         }
         for (_, y) in &mut new_body {
             *y -= 1;
         }
-        self.change_position(&new_body, playfield);
-        ShiftError::BottomCollision
+        new_body
     }
 
-    pub fn rotate(&mut self, playfield: &mut Playfield, clockwise: bool) {
-        if self.shape == 'O' {
-            return;
+    // Copy of the tetromino at its landing position, for drawing the ghost
+    pub fn ghost(&self, board: &Board) -> Tetromino {
+        Tetromino {
+            body: self.ghost_body(board),
+            ..*self
         }
+    }
+
+    // Drops straight onto the stack, returns how many rows the tetromino fell
+    pub fn hard_drop(&mut self, board: &mut Board) -> usize {
+        let new_body = self.ghost_body(board);
+        let distance = new_body[0].1 - self.body[0].1;
+        self.change_position(&new_body, board);
+        distance
+    }
+
+    // Returns the index of the first kick that fit, the tetromino is left untouched if
+    // none did
+    pub fn rotate(&mut self, board: &mut Board, clockwise: bool) -> Option<usize> {
+        self.rotate_traced(board, clockwise)
+            .last()
+            .filter(|attempt| attempt.failure.is_none())
+            .map(|attempt| attempt.index)
+    }
+
+    // Same as rotate(), but returns every kick tried up to the one that fit, or all of
+    // them when none did
+    pub fn rotate_traced(&mut self, board: &mut Board, clockwise: bool) -> Vec<KickAttempt> {
         // The pivot is always the first element of the body array
-        let x_pivot: i32 = self.body[0].0.try_into().unwrap();
-        let y_pivot: i32 = self.body[0].1.try_into().unwrap();
-        let mut new_coords = Vec::new();
-        for (x, y) in &mut self.body {
-            let mut x_relative: i32 = *x as i32 - x_pivot;
-            let mut y_relative: i32 = *y as i32 - y_pivot;
-            swap(&mut x_relative, &mut y_relative);
-            if clockwise {
-                x_relative = -x_relative;
-            } else {
-                y_relative = -y_relative;
+        let (x_pivot, y_pivot) = (self.body[0].0 as i32, self.body[0].1 as i32);
+        let rotated = self.body.map(|(x, y)| {
+            let mut x_relative = x as i32 - x_pivot;
+            let mut y_relative = y as i32 - y_pivot;
+            // The O tetromino looks the same in every state, only the state changes
+            if self.shape != 'O' {
+                swap(&mut x_relative, &mut y_relative);
+                if clockwise {
+                    x_relative = -x_relative;
+                } else {
+                    y_relative = -y_relative;
+                }
             }
-            new_coords.push(((x_relative + x_pivot), (y_relative + y_pivot)));
-        }
+            (x_relative + x_pivot, y_relative + y_pivot)
+        });
         let new_rotation = match self.rotation {
             RotationState::Normal if clockwise => RotationState::QuarterTurned,
             RotationState::Normal => RotationState::ThreeQuartersTurned,
@@ -169,76 +346,89 @@ impl Tetromino {
             RotationState::ThreeQuartersTurned => RotationState::HalfTurned,
         };
 
-        let from_rotation = if self.shape == 'I' {
-            HashMap::from([
-                (
-                    RotationState::Normal,
-                    [(0, 0), (-1, 0), (2, 0), (-1, 0), (2, 0)],
-                ),
-                (
-                    RotationState::QuarterTurned,
-                    [(-1, 0), (0, 0), (0, 0), (0, 1), (0, -2)],
-                ),
-                (
-                    RotationState::HalfTurned,
-                    [(-1, 1), (1, 1), (-2, 1), (1, 0), (-2, 0)],
-                ),
-                (
-                    RotationState::ThreeQuartersTurned,
-                    [(0, 1), (0, 1), (0, 1), (0, -1), (0, 2)],
-                ),
-            ])
-        } else {
-            HashMap::from([
-                (
-                    RotationState::Normal,
-                    [(0, 0), (0, 0), (0, 0), (0, 0), (0, 0)],
-                ),
-                (
-                    RotationState::QuarterTurned,
-                    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
-                ),
-                (
-                    RotationState::HalfTurned,
-                    [(0, 0), (0, 0), (0, 0), (0, 0), (0, 0)],
-                ),
-                (
-                    RotationState::ThreeQuartersTurned,
-                    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
-                ),
-            ])
-        };
-
-        // Behold! A hideous piece of code
-        let wallkick_tries: Vec<(i32, i32)> = from_rotation
-            .get(&self.rotation)
-            .unwrap()
-            .iter()
-            .zip(from_rotation.get(&new_rotation).unwrap())
-            .map(|((x_from, y_from), (x_to, y_to))| -> (i32, i32) {
+        let (x_center, y_center) = match self.shape {
+            'I' => {
+                let (x_from, y_from) = I_CENTER_OFFSETS[self.rotation as usize];
+                let (x_to, y_to) = I_CENTER_OFFSETS[new_rotation as usize];
                 (x_from - x_to, y_from - y_to)
-            })
-            .collect();
-
-        for (x_to_try, y_to_try) in wallkick_tries {
-            let to_try: Vec<(usize, usize)> = new_coords
-                .iter_mut()
-                .map(|(x, y)| {
-                    (
-                        ((*x).checked_add(x_to_try).unwrap()) as usize,
-                        ((*y).checked_sub(y_to_try).unwrap()) as usize,
-                    )
+            }
+            _ => (0, 0),
+        };
+        let mut attempts = Vec::new();
+        for (index, (x_kick, y_kick)) in kicks(self.shape, self.rotation, new_rotation)
+            .into_iter()
+            .enumerate()
+        {
+            // The tables count y upwards, the board downwards
+            let x_offset = x_kick + x_center;
+            let y_offset = -(y_kick + y_center);
+            let cells = rotated.map(|(x, y)| (x + x_offset, y + y_offset));
+            let (width, height) = (board.tiles[0].len(), board.tiles.len());
+            let candidate: Option<Vec<Coordinates>> = cells
+                .iter()
+                .map(|&(x, y)| {
+                    let (x, y) = (usize::try_from(x).ok()?, usize::try_from(y).ok()?);
+                    (x < width && y < height).then_some((x, y))
                 })
                 .collect();
-            if self.collides(&to_try, playfield, Direction::Up).is_ok() {
-                self.change_position(&to_try, playfield);
-                self.rotation = new_rotation;
+            let failure = match candidate {
+                None => Some(KickFailure::OutOfBounds),
+                Some(candidate) if self.collides(&candidate, board, Direction::Up).is_err() => {
+                    Some(KickFailure::Blocked)
+                }
+                Some(candidate) => {
+                    self.change_position(&candidate, board);
+                    self.rotation = new_rotation;
+                    None
+                }
+            };
+            attempts.push(KickAttempt {
+                index,
+                cells,
+                failure,
+            });
+            if failure.is_none() {
                 break;
             }
         }
+        attempts
+    }
+
+    // Whether a T that just got into place with the given kick is a T-spin. At least
+    // three of the corners around its center have to be taken, walls and floor
+    // included. With both corners on its pointy side taken it's a full one, otherwise
+    // a mini, unless it took the last kick to get there.
+    pub fn t_spin(&self, board: &Board, kick: usize) -> Option<TSpin> {
+        if self.shape != 'T' {
+            return None;
+        }
+        let (x, y) = self.body[0];
+        let taken =
+            |dx: isize, dy: isize| match (x.checked_add_signed(dx), y.checked_add_signed(dy)) {
+                (Some(x), Some(y)) => board.is_occupied(x, y),
+                _ => true,
+            };
+        // Front corners first, from the spawn state on clockwise the T points up, right,
+        // down and left
+        let corners = match self.rotation {
+            RotationState::Normal => [(-1, -1), (1, -1), (-1, 1), (1, 1)],
+            RotationState::QuarterTurned => [(1, -1), (1, 1), (-1, -1), (-1, 1)],
+            RotationState::HalfTurned => [(-1, 1), (1, 1), (-1, -1), (1, -1)],
+            RotationState::ThreeQuartersTurned => [(-1, -1), (-1, 1), (1, -1), (1, 1)],
+        };
+        let taken = corners.map(|(dx, dy)| taken(dx, dy));
+        if taken.iter().filter(|taken| **taken).count() < 3 {
+            return None;
+        }
+        if (taken[0] && taken[1]) || kick == 4 {
+            Some(TSpin::Full)
+        } else {
+            Some(TSpin::Mini)
+        }
     }
 }
 
+// Declaration order matters, I_CENTER_OFFSETS is indexed by it
 #[derive(Clone, Copy, Eq, PartialEq, Hash)]
 enum RotationState {
     Normal,
@@ -247,76 +437,331 @@ enum RotationState {
     ThreeQuartersTurned,
 }
 
+type Kicks = [(i32, i32); 5];
+
+// SRS wall kicks for every rotation, as (from, to, tests) with x to the right and y
+// upwards like in the guideline. The first test is the rotation in place.
+#[rustfmt::skip]
+const JLSTZ_KICKS: [(RotationState, RotationState, Kicks); 8] = {
+    use RotationState::*;
+    [
+        (Normal, QuarterTurned, [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)]),
+        (QuarterTurned, Normal, [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)]),
+        (QuarterTurned, HalfTurned, [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)]),
+        (HalfTurned, QuarterTurned, [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)]),
+        (HalfTurned, ThreeQuartersTurned, [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)]),
+        (ThreeQuartersTurned, HalfTurned, [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)]),
+        (ThreeQuartersTurned, Normal, [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)]),
+        (Normal, ThreeQuartersTurned, [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)]),
+    ]
+};
+
+#[rustfmt::skip]
+const I_KICKS: [(RotationState, RotationState, Kicks); 8] = {
+    use RotationState::*;
+    [
+        (Normal, QuarterTurned, [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)]),
+        (QuarterTurned, Normal, [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)]),
+        (QuarterTurned, HalfTurned, [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)]),
+        (HalfTurned, QuarterTurned, [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)]),
+        (HalfTurned, ThreeQuartersTurned, [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)]),
+        (ThreeQuartersTurned, HalfTurned, [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)]),
+        (ThreeQuartersTurned, Normal, [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)]),
+        (Normal, ThreeQuartersTurned, [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)]),
+    ]
+};
+
+// The I tetromino turns around the middle of its 4x4 box rather than around one of its
+// cells. Rotating around the pivot cell leaves it off by the difference between these.
+const I_CENTER_OFFSETS: [(i32, i32); 4] = [(0, 0), (-1, 0), (-1, 1), (0, 1)];
+
+fn kicks(shape: char, from: RotationState, to: RotationState) -> Kicks {
+    let table = match shape {
+        // It always fits where it already is
+        'O' => return [(0, 0); 5],
+        'I' => &I_KICKS,
+        _ => &JLSTZ_KICKS,
+    };
+    table
+        .iter()
+        .find(|(table_from, table_to, _)| (*table_from, *table_to) == (from, to))
+        .map(|(_, _, kicks)| *kicks)
+        .unwrap()
+}
+
+// Every tetromino at its spawn coordinates
+const TEMPLATES: [Tetromino; 7] = [
+    // the first tuple is the center, necessary for rotation
+    Tetromino {
+        shape: 'O',
+        body: [(0, 0), (0, 1), (1, 0), (1, 1)],
+        rotation: RotationState::Normal,
+    },
+    Tetromino {
+        shape: 'I',
+        body: [(1, 0), (2, 0), (0, 0), (3, 0)],
+        rotation: RotationState::Normal,
+    },
+    Tetromino {
+        shape: 'J',
+        body: [(1, 1), (0, 1), (0, 0), (2, 1)],
+        rotation: RotationState::Normal,
+    },
+    Tetromino {
+        shape: 'L',
+        body: [(1, 1), (0, 1), (2, 1), (2, 0)],
+        rotation: RotationState::Normal,
+    },
+    Tetromino {
+        shape: 'S',
+        body: [(1, 1), (0, 1), (1, 0), (2, 0)],
+        rotation: RotationState::Normal,
+    },
+    Tetromino {
+        shape: 'Z',
+        body: [(1, 1), (1, 0), (0, 0), (2, 1)],
+        rotation: RotationState::Normal,
+    },
+    Tetromino {
+        shape: 'T',
+        body: [(1, 1), (0, 1), (1, 0), (2, 1)],
+        rotation: RotationState::Normal,
+    },
+];
+
+// What a scripted sequence does once every tetromino in it has been dealt. Bags
+// never run out: the next one is always shuffled ahead of time.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SequenceEnd {
+    // Deals it again from the start
+    Loop,
+    // Goes on with shuffled bags of seven
+    Bag,
+    // Nothing comes after it, the game is complete once the last tetromino locks and
+    // nothing is left in hold either
+    End,
+}
+
+#[derive(Clone)]
 pub struct TetrominosBag {
-    tetrominos: [Tetromino; 7],
+    tetrominos: Vec<Tetromino>,
+    // Shuffled ahead of time so the preview can look past the end of the current bag.
+    // What comes after a scripted sequence, empty when nothing does.
+    next_bag: Vec<Tetromino>,
     index: usize,
+    rng: StdRng,
+    // Until a scripted sequence has been dealt in full
+    script_end: Option<SequenceEnd>,
+    // Every order a shuffle came to, as shapes, and the orders to deal instead of
+    // shuffling while there are any left
+    decisions: Vec<String>,
+    recorded: VecDeque<Vec<Tetromino>>,
 }
 
 impl TetrominosBag {
+    // In template order until shuffled. The shuffles follow a fixed seed until
+    // reseed() gives it another one, the engine never picks one by itself.
     pub fn new() -> Self {
+        let tetrominos = TEMPLATES.to_vec();
         Self {
-            tetrominos: [
-                // the first tuple is the center, necessary for rotation
-                Tetromino {
-                    shape: 'O',
-                    body: [(0, 0), (0, 1), (1, 0), (1, 1)],
-                    color: Color::Yellow,
-                    rotation: RotationState::Normal,
-                },
-                Tetromino {
-                    shape: 'I',
-                    body: [(1, 0), (2, 0), (0, 0), (3, 0)],
-                    color: Color::Cyan,
-                    rotation: RotationState::Normal,
-                },
-                Tetromino {
-                    shape: 'J',
-                    body: [(1, 1), (0, 1), (0, 0), (2, 1)],
-                    color: Color::Gray,
-                    rotation: RotationState::Normal,
-                },
-                Tetromino {
-                    shape: 'L',
-                    body: [(1, 1), (0, 1), (2, 1), (2, 0)],
-                    color: Color::Blue,
-                    rotation: RotationState::Normal,
-                },
-                Tetromino {
-                    shape: 'S',
-                    body: [(1, 1), (0, 1), (1, 0), (2, 0)],
-                    color: Color::Green,
-                    rotation: RotationState::Normal,
-                },
-                Tetromino {
-                    shape: 'Z',
-                    body: [(1, 1), (1, 0), (0, 0), (2, 1)],
-                    color: Color::Red,
-                    rotation: RotationState::Normal,
-                },
-                Tetromino {
-                    shape: 'T',
-                    body: [(1, 1), (0, 1), (1, 0), (2, 1)],
-                    color: Color::White,
-                    rotation: RotationState::Normal,
-                },
-            ],
+            next_bag: tetrominos.clone(),
+            tetrominos,
+            index: 0,
+            rng: StdRng::seed_from_u64(0),
+            script_end: None,
+            decisions: Vec::new(),
+            recorded: VecDeque::new(),
+        }
+    }
+
+    // The tetrominos in the order of the shapes, None if there aren't any or one isn't
+    // a tetromino
+    pub fn scripted(shapes: &str, end: SequenceEnd) -> Option<Self> {
+        let tetrominos: Vec<Tetromino> = shapes.chars().map(template).collect::<Option<_>>()?;
+        if tetrominos.is_empty() {
+            return None;
+        }
+        let next_bag = match end {
+            SequenceEnd::Loop => tetrominos.clone(),
+            SequenceEnd::Bag => TEMPLATES.to_vec(),
+            SequenceEnd::End => Vec::new(),
+        };
+        Some(Self {
+            tetrominos,
+            next_bag,
             index: 0,
+            rng: StdRng::seed_from_u64(0),
+            script_end: Some(end),
+            decisions: Vec::new(),
+            recorded: VecDeque::new(),
+        })
+    }
+
+    // Ready to deal, in an order that follows from the seed
+    pub fn shuffled(seed: u64) -> Self {
+        let mut bag = Self::new();
+        bag.reseed(seed);
+        bag.shuffle();
+        bag
+    }
+
+    // Every shuffle from now on follows from the seed, so the same seed deals the same
+    // tetrominos in the same order
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    // Several copies of the seven tetrominos shuffled together, each one shows up
+    // exactly bag_count times per cycle
+    pub fn with_bag_count(bag_count: usize) -> Self {
+        let mut bag = Self::new();
+        bag.tetrominos = bag.tetrominos.repeat(bag_count.max(1));
+        bag.next_bag = bag.tetrominos.clone();
+        bag
+    }
+
+    // Shuffles give way to these orders, as decisions() had them, until they run out.
+    // False if a shape in them isn't a tetromino.
+    pub fn follow(&mut self, decisions: &[String]) -> bool {
+        let orders: Option<VecDeque<Vec<Tetromino>>> = decisions
+            .iter()
+            .map(|order| order.chars().map(template).collect())
+            .collect();
+        match orders {
+            Some(orders) => {
+                self.recorded = orders;
+                true
+            }
+            None => false,
         }
     }
 
+    // The order of every bag shuffled so far, the one dealing and the one after it
+    pub fn decisions(&self) -> &[String] {
+        &self.decisions
+    }
+
+    // A scripted sequence keeps its order, only the bags after it get shuffled
     pub fn shuffle(&mut self) {
-        let mut rng = thread_rng();
-        self.tetrominos.shuffle(&mut rng);
+        if self.script_end.is_none() {
+            let tetrominos = take(&mut self.tetrominos);
+            self.tetrominos = self.decide(tetrominos);
+        }
+        if self.script_end != Some(SequenceEnd::Loop) {
+            let next_bag = take(&mut self.next_bag);
+            self.next_bag = self.decide(next_bag);
+        }
         self.index = 0;
     }
 
-    pub fn get(&mut self) -> Tetromino {
+    fn decide(&mut self, mut bag: Vec<Tetromino>) -> Vec<Tetromino> {
+        // A sequence that ends has nothing after it to shuffle
+        if bag.is_empty() {
+            return bag;
+        }
+        match self.recorded.pop_front() {
+            Some(order) => bag = order,
+            None => bag.shuffle(&mut self.rng),
+        }
+        self.decisions
+            .push(bag.iter().map(|tetromino| tetromino.shape).collect());
+        bag
+    }
+
+    // None once a scripted sequence that ends has been dealt in full
+    pub fn get(&mut self) -> Option<Tetromino> {
         if self.index >= self.tetrominos.len() {
-            self.shuffle();
+            if self.next_bag.is_empty() {
+                return None;
+            }
+            swap(&mut self.tetrominos, &mut self.next_bag);
+            match self.script_end {
+                Some(SequenceEnd::Loop) => self.next_bag = self.tetrominos.clone(),
+                // Bags from now on, the sequence is gone for good
+                Some(SequenceEnd::Bag) => {
+                    self.next_bag = self.decide(TEMPLATES.to_vec());
+                    self.script_end = None;
+                }
+                _ => {
+                    let next_bag = take(&mut self.next_bag);
+                    self.next_bag = self.decide(next_bag);
+                }
+            }
+            self.index = 0;
         }
         self.index += 1;
-        self.tetrominos[self.index - 1]
+        Some(self.tetrominos[self.index - 1])
+    }
+
+    // The tetromino the next get() will return
+    pub fn peek(&self) -> Option<Tetromino> {
+        self.tetrominos
+            .get(self.index)
+            .or(self.next_bag.first())
+            .copied()
+    }
+}
+
+// Fresh, unspawned tetromino of the given shape
+pub fn template(shape: char) -> Option<Tetromino> {
+    TEMPLATES
+        .iter()
+        .find(|tetromino| tetromino.shape == shape)
+        .copied()
+}
+
+// The piece a shape turns into when seen in a mirror
+pub fn mirrored_shape(shape: char) -> char {
+    match shape {
+        'J' => 'L',
+        'L' => 'J',
+        'S' => 'Z',
+        'Z' => 'S',
+        _ => shape,
+    }
+}
+
+// FNV-1a over everything deciding how the game goes on: the locked cells, the falling
+// tetromino, the held one and what's left in the bag. Meant for catching desyncs, so
+// it must not depend on anything with an unspecified iteration order. See
+// Game::state_hash.
+pub fn state_hash(
+    board: &Board,
+    tetromino: &Tetromino,
+    held: Option<char>,
+    can_hold: bool,
+    bag: &TetrominosBag,
+) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+    let mut hash = OFFSET_BASIS;
+    let mut feed = |bytes: &[u8]| {
+        for byte in bytes {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(PRIME);
+        }
+    };
+    feed(&(board.tiles.len() as u32).to_le_bytes());
+    feed(&(board.tiles[0].len() as u32).to_le_bytes());
+    for cell in board.tiles.iter().flatten() {
+        // The falling tetromino comes next, on its own
+        feed(&[match cell {
+            Some(cell) if !cell.is_active => cell.shape as u8,
+            _ => 0,
+        }]);
     }
+    feed(&[tetromino.shape as u8, tetromino.rotation as u8]);
+    for (x, y) in tetromino.body {
+        feed(&(x as u32).to_le_bytes());
+        feed(&(y as u32).to_le_bytes());
+    }
+    feed(&[held.map_or(0, |held| held as u8), u8::from(can_hold)]);
+    feed(&(bag.index as u32).to_le_bytes());
+    for upcoming in bag.tetrominos[bag.index..].iter().chain(&bag.next_bag) {
+        feed(&[upcoming.shape as u8]);
+    }
+    hash
 }
 
 impl Default for TetrominosBag {
@@ -325,18 +770,223 @@ impl Default for TetrominosBag {
     }
 }
 
-impl Playfield {
-    pub fn clear_lines(&mut self) -> bool {
-        let mut cleared_something = false;
-        for y in 0..self.tiles.len() {
-            if self.tiles[y].iter().all(|x| x.is_some()) {
-                cleared_something = true;
-                self.tiles[y].iter_mut().for_each(|x| *x = None);
-                for line in (0..y).rev() {
-                    self.tiles.swap(line, line+1);
-                }
+impl Board {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            tiles: vec![vec![None; width]; height],
+            row_fill: vec![0; height],
+        }
+    }
+
+    pub fn get_x_midpoint(&self) -> usize {
+        self.tiles[0].len() / 2
+    }
+
+    fn is_locked(cell: &Option<Playcell>) -> bool {
+        matches!(cell, Some(cell) if !cell.is_active)
+    }
+
+    // Only locked cells count, the falling piece is ignored. Anything outside the
+    // board is considered occupied, just like the walls and the floor.
+    pub fn is_occupied(&self, x: usize, y: usize) -> bool {
+        match self.tiles.get(y).and_then(|row| row.get(x)) {
+            Some(cell) => Self::is_locked(cell),
+            None => true,
+        }
+    }
+
+    // Height of the highest locked cell of each column, counted from the floor
+    pub fn column_heights(&self) -> Vec<u8> {
+        let rows = self.tiles.len();
+        (0..self.tiles[0].len())
+            .map(|x| {
+                (0..rows)
+                    .find(|&y| Self::is_locked(&self.tiles[y][x]))
+                    .map_or(0, |y| (rows - y) as u8)
+            })
+            .collect()
+    }
+
+    // Empty cells with at least one locked cell above them in the same column
+    pub fn hole_count(&self) -> usize {
+        let rows = self.tiles.len();
+        self.column_heights()
+            .iter()
+            .enumerate()
+            .map(|(x, &height)| {
+                (rows - usize::from(height)..rows)
+                    .filter(|&y| !Self::is_locked(&self.tiles[y][x]))
+                    .count()
+            })
+            .sum()
+    }
+
+    pub fn row_fill_counts(&self) -> Vec<u8> {
+        self.row_fill.clone()
+    }
+
+    // Recount every row from scratch, the counters must always agree with this
+    fn count_row_fill(&self) -> Vec<u8> {
+        self.tiles
+            .iter()
+            .map(|row| row.iter().filter(|cell| Self::is_locked(cell)).count() as u8)
+            .collect()
+    }
+
+    fn check_row_fill(&self) {
+        debug_assert_eq!(self.row_fill, self.count_row_fill());
+    }
+
+    // One character per locked cell, '.' when empty, rows from top to bottom.
+    // The falling piece isn't part of the board and shows up as empty.
+    pub fn to_text(&self) -> String {
+        self.tiles
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| match cell {
+                        Some(cell) if !cell.is_active => cell.shape,
+                        _ => '.',
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    // Inverse of to_text, the board is as wide and as high as the text
+    pub fn from_text(text: &str) -> Result<Board, BoardTextError> {
+        let width = text.lines().next().map_or(0, |line| line.chars().count());
+        if width == 0 {
+            return Err(BoardTextError::Empty);
+        }
+        let mut tiles = Vec::new();
+        for (row, line) in text.lines().enumerate() {
+            let mut cells = Vec::with_capacity(width);
+            for (column, cell) in line.chars().enumerate() {
+                cells.push(match cell {
+                    '.' => None,
+                    'G' => Some(Playcell::new(false, 'G')),
+                    _ if template(cell).is_some() => Some(Playcell::new(false, cell)),
+                    _ => return Err(BoardTextError::UnknownCell { row, column, cell }),
+                });
+            }
+            if cells.len() != width {
+                return Err(BoardTextError::WrongWidth {
+                    row,
+                    width: cells.len(),
+                    expected: width,
+                });
             }
+            tiles.push(cells);
+        }
+        let row_fill = vec![0; tiles.len()];
+        let mut board = Board { tiles, row_fill };
+        board.row_fill = board.count_row_fill();
+        Ok(board)
+    }
+
+    // Like from_text, but boards with fewer rows than this one sit on its floor and
+    // empty text clears it. The board is left untouched if the text is malformed.
+    pub fn load_text(&mut self, text: &str) -> Result<(), BoardTextError> {
+        let width = self.tiles[0].len();
+        let loaded = if text.is_empty() {
+            Board::new(width, 0)
+        } else {
+            Self::from_text(text)?
+        };
+        if loaded.tiles.len() > self.tiles.len() {
+            return Err(BoardTextError::TooManyRows {
+                rows: loaded.tiles.len(),
+                max: self.tiles.len(),
+            });
+        }
+        if let Some(row) = loaded.tiles.first().filter(|row| row.len() != width) {
+            return Err(BoardTextError::WrongWidth {
+                row: 0,
+                width: row.len(),
+                expected: width,
+            });
+        }
+        let mut tiles = vec![vec![None; width]; self.tiles.len() - loaded.tiles.len()];
+        tiles.extend(loaded.tiles);
+        self.tiles = tiles;
+        self.row_fill = self.count_row_fill();
+        Ok(())
+    }
+
+    // Left-right mirror image, pieces turn into their mirrored counterparts
+    pub fn mirrored(&self) -> Board {
+        let mut mirrored = self.clone();
+        for row in &mut mirrored.tiles {
+            row.reverse();
+            for cell in row.iter_mut().flatten() {
+                cell.shape = mirrored_shape(cell.shape);
+            }
+        }
+        mirrored
+    }
+
+    // Compares locked cells only, whatever the piece. Cells set in ignore_mask
+    // (indexed like tiles) are "don't care" and always match.
+    pub fn matches_template(&self, template: &Board, ignore_mask: &[Vec<bool>]) -> bool {
+        if self.tiles.len() != template.tiles.len()
+            || self.tiles[0].len() != template.tiles[0].len()
+        {
+            return false;
+        }
+        (0..self.tiles.len()).all(|y| {
+            (0..self.tiles[y].len()).all(|x| {
+                let ignored = ignore_mask
+                    .get(y)
+                    .and_then(|row| row.get(x))
+                    .copied()
+                    .unwrap_or(false);
+                ignored
+                    || Self::is_locked(&self.tiles[y][x]) == Self::is_locked(&template.tiles[y][x])
+            })
+        })
+    }
+
+    pub fn stack_height(&self) -> u8 {
+        self.column_heights().into_iter().max().unwrap_or(0)
+    }
+
+    // The full rows among the given ones, from top to bottom
+    pub fn full_rows(&self, rows: &[usize]) -> Vec<usize> {
+        let width = self.tiles[0].len();
+        let mut full: Vec<usize> = rows
+            .iter()
+            .copied()
+            .filter(|&y| usize::from(self.row_fill[y]) == width)
+            .collect();
+        full.sort_unstable();
+        full.dedup();
+        full
+    }
+
+    // Only the given rows are checked, in any order. Every full row is found before
+    // anything moves, then the rows left over drop down in one pass.
+    // Returns the cleared rows from top to bottom.
+    pub fn clear_lines(&mut self, rows: &[usize]) -> Vec<usize> {
+        let full = self.full_rows(rows);
+        let Some(&lowest) = full.last() else {
+            return full;
+        };
+        // Rows are swapped down past the full ones, which end up at the top
+        let mut target = lowest + 1;
+        for y in (0..=lowest).rev() {
+            if full.binary_search(&y).is_err() {
+                target -= 1;
+                self.tiles.swap(y, target);
+                self.row_fill.swap(y, target);
+            }
+        }
+        for y in 0..full.len() {
+            self.tiles[y].iter_mut().for_each(|cell| *cell = None);
+            self.row_fill[y] = 0;
         }
-        cleared_something
+        self.check_row_fill();
+        full
     }
 }
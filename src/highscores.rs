@@ -0,0 +1,261 @@
+// The best runs so far, kept in a small text file per game mode. The first line names
+// the format, then every entry is one tab separated line, best first:
+//
+//     tetrs highscores 3
+//     <points>	<lines>	<level>	<milliseconds played>	<unix timestamp>	<replay>	<hash>	<name>
+//
+// The replay is the file the run was kept in, in the replays directory beside the
+// table, and the hash is that of the file as it was written, in hex. Both are - for a
+// run without one. An entry is only verified while its replay is still there and the
+// same, see Entry::verified.
+//
+// Files from before the time column are still read, their runs took no time at all,
+// and so are those from before the replay columns, none of their runs are verified.
+
+use std::{
+    fs, io,
+    path::Path,
+    process,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::score::Score;
+
+const HEADER: &str = "tetrs highscores 3";
+const UNVERIFIED_HEADER: &str = "tetrs highscores 2";
+const UNTIMED_HEADER: &str = "tetrs highscores 1";
+
+// Where the replay of a run was kept
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ReplayRef {
+    // A file name in the replays directory
+    pub file: String,
+    pub hash: u64,
+}
+
+// FNV-1a over the bytes of a replay file, the way Game::state_hash goes
+pub fn replay_hash(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+    bytes.iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(PRIME)
+    })
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Entry {
+    pub points: u32,
+    pub lines: u32,
+    pub level: u32,
+    pub time: Duration,
+    // Seconds since the Unix epoch
+    pub timestamp: u64,
+    // Can be empty, the player doesn't have to give one
+    pub name: String,
+    pub replay: Option<ReplayRef>,
+}
+
+impl Entry {
+    pub const MAX_NAME_LEN: usize = 12;
+
+    // An entry for the run that just ended, dated now
+    pub fn new(score: Score, time: Duration, name: &str) -> Self {
+        Self {
+            points: score.points,
+            lines: score.lines,
+            level: score.level,
+            time,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |time| time.as_secs()),
+            name: Self::clean_name(name),
+            replay: None,
+        }
+    }
+
+    // Whether the replay the entry refers to is in `replays` as it was written.
+    // An entry without one never is.
+    pub fn verified(&self, replays: &Path) -> bool {
+        self.replay.as_ref().is_some_and(|replay| {
+            fs::read(replays.join(&replay.file))
+                .is_ok_and(|bytes| replay_hash(&bytes) == replay.hash)
+        })
+    }
+
+    // Tabs and line breaks would break the file format
+    fn clean_name(name: &str) -> String {
+        name.chars()
+            .filter(|c| !c.is_control())
+            .take(Self::MAX_NAME_LEN)
+            .collect::<String>()
+            .trim()
+            .to_string()
+    }
+}
+
+// What makes one run better than another
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Ranking {
+    #[default]
+    Points,
+    // Fastest first, for runs that all reach the same goal
+    Time,
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct HighScores {
+    ranking: Ranking,
+    entries: Vec<Entry>,
+}
+
+impl HighScores {
+    pub const MAX_ENTRIES: usize = 10;
+
+    pub fn new(ranking: Ranking) -> Self {
+        Self {
+            ranking,
+            entries: Vec::new(),
+        }
+    }
+
+    // A missing or unreadable file is an empty table, whatever was in a corrupt one
+    // is lost once the next entry gets saved
+    pub fn load(path: &Path, ranking: Ranking) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|text| Self::parse(&text, ranking))
+            .unwrap_or_else(|| Self::new(ranking))
+    }
+
+    // The whole file has to make sense, None otherwise
+    pub fn parse(text: &str, ranking: Ranking) -> Option<Self> {
+        let mut lines = text.lines();
+        // How many columns there are before the name
+        let columns = match lines.next()? {
+            HEADER => 7,
+            UNVERIFIED_HEADER => 5,
+            UNTIMED_HEADER => 4,
+            _ => return None,
+        };
+        let mut entries = Vec::new();
+        for line in lines.filter(|line| !line.is_empty()) {
+            let mut fields = line.splitn(columns + 1, '\t');
+            let mut number = || fields.next()?.parse().ok();
+            let (points, lines, level) = (number()?, number()?, number()?);
+            let time = if columns > 4 {
+                Duration::from_millis(fields.next()?.parse().ok()?)
+            } else {
+                Duration::ZERO
+            };
+            let timestamp = fields.next()?.parse().ok()?;
+            let replay = if columns > 5 {
+                match (fields.next()?, fields.next()?) {
+                    ("-", "-") => None,
+                    (file, hash) => Some(ReplayRef {
+                        file: file.to_string(),
+                        hash: u64::from_str_radix(hash, 16).ok()?,
+                    }),
+                }
+            } else {
+                None
+            };
+            let name = fields.next()?.to_string();
+            entries.push(Entry {
+                points,
+                lines,
+                level,
+                time,
+                timestamp,
+                name,
+                replay,
+            });
+        }
+        // Whatever wrote the file, the table is kept in order and no longer than it
+        // should be
+        let mut high_scores = Self::new(ranking);
+        for entry in entries {
+            high_scores.insert(entry);
+        }
+        Some(high_scores)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{HEADER}\n");
+        for entry in &self.entries {
+            let (file, hash) = match &entry.replay {
+                Some(replay) => (replay.file.clone(), format!("{:016x}", replay.hash)),
+                None => ("-".to_string(), "-".to_string()),
+            };
+            text.push_str(&format!(
+                "{}\t{}\t{}\t{}\t{}\t{file}\t{hash}\t{}\n",
+                entry.points,
+                entry.lines,
+                entry.level,
+                entry.time.as_millis(),
+                entry.timestamp,
+                entry.name
+            ));
+        }
+        text
+    }
+
+    // The table is written next to the file and renamed over it, so the file is either
+    // the old table or the new one even if two games save at once or one of them dies
+    // halfway through. The last one to finish wins.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(format!(".{}.tmp", process::id()));
+        fs::write(&temporary, self.to_text())?;
+        fs::rename(&temporary, path).inspect_err(|_| {
+            let _ = fs::remove_file(&temporary);
+        })
+    }
+
+    // Best first
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn ranking(&self) -> Ranking {
+        self.ranking
+    }
+
+    // Whether the run would make it into the table. Runs without points never do.
+    pub fn qualifies(&self, entry: &Entry) -> bool {
+        entry.points > 0
+            && (self.entries.len() < Self::MAX_ENTRIES
+                || self
+                    .entries
+                    .last()
+                    .is_some_and(|last| self.beats(entry, last)))
+    }
+
+    fn beats(&self, entry: &Entry, other: &Entry) -> bool {
+        match self.ranking {
+            Ranking::Points => entry.points > other.points,
+            Ranking::Time => entry.time < other.time,
+        }
+    }
+
+    // Where the entry would end up, None if it wouldn't make it. Ties go to the
+    // entry that was there first.
+    pub fn rank_of(&self, entry: &Entry) -> Option<usize> {
+        let rank = self
+            .entries
+            .iter()
+            .position(|other| self.beats(entry, other))
+            .unwrap_or(self.entries.len());
+        (rank < Self::MAX_ENTRIES).then_some(rank)
+    }
+
+    // Returns where the entry ended up, see rank_of()
+    pub fn insert(&mut self, entry: Entry) -> Option<usize> {
+        let rank = self.rank_of(&entry)?;
+        self.entries.insert(rank, entry);
+        self.entries.truncate(Self::MAX_ENTRIES);
+        Some(rank)
+    }
+}
@@ -0,0 +1,396 @@
+// Translates key presses into the actions the game understands. Nothing past this
+// point should have to look at a KeyEvent.
+
+use std::time::Duration;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind};
+use serde::{Deserialize, Serialize};
+
+use tetris::state::{Input, SoftDropLock};
+
+// Serialized by name, like in the config file
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    RotateCw,
+    RotateCcw,
+    Hold,
+    Pause,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Action; 9] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::SoftDrop,
+        Action::HardDrop,
+        Action::RotateCw,
+        Action::RotateCcw,
+        Action::Hold,
+        Action::Pause,
+        Action::Quit,
+    ];
+
+    pub fn is_movement(self) -> bool {
+        matches!(
+            self,
+            Action::MoveLeft | Action::MoveRight | Action::SoftDrop
+        )
+    }
+
+    // What the action means to the game, pausing and quitting are handled outside of it
+    pub fn input(self) -> Option<Input> {
+        match self {
+            Action::MoveLeft => Some(Input::MoveLeft),
+            Action::MoveRight => Some(Input::MoveRight),
+            Action::SoftDrop => Some(Input::SoftDrop),
+            Action::HardDrop => Some(Input::HardDrop),
+            Action::RotateCw => Some(Input::RotateCw),
+            Action::RotateCcw => Some(Input::RotateCcw),
+            Action::Hold => Some(Input::Hold),
+            Action::Pause | Action::Quit => None,
+        }
+    }
+
+    // As written in the config file
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::SoftDrop => "soft_drop",
+            Action::HardDrop => "hard_drop",
+            Action::RotateCw => "rotate_cw",
+            Action::RotateCcw => "rotate_ccw",
+            Action::Hold => "hold",
+            Action::Pause => "pause",
+            Action::Quit => "quit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|action| action.name() == name)
+    }
+}
+
+pub const DEFAULT_KEYMAP: [(KeyCode, Action); 16] = [
+    (KeyCode::Char('q'), Action::Quit),
+    (KeyCode::Char('j'), Action::SoftDrop),
+    (KeyCode::Down, Action::SoftDrop),
+    (KeyCode::Char('h'), Action::MoveLeft),
+    (KeyCode::Left, Action::MoveLeft),
+    (KeyCode::Char('l'), Action::MoveRight),
+    (KeyCode::Right, Action::MoveRight),
+    (KeyCode::Char('r'), Action::RotateCw),
+    (KeyCode::Up, Action::RotateCw),
+    (KeyCode::Char('R'), Action::RotateCcw),
+    (KeyCode::Char('e'), Action::RotateCcw),
+    (KeyCode::Char(' '), Action::HardDrop),
+    (KeyCode::Char('J'), Action::HardDrop),
+    (KeyCode::Char('c'), Action::Hold),
+    (KeyCode::Char('p'), Action::Pause),
+    (KeyCode::Esc, Action::Pause),
+];
+
+// Any number of keys can lead to the same action, but a key only ever has one action.
+// Only a draft of the settings screen can have a key on two, see add().
+#[derive(Clone, Debug)]
+pub struct Keymap {
+    bindings: Vec<(KeyCode, Action)>,
+}
+
+impl Keymap {
+    pub fn action_for(&self, key: &KeyEvent) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(code, _)| *code == key.code)
+            .map(|(_, action)| *action)
+    }
+
+    pub fn bindings(&self) -> &[(KeyCode, Action)] {
+        &self.bindings
+    }
+
+    // Replaces every key bound to the action, the keys are taken away from whatever
+    // other action they had
+    pub fn bind(&mut self, action: Action, keys: Vec<KeyCode>) {
+        self.bindings
+            .retain(|(code, bound)| *bound != action && !keys.contains(code));
+        self.bindings
+            .extend(keys.into_iter().map(|code| (code, action)));
+    }
+
+    pub fn keys(&self, action: Action) -> Vec<KeyCode> {
+        self.bindings
+            .iter()
+            .filter(|(_, bound)| *bound == action)
+            .map(|&(code, _)| code)
+            .collect()
+    }
+
+    // One more key for the action, left on any other action it already leads to so
+    // that the settings screen can point the conflict out rather than quietly move it
+    pub fn add(&mut self, action: Action, key: KeyCode) {
+        if !self.bindings.contains(&(key, action)) {
+            self.bindings.push((key, action));
+        }
+    }
+
+    // The first key found on two actions, and those actions
+    pub fn conflict(&self) -> Option<(KeyCode, Action, Action)> {
+        self.bindings
+            .iter()
+            .enumerate()
+            .find_map(|(index, &(code, first))| {
+                self.bindings[index + 1..]
+                    .iter()
+                    .find(|(other, _)| *other == code)
+                    .map(|&(_, second)| (code, first, second))
+            })
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self {
+            bindings: DEFAULT_KEYMAP.to_vec(),
+        }
+    }
+}
+
+// Soft drop speed as a multiple of gravity, rather than a rate of its own
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SoftDropFactor {
+    Times(u32),
+    // Down to the stack the moment the key goes down, see Input::SoftDropToStack
+    Infinite,
+}
+
+// How held movement keys repeat
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RepeatSettings {
+    // Delayed auto shift: how long a sideways key has to be held before it repeats
+    pub delay: Duration,
+    // Auto repeat rate: time between two repeated sideways moves
+    pub interval: Duration,
+    // Soft drop repeats right away, at its own rate
+    pub soft_drop_interval: Duration,
+    // Takes over from soft_drop_interval, the rate then follows the level
+    pub soft_drop_factor: Option<SoftDropFactor>,
+    // Whether a soft drop onto the stack locks the tetromino there
+    pub soft_drop_lock: SoftDropLock,
+}
+
+impl Default for RepeatSettings {
+    fn default() -> Self {
+        Self {
+            delay: Duration::from_millis(170),
+            interval: Duration::from_millis(40),
+            soft_drop_interval: Duration::from_millis(30),
+            soft_drop_factor: None,
+            soft_drop_lock: SoftDropLock::Tap,
+        }
+    }
+}
+
+// Repeats held movement keys at our own pace rather than the terminal's. A press moves
+// once right away, and the repeats come from poll(). Times are on the game's clock,
+// see Session.
+//
+// Only terminals speaking the kitty keyboard protocol report releases. Everywhere else
+// the terminal's own repeats are the only sign of a held key: once LEGACY_REPEATS
+// presses in a row each came within LEGACY_TIMEOUT of the last one the key repeats,
+// until they stop for longer than that. The terminal's repeat delay then stands in
+// for ours. A quick double tap is two presses, not a held key.
+#[derive(Clone)]
+pub struct AutoRepeat {
+    settings: RepeatSettings,
+    releases_reported: bool,
+    shift: Option<HeldKey>,
+    soft_drop: Option<HeldKey>,
+}
+
+#[derive(Clone)]
+struct HeldKey {
+    action: Action,
+    pressed_at: Duration,
+    last_seen: Duration,
+    // Presses in a row that each came right after the last, without the protocol
+    quick_presses: u32,
+    // None until the key has been held long enough to repeat
+    next_repeat: Option<Duration>,
+}
+
+impl AutoRepeat {
+    const LEGACY_TIMEOUT: Duration = Duration::from_millis(100);
+    const LEGACY_REPEATS: u32 = 2;
+
+    pub fn new(settings: RepeatSettings) -> Self {
+        Self {
+            settings,
+            releases_reported: false,
+            shift: None,
+            soft_drop: None,
+        }
+    }
+
+    // For a soft drop that keeps up with gravity, from the next repeat on
+    pub fn set_soft_drop_interval(&mut self, interval: Duration) {
+        self.settings.soft_drop_interval = interval;
+    }
+
+    // Takes every event of a movement key, returns whether its action has to be applied
+    // now. Releases and repeats never do, holding the key is handled by poll().
+    pub fn key_event(&mut self, kind: KeyEventKind, action: Action, now: Duration) -> bool {
+        let interval = match action {
+            Action::SoftDrop => self.settings.soft_drop_interval,
+            _ => self.settings.delay,
+        };
+        // Repeats are reported along with releases, and only then
+        let releases_reported = self.releases_reported || kind != KeyEventKind::Press;
+        self.releases_reported = releases_reported;
+        let slot = match action {
+            Action::SoftDrop => &mut self.soft_drop,
+            _ => &mut self.shift,
+        };
+        let held = slot.as_mut().filter(|held| held.action == action);
+        match (kind, held) {
+            (KeyEventKind::Release, Some(_)) => {
+                *slot = None;
+                false
+            }
+            (KeyEventKind::Release, None) => false,
+            (KeyEventKind::Repeat, held) => {
+                if let Some(held) = held {
+                    held.last_seen = now;
+                    held.next_repeat.get_or_insert(held.pressed_at + interval);
+                }
+                false
+            }
+            // Either the terminal repeating the key by itself or a quick tap, only the
+            // terminal keeps it up
+            (KeyEventKind::Press, Some(held))
+                if !releases_reported && now - held.last_seen <= Self::LEGACY_TIMEOUT =>
+            {
+                held.last_seen = now;
+                held.quick_presses += 1;
+                if held.next_repeat.is_none() && held.quick_presses < Self::LEGACY_REPEATS {
+                    return true;
+                }
+                held.next_repeat.get_or_insert(now);
+                false
+            }
+            (KeyEventKind::Press, _) => {
+                *slot = Some(HeldKey {
+                    action,
+                    pressed_at: now,
+                    last_seen: now,
+                    quick_presses: 0,
+                    next_repeat: releases_reported.then_some(now + interval),
+                });
+                true
+            }
+        }
+    }
+
+    // The next repeated action due by `now`, if any
+    pub fn poll(&mut self, now: Duration) -> Option<Action> {
+        for (slot, interval) in [
+            (&mut self.shift, self.settings.interval),
+            (&mut self.soft_drop, self.settings.soft_drop_interval),
+        ] {
+            let Some(held) = slot else {
+                continue;
+            };
+            if !self.releases_reported && now - held.last_seen > Self::LEGACY_TIMEOUT {
+                *slot = None;
+                continue;
+            }
+            if held.next_repeat.is_some_and(|next| next <= now) {
+                // Counted from now rather than from when it was due, so a late poll
+                // doesn't turn into a burst of moves
+                held.next_repeat = Some(now + interval);
+                return Some(held.action);
+            }
+        }
+        None
+    }
+
+    // When poll() could have something next
+    pub fn next_deadline(&self) -> Option<Duration> {
+        [&self.shift, &self.soft_drop]
+            .into_iter()
+            .flatten()
+            .filter_map(|held| held.next_repeat)
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    // Runs the clock a millisecond at a time up to `end`, feeding the events on their
+    // times, and returns when the key moved the piece
+    fn moves(events: &[(u64, KeyEventKind)], end: u64) -> Vec<u64> {
+        let mut auto_repeat = AutoRepeat::new(RepeatSettings::default());
+        let mut moves = Vec::new();
+        for now in 0..=end {
+            for (_, kind) in events.iter().filter(|(at, _)| *at == now) {
+                if auto_repeat.key_event(*kind, Action::MoveLeft, ms(now)) {
+                    moves.push(now);
+                }
+            }
+            while auto_repeat.poll(ms(now)).is_some() {
+                moves.push(now);
+            }
+        }
+        moves
+    }
+
+    #[test]
+    fn a_quick_double_tap_without_releases_moves_twice() {
+        let tap = KeyEventKind::Press;
+        assert_eq!(moves(&[(0, tap), (60, tap)], 1000), [0, 60]);
+    }
+
+    #[test]
+    fn a_key_held_without_releases_repeats_at_our_rate_once_the_terminal_does() {
+        // The terminal's own delay, then its repeats every 30ms up to 1000ms
+        let mut events = vec![(0, KeyEventKind::Press)];
+        events.extend((500..=1000).step_by(30).map(|at| (at, KeyEventKind::Press)));
+        let moves = moves(&events, 1500);
+        assert_eq!(moves[..4], [0, 500, 530, 560]);
+        let interval = RepeatSettings::default().interval.as_millis() as u64;
+        assert!(moves[4..]
+            .windows(2)
+            .all(|pair| pair[1] - pair[0] == interval));
+        // Stops once the terminal has been quiet for a while
+        let last = *moves.last().unwrap();
+        assert!(last > 1000 && last <= 1000 + AutoRepeat::LEGACY_TIMEOUT.as_millis() as u64);
+    }
+
+    #[test]
+    fn a_key_held_with_releases_repeats_after_the_delay_until_released() {
+        let mut events = vec![(0, KeyEventKind::Press)];
+        events.extend((20..400).step_by(30).map(|at| (at, KeyEventKind::Repeat)));
+        events.push((400, KeyEventKind::Release));
+        let settings = RepeatSettings::default();
+        let delay = settings.delay.as_millis() as u64;
+        let interval = settings.interval.as_millis() as u64;
+        let expected: Vec<u64> = [0]
+            .into_iter()
+            .chain((delay..400).step_by(interval as usize))
+            .collect();
+        assert_eq!(moves(&events, 1000), expected);
+    }
+}
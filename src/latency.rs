@@ -0,0 +1,287 @@
+// `tetris latency`: measures how long a key takes to come back as a frame. A cell
+// lights up after a random wait and the player presses any key as soon as they see
+// it, ten times over. The reaction times include the player's own reflexes, so they
+// mean the most next to a run in a local terminal, the difference is what the
+// connection and the terminal add. The time from the input thread receiving a key to
+// the frame it caused being flushed is measured on the side, that part is all ours.
+
+use std::{
+    error::Error,
+    io,
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    event::{
+        Event, KeyCode, KeyEventKind, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags,
+        PushKeyboardEnhancementFlags,
+    },
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use rand::Rng;
+use tui::{
+    backend::{Backend, CrosstermBackend},
+    layout::{Alignment, Rect},
+    style::{Modifier, Style},
+    widgets::{Block, Borders, Paragraph},
+    Terminal,
+};
+
+use crate::input_thread;
+
+const ROUNDS: usize = 10;
+// How long the cell stays dark, picked anew every round so it can't be anticipated
+const WAIT: std::ops::Range<u64> = 1000..3000;
+
+// Mean, median, 90th percentile and worst of a set of samples
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Summary {
+    mean: Duration,
+    p50: Duration,
+    p90: Duration,
+    max: Duration,
+}
+
+impl Summary {
+    // Percentiles are nearest rank, always one of the samples
+    fn of(samples: &[Duration]) -> Option<Summary> {
+        let mut sorted = samples.to_vec();
+        sorted.sort();
+        let max = *sorted.last()?;
+        let percentile = |p: usize| sorted[(sorted.len() * p).div_ceil(100).max(1) - 1];
+        Some(Summary {
+            mean: sorted.iter().sum::<Duration>() / sorted.len() as u32,
+            p50: percentile(50),
+            p90: percentile(90),
+            max,
+        })
+    }
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let ms = |time: Duration| time.as_secs_f64() * 1000.0;
+        write!(
+            f,
+            "mean {:.1}ms, p50 {:.1}ms, p90 {:.1}ms, max {:.1}ms",
+            ms(self.mean),
+            ms(self.p50),
+            ms(self.p90),
+            ms(self.max)
+        )
+    }
+}
+
+// Every round's timings
+#[derive(Default)]
+struct Measurements {
+    // From the lit frame being flushed to the input thread receiving the key
+    reactions: Vec<Duration>,
+    // From the input thread to the main loop picking the key up
+    pickups: Vec<Duration>,
+    // From the input thread to the frame that answered the key being flushed
+    processing: Vec<Duration>,
+    false_starts: u32,
+}
+
+impl Measurements {
+    fn report(&self) -> String {
+        let line = |name, samples: &[Duration]| match Summary::of(samples) {
+            Some(summary) => format!("{name:<11}{summary}"),
+            None => format!("{name:<11}-"),
+        };
+        format!(
+            "{}\n{}\n{}\n{} rounds, {} false starts",
+            line("reaction", &self.reactions),
+            line("pickup", &self.pickups),
+            line("processing", &self.processing),
+            self.reactions.len(),
+            self.false_starts
+        )
+    }
+}
+
+enum Wait {
+    Key(Instant),
+    Timeout,
+    Quit,
+}
+
+// Waits until `deadline`, or for good without one, for a key press. Esc and q quit.
+fn wait_for_key(rx_input: &mpsc::Receiver<(Event, Instant)>, deadline: Option<Instant>) -> Wait {
+    loop {
+        let event = match deadline {
+            Some(deadline) => {
+                match rx_input.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(event) => event,
+                    Err(mpsc::RecvTimeoutError::Timeout) => return Wait::Timeout,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => return Wait::Quit,
+                }
+            }
+            None => match rx_input.recv() {
+                Ok(event) => event,
+                Err(_) => return Wait::Quit,
+            },
+        };
+        match event {
+            (Event::Key(key), _) if matches!(key.code, KeyCode::Esc | KeyCode::Char('q')) => {
+                return Wait::Quit
+            }
+            (Event::Key(key), received) if key.kind == KeyEventKind::Press => {
+                return Wait::Key(received)
+            }
+            _ => {}
+        }
+    }
+}
+
+// Returns once the frame is flushed
+fn draw<B: Backend>(terminal: &mut Terminal<B>, lit: bool, text: &str) -> io::Result<()> {
+    terminal.draw(|f| {
+        let area = f.size();
+        let cell = Rect::new(
+            area.width.saturating_sub(12) / 2,
+            area.height.saturating_sub(6) / 2,
+            12.min(area.width),
+            6.min(area.height),
+        );
+        let style = match lit {
+            true => Style::default().add_modifier(Modifier::REVERSED),
+            false => Style::default(),
+        };
+        f.render_widget(
+            Paragraph::new("")
+                .style(style)
+                .block(Block::default().borders(Borders::ALL)),
+            cell,
+        );
+        let below = Rect::new(
+            0,
+            cell.bottom().min(area.height),
+            area.width,
+            area.height.saturating_sub(cell.bottom()),
+        );
+        f.render_widget(Paragraph::new(text).alignment(Alignment::Center), below);
+    })?;
+    Ok(())
+}
+
+fn measure<B: Backend>(
+    terminal: &mut Terminal<B>,
+    rx_input: &mpsc::Receiver<(Event, Instant)>,
+) -> io::Result<Option<Measurements>> {
+    let mut measurements = Measurements::default();
+    let mut prompt = String::from("press any key when the box lights up, q to quit");
+    while measurements.reactions.len() < ROUNDS {
+        let round = measurements.reactions.len() + 1;
+        draw(
+            terminal,
+            false,
+            &format!("round {round}/{ROUNDS}\n{prompt}"),
+        )?;
+        let lit_at = Instant::now() + Duration::from_millis(rand::thread_rng().gen_range(WAIT));
+        match wait_for_key(rx_input, Some(lit_at)) {
+            Wait::Timeout => {}
+            Wait::Key(_) => {
+                measurements.false_starts += 1;
+                prompt = String::from("too early, wait for the box to light up");
+                continue;
+            }
+            Wait::Quit => return Ok(None),
+        }
+        draw(terminal, true, &format!("round {round}/{ROUNDS}\nnow!"))?;
+        let shown = Instant::now();
+        let received = match wait_for_key(rx_input, None) {
+            Wait::Key(received) => received,
+            Wait::Timeout | Wait::Quit => return Ok(None),
+        };
+        let picked = Instant::now();
+        draw(terminal, false, &format!("round {round}/{ROUNDS}"))?;
+        let flushed = Instant::now();
+        measurements
+            .reactions
+            .push(received.saturating_duration_since(shown));
+        measurements.pickups.push(picked - received);
+        measurements.processing.push(flushed - received);
+        prompt = String::new();
+    }
+    let report = measurements.report();
+    draw(terminal, false, &format!("{report}\n\npress any key"))?;
+    wait_for_key(rx_input, None);
+    Ok(Some(measurements))
+}
+
+pub fn run() -> Result<(), Box<dyn Error>> {
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(
+        stdout,
+        EnterAlternateScreen,
+        PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+    )?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+    terminal.hide_cursor()?;
+    let (tx_input, rx_input) = mpsc::channel();
+    let _input_thread = input_thread(tx_input);
+    let measured = measure(&mut terminal, &rx_input);
+    terminal.show_cursor()?;
+    disable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        PopKeyboardEnhancementFlags,
+        LeaveAlternateScreen
+    )?;
+    // Printed again so that it can be pasted somewhere
+    if let Some(measurements) = measured? {
+        println!("{}", measurements.report());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(samples: &[u64]) -> Vec<Duration> {
+        samples.iter().copied().map(Duration::from_millis).collect()
+    }
+
+    #[test]
+    fn percentiles_are_nearest_rank() {
+        let summary = Summary::of(&ms(&[10, 90, 20, 80, 30, 70, 40, 60, 50, 100])).unwrap();
+        assert_eq!(
+            summary,
+            Summary {
+                mean: Duration::from_millis(55),
+                p50: Duration::from_millis(50),
+                p90: Duration::from_millis(90),
+                max: Duration::from_millis(100),
+            }
+        );
+        let single = Summary::of(&ms(&[7])).unwrap();
+        assert_eq!((single.p50, single.p90), (single.max, single.max));
+        assert_eq!(Summary::of(&[]), None);
+    }
+
+    #[test]
+    fn the_report_has_a_line_per_measurement() {
+        let measurements = Measurements {
+            reactions: ms(&[200, 250]),
+            pickups: ms(&[0, 1]),
+            processing: ms(&[2, 4]),
+            false_starts: 1,
+        };
+        assert_eq!(
+            measurements.report(),
+            "reaction   mean 225.0ms, p50 200.0ms, p90 250.0ms, max 250.0ms\n\
+             pickup     mean 0.5ms, p50 0.0ms, p90 1.0ms, max 1.0ms\n\
+             processing mean 3.0ms, p50 2.0ms, p90 4.0ms, max 4.0ms\n\
+             2 rounds, 1 false starts"
+        );
+        assert!(Measurements::default()
+            .report()
+            .starts_with("reaction   -\n"));
+    }
+}
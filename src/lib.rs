@@ -0,0 +1,12 @@
+// Everything about the game itself, without any terminal I/O, so that it can be
+// driven and tested on its own. The binary adds input, rendering and the main loop.
+
+pub mod engine;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod game;
+pub mod highscores;
+pub mod score;
+pub mod state;
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -1,129 +1,845 @@
 use std::{
     error::Error,
-    io,
-    sync::{mpsc, Arc, Mutex, RwLock},
+    io, iter,
+    path::{Path, PathBuf},
+    sync::mpsc,
     thread,
     time::{Duration, Instant},
 };
 
 use tui::{
-    backend::{self, Backend, CrosstermBackend},
-    buffer,
-    layout::{Alignment::Center, Rect},
-    style::Color,
-    widgets::{Block, Borders, Widget},
+    backend::{Backend, CrosstermBackend},
+    layout::Rect,
     Terminal,
 };
 
 use crossterm::{
-    event::{self, poll, read, Event, KeyCode, KeyEvent},
+    event::{
+        poll, read, Event, KeyCode, KeyEventKind, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 
-use crate::game::{Direction, ShiftError, TetrominosBag};
+use tetris::{
+    game::SequenceEnd,
+    highscores::{Entry, HighScores, Ranking, ReplayRef},
+    state::{Game, GameMode, Hold, Ruleset},
+};
+
+use crate::{
+    challenge::Challenge,
+    config::{Config, Preset},
+    dirs::Persistence,
+    input::Action,
+    pace::Splits,
+    replay::{Replay, Sequence},
+    results::Results,
+    session::{Session, TICK},
+    stream::StateStream,
+    ui::{Background, ColorDepth, FrameStats, GameOver, Playfield, ReplaySave, TitleUpdater},
+};
 
-mod game;
+mod browser;
+mod challenge;
+mod config;
+mod crash;
+mod dirs;
+mod doctor;
+mod fuzz;
+mod input;
+mod latency;
+mod pace;
+mod replay;
+mod results;
+mod scrub;
+mod session;
+mod settings;
+mod skin;
+mod stream;
 mod ui;
+mod verify;
+
+const PLAYFIELD_ROWS: u16 = 20;
+const PLAYFIELD_COLS: u16 = 10;
+const X_SCALING: u16 = 2;
+const Y_SCALING: u16 = 1;
 
 fn main() -> Result<(), Box<dyn Error>> {
-    const PLAYFIELD_ROWS: u16 = 20;
-    const PLAYFIELD_COLS: u16 = 10;
+    match std::env::args().nth(1).as_deref() {
+        Some("doctor") => return doctor::run(),
+        Some("latency") => return latency::run(),
+        Some("fuzz") => return fuzz::run(),
+        Some("verify") => return verify::run(),
+        _ => {}
+    }
+    const DEFAULT_FPS: u32 = 60;
+    let fps = match arg_value("--fps") {
+        Some(value) => value
+            .parse()
+            .ok()
+            .filter(|fps| *fps > 0)
+            .ok_or(format!("invalid --fps value: {value}"))?,
+        None => DEFAULT_FPS,
+    };
+    let frame_interval = Duration::from_secs(1) / fps;
+    let seed = match arg_value("--seed") {
+        Some(value) => Some(
+            value
+                .parse::<u64>()
+                .map_err(|_| format!("invalid --seed value: {value}"))?,
+        ),
+        None => None,
+    };
+    let record_path = arg_value("--record").map(PathBuf::from);
+    let results_path = arg_value("--json-results").map(PathBuf::from);
+    // Keeps everything off the disk, files asked for by name included
+    let no_persist = std::env::args().any(|arg| arg == "--no-persist");
+    for (flag, path) in [
+        ("--record", &record_path),
+        ("--json-results", &results_path),
+    ] {
+        if no_persist && path.is_some() {
+            return Err(format!("--no-persist and {flag} can't be used together").into());
+        }
+    }
+    // Ten snapshots a second are plenty for an overlay
+    const DEFAULT_STATE_RATE: u32 = 10;
+    let state_rate = match arg_value("--state-rate") {
+        Some(value) => value
+            .parse()
+            .ok()
+            .filter(|rate| *rate > 0)
+            .ok_or(format!("invalid --state-rate value: {value}"))?,
+        None => DEFAULT_STATE_RATE,
+    };
+    let mut state_stream = match arg_value("--state-socket") {
+        Some(path) => Some(
+            StateStream::bind(Path::new(&path), state_rate)
+                .map_err(|err| format!("can't serve the game state at {path}: {err}"))?,
+        ),
+        None => None,
+    };
+    let replay = match arg_value("--replay") {
+        Some(path) => Some(
+            Replay::load(Path::new(&path)).map_err(|err| format!("can't replay {path}: {err}"))?,
+        ),
+        None => None,
+    };
+    // Steps through the replay by hand instead of playing it, see scrub.rs
+    let scrub = std::env::args().any(|arg| arg == "--scrub");
+    if scrub && replay.is_none() {
+        return Err("--scrub needs a --replay to step through".into());
+    }
+    // --sequence TSZ deals exactly those, --sequence-end says what comes after them
+    let sequence = match arg_value("--sequence") {
+        Some(shapes) => {
+            let end = match arg_value("--sequence-end").as_deref() {
+                Some("loop") => SequenceEnd::Loop,
+                Some("bag") => SequenceEnd::Bag,
+                Some("end") | None => SequenceEnd::End,
+                Some(other) => {
+                    return Err(format!(
+                        "invalid --sequence-end value: {other} (expected loop, bag or end)"
+                    )
+                    .into())
+                }
+            };
+            let sequence = Sequence { shapes, end };
+            if sequence.bag().is_none() {
+                return Err(format!(
+                    "invalid --sequence value: {} (expected letters out of IJLOSTZ)",
+                    sequence.shapes
+                )
+                .into());
+            }
+            Some(sequence)
+        }
+        None => None,
+    };
+    let challenge = match arg_value("--challenge") {
+        Some(_) if replay.is_some() => {
+            return Err("--challenge and --replay can't be used together".into())
+        }
+        Some(code) => Some(
+            Challenge::parse(&code).map_err(|err| format!("invalid --challenge value: {err}"))?,
+        ),
+        None => None,
+    };
+    // Printed as is rather than returned, syntax errors span several lines
+    let mut config = match Config::load(preset()?) {
+        Ok(config) => config,
+        Err(err) => {
+            let path = err.file().unwrap_or_default();
+            eprintln!("error in {}: {err}", path.display());
+            std::process::exit(1);
+        }
+    };
+    config.apply_args(std::env::args())?;
+    let persistence = Persistence::detect(!no_persist);
+    let data_dir = persistence.data_dir();
+    crash::set_config(format!("{config:#?}"));
+    crash::install_hook(persistence.clone());
     // setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
+    // Asks for key releases, which terminals without the kitty keyboard protocol ignore
+    execute!(
+        stdout,
+        EnterAlternateScreen,
+        PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+    )?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
     let terminal_size = terminal.size()?;
 
-    let mut playfield = ui::Playfield::new(
+    let mut playfield = Playfield::new(
         terminal_size.width,
         terminal_size.height,
         PLAYFIELD_COLS,
         PLAYFIELD_ROWS,
-        2,
-        1,
+        X_SCALING,
+        Y_SCALING,
+        config.framing,
     );
-    let mut bag = TetrominosBag::new();
-    bag.shuffle();
-    let mut tetromino = bag.get();
-    tetromino.spawn(&mut playfield);
+    playfield.glyphs_only = !ui::color_supported();
+    let mut title = TitleUpdater::new(config.title);
+    configure(&config, &mut playfield, &mut title);
     terminal.show_cursor()?;
-    playfield.draw(&mut terminal);
+    let debug_kicks = std::env::args().any(|arg| arg == "--debug-kicks");
+    let mut frame_stats = std::env::args()
+        .any(|arg| arg == "--show-frametime")
+        .then(FrameStats::new);
 
     let (tx_input, rx_input) = mpsc::channel();
-    let (tx_timer, rx_timer) = mpsc::channel();
-    let accept_input = Arc::new(Mutex::new(true));
-    let input_thread = input_thread(tx_input, accept_input.clone());
-    let timer_thread = thread::spawn(move || {
-        loop {
-            thread::sleep(Duration::from_millis(1000));
-            tx_timer.send(true).unwrap();
+    let _input_thread = input_thread(tx_input);
+    let bag_count = match (&replay, challenge) {
+        (Some(replay), _) => replay.bag_count,
+        (None, Some(challenge)) => challenge.bag_count,
+        (None, None) if std::env::args().any(|arg| arg == "--double-bag") => 2,
+        (None, None) => 1,
+    };
+    let mut input_received = None;
+    let mut last_frame = Instant::now();
+    // Only reported once the terminal is back to normal
+    let mut record_error = None;
+    let mut results_error = None;
+    let mut splits_error = None;
+    let mut replay_error = None;
+    let mut high_scores_error = None;
+    let mut desync = None;
+    // Shown on the menu until the first game starts
+    let mut banner = persistence
+        .off_reason()
+        .map(|reason| format!("running without persistence: {reason}"));
+    // The built-in modes and then those from the config file. The menu stays on the
+    // last one played.
+    let modes: Vec<Ruleset> = GameMode::ALL
+        .map(GameMode::ruleset)
+        .into_iter()
+        .chain(config.modes.iter().cloned())
+        .collect();
+    let mut selected = 0;
+    // Counts the games played, each one is recorded to a file of its own
+    let mut games = 0;
+    // Each pass is one game, the game over screen either starts another one or quits
+    'session: loop {
+        if let (true, Some(replay)) = (scrub, &replay) {
+            playfield.hud = config.hud.for_rules(&replay.rules());
+            scrub::run(&mut terminal, &mut playfield, replay, &rx_input)?;
+            break 'session;
         }
-    });
-    loop {
-        let mut went_down = false;
-        let mut result = Ok(());
-        playfield.draw(&mut terminal);
-        if let Ok(key) = rx_input.recv_timeout(Duration::from_millis(500)) {
-            result = match key.code {
-                KeyCode::Char('q') => break,
-                KeyCode::Char('j') | KeyCode::Down => {
-                    went_down = true;
-                    tetromino.shift(&mut playfield, Direction::Down)
+        // A replay picked in the menu's replay browser, played back for this game
+        let mut watched = None;
+        // A replay or a challenge already knows its mode, otherwise the menu asks for one
+        let mut rules = match (&replay, challenge) {
+            (Some(replay), _) => replay.rules(),
+            (None, Some(challenge)) => challenge.mode.ruleset(),
+            (None, None) => loop {
+                title.update(terminal.backend_mut(), "tetrs", Instant::now())?;
+                playfield.draw_menu(&mut terminal, &modes, selected, banner.as_deref());
+                let Some((event, _)) = wait_for_input(&rx_input, &mut title, &mut terminal)? else {
+                    break 'session;
+                };
+                let key = match event {
+                    Event::Key(key) if key.kind != KeyEventKind::Release => key,
+                    Event::Resize(width, height) => {
+                        resize(&mut terminal, &mut playfield, width, height)?;
+                        continue;
+                    }
+                    _ => continue,
+                };
+                // The modes, then Replays and Settings
+                let entries = modes.len() + 2;
+                match key.code {
+                    KeyCode::Up => selected = (selected + entries - 1) % entries,
+                    KeyCode::Down => selected = (selected + 1) % entries,
+                    KeyCode::Enter | KeyCode::Char(' ') if selected == modes.len() + 1 => {
+                        let done =
+                            settings::run(&mut terminal, &mut playfield, &config, &rx_input)?;
+                        if let settings::Done::Applied(applied) = done {
+                            config = *applied;
+                            configure(&config, &mut playfield, &mut title);
+                            banner = if no_persist {
+                                Some("settings applied for this run, --no-persist".to_string())
+                            } else {
+                                config
+                                    .save_settings()
+                                    .err()
+                                    .map(|err| format!("settings applied but not saved: {err}"))
+                            };
+                        }
+                    }
+                    KeyCode::Enter | KeyCode::Char(' ') if selected == modes.len() => {
+                        let dir = data_dir.map(|dir| dir.join("replays"));
+                        let picked =
+                            browser::run(&mut terminal, &mut playfield, dir.as_deref(), &rx_input)?;
+                        if let Some(picked) = picked {
+                            let rules = picked.rules();
+                            watched = Some(picked);
+                            break rules;
+                        }
+                    }
+                    KeyCode::Enter | KeyCode::Char(' ') => break modes[selected].clone(),
+                    _ if config.keymap.action_for(&key) == Some(Action::Quit) => break 'session,
+                    _ => {}
+                }
+            },
+        };
+        banner = None;
+        // From here on a replay is the one given on the command line or the one picked
+        let replay = replay.as_ref().or(watched.as_ref());
+        let bag_count = replay.map_or(bag_count, |replay| replay.bag_count);
+        // The preset or the config file can turn holding off altogether and pick the
+        // gravity, a replay already has them the way it was played
+        if replay.is_none() {
+            if !config.hold {
+                rules.hold = Hold::Off;
+            }
+            rules.gravity = config.gravity.unwrap_or(rules.gravity);
+        }
+        let mode = rules.mode();
+        // Without --seed every game gets one of its own, shown at the end so that the
+        // same tetrominos can be played again
+        let seed = match (&replay, challenge) {
+            (Some(replay), _) => replay.seed,
+            (None, Some(challenge)) => challenge.seed,
+            (None, None) => seed.unwrap_or_else(rand::random),
+        };
+        // A replay is played back with the handling it was recorded with, whatever the
+        // config says now
+        let repeat = match &replay {
+            Some(replay) => replay.repeat(),
+            None => config.repeat,
+        };
+        crash::set_seed(seed);
+        games += 1;
+        let record_path = record_path
+            .as_deref()
+            .map(|path| Replay::numbered_path(path, games));
+        let mut recording = Replay::new(seed, bag_count, rules.clone(), repeat);
+        recording.sequence = match &replay {
+            Some(replay) => replay.sequence.clone(),
+            None => sequence.clone(),
+        };
+        // A replay deals its bags the way they were recorded
+        // The best run to keep pace with, in modes that have a line goal
+        let splits_path = data_dir
+            .filter(|_| rules.line_goal.is_some())
+            .map(|dir| dir.join(format!("splits-{}", rules.id)));
+        let best_splits = splits_path.as_deref().and_then(Splits::load);
+        playfield.pace = best_splits.clone();
+        playfield.hud = config.hud.for_rules(&rules);
+        let mut session = Session::new(replay.unwrap_or(&recording).game(), repeat);
+        session.set_debug_kicks(debug_kicks);
+        if replay.is_none() {
+            session.set_idle_pause(config.idle_pause);
+        }
+        let mut replayed = replay
+            .iter()
+            .flat_map(|replay| &replay.inputs)
+            .enumerate()
+            .peekable();
+        // Time the game has been running for, the session's clock follows it in whole ticks
+        let mut game_time = Duration::ZERO;
+        let mut last_wall = Instant::now();
+        let mut pending = Vec::new();
+        // Frames are only drawn when something changed, and no more than `fps` times a second
+        let mut dirty = true;
+        loop {
+            let now = Instant::now();
+            // Time stands still until the game is visible again or unpaused, so that
+            // nothing piles up to happen all at once on resume
+            let running = playfield.fits() && !session.game().is_paused();
+            if running {
+                game_time += now - last_wall;
+            } else {
+                game_time = session.clock();
+            }
+            last_wall = now;
+            if playfield.fits() {
+                while let Some((index, input)) =
+                    replayed.next_if(|(_, input)| Duration::from_millis(input.tick) <= game_time)
+                {
+                    session.advance(Duration::from_millis(input.tick));
+                    session.key(input.action, input.kind.into());
+                    let hash = session.game().state_hash();
+                    recording.record(input.tick, input.action, input.kind.into(), hash);
+                    // Only the first one matters, everything after it is off anyway
+                    if let (Err(err), None) = (input.check(index, hash), &desync) {
+                        crash::record(err.to_string());
+                        desync = Some(err.to_string());
+                    }
                 }
-                KeyCode::Char('l') | KeyCode::Left => {
-                    tetromino.shift(&mut playfield, Direction::Left)
+            }
+            session.advance(game_time);
+            // Whatever came in while waiting happens now, after everything that was due
+            for (event, received) in pending.drain(..) {
+                let key = match event {
+                    Event::Key(key) => key,
+                    Event::Resize(width, height) => {
+                        resize(&mut terminal, &mut playfield, width, height)?;
+                        dirty = true;
+                        continue;
+                    }
+                    _ => continue,
+                };
+                let Some(action) = config.keymap.action_for(&key) else {
+                    continue;
+                };
+                if action == Action::Quit && key.kind == KeyEventKind::Press {
+                    recording.note_decisions(session.game());
+                    save_recording(record_path.as_deref(), &recording, &mut record_error);
+                    break 'session;
                 }
-                KeyCode::Char('h') | KeyCode::Right => {
-                    tetromino.shift(&mut playfield, Direction::Right)
+                // A replay plays by itself, quitting is all that's left to the player
+                if replay.is_some() {
+                    continue;
                 }
-                KeyCode::Char('r') => {
-                    tetromino.rotate(&mut playfield, true);
-                    Ok(())
+                // The board isn't on screen, so the game waits for the terminal to grow.
+                // Pausing still works, and so do the releases that end key repeats.
+                if !playfield.fits() && action != Action::Pause && key.kind != KeyEventKind::Release
+                {
+                    continue;
                 }
-                KeyCode::Char('R') | KeyCode::Char('e') => {
-                    tetromino.rotate(&mut playfield, false);
-                    Ok(())
+                if let Some(stats) = &mut frame_stats {
+                    stats.record_event(received);
                 }
-                KeyCode::Char(' ') | KeyCode::Char('J') => {
-                    Err(tetromino.hard_drop(&mut playfield))
+                input_received = Some(received);
+                session.key(action, key.kind);
+                let hash = session.game().state_hash();
+                recording.record(session.tick_count(), action, key.kind, hash);
+            }
+            // The clock of a sprint or an ultra changes on its own, on every frame
+            let clock_shown = rules.is_timed();
+            let ticking = clock_shown && playfield.fits() && !session.game().is_paused();
+            dirty |= session.take_changed() || ticking;
+            if session.game().is_over() {
+                break;
+            }
+            if dirty && last_frame.elapsed() >= frame_interval {
+                last_frame = Instant::now();
+                playfield.idle_paused = session.is_idle_paused();
+                playfield.draw(
+                    &mut terminal,
+                    session.game(),
+                    session.clear_shown(),
+                    session.kicks_shown(),
+                    None,
+                    frame_stats.as_ref(),
+                );
+                if let Some(stats) = &mut frame_stats {
+                    stats.record_frame(last_frame, input_received.take());
                 }
-                _ => Ok(()),
+                dirty = false;
+            }
+            title.update(
+                terminal.backend_mut(),
+                &ui::window_title(session.game()),
+                Instant::now(),
+            )?;
+            if let Some(stream) = &mut state_stream {
+                stream.publish(session.game(), Instant::now());
+            }
+            // Sleeps until whatever comes first: a key, the next thing due in the game
+            // or the next frame
+            let running = playfield.fits() && !session.game().is_paused();
+            let next_tick = session.clock() + TICK;
+            let due = session
+                .next_deadline()
+                .into_iter()
+                .chain(
+                    replayed
+                        .peek()
+                        .map(|(_, input)| Duration::from_millis(input.tick)),
+                )
+                .min()
+                .filter(|_| running)
+                .map(|due| due.max(next_tick) - game_time.min(next_tick));
+            let frame =
+                (dirty || ticking).then(|| frame_interval.saturating_sub(last_frame.elapsed()));
+            // The title held back and the next snapshot for --state-socket
+            let outside = title
+                .deadline()
+                .into_iter()
+                .chain(state_stream.as_ref().and_then(StateStream::deadline))
+                .min()
+                .map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            let first = match due.into_iter().chain(frame).chain(outside).min() {
+                Some(timeout) => rx_input.recv_timeout(timeout).ok(),
+                None => rx_input.recv().ok(),
             };
+            pending.extend(
+                first
+                    .into_iter()
+                    .chain(iter::from_fn(|| rx_input.try_recv().ok())),
+            );
+        }
+        // The stack reached the top, the final board stays up under the overlay until
+        // the player asks for a new game or quits. A run that makes it into the high
+        // score table asks for a name first.
+        recording.note_decisions(session.game());
+        recording.note_result(session.game());
+        save_recording(record_path.as_deref(), &recording, &mut record_error);
+        let game = session.game();
+        let score = game.score();
+        let results = Results::new(
+            game,
+            session.tally(),
+            seed,
+            bag_count,
+            recording.sequence.clone(),
+            repeat.into(),
+        );
+        if let Some(path) = &results_path {
+            let path = Replay::numbered_path(path, games);
+            if let Err(err) = results.save(&path) {
+                results_error = Some(format!("can't write {}: {err}", path.display()));
+            }
+        }
+        // The game loop is left before the title catches up with the last lines
+        title.update(
+            terminal.backend_mut(),
+            &ui::window_title(game),
+            Instant::now(),
+        )?;
+        if let Some(stream) = &mut state_stream {
+            stream.publish(game, Instant::now());
         }
-        if rx_timer.recv_timeout(Duration::from_millis(1)) == Ok(true) && !went_down {
-            result = tetromino.shift(&mut playfield, Direction::Down);
-        } 
-        if let Err(ShiftError::BottomCollision) = result {
-            playfield.draw(&mut terminal);
-            tetromino.place_in_playfield(&mut playfield);
-            playfield.clear_lines();
-            *accept_input.lock().unwrap() = false;
-            thread::sleep(Duration::from_millis(100));
-            tetromino = bag.get();
-            tetromino.spawn(&mut playfield);
-            *accept_input.lock().unwrap() = true;
+        // Every mode has a table of its own, read now so that runs saved by another
+        // game in the meantime are kept
+        let high_scores_path = data_dir.map(|dir| dir.join(high_scores_file(&rules)));
+        // Whoever gets to the goal first comes first
+        let ranking = match rules.line_goal {
+            Some(_) => Ranking::Time,
+            None => Ranking::Points,
+        };
+        let mut high_scores = match &high_scores_path {
+            Some(path) => HighScores::load(path, ranking),
+            None => HighScores::new(ranking),
+        };
+        // Replays don't make it into the table, they were either entered already or
+        // they're someone else's. A sprint has to be finished to be timed, and a
+        // sequence of one's own choosing doesn't compare with bags.
+        let entered = replay.is_none()
+            && recording.sequence.is_none()
+            && (rules.line_goal.is_none() || game.is_completed());
+        let splits = session.splits();
+        if let Some(path) = &splits_path {
+            if entered && game.is_completed() && splits.beat(best_splits.as_ref()) {
+                if let Err(err) = splits.save(path) {
+                    splits_error = Some(format!("can't write {}: {err}", path.display()));
+                }
+            }
+        }
+        // Saved replays go there, those of the runs in the tables among them
+        let replays_dir = data_dir.map(|dir| dir.join("replays"));
+        let mut name = (entered && high_scores.qualifies(&Entry::new(score, game.played(), "")))
+            .then(String::new);
+        let mut rank = None;
+        let mut save_error = None;
+        // A run that goes into the table has its replay saved along with it without
+        // asking, see below, and a replay being watched is saved already
+        let mut replay_save = match &replays_dir {
+            Some(_) if replay.is_some() || name.is_some() => None,
+            Some(dir) if config.always_save_replays => {
+                Some(save_replay(dir, &mut recording, game, "", false, &mut replay_error).0)
+            }
+            Some(_) => Some(ReplaySave::Ask),
+            None => None,
+        };
+        loop {
+            let game_over = GameOver {
+                results: &results,
+                // A code only has room for bags and the built-in modes
+                challenge: (recording.sequence.is_none() && mode != GameMode::Custom)
+                    .then(|| Challenge::new(mode, seed, bag_count, repeat.into()).code()),
+                other_handling: challenge
+                    .is_some_and(|challenge| !challenge.same_handling(repeat.into())),
+                high_scores: &high_scores,
+                unverified: high_scores
+                    .entries()
+                    .iter()
+                    .map(|entry| {
+                        !replays_dir
+                            .as_deref()
+                            .is_some_and(|dir| entry.verified(dir))
+                    })
+                    .collect(),
+                rank,
+                name: name.as_deref(),
+                save_error: save_error.as_deref(),
+                replay_save: replay_save.as_ref(),
+            };
+            playfield.draw(
+                &mut terminal,
+                game,
+                None,
+                None,
+                Some(&game_over),
+                frame_stats.as_ref(),
+            );
+            let Some((event, _)) = wait_for_input(&rx_input, &mut title, &mut terminal)? else {
+                break 'session;
+            };
+            let key = match event {
+                Event::Key(key) if key.kind != KeyEventKind::Release => key,
+                Event::Resize(width, height) => {
+                    resize(&mut terminal, &mut playfield, width, height)?;
+                    continue;
+                }
+                _ => continue,
+            };
+            match (&mut name, &mut replay_save) {
+                (Some(typed), _) => match key.code {
+                    // Esc leaves the entry without a name
+                    KeyCode::Enter | KeyCode::Esc => {
+                        if key.code == KeyCode::Esc {
+                            typed.clear();
+                        }
+                        let mut entry = Entry::new(score, game.played(), typed);
+                        // The replay is written first, so an entry never refers to
+                        // one that isn't there. Every entry's is, a new best's too.
+                        if let Some(dir) = &replays_dir {
+                            let best = high_scores.rank_of(&entry) == Some(0);
+                            let saved;
+                            (saved, entry.replay) = save_replay(
+                                dir,
+                                &mut recording,
+                                game,
+                                typed,
+                                best,
+                                &mut replay_error,
+                            );
+                            replay_save = Some(saved);
+                        }
+                        rank = high_scores.insert(entry);
+                        if let Some(path) = &high_scores_path {
+                            if let Err(err) = high_scores.save(path) {
+                                save_error = Some(err.kind().to_string());
+                                high_scores_error = Some(format!(
+                                    "can't save the high scores to {}: {err}",
+                                    path.display()
+                                ));
+                            }
+                        }
+                        name = None;
+                    }
+                    code => type_name(typed, code),
+                },
+                (None, Some(ReplaySave::Ask)) => match key.code {
+                    KeyCode::Char('y') => replay_save = Some(ReplaySave::Naming(String::new())),
+                    KeyCode::Char('n') | KeyCode::Esc => replay_save = None,
+                    _ if config.keymap.action_for(&key) == Some(Action::Quit) => break 'session,
+                    _ => {}
+                },
+                (None, Some(ReplaySave::Naming(typed))) => match key.code {
+                    // Esc saves it without a name
+                    KeyCode::Enter | KeyCode::Esc => {
+                        if key.code == KeyCode::Esc {
+                            typed.clear();
+                        }
+                        let dir = replays_dir.as_deref().expect("only asked with a directory");
+                        let typed = typed.clone();
+                        replay_save = Some(
+                            save_replay(
+                                dir,
+                                &mut recording,
+                                game,
+                                &typed,
+                                false,
+                                &mut replay_error,
+                            )
+                            .0,
+                        );
+                    }
+                    code => type_name(typed, code),
+                },
+                (None, _) if config.keymap.action_for(&key) == Some(Action::Quit) => break 'session,
+                (None, _) if matches!(key.code, KeyCode::Char('n') | KeyCode::Enter) => {
+                    continue 'session;
+                }
+                (None, _) => {}
+            }
         }
     }
 
+    title.restore(terminal.backend_mut())?;
     disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    execute!(
+        terminal.backend_mut(),
+        PopKeyboardEnhancementFlags,
+        LeaveAlternateScreen
+    )?;
+    if let Some(err) = record_error {
+        eprintln!("{err}");
+    }
+    if let Some(err) = results_error {
+        eprintln!("{err}");
+    }
+    if let Some(err) = splits_error {
+        eprintln!("{err}");
+    }
+    if let Some(err) = replay_error {
+        eprintln!("{err}");
+    }
+    if let Some(err) = high_scores_error {
+        eprintln!("{err}");
+    }
+    if let Some(err) = desync {
+        eprintln!("{err}");
+    }
 
     Ok(())
 }
 
-fn input_thread(
-    sender: std::sync::mpsc::Sender<crossterm::event::KeyEvent>,
-    accept_input: Arc<Mutex<bool>>,
-) -> std::thread::JoinHandle<()> {
+// Typing a name on the game over screen, for the high score table or a replay
+fn type_name(typed: &mut String, code: KeyCode) {
+    match code {
+        KeyCode::Backspace => {
+            typed.pop();
+        }
+        // A leading space is far more likely a late hard drop than a name
+        KeyCode::Char(' ') if typed.is_empty() => {}
+        KeyCode::Char(c) if typed.chars().count() < Entry::MAX_NAME_LEN => typed.push(c),
+        _ => {}
+    }
+}
+
+// Saves the game's replay into the replays directory, named as replay::file_stem has
+// it. Returns how that went for the game over screen, and what a high score entry
+// keeps of the file.
+fn save_replay(
+    dir: &Path,
+    recording: &mut Replay,
+    game: &Game,
+    name: &str,
+    best: bool,
+    error: &mut Option<String>,
+) -> (ReplaySave, Option<ReplayRef>) {
+    let now = replay::now();
+    recording.name = (!name.is_empty()).then(|| name.to_string());
+    recording.saved = Some(now);
+    match recording.keep(dir, &replay::file_stem(now, game, name, best)) {
+        Ok(kept) => (ReplaySave::Saved, Some(kept)),
+        Err(err) => {
+            *error = Some(format!("can't save the replay in {}: {err}", dir.display()));
+            (ReplaySave::Failed(err.kind().to_string()), None)
+        }
+    }
+}
+
+fn high_scores_file(rules: &Ruleset) -> String {
+    match rules.mode() {
+        // From before there were other modes
+        GameMode::Marathon => "highscores".to_string(),
+        _ => format!("highscores-{}", rules.id),
+    }
+}
+
+fn save_recording(path: Option<&Path>, recording: &Replay, error: &mut Option<String>) {
+    if let Some(path) = path {
+        if let Err(err) = recording.save(path) {
+            *error = Some(format!("can't write {}: {err}", path.display()));
+        }
+    }
+}
+
+// The board moves to stay centered, and the whole screen gets redrawn on the next frame
+// Everything the game takes from the config that can change while it runs, from the
+// menu's settings. The keymap, the handling and the gravity are read from the config
+// as they're needed, the rest is kept here.
+fn configure(config: &Config, playfield: &mut Playfield, title: &mut TitleUpdater) {
+    playfield.ghost = config.ghost;
+    playfield.hold_hint = config.hold_hint;
+    let background = config
+        .background
+        .or_else(Background::detect)
+        .unwrap_or_default();
+    playfield.palette = config
+        .skin
+        .palette(background.palette(ColorDepth::detect()));
+    playfield.glyphs = config.skin.glyphs;
+    playfield.border_type = config.border_type;
+    playfield.hud = config.hud.clone();
+    title.set_enabled(config.title);
+}
+
+fn resize<B: Backend>(
+    terminal: &mut Terminal<B>,
+    playfield: &mut Playfield,
+    width: u16,
+    height: u16,
+) -> io::Result<()> {
+    terminal.resize(Rect::new(0, 0, width, height))?;
+    playfield.resize(width, height);
+    Ok(())
+}
+
+// The layer under the config file, the doctor takes it too
+fn preset() -> Result<Preset, String> {
+    match arg_value("--preset") {
+        Some(name) => Preset::from_name(&name).ok_or(format!(
+            "invalid --preset value: {name} (expected {})",
+            Preset::ALL.map(Preset::name).join(" or ")
+        )),
+        None => Ok(Preset::Guideline),
+    }
+}
+
+// Value following `name` on the command line, as in `--fps 30`
+fn arg_value(name: &str) -> Option<String> {
+    std::env::args().skip_while(|arg| arg != name).nth(1)
+}
+
+// Blocks until the next event, writing the window title held back once it's due.
+// None once the input thread is gone.
+fn wait_for_input<B: Backend + io::Write>(
+    rx_input: &mpsc::Receiver<(Event, Instant)>,
+    title: &mut TitleUpdater,
+    terminal: &mut Terminal<B>,
+) -> io::Result<Option<(Event, Instant)>> {
+    loop {
+        let Some(deadline) = title.deadline() else {
+            return Ok(rx_input.recv().ok());
+        };
+        match rx_input.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(event) => return Ok(Some(event)),
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                title.flush(terminal.backend_mut(), Instant::now())?
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(None),
+        }
+    }
+}
+
+fn input_thread(sender: std::sync::mpsc::Sender<(Event, Instant)>) -> std::thread::JoinHandle<()> {
     thread::spawn(move || loop {
-        if let Ok(poll) = poll(Duration::from_millis(5)) {
-            if poll && *accept_input.lock().unwrap() {
-                if let Ok(Event::Key(key)) = read() {
-                    sender.send(key).unwrap();
-                }
+        if let Ok(true) = poll(Duration::from_millis(5)) {
+            // The session decides what to do with keys, on the tick they came in on
+            if let Ok(event @ (Event::Key(_) | Event::Resize(..))) = read() {
+                sender.send((event, Instant::now())).unwrap();
             }
         }
     })
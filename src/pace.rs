@@ -0,0 +1,136 @@
+// The splits of the best run of a mode with a line goal, when each of its lines was
+// cleared, for the pace bar beside the board. One file per mode, next to its high
+// score table:
+//
+//     tetrs splits 1
+//     <milliseconds played when line 1 went>
+//     <line 2>
+//     ...
+//
+// Lines that went together have the same time. A run only replaces the splits when
+// it reached the goal faster.
+
+use std::{fs, io, path::Path, process, time::Duration};
+
+const HEADER: &str = "tetrs splits 1";
+
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Splits {
+    times: Vec<Duration>,
+}
+
+impl Splits {
+    pub fn new(times: Vec<Duration>) -> Self {
+        Self { times }
+    }
+
+    // A missing or unreadable file is no best run yet
+    pub fn load(path: &Path) -> Option<Self> {
+        Self::parse(&fs::read_to_string(path).ok()?)
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        let mut lines = text.lines();
+        if lines.next()? != HEADER {
+            return None;
+        }
+        let times: Vec<Duration> = lines
+            .filter(|line| !line.is_empty())
+            .map(|line| line.parse().ok().map(Duration::from_millis))
+            .collect::<Option<_>>()?;
+        // Out of order they'd be no run at all
+        if times.is_empty() || times.windows(2).any(|pair| pair[1] < pair[0]) {
+            return None;
+        }
+        Some(Self { times })
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = format!("{HEADER}\n");
+        for time in &self.times {
+            text.push_str(&format!("{}\n", time.as_millis()));
+        }
+        text
+    }
+
+    // Written next to the file and renamed over it, like the high scores
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(format!(".{}.tmp", process::id()));
+        fs::write(&temporary, self.to_text())?;
+        fs::rename(&temporary, path).inspect_err(|_| {
+            let _ = fs::remove_file(&temporary);
+        })
+    }
+
+    // When the last line went
+    pub fn time(&self) -> Duration {
+        self.times.last().copied().unwrap_or_default()
+    }
+
+    // Whether these reached the same goal faster than the best run
+    pub fn beat(&self, best: Option<&Splits>) -> bool {
+        best.is_none_or(|best| self.times.len() >= best.times.len() && self.time() < best.time())
+    }
+
+    // The lines the run had at `played`, going evenly from one split to the next
+    pub fn lines_at(&self, played: Duration) -> f64 {
+        let done = self.times.partition_point(|&time| time <= played);
+        let Some(&next) = self.times.get(done) else {
+            return self.times.len() as f64;
+        };
+        let last = match done {
+            0 => Duration::ZERO,
+            done => self.times[done - 1],
+        };
+        let between = (played - last).as_secs_f64() / (next - last).as_secs_f64();
+        done as f64 + between
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    fn splits(millis: &[u64]) -> Splits {
+        Splits::new(millis.iter().copied().map(Duration::from_millis).collect())
+    }
+
+    #[test]
+    fn the_pace_goes_evenly_between_splits() {
+        let best = splits(&[1000, 3000, 3000, 4000]);
+        assert_eq!(best.lines_at(Duration::ZERO), 0.0);
+        assert_eq!(best.lines_at(Duration::from_millis(500)), 0.5);
+        assert_eq!(best.lines_at(Duration::from_millis(2000)), 1.5);
+        // Two lines at once
+        assert_eq!(best.lines_at(Duration::from_millis(3000)), 3.0);
+        assert_eq!(best.lines_at(Duration::from_millis(3500)), 3.5);
+        assert_eq!(best.lines_at(Duration::from_secs(60)), 4.0);
+    }
+
+    #[test]
+    fn only_a_faster_run_to_the_goal_beats_the_best() {
+        let best = splits(&[1000, 2000]);
+        assert!(splits(&[900, 1900]).beat(Some(&best)));
+        assert!(!splits(&[900, 2100]).beat(Some(&best)));
+        assert!(!splits(&[1500]).beat(Some(&best)));
+        assert!(splits(&[3000, 4000]).beat(None));
+    }
+
+    #[test]
+    fn splits_are_saved_and_read_back() {
+        let path = env::temp_dir().join(format!("tetrs-splits-{}", process::id()));
+        let best = splits(&[812, 1650, 1650]);
+        best.save(&path).unwrap();
+        assert_eq!(Splits::load(&path), Some(best));
+        fs::write(&path, "tetrs splits 1\n2000\n1000\n").unwrap();
+        assert_eq!(Splits::load(&path), None);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(Splits::load(&path), None);
+    }
+}
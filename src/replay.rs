@@ -0,0 +1,828 @@
+// Recorded games for --record and --replay, kept as JSON:
+//
+//     {"version": 3, "seed": 42, "bag_count": 1, "mode": "sprint",
+//      "handling": {"das": 170, "arr": 40, "soft_drop": 30, "soft_drop_factor": "infinite",
+//                   "soft_drop_lock": "tap"},
+//      "inputs": [{"tick": 812, "action": "move_left", "kind": "press",
+//                  "hash": 1469598103934665603}, ...]}
+//
+// Ticks are milliseconds on the game's own clock, see Session. With the seed and the
+// bag count the piece sequence is the same, and with the handling the game was played
+// with held keys repeat the same way, so the rest of the game is the same too. The
+// state hash after each input is there to tell right away when it isn't.
+//
+// A game played with --sequence keeps it as "sequence": {"shapes": "TSZ", "end": "loop"}
+// and its seed only shuffles the bags after it, if any. A game whose rules aren't
+// those of its mode, a custom mode or one played with hold turned off, keeps them
+// as "rules", see Ruleset.
+//
+// The order every bag was shuffled to is kept too, in a section with a version of its
+// own: "decisions": {"version": 1, "bags": ["TZOJLIS", ...]}. Playing back follows
+// those orders rather than shuffling again, so the game stays the same even if the
+// shuffling changes. A replay without them, or with a version this build doesn't
+// know, is shuffled from its seed as before. There's nothing else random to keep,
+// no garbage comes in.
+//
+// A game that ended, rather than being quit, keeps what it came to as "result":
+// {"points": 5400, "lines": 40, "level": 5, "time_ms": 61250, "completed": true}, which
+// `tetris verify` plays the replay back to check.
+//
+// Once a game is over the game over screen asks whether to save its replay, to the
+// replays directory under a name like 2024-06-01_sprint_41.2s_pb.json, see file_stem.
+// It keeps the name it was given as "name" and when it was saved as "saved", in
+// seconds since the Unix epoch, for the replay browser.
+// The replay of a run entered in the high score table is saved there without asking,
+// a new best of its mode among them, and the entry holds the hash of the file, see
+// Replay::keep. With always_save_replays every game's is.
+//
+// A file holds a single game. With --record PATH every game played from the menu gets
+// a file of its own: the first one goes to PATH, the ones after it get their number
+// added to the name, as in run.json, run-2.json, run-3.json.
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crossterm::event::KeyEventKind;
+use serde::{Deserialize, Serialize};
+
+use tetris::{
+    game::{SequenceEnd, TetrominosBag},
+    highscores::{replay_hash, ReplayRef},
+    state::{Game, GameMode, Ruleset, SoftDropLock},
+};
+
+use crate::{
+    input::{Action, RepeatSettings, SoftDropFactor},
+    PLAYFIELD_COLS, PLAYFIELD_ROWS,
+};
+
+#[derive(Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    pub bag_count: usize,
+    pub mode: GameMode,
+    pub handling: Handling,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<Sequence>,
+    // Left out when they're the mode's own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rules: Option<Ruleset>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decisions: Option<Decisions>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Claim>,
+    // The name it was saved under, if it was given one, and when it was saved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub saved: Option<u64>,
+    pub inputs: Vec<RecordedInput>,
+}
+
+// What the replay browser lists of a replay, read without its inputs
+#[derive(Clone, Debug, Deserialize)]
+pub struct Header {
+    version: u32,
+    pub mode: GameMode,
+    #[serde(default)]
+    rules: Option<HeaderRules>,
+    #[serde(default)]
+    pub result: Option<Claim>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub saved: Option<u64>,
+}
+
+// Out of the rules, only what the browser shows
+#[derive(Clone, Debug, Deserialize)]
+struct HeaderRules {
+    name: String,
+    line_goal: Option<u32>,
+}
+
+impl Header {
+    // The name of the mode it was played in, a custom mode's own
+    pub fn mode_name(&self) -> String {
+        match &self.rules {
+            Some(rules) => rules.name.clone(),
+            None => self.mode.ruleset().name,
+        }
+    }
+
+    // Whether the result is a time, the game reached a line goal
+    pub fn timed(&self) -> bool {
+        let line_goal = match &self.rules {
+            Some(rules) => rules.line_goal,
+            None => self.mode.ruleset().line_goal,
+        };
+        line_goal.is_some() && self.result.is_some_and(|result| result.completed)
+    }
+}
+
+// What a recorded game came to, as the game had it when it ended
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Claim {
+    pub points: u32,
+    pub lines: u32,
+    pub level: u32,
+    pub time_ms: u64,
+    pub completed: bool,
+}
+
+impl Claim {
+    pub fn time(&self) -> Duration {
+        Duration::from_millis(self.time_ms)
+    }
+
+    pub fn of(game: &Game) -> Self {
+        let score = game.score();
+        Self {
+            points: score.points,
+            lines: score.lines,
+            level: score.level,
+            time_ms: game.played().as_millis() as u64,
+            completed: game.is_completed(),
+        }
+    }
+}
+
+impl fmt::Display for Claim {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let time = self.time();
+        write!(
+            f,
+            "{} points, {} lines, level {} in {}.{:03}s",
+            self.points,
+            self.lines,
+            self.level,
+            time.as_secs(),
+            time.subsec_millis()
+        )?;
+        if self.completed {
+            write!(f, ", completed")?;
+        }
+        Ok(())
+    }
+}
+
+// What the randomizer came to, in the order it did
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Decisions {
+    pub version: u32,
+    pub bags: Vec<String>,
+}
+
+impl Decisions {
+    pub const VERSION: u32 = 1;
+}
+
+// Tetrominos dealt in a set order instead of out of bags
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Sequence {
+    pub shapes: String,
+    pub end: SequenceEnd,
+}
+
+impl Sequence {
+    // None if the shapes aren't all tetrominos, or there are none
+    pub fn bag(&self) -> Option<TetrominosBag> {
+        TetrominosBag::scripted(&self.shapes, self.end)
+    }
+}
+
+// The [handling] settings of the config the game was played with, in milliseconds
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Handling {
+    pub das: u64,
+    pub arr: u64,
+    pub soft_drop: u64,
+    // Left out when the soft drop went at its own rate, like in older replays
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub soft_drop_factor: Option<SoftDropFactor>,
+    // Left out by older replays, whose soft drops never locked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub soft_drop_lock: Option<SoftDropLock>,
+}
+
+impl From<RepeatSettings> for Handling {
+    fn from(repeat: RepeatSettings) -> Self {
+        Self {
+            das: repeat.delay.as_millis() as u64,
+            arr: repeat.interval.as_millis() as u64,
+            soft_drop: repeat.soft_drop_interval.as_millis() as u64,
+            soft_drop_factor: repeat.soft_drop_factor,
+            soft_drop_lock: Some(repeat.soft_drop_lock),
+        }
+    }
+}
+
+impl From<Handling> for RepeatSettings {
+    fn from(handling: Handling) -> Self {
+        Self {
+            delay: Duration::from_millis(handling.das),
+            interval: Duration::from_millis(handling.arr),
+            soft_drop_interval: Duration::from_millis(handling.soft_drop),
+            soft_drop_factor: handling.soft_drop_factor,
+            soft_drop_lock: handling.soft_drop_lock.unwrap_or(SoftDropLock::Never),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct RecordedInput {
+    pub tick: u64,
+    pub action: Action,
+    pub kind: KeyKind,
+    // Game::state_hash right after the input, absent from hand-written replays
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<u64>,
+}
+
+impl RecordedInput {
+    // Compares the state after playing the input back with the recorded one, `index`
+    // is the input's place in the replay
+    pub fn check(&self, index: usize, hash: u64) -> Result<(), ReplayError> {
+        match self.hash {
+            Some(recorded) if recorded != hash => Err(ReplayError::Desync {
+                input: index,
+                tick: self.tick,
+                recorded,
+                played: hash,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyKind {
+    Press,
+    Repeat,
+    Release,
+}
+
+impl From<KeyEventKind> for KeyKind {
+    fn from(kind: KeyEventKind) -> Self {
+        match kind {
+            KeyEventKind::Press => KeyKind::Press,
+            KeyEventKind::Repeat => KeyKind::Repeat,
+            KeyEventKind::Release => KeyKind::Release,
+        }
+    }
+}
+
+impl From<KeyKind> for KeyEventKind {
+    fn from(kind: KeyKind) -> Self {
+        match kind {
+            KeyKind::Press => KeyEventKind::Press,
+            KeyKind::Repeat => KeyEventKind::Repeat,
+            KeyKind::Release => KeyEventKind::Release,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Read(io::Error),
+    Format(serde_json::Error),
+    UnsupportedVersion(u32),
+    BadSequence(String),
+    BadDecisions,
+    Desync {
+        input: usize,
+        tick: u64,
+        recorded: u64,
+        played: u64,
+    },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::Read(err) => write!(f, "{err}"),
+            ReplayError::Format(err) => write!(f, "not a replay: {err}"),
+            ReplayError::UnsupportedVersion(version) => write!(
+                f,
+                "replay version {version} isn't supported, this build reads versions 2 to {}",
+                Replay::VERSION
+            ),
+            ReplayError::BadSequence(shapes) => {
+                write!(f, "the sequence {shapes:?} isn't made of tetrominos")
+            }
+            ReplayError::BadDecisions => {
+                write!(f, "a bag in the decisions isn't made of tetrominos")
+            }
+            ReplayError::Desync {
+                input,
+                tick,
+                recorded,
+                played,
+            } => write!(
+                f,
+                "replay went out of sync at input {input} (tick {tick}): \
+                 recorded state {recorded:016x}, played back {played:016x}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl Replay {
+    // Version 1 didn't keep the handling, its games can't be played back the same way.
+    // Version 3 added the rules, which builds before it would ignore.
+    pub const VERSION: u32 = 3;
+    const OLDEST_VERSION: u32 = 2;
+
+    pub fn new(seed: u64, bag_count: usize, rules: Ruleset, repeat: RepeatSettings) -> Self {
+        let mode = rules.mode();
+        Self {
+            version: Self::VERSION,
+            seed,
+            bag_count,
+            mode,
+            handling: repeat.into(),
+            sequence: None,
+            rules: (rules != mode.ruleset()).then_some(rules),
+            decisions: None,
+            result: None,
+            name: None,
+            saved: None,
+            inputs: Vec::new(),
+        }
+    }
+
+    pub fn rules(&self) -> Ruleset {
+        self.rules.clone().unwrap_or_else(|| self.mode.ruleset())
+    }
+
+    // The game as it starts, before any input
+    pub fn game(&self) -> Game {
+        let mut bag = match &self.sequence {
+            Some(sequence) => sequence.bag().expect("checked when loaded"),
+            None => TetrominosBag::with_bag_count(self.bag_count),
+        };
+        bag.reseed(self.seed);
+        if let Some(bags) = self.known_decisions() {
+            assert!(bag.follow(bags), "checked when loaded");
+        }
+        bag.shuffle();
+        Game::with_rules(
+            PLAYFIELD_COLS.into(),
+            PLAYFIELD_ROWS.into(),
+            bag,
+            self.rules(),
+        )
+    }
+
+    fn known_decisions(&self) -> Option<&[String]> {
+        self.decisions
+            .as_ref()
+            .filter(|decisions| decisions.version == Decisions::VERSION)
+            .map(|decisions| decisions.bags.as_slice())
+    }
+
+    // Keeps how the game's bags were shuffled, up to now
+    pub fn note_decisions(&mut self, game: &Game) {
+        self.decisions = Some(Decisions {
+            version: Decisions::VERSION,
+            bags: game.bag_decisions().to_vec(),
+        });
+    }
+
+    // Keeps what the game came to, once it's over
+    pub fn note_result(&mut self, game: &Game) {
+        self.result = Some(Claim::of(game));
+    }
+
+    pub fn repeat(&self) -> RepeatSettings {
+        self.handling.into()
+    }
+
+    // `hash` is the game's state hash once the input was applied
+    pub fn record(&mut self, tick: u64, action: Action, kind: KeyEventKind, hash: u64) {
+        self.inputs.push(RecordedInput {
+            tick,
+            action,
+            kind: kind.into(),
+            hash: Some(hash),
+        });
+    }
+
+    // The version is checked on its own first, a newer format may not parse as this one
+    pub fn load(path: &Path) -> Result<Self, ReplayError> {
+        #[derive(Deserialize)]
+        struct Version {
+            version: u32,
+        }
+        let text = fs::read_to_string(path).map_err(ReplayError::Read)?;
+        let Version { version } = serde_json::from_str(&text).map_err(ReplayError::Format)?;
+        if !(Self::OLDEST_VERSION..=Self::VERSION).contains(&version) {
+            return Err(ReplayError::UnsupportedVersion(version));
+        }
+        let replay: Replay = serde_json::from_str(&text).map_err(ReplayError::Format)?;
+        if let Some(sequence) = replay.sequence.as_ref().filter(|seq| seq.bag().is_none()) {
+            return Err(ReplayError::BadSequence(sequence.shapes.clone()));
+        }
+        if let Some(bags) = replay.known_decisions() {
+            if !TetrominosBag::new().follow(bags) {
+                return Err(ReplayError::BadDecisions);
+            }
+        }
+        Ok(replay)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_string(self)?)
+    }
+
+    // Reads no more of the file than the browser lists, the inputs are skipped over
+    // without being kept
+    pub fn header(path: &Path) -> Result<Header, ReplayError> {
+        let file = fs::File::open(path).map_err(ReplayError::Read)?;
+        let header: Header =
+            serde_json::from_reader(io::BufReader::new(file)).map_err(ReplayError::Format)?;
+        if !(Self::OLDEST_VERSION..=Self::VERSION).contains(&header.version) {
+            return Err(ReplayError::UnsupportedVersion(header.version));
+        }
+        Ok(header)
+    }
+
+    // Writes the replay into `dir` as `name`.json, or with a number after the name if
+    // that's taken, and returns what a high score entry keeps to find it and tell
+    // whether it's still the same
+    pub fn keep(&self, dir: &Path, name: &str) -> io::Result<ReplayRef> {
+        fs::create_dir_all(dir)?;
+        let text = serde_json::to_string(self)?;
+        let mut file = format!("{name}.json");
+        let mut number = 1;
+        while dir.join(&file).exists() {
+            number += 1;
+            file = format!("{name}-{number}.json");
+        }
+        fs::write(dir.join(&file), &text)?;
+        Ok(ReplayRef {
+            file,
+            hash: replay_hash(text.as_bytes()),
+        })
+    }
+
+    // Where --record PATH keeps the `game`th game of the session, counted from 1
+    pub fn numbered_path(path: &Path, game: usize) -> PathBuf {
+        if game <= 1 {
+            return path.to_path_buf();
+        }
+        let mut name = path.file_stem().unwrap_or_default().to_owned();
+        name.push(format!("-{game}"));
+        if let Some(extension) = path.extension() {
+            name.push(".");
+            name.push(extension);
+        }
+        path.with_file_name(name)
+    }
+}
+
+// Seconds since the Unix epoch
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
+// What a replay saved at `timestamp` is called: the date, the mode, the time of a
+// finished run with a line goal or the points of any other, the name it was given if
+// any and _pb for the best run of its mode yet
+pub fn file_stem(timestamp: u64, game: &Game, name: &str, best: bool) -> String {
+    let result = match game.rules().line_goal {
+        Some(_) if game.is_completed() => format!("{:.1}s", game.played().as_secs_f64()),
+        _ => format!("{}pts", game.score().points),
+    };
+    let mut stem = format!("{}_{}_{result}", date(timestamp), game.rules().id);
+    // Whatever the name, it stays a single file name
+    let name: String = name
+        .trim()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    if !name.is_empty() {
+        stem.push_str(&format!("_{name}"));
+    }
+    if best {
+        stem.push_str("_pb");
+    }
+    stem
+}
+
+// The UTC date of a Unix timestamp, as in 2024-06-01
+pub fn date(timestamp: u64) -> String {
+    // Days counted from 0000-03-01, so that leap days come last in the year
+    let days = timestamp / 86400 + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use tetris::state::{Hold, Input};
+
+    use super::*;
+    use crate::session::Session;
+
+    const KEYS: [(u64, Action, KeyEventKind); 6] = [
+        (100, Action::MoveLeft, KeyEventKind::Press),
+        (250, Action::MoveLeft, KeyEventKind::Release),
+        (300, Action::HardDrop, KeyEventKind::Press),
+        (700, Action::MoveRight, KeyEventKind::Press),
+        (850, Action::MoveRight, KeyEventKind::Release),
+        (900, Action::HardDrop, KeyEventKind::Press),
+    ];
+
+    // Feeds the inputs on their ticks, then lets the game run on by itself for a while
+    fn play_back(replay: &Replay, repeat: RepeatSettings) -> Session {
+        let mut session = Session::new(replay.game(), repeat);
+        for input in &replay.inputs {
+            session.advance(Duration::from_millis(input.tick));
+            session.key(input.action, input.kind.into());
+        }
+        session.advance(Duration::from_secs(5));
+        session
+    }
+
+    // Plays the keys as they come and records them along the way
+    fn record(keys: &[(u64, Action, KeyEventKind)], repeat: RepeatSettings) -> Replay {
+        let mut recording = Replay::new(42, 1, GameMode::Marathon.ruleset(), repeat);
+        let mut session = Session::new(recording.game(), repeat);
+        for &(tick, action, kind) in keys {
+            session.advance(Duration::from_millis(tick));
+            session.key(action, kind);
+            recording.record(tick, action, kind, session.game().state_hash());
+        }
+        recording
+    }
+
+    // The first input whose hash doesn't match once played back
+    fn first_desync(replay: &Replay) -> Option<usize> {
+        let mut session = Session::new(replay.game(), replay.repeat());
+        replay.inputs.iter().enumerate().find_map(|(index, input)| {
+            session.advance(Duration::from_millis(input.tick));
+            session.key(input.action, input.kind.into());
+            match input.check(index, session.game().state_hash()) {
+                Err(ReplayError::Desync { input, .. }) => Some(input),
+                _ => None,
+            }
+        })
+    }
+
+    fn outcome(session: &Session) -> (String, [(usize, usize); 4], u32) {
+        let game = session.game();
+        (
+            game.board().to_text(),
+            game.tetromino().body(),
+            game.score().points,
+        )
+    }
+
+    #[test]
+    fn a_saved_replay_plays_back_the_same_game() {
+        let handling = RepeatSettings {
+            delay: Duration::from_millis(100),
+            interval: Duration::from_millis(10),
+            soft_drop_interval: Duration::from_millis(30),
+            soft_drop_factor: Some(SoftDropFactor::Times(20)),
+            soft_drop_lock: SoftDropLock::Hold,
+        };
+        // Each key is held past the delay above but not past the default one
+        let recording = record(&KEYS, handling);
+        let path = env::temp_dir().join(format!("tetrs-replay-{}.json", process::id()));
+        recording.save(&path).unwrap();
+        let loaded = Replay::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.handling, Handling::from(handling));
+        let played = outcome(&play_back(&recording, handling));
+        assert_eq!(outcome(&play_back(&loaded, loaded.repeat())), played);
+        // Played back with other handling the pieces end up elsewhere
+        assert_ne!(
+            outcome(&play_back(&loaded, RepeatSettings::default())),
+            played
+        );
+        assert_eq!(first_desync(&loaded), None);
+    }
+
+    #[test]
+    fn playback_stops_at_the_first_input_that_goes_another_way() {
+        let mut replay = record(&KEYS, RepeatSettings::default());
+        // The second hard drop lands somewhere else without the move before it
+        replay.inputs[3].action = Action::MoveLeft;
+        assert_eq!(first_desync(&replay), Some(3));
+        // Inputs without a hash are taken as they are
+        for input in &mut replay.inputs {
+            input.hash = None;
+        }
+        assert_eq!(first_desync(&replay), None);
+    }
+
+    #[test]
+    fn desyncs_are_reported_with_both_hashes() {
+        let mut replay = record(&KEYS[..1], RepeatSettings::default());
+        let input = &mut replay.inputs[0];
+        input.hash = Some(0xabc);
+        assert_eq!(
+            input.check(0, 0xdef).unwrap_err().to_string(),
+            "replay went out of sync at input 0 (tick 100): \
+             recorded state 0000000000000abc, played back 0000000000000def"
+        );
+        assert!(input.check(0, 0xabc).is_ok());
+    }
+
+    #[test]
+    fn every_game_after_the_first_is_recorded_next_to_it() {
+        let path = Path::new("replays/run.json");
+        assert_eq!(Replay::numbered_path(path, 1), path);
+        assert_eq!(
+            Replay::numbered_path(path, 2),
+            Path::new("replays/run-2.json")
+        );
+        assert_eq!(
+            Replay::numbered_path(Path::new("run"), 12),
+            Path::new("run-12")
+        );
+    }
+
+    #[test]
+    fn saved_replays_are_named_by_date_mode_and_result() {
+        assert_eq!(date(0), "1970-01-01");
+        assert_eq!(date(1_717_200_000), "2024-06-01");
+        assert_eq!(date(951_782_400), "2000-02-29");
+        let mut sprint = GameMode::Sprint.ruleset();
+        sprint.line_goal = Some(1);
+        let bag = TetrominosBag::scripted("IIO", SequenceEnd::Loop).unwrap();
+        let mut game = Game::with_rules(10, 20, bag, sprint);
+        let timestamp = 1_717_200_000;
+        assert_eq!(
+            file_stem(timestamp, &game, "", false),
+            "2024-06-01_sprint_0pts"
+        );
+        // Across the bottom row, an I to the left, an I beside it and an O on the right
+        let mut left = true;
+        for _ in 0..3 {
+            let (input, times) = match game.tetromino().shape() {
+                'O' => (Input::MoveRight, 9),
+                _ if left => (Input::MoveLeft, 9),
+                _ => (Input::MoveRight, 1),
+            };
+            left &= game.tetromino().shape() != 'I';
+            for _ in 0..times {
+                game.apply(input);
+            }
+            game.apply(Input::HardDrop);
+            game.finish_clear();
+        }
+        assert!(game.is_completed());
+        assert_eq!(
+            file_stem(timestamp, &game, "", false),
+            "2024-06-01_sprint_0.0s"
+        );
+        let marathon = Game::with_rules(10, 20, TetrominosBag::new(), GameMode::Marathon.ruleset());
+        assert_eq!(
+            file_stem(timestamp, &marathon, " my best/run ", true),
+            "2024-06-01_marathon_0pts_my-best-run_pb"
+        );
+    }
+
+    #[test]
+    fn a_kept_replay_never_goes_over_another() {
+        let dir = env::temp_dir().join(format!("tetrs-kept-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let replay = record(&KEYS, RepeatSettings::default());
+        let first = replay.keep(&dir, "run").unwrap();
+        let second = replay.keep(&dir, "run").unwrap();
+        assert_eq!(
+            (first.file.as_str(), second.file.as_str()),
+            ("run.json", "run-2.json")
+        );
+        assert_eq!(first.hash, second.hash);
+        let text = fs::read(dir.join("run.json")).unwrap();
+        assert_eq!(replay_hash(&text), first.hash);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn replays_without_the_handling_are_refused() {
+        let path = env::temp_dir().join(format!("tetrs-replay-v1-{}.json", process::id()));
+        fs::write(
+            &path,
+            r#"{"version": 1, "seed": 1, "bag_count": 1, "inputs": []}"#,
+        )
+        .unwrap();
+        let loaded = Replay::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(loaded, Err(ReplayError::UnsupportedVersion(1))));
+    }
+
+    #[test]
+    fn rules_other_than_the_modes_own_are_kept() {
+        let repeat = RepeatSettings::default();
+        assert!(Replay::new(1, 1, GameMode::Sprint.ruleset(), repeat)
+            .rules
+            .is_none());
+        let rules = Ruleset {
+            id: "zen".to_string(),
+            line_goal: Some(2),
+            hold: Hold::Off,
+            ..GameMode::Marathon.ruleset()
+        };
+        let replay = Replay::new(1, 1, rules.clone(), repeat);
+        assert_eq!(replay.mode, GameMode::Custom);
+        let path = env::temp_dir().join(format!("tetrs-replay-rules-{}.json", process::id()));
+        replay.save(&path).unwrap();
+        let loaded = Replay::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.rules(), rules);
+        assert_eq!(loaded.game().rules(), &rules);
+    }
+
+    #[test]
+    fn version_2_replays_play_by_their_mode() {
+        let path = env::temp_dir().join(format!("tetrs-replay-v2-{}.json", process::id()));
+        fs::write(
+            &path,
+            r#"{"version": 2, "seed": 1, "bag_count": 1, "mode": "ultra",
+                "handling": {"das": 170, "arr": 50, "soft_drop": 50}, "inputs": []}"#,
+        )
+        .unwrap();
+        let loaded = Replay::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.rules(), GameMode::Ultra.ruleset());
+    }
+
+    #[test]
+    fn playback_follows_the_recorded_bags_over_the_seed() {
+        let mut recording = record(&KEYS, RepeatSettings::default());
+        let played = outcome(&play_back(&recording, RepeatSettings::default()));
+        let game = play_back(&recording, RepeatSettings::default());
+        recording.note_decisions(game.game());
+        // As if the shuffling had changed since
+        recording.seed = 7;
+        assert_eq!(
+            outcome(&play_back(&recording, RepeatSettings::default())),
+            played
+        );
+        assert_eq!(first_desync(&recording), None);
+        // A version of them this build doesn't know is left to the seed
+        recording.decisions.as_mut().unwrap().version = 2;
+        assert_eq!(first_desync(&recording), Some(0));
+    }
+
+    #[test]
+    fn decisions_are_saved_and_checked() {
+        let mut replay = Replay::new(
+            1,
+            1,
+            GameMode::Marathon.ruleset(),
+            RepeatSettings::default(),
+        );
+        replay.note_decisions(&replay.game());
+        let path = env::temp_dir().join(format!("tetrs-replay-bags-{}.json", process::id()));
+        replay.save(&path).unwrap();
+        let loaded = Replay::load(&path).unwrap();
+        assert_eq!(loaded.decisions, replay.decisions);
+        replay.decisions.as_mut().unwrap().bags[0] = "OOOOOOX".to_string();
+        replay.save(&path).unwrap();
+        let loaded = Replay::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(loaded, Err(ReplayError::BadDecisions)));
+    }
+
+    #[test]
+    fn hold_reads_as_it_was_written_before_it_had_a_limit() {
+        let rules = r#"{"id": "zen", "name": "zen", "description": "", "line_goal": null,
+            "time_limit": null, "level_up": true, "hold": false, "ghost": true,
+            "scoring": true}"#;
+        let rules: Ruleset = serde_json::from_str(rules).unwrap();
+        assert_eq!(rules.hold, Hold::Off);
+        let limited = Ruleset {
+            hold: Hold::Limited(3),
+            ..rules
+        };
+        let text = serde_json::to_string(&limited).unwrap();
+        assert!(text.contains(r#""hold":3"#));
+        assert_eq!(serde_json::from_str::<Ruleset>(&text).unwrap(), limited);
+    }
+}
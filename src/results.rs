@@ -0,0 +1,223 @@
+// What a finished game came to, as the game over screen shows it and as --json-results
+// PATH writes it for spreadsheets and dashboards:
+//
+//     {"format_version": 1, "mode": "sprint", "seed": 42, "bag_count": 1,
+//      "handling": {"das": 170, "arr": 50, "soft_drop": 50}, "completed": true,
+//      "score": {"points": 5400, "lines": 40, "level": 5, "back_to_back": false, ...},
+//      "time_ms": 61250, "pieces": 102, "pps": 1.67, "gravity": 2.82,
+//      "clears": {"single": 6, "double": 3, "tetris": 7, "t_spin_double": 1},
+//      "back_to_backs": 4,
+//      "placements": [{"ms": 812, "shape": "I", "cells": [[3, 19], [4, 19], ...]}, ...]}
+//
+// "sequence" is there too for a game played with --sequence, and "rules" for a game
+// whose rules aren't its mode's own, like in a replay. Clears
+// are counted by the name shown for them, without the B2B. Cells are (x, y) from the
+// top left corner of the board. Gravity is the speed the game ended on, in rows a
+// second. There are no finesse faults, nothing counts them.
+//
+// "idle_ms" is there for a game that paused itself waiting for the player, the time
+// it waited. It's in time_ms like the rest of the game, but pps leaves it out.
+//
+// New fields can turn up in any version, format_version only goes up when one changes
+// meaning or goes away. With --json-results every game played from the menu is
+// written, numbered like the files of --record.
+
+use std::{collections::BTreeMap, fs, io, path::Path, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use tetris::{
+    score::{LineClear, Score},
+    state::{Game, GameMode, Ruleset},
+};
+
+use crate::replay::{Handling, Sequence};
+
+// Where a tetromino locked, and when on the game's clock
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Placement {
+    pub ms: u64,
+    pub shape: char,
+    pub cells: [(usize, usize); 4],
+}
+
+// Counted along by Session while the game is played
+#[derive(Clone, Default, Debug)]
+pub struct Tally {
+    pub placements: Vec<Placement>,
+    pub clears: BTreeMap<String, u32>,
+    pub back_to_backs: u32,
+    // Spent waiting for a key before the game paused by itself, see Session
+    pub idle: Duration,
+}
+
+impl Tally {
+    pub fn count(&mut self, clear: LineClear) {
+        let Some(name) = clear.name() else {
+            return;
+        };
+        let name = name.trim_start_matches("B2B ").to_lowercase();
+        *self
+            .clears
+            .entry(name.replace([' ', '-'], "_"))
+            .or_default() += 1;
+        if clear.back_to_back {
+            self.back_to_backs += 1;
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Results {
+    pub format_version: u32,
+    pub mode: GameMode,
+    pub seed: u64,
+    pub bag_count: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<Sequence>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rules: Option<Ruleset>,
+    pub handling: Handling,
+    // The mode's goal was reached, rather than topping out
+    pub completed: bool,
+    pub score: Score,
+    pub time_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_ms: Option<u64>,
+    pub pieces: usize,
+    // Pieces per second, the idle time aside
+    pub pps: f64,
+    // Not in the first results written
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gravity: Option<f64>,
+    pub clears: BTreeMap<String, u32>,
+    pub back_to_backs: u32,
+    pub placements: Vec<Placement>,
+}
+
+impl Results {
+    pub const FORMAT_VERSION: u32 = 1;
+
+    pub fn new(
+        game: &Game,
+        tally: &Tally,
+        seed: u64,
+        bag_count: usize,
+        sequence: Option<Sequence>,
+        handling: Handling,
+    ) -> Self {
+        let played = game.played().saturating_sub(tally.idle);
+        let pieces = tally.placements.len();
+        let pps = if played.is_zero() {
+            0.0
+        } else {
+            // Two decimals are plenty, and keep the files tidy
+            (pieces as f64 / played.as_secs_f64() * 100.0).round() / 100.0
+        };
+        Self {
+            format_version: Self::FORMAT_VERSION,
+            mode: game.mode(),
+            seed,
+            bag_count,
+            sequence,
+            rules: (*game.rules() != game.mode().ruleset()).then(|| game.rules().clone()),
+            handling,
+            completed: game.is_completed(),
+            score: game.score(),
+            time_ms: game.played().as_millis() as u64,
+            idle_ms: (!tally.idle.is_zero()).then_some(tally.idle.as_millis() as u64),
+            pieces,
+            pps,
+            gravity: Some((game.score().gravity_speed() * 100.0).round() / 100.0),
+            clears: tally.clears.clone(),
+            back_to_backs: tally.back_to_backs,
+            placements: tally.placements.clone(),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_string(self)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crossterm::event::KeyEventKind;
+    use tetris::game::TetrominosBag;
+
+    use super::*;
+    use crate::{
+        input::{Action, RepeatSettings},
+        session::Session,
+    };
+
+    // Written by a version 1 build, it has to keep reading the same
+    const FIXTURE: &str = include_str!("../tests/fixtures/results-v1.json");
+
+    #[test]
+    fn the_version_1_fixture_still_reads() {
+        let results: Results = serde_json::from_str(FIXTURE).unwrap();
+        assert_eq!(results.format_version, 1);
+        assert_eq!(results.mode, GameMode::Sprint);
+        assert_eq!(results.seed, 42);
+        assert_eq!(results.score.lines, 40);
+        assert_eq!(results.clears["tetris"], 7);
+        assert_eq!(results.clears["t_spin_double"], 1);
+        assert_eq!(results.placements[0].shape, 'I');
+        assert_eq!(results.placements[0].cells[0], (3, 19));
+        // And writes back with the same fields
+        let written = serde_json::to_value(&results).unwrap();
+        let fixture: serde_json::Value = serde_json::from_str(FIXTURE).unwrap();
+        assert_eq!(written, fixture);
+    }
+
+    #[test]
+    fn a_finished_game_has_its_placements_and_clears() {
+        let mut session = Session::new(
+            Game::with_bag(10, 20, TetrominosBag::new()),
+            RepeatSettings::default(),
+        );
+        session.key(Action::HardDrop, KeyEventKind::Press);
+        session.advance(Duration::from_millis(200));
+        session.key(Action::HardDrop, KeyEventKind::Press);
+        session.advance(Duration::from_secs(2));
+        let results = Results::new(
+            session.game(),
+            session.tally(),
+            7,
+            1,
+            None,
+            RepeatSettings::default().into(),
+        );
+        assert_eq!(results.pieces, 2);
+        assert_eq!(
+            results.placements[0],
+            Placement {
+                ms: 0,
+                shape: 'O',
+                cells: [(4, 18), (4, 19), (5, 18), (5, 19)],
+            }
+        );
+        assert_eq!(results.placements[1].ms, 200);
+        assert_eq!(results.pps, 1.0);
+        assert_eq!(results.gravity, Some(1.0));
+        assert!(results.clears.is_empty());
+    }
+
+    #[test]
+    fn clears_are_counted_by_name_without_the_back_to_back() {
+        let mut tally = Tally::default();
+        let mut score = Score::new();
+        score.line_clear(4, None);
+        tally.count(score.line_clear(4, None));
+        tally.count(score.line_clear(1, None));
+        tally.count(score.line_clear(0, None));
+        assert_eq!(
+            tally.clears,
+            BTreeMap::from([("tetris".to_string(), 1), ("single".to_string(), 1)])
+        );
+        assert_eq!(tally.back_to_backs, 1);
+    }
+}
@@ -0,0 +1,185 @@
+// Scoring, line count and level, following the guideline formulas
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::TSpin;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub struct Score {
+    pub points: u32,
+    pub lines: u32,
+    pub level: u32,
+    // The last clear was a tetris or a T-spin, the next one of those gets a bonus
+    pub back_to_back: bool,
+    // The level stays where it is however many lines get cleared
+    pub fixed_level: bool,
+    // Kept by the rules, not with the score
+    #[serde(skip)]
+    pub gravity: GravityCurve,
+    // Kept by the rules too, see Ruleset
+    #[serde(skip)]
+    pub spin_b2b: bool,
+    // Levels the speed is over the level, or under it, with the adaptive rules
+    #[serde(skip)]
+    pub speed_offset: i32,
+}
+
+// How gravity goes from one level's speed to the next one's. Stepped it changes at
+// the level up, the other two get there bit by bit with every line cleared on the
+// way, in even steps of time or of speed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GravityCurve {
+    #[default]
+    Stepped,
+    Linear,
+    Exponential,
+}
+
+impl GravityCurve {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "stepped" => Some(Self::Stepped),
+            "linear" => Some(Self::Linear),
+            "exponential" => Some(Self::Exponential),
+            _ => None,
+        }
+    }
+}
+
+// What a locked tetromino was worth
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LineClear {
+    pub lines: usize,
+    pub spin: Option<TSpin>,
+    // Whether the back-to-back bonus applied
+    pub back_to_back: bool,
+    pub points: u32,
+}
+
+impl LineClear {
+    // As shown to the player, None for a plain lock
+    pub fn name(&self) -> Option<String> {
+        const LINES: [&str; 4] = ["", "SINGLE", "DOUBLE", "TRIPLE"];
+        let name = match (self.spin, self.lines) {
+            (None, 0) => return None,
+            (None, 4..) => "TETRIS".to_string(),
+            (None, lines) => LINES[lines].to_string(),
+            (Some(spin), lines) => {
+                let spin = match spin {
+                    TSpin::Mini => "MINI T-SPIN",
+                    TSpin::Full => "T-SPIN",
+                };
+                format!("{spin} {}", LINES[lines.min(3)])
+                    .trim_end()
+                    .to_string()
+            }
+        };
+        if self.back_to_back {
+            return Some(format!("B2B {name}"));
+        }
+        Some(name)
+    }
+}
+
+impl Score {
+    const LINES_PER_LEVEL: u32 = 10;
+    pub const MAX_SPEED_LEVEL: u32 = 20;
+
+    pub fn new() -> Self {
+        Self {
+            points: 0,
+            lines: 0,
+            level: 1,
+            back_to_back: false,
+            fixed_level: false,
+            gravity: GravityCurve::Stepped,
+            spin_b2b: false,
+            speed_offset: 0,
+        }
+    }
+
+    // Points are awarded at the level the lines were cleared on, the level up comes after.
+    // Tetrises and T-spin clears in a row are worth half again as much, any other clear
+    // breaks the streak but a T-spin without lines doesn't, and with spin_b2b it
+    // starts or keeps one going.
+    pub fn line_clear(&mut self, lines: usize, spin: Option<TSpin>) -> LineClear {
+        const POINTS: [u32; 5] = [0, 100, 300, 500, 800];
+        const T_SPIN_POINTS: [u32; 4] = [400, 800, 1200, 1600];
+        const MINI_T_SPIN_POINTS: [u32; 3] = [100, 200, 400];
+        let base = match spin {
+            None => POINTS[lines.min(4)],
+            Some(TSpin::Full) => T_SPIN_POINTS[lines.min(3)],
+            Some(TSpin::Mini) => MINI_T_SPIN_POINTS[lines.min(2)],
+        };
+        let difficult = lines >= 4 || (lines > 0 && spin.is_some());
+        let back_to_back = difficult && self.back_to_back;
+        let mut points = base * self.level;
+        if back_to_back {
+            points += points / 2;
+        }
+        if lines > 0 {
+            self.back_to_back = difficult;
+        } else if spin.is_some() && self.spin_b2b {
+            self.back_to_back = true;
+        }
+        self.points += points;
+        self.lines += lines as u32;
+        if !self.fixed_level {
+            self.level = 1 + self.lines / Self::LINES_PER_LEVEL;
+        }
+        LineClear {
+            lines,
+            spin,
+            back_to_back,
+            points,
+        }
+    }
+
+    pub fn soft_drop(&mut self, cells: usize) {
+        self.points += cells as u32;
+    }
+
+    pub fn hard_drop(&mut self, cells: usize) {
+        self.points += 2 * cells as u32;
+    }
+
+    // Time a tetromino takes to fall one row by itself, worked out from the level and
+    // the lines into it every time. The speed stops going up past MAX_SPEED_LEVEL, the
+    // formula would go below zero a hundred levels later.
+    pub fn gravity_interval(&self) -> Duration {
+        let level = self.speed_level();
+        let at_level = Self::level_interval(level);
+        if self.fixed_level || level == Self::MAX_SPEED_LEVEL {
+            return Duration::from_secs_f64(at_level);
+        }
+        let next = Self::level_interval(level + 1);
+        let into_level = f64::from(self.lines % Self::LINES_PER_LEVEL);
+        let fraction = into_level / f64::from(Self::LINES_PER_LEVEL);
+        Duration::from_secs_f64(match self.gravity {
+            GravityCurve::Stepped => at_level,
+            GravityCurve::Linear => at_level + (next - at_level) * fraction,
+            GravityCurve::Exponential => at_level * (next / at_level).powf(fraction),
+        })
+    }
+
+    // The level whose speed the game goes at, the level itself unless it's adaptive
+    pub fn speed_level(&self) -> u32 {
+        self.level
+            .saturating_add_signed(self.speed_offset)
+            .clamp(1, Self::MAX_SPEED_LEVEL)
+    }
+
+    // Rows a second, as shown on the game over screen
+    pub fn gravity_speed(&self) -> f64 {
+        1.0 / self.gravity_interval().as_secs_f64()
+    }
+
+    // The guideline's seconds a row, for a level from 1 to MAX_SPEED_LEVEL
+    fn level_interval(level: u32) -> f64 {
+        let level = f64::from(level - 1);
+        (0.8 - level * 0.007).powf(level)
+    }
+}
@@ -0,0 +1,277 @@
+// --scrub, with --replay: the replay isn't played on the clock but stepped through by
+// hand, one recorded input at a time, to see how the game got where it did:
+//
+//     left, right   one input back or on
+//     up, down      ten inputs back or on
+//     g             goes to the input typed after it, on enter
+//     q             quits
+//
+// The line under the board is the whole replay, with a | where lines were cleared
+// and an X where it topped out. The session is kept as it was every CHECKPOINT_EVERY
+// inputs along the way, so a step back plays on from the nearest one rather than from
+// the start.
+
+use std::{io, sync::mpsc, time::Instant};
+
+use crossterm::event::{Event, KeyCode, KeyEventKind};
+use tui::{backend::Backend, Terminal};
+
+use crate::{
+    replay::{RecordedInput, Replay},
+    session::Session,
+    ui::Playfield,
+};
+
+// What happened on an input, for the timeline
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Mark {
+    Clear,
+    TopOut,
+}
+
+pub struct Scrubber<'a> {
+    replay: &'a Replay,
+    // The session after 0, CHECKPOINT_EVERY, twice that and so on inputs
+    checkpoints: Vec<Session>,
+    marks: Vec<Option<Mark>>,
+    session: Session,
+    // Inputs played so far
+    at: usize,
+}
+
+impl<'a> Scrubber<'a> {
+    pub const CHECKPOINT_EVERY: usize = 100;
+
+    // Plays the replay through once, for the checkpoints and the marks
+    pub fn new(replay: &'a Replay) -> Self {
+        let mut session = Session::new(replay.game(), replay.repeat());
+        let mut checkpoints = vec![session.clone()];
+        let mut marks = Vec::new();
+        for (index, input) in replay.inputs.iter().enumerate() {
+            let lines = session.game().score().lines;
+            let was_over = session.game().is_over();
+            step(&mut session, input);
+            let game = session.game();
+            marks.push(if game.is_over() && !was_over && !game.is_completed() {
+                Some(Mark::TopOut)
+            } else if game.score().lines > lines {
+                Some(Mark::Clear)
+            } else {
+                None
+            });
+            if (index + 1) % Self::CHECKPOINT_EVERY == 0 {
+                checkpoints.push(session.clone());
+            }
+        }
+        Self {
+            replay,
+            session: checkpoints[0].clone(),
+            checkpoints,
+            marks,
+            at: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.replay.inputs.len()
+    }
+
+    pub fn at(&self) -> usize {
+        self.at
+    }
+
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    // Goes to the state after `target` inputs, or after the last one
+    pub fn seek(&mut self, target: usize) {
+        let target = target.min(self.len());
+        let checkpoint = target / Self::CHECKPOINT_EVERY;
+        if target < self.at || checkpoint > self.at / Self::CHECKPOINT_EVERY {
+            self.session = self.checkpoints[checkpoint].clone();
+            self.at = checkpoint * Self::CHECKPOINT_EVERY;
+        }
+        for input in &self.replay.inputs[self.at..target] {
+            step(&mut self.session, input);
+        }
+        self.at = target;
+    }
+
+    pub fn step_by(&mut self, inputs: isize) {
+        self.seek(self.at.saturating_add_signed(inputs));
+    }
+
+    // The whole replay in `width` columns, and the column of the current input. A
+    // column with several inputs shows the one that matters most.
+    pub fn timeline(&self, width: usize) -> (String, usize) {
+        let width = width.max(1);
+        let mut columns = vec![None; width];
+        for (index, mark) in self.marks.iter().enumerate() {
+            let column = &mut columns[index * width / self.len()];
+            *column = (*column).max(*mark);
+        }
+        let line = columns
+            .into_iter()
+            .map(|mark| match mark {
+                Some(Mark::TopOut) => 'X',
+                Some(Mark::Clear) => '|',
+                None => '-',
+            })
+            .collect();
+        let cursor = (self.at * width)
+            .checked_div(self.len())
+            .unwrap_or(0)
+            .min(width - 1);
+        (line, cursor)
+    }
+}
+
+fn step(session: &mut Session, input: &RecordedInput) {
+    session.advance(std::time::Duration::from_millis(input.tick));
+    session.key(input.action, input.kind.into());
+}
+
+pub fn run<B: Backend>(
+    terminal: &mut Terminal<B>,
+    playfield: &mut Playfield,
+    replay: &Replay,
+    rx_input: &mpsc::Receiver<(Event, Instant)>,
+) -> io::Result<()> {
+    let mut scrubber = Scrubber::new(replay);
+    // The input index typed after g
+    let mut typed: Option<String> = None;
+    loop {
+        let tick = scrubber.session().tick_count();
+        let status = match &typed {
+            Some(typed) => format!("go to input: {typed}_"),
+            None => format!(
+                "input {}/{}  tick {tick}  \u{2190}\u{2192} step  \u{2191}\u{2193} 10  g go to  q quit",
+                scrubber.at(),
+                scrubber.len()
+            ),
+        };
+        let (width, _) = playfield.frame();
+        let timeline = scrubber.timeline(usize::from(width.saturating_sub(2)));
+        playfield.draw_scrub(terminal, scrubber.session().game(), &status, timeline);
+        let Ok((event, _)) = rx_input.recv() else {
+            return Ok(());
+        };
+        let key = match event {
+            Event::Key(key) if key.kind != KeyEventKind::Release => key,
+            Event::Resize(width, height) => {
+                crate::resize(terminal, playfield, width, height)?;
+                continue;
+            }
+            _ => continue,
+        };
+        match (&mut typed, key.code) {
+            (Some(digits), KeyCode::Char(digit @ '0'..='9')) => digits.push(digit),
+            (Some(digits), KeyCode::Backspace) => {
+                digits.pop();
+            }
+            (Some(digits), KeyCode::Enter) => {
+                if let Ok(target) = digits.parse() {
+                    scrubber.seek(target);
+                }
+                typed = None;
+            }
+            (Some(_), KeyCode::Esc) => typed = None,
+            (Some(_), _) => {}
+            (None, KeyCode::Left) => scrubber.step_by(-1),
+            (None, KeyCode::Right) => scrubber.step_by(1),
+            (None, KeyCode::Up) => scrubber.step_by(-10),
+            (None, KeyCode::Down) => scrubber.step_by(10),
+            (None, KeyCode::Char('g')) => typed = Some(String::new()),
+            (None, KeyCode::Char('q') | KeyCode::Esc) => return Ok(()),
+            (None, _) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crossterm::event::KeyEventKind;
+    use tetris::state::GameMode;
+
+    use super::*;
+    use crate::input::{Action, RepeatSettings};
+
+    // An input every 50 ticks, every third one a hard drop
+    fn replay(drops: u64) -> Replay {
+        let repeat = RepeatSettings::default();
+        let mut replay = Replay::new(3, 1, GameMode::Marathon.ruleset(), repeat);
+        let mut session = Session::new(replay.game(), repeat);
+        for drop in 0..drops {
+            let tick = 50 * (drop + 1);
+            let action = match drop % 3 {
+                0 => Action::MoveLeft,
+                1 => Action::MoveRight,
+                _ => Action::HardDrop,
+            };
+            session.advance(Duration::from_millis(tick));
+            session.key(action, KeyEventKind::Press);
+            replay.record(
+                tick,
+                action,
+                KeyEventKind::Press,
+                session.game().state_hash(),
+            );
+        }
+        replay
+    }
+
+    fn played_to(replay: &Replay, inputs: usize) -> u64 {
+        let mut session = Session::new(replay.game(), replay.repeat());
+        for input in &replay.inputs[..inputs] {
+            step(&mut session, input);
+        }
+        session.game().state_hash()
+    }
+
+    #[test]
+    fn seeking_anywhere_lands_where_playing_to_it_would() {
+        let replay = replay(250);
+        let mut scrubber = Scrubber::new(&replay);
+        assert_eq!(scrubber.checkpoints.len(), 3);
+        for target in [130, 7, 199, 200, 201, 250, 99, 0, 1000] {
+            scrubber.seek(target);
+            let at = target.min(250);
+            assert_eq!(scrubber.at(), at);
+            assert_eq!(
+                scrubber.session().game().state_hash(),
+                played_to(&replay, at)
+            );
+        }
+        scrubber.seek(5);
+        scrubber.step_by(-10);
+        assert_eq!(scrubber.at(), 0);
+    }
+
+    #[test]
+    fn the_timeline_marks_where_the_game_topped_out() {
+        // Dropped where they spawn, they stack up to the top long before 250 inputs
+        let replay = replay(250);
+        let mut scrubber = Scrubber::new(&replay);
+        let (line, cursor) = scrubber.timeline(50);
+        assert_eq!(line.chars().count(), 50);
+        assert_eq!(line.matches('X').count(), 1);
+        assert_eq!(cursor, 0);
+        scrubber.seek(250);
+        assert_eq!(scrubber.timeline(50).1, 49);
+        assert!(scrubber.session().game().is_over());
+    }
+
+    #[test]
+    fn seeking_back_is_quick_on_a_long_game() {
+        let replay = replay(5000);
+        let mut scrubber = Scrubber::new(&replay);
+        scrubber.seek(4990);
+        let started = Instant::now();
+        scrubber.seek(4901);
+        scrubber.seek(3);
+        assert!(started.elapsed() < Duration::from_millis(500));
+    }
+}
@@ -0,0 +1,913 @@
+// One game played out on a clock of its own. The clock only moves while the game is
+// running, one millisecond tick at a time, and every key is applied on the tick it
+// came in on. Gravity, key repeats and the lock delay all count ticks, never the wall
+// clock, so feeding the same keys on the same ticks plays the same game again.
+
+use std::{iter, time::Duration};
+
+use crossterm::event::KeyEventKind;
+
+use tetris::{
+    game::KickAttempt,
+    score::LineClear,
+    state::{Game, GameEvent, Input, Outcome},
+};
+
+use crate::{
+    crash,
+    input::{Action, AutoRepeat, RepeatSettings, SoftDropFactor},
+    pace::Splits,
+    results::{Placement, Tally},
+};
+
+pub const TICK: Duration = Duration::from_millis(1);
+
+// Cloned as it is for the checkpoints of --scrub
+#[derive(Clone)]
+pub struct Session {
+    game: Game,
+    auto_repeat: AutoRepeat,
+    soft_drop_factor: Option<SoftDropFactor>,
+    clock: Duration,
+    last_gravity: Duration,
+    spawned_at: Duration,
+    // Nothing moves after a lock until then, full rows flash in the meantime
+    locked_until: Option<Duration>,
+    last_movement: Option<Action>,
+    held_through_lock: Option<Action>,
+    // With the ruleset's clear_buffer, a rotation or hold pressed while lines clear,
+    // for the next tetromino
+    buffered: Option<Action>,
+    // Name of the last line clear worth mentioning and when it was made
+    clear_shown: Option<(String, Duration)>,
+    // With --debug-kicks, when the kicks of the last rotation were first shown
+    debug_kicks: bool,
+    kicks_shown: Option<Duration>,
+    // Shapes in the order they came out of the bag, held ones coming back aren't in it
+    dealt: Vec<char>,
+    tally: Tally,
+    // When each line went, for the pace bar of the next runs
+    splits: Vec<Duration>,
+    // The game pauses by itself once no key has come in for that long
+    idle_pause: Option<Duration>,
+    last_key: Duration,
+    // The pause it's in is one of those, not the player's
+    idle_paused: bool,
+    changed: bool,
+}
+
+impl Session {
+    const SPAWN_DEBOUNCE: Duration = Duration::from_millis(50);
+    const LOCK_PAUSE: Duration = Duration::from_millis(100);
+    const CLEAR_FLASH: Duration = Duration::from_millis(150);
+    const CLEAR_SHOWN: Duration = Duration::from_millis(1500);
+    const KICKS_SHOWN: Duration = Duration::from_millis(200);
+
+    pub fn new(game: Game, repeat: RepeatSettings) -> Self {
+        let mut session = Self {
+            game,
+            auto_repeat: AutoRepeat::new(repeat),
+            soft_drop_factor: repeat.soft_drop_factor,
+            clock: Duration::ZERO,
+            last_gravity: Duration::ZERO,
+            spawned_at: Duration::ZERO,
+            locked_until: None,
+            last_movement: None,
+            held_through_lock: None,
+            buffered: None,
+            clear_shown: None,
+            debug_kicks: false,
+            kicks_shown: None,
+            dealt: Vec::new(),
+            tally: Tally::default(),
+            splits: Vec::new(),
+            idle_pause: None,
+            last_key: Duration::ZERO,
+            idle_paused: false,
+            changed: true,
+        };
+        session.game.set_soft_drop_lock(repeat.soft_drop_lock);
+        session.follow_events();
+        session.pace_soft_drop();
+        session
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    pub fn clock(&self) -> Duration {
+        self.clock
+    }
+
+    // Whole ticks since the game started, as kept in replays
+    pub fn tick_count(&self) -> u64 {
+        self.clock.as_millis() as u64
+    }
+
+    pub fn dealt(&self) -> &[char] {
+        &self.dealt
+    }
+
+    // Placements and clears so far, for the results
+    pub fn tally(&self) -> &Tally {
+        &self.tally
+    }
+
+    pub fn splits(&self) -> Splits {
+        Splits::new(self.splits.clone())
+    }
+
+    pub fn clear_shown(&self) -> Option<&str> {
+        self.clear_shown.as_ref().map(|(name, _)| name.as_str())
+    }
+
+    // Not for replays, nobody is there to come back to them
+    pub fn set_idle_pause(&mut self, after: Option<Duration>) {
+        self.idle_pause = after;
+    }
+
+    pub fn is_idle_paused(&self) -> bool {
+        self.idle_paused && self.game.is_paused()
+    }
+
+    pub fn set_debug_kicks(&mut self, enabled: bool) {
+        self.debug_kicks = enabled;
+    }
+
+    // The kicks tried by a rotation made less than KICKS_SHOWN ago
+    pub fn kicks_shown(&self) -> Option<&[KickAttempt]> {
+        self.kicks_shown.map(|_| self.game.last_rotation())
+    }
+
+    // Whether anything changed since the last call, the caller redraws when it did
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+
+    // Runs every tick up to `to`. The clock stands still while the game is paused.
+    pub fn advance(&mut self, to: Duration) {
+        while self.clock + TICK <= to && !self.game.is_over() && !self.game.is_paused() {
+            self.clock += TICK;
+            self.tick();
+            self.pause_if_idle();
+        }
+    }
+
+    // Gravity and the lock delay go on without the player, only keys keep the game
+    // going. The time it waited for one isn't play, the tally keeps it apart.
+    fn pause_if_idle(&mut self) {
+        let Some(after) = self.idle_pause else {
+            return;
+        };
+        if self.game.is_over() || self.clock - self.last_key < after {
+            return;
+        }
+        crash::record(String::from("idle"));
+        self.game.toggle_pause();
+        self.idle_paused = true;
+        self.tally.idle += after;
+        self.changed = true;
+    }
+
+    // The next time something happens by itself, keys aside
+    pub fn next_deadline(&self) -> Option<Duration> {
+        let label = self
+            .clear_shown
+            .as_ref()
+            .map(|(_, shown_at)| *shown_at + Self::CLEAR_SHOWN);
+        let kicks = self
+            .kicks_shown
+            .map(|shown_at| shown_at + Self::KICKS_SHOWN);
+        let time_up = self.game.time_left().map(|left| self.clock + left);
+        let idle = self.idle_pause.map(|after| self.last_key + after);
+        if self.locked_until.is_some() {
+            return [self.locked_until, label, kicks, time_up, idle]
+                .into_iter()
+                .flatten()
+                .min();
+        }
+        let gravity = self.last_gravity + self.game.score().gravity_interval();
+        let lock = self.game.lock_delay_left().map(|left| self.clock + left);
+        [
+            Some(gravity),
+            lock,
+            self.auto_repeat.next_deadline(),
+            label,
+            kicks,
+            time_up,
+            idle,
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+    }
+
+    // Applies a key on the current tick. In the pause after a lock only releases go
+    // through, to end key repeats, unless the ruleset has a use for keys while lines
+    // clear.
+    pub fn key(&mut self, action: Action, kind: KeyEventKind) {
+        if self.game.is_over() {
+            return;
+        }
+        self.last_key = self.clock;
+        if self.locked_until.is_some() && kind != KeyEventKind::Release {
+            if self.game.is_clearing() {
+                self.key_while_clearing(action, kind);
+            }
+            return;
+        }
+        if action.is_movement() {
+            if !self.auto_repeat.key_event(kind, action, self.clock) {
+                return;
+            }
+            // Terminal auto-repeat of a key held through the lock would otherwise
+            // shove the new tetromino straight into the wall
+            if Some(action) == self.held_through_lock
+                && self.clock - self.spawned_at < Self::SPAWN_DEBOUNCE
+            {
+                return;
+            }
+            self.last_movement = Some(action);
+        } else if kind != KeyEventKind::Press {
+            // Nothing but movement repeats, and releases only end the repeating
+            return;
+        }
+        self.held_through_lock = None;
+        crash::record(format!("{action:?}"));
+        if action == Action::Pause {
+            self.game.toggle_pause();
+            self.idle_paused = false;
+            self.changed = true;
+            return;
+        }
+        if let Some(input) = self.input(action, false) {
+            self.apply(input);
+        }
+    }
+
+    fn key_while_clearing(&mut self, action: Action, kind: KeyEventKind) {
+        let rules = self.game.rules();
+        let (zangi, buffer) = (rules.zangi, rules.clear_buffer);
+        let sideways = matches!(action, Action::MoveLeft | Action::MoveRight);
+        if zangi && sideways {
+            if kind == KeyEventKind::Press {
+                crash::record(format!("{action:?}"));
+                self.apply(action.input().unwrap());
+            }
+        } else if buffer && action.is_movement() {
+            // Pressed now it starts repeating as soon as the next tetromino is out,
+            // and it's no key held through the lock
+            self.auto_repeat.key_event(kind, action, self.clock);
+            self.held_through_lock = None;
+        } else if buffer
+            && kind == KeyEventKind::Press
+            && matches!(action, Action::RotateCw | Action::RotateCcw | Action::Hold)
+        {
+            crash::record(format!("{action:?} buffered"));
+            self.buffered = Some(action);
+        }
+    }
+
+    // With an infinite soft drop factor the soft drop goes all the way down at once.
+    // Repeats of a held soft drop are told apart from presses, see SoftDropLock.
+    fn input(&self, action: Action, repeated: bool) -> Option<Input> {
+        let infinite = self.soft_drop_factor == Some(SoftDropFactor::Infinite);
+        match action {
+            Action::SoftDrop => Some(match (infinite, repeated) {
+                (false, false) => Input::SoftDrop,
+                (false, true) => Input::SoftDropRepeat,
+                (true, false) => Input::SoftDropToStack,
+                (true, true) => Input::SoftDropToStackRepeat,
+            }),
+            _ => action.input(),
+        }
+    }
+
+    // A soft drop factor makes the soft drop a multiple of the gravity of the level.
+    // Held down with an infinite one, the key keeps every new tetromino on the stack.
+    fn pace_soft_drop(&mut self) {
+        let interval = match self.soft_drop_factor {
+            None => return,
+            Some(SoftDropFactor::Times(factor)) => {
+                self.game.score().gravity_interval() / factor.max(1)
+            }
+            Some(SoftDropFactor::Infinite) => TICK,
+        };
+        self.auto_repeat.set_soft_drop_interval(interval.max(TICK));
+    }
+
+    // Input always goes first, then key repeats, gravity and the lock delay. The game
+    // time runs on through the pause after a lock.
+    fn tick(&mut self) {
+        if let Some((_, shown_at)) = &self.clear_shown {
+            if self.clock - *shown_at >= Self::CLEAR_SHOWN {
+                self.clear_shown = None;
+                self.changed = true;
+            }
+        }
+        if self
+            .kicks_shown
+            .is_some_and(|shown_at| self.clock - shown_at >= Self::KICKS_SHOWN)
+        {
+            self.kicks_shown = None;
+            self.changed = true;
+        }
+        if self
+            .locked_until
+            .is_some_and(|locked_until| self.clock >= locked_until)
+        {
+            self.locked_until = None;
+            self.changed = true;
+            if self.game.is_clearing() {
+                let outcome = self.game.finish_clear();
+                self.follow_events();
+                if outcome == Outcome::GameOver {
+                    return;
+                }
+            }
+            self.last_gravity = self.clock;
+            self.spawned_at = self.clock;
+            // The initial rotation or hold, before the tetromino has even moved
+            if let Some(action) = self.buffered.take() {
+                self.apply(action.input().unwrap());
+            }
+        }
+        if self.locked_until.is_none() {
+            self.fall();
+        }
+        let outcome = self.game.elapse(TICK);
+        self.outcome(outcome);
+    }
+
+    // Key repeats, then gravity, until the tetromino locks
+    fn fall(&mut self) {
+        while let Some(action) = self.auto_repeat.poll(self.clock) {
+            if Some(action) == self.held_through_lock
+                && self.clock - self.spawned_at < Self::SPAWN_DEBOUNCE
+            {
+                continue;
+            }
+            if self.apply(self.input(action, true).unwrap()) {
+                return;
+            }
+        }
+        if self.clock - self.last_gravity >= self.game.score().gravity_interval() {
+            self.last_gravity = self.clock;
+            self.apply(Input::Tick);
+        }
+    }
+
+    // Returns whether the tetromino is done with
+    fn apply(&mut self, input: Input) -> bool {
+        let outcome = self.game.apply(input);
+        // Shown even when no kick fit, that's when it's most interesting
+        if self.debug_kicks && matches!(input, Input::RotateCw | Input::RotateCcw) {
+            self.kicks_shown = Some(self.clock);
+            self.changed = true;
+        }
+        if outcome == Outcome::Moved
+            && matches!(
+                input,
+                Input::SoftDrop
+                    | Input::SoftDropToStack
+                    | Input::SoftDropRepeat
+                    | Input::SoftDropToStackRepeat
+                    | Input::Hold
+            )
+        {
+            self.last_gravity = self.clock;
+        }
+        self.outcome(outcome)
+    }
+
+    fn outcome(&mut self, outcome: Outcome) -> bool {
+        self.follow_events();
+        match outcome {
+            Outcome::Blocked => false,
+            Outcome::Moved => {
+                self.changed = true;
+                false
+            }
+            Outcome::Locked { lines } => {
+                self.changed = true;
+                // They were tried around the tetromino that just locked
+                self.kicks_shown = None;
+                self.held_through_lock = self.last_movement.take();
+                // Lines stay on the board while they flash, finish_clear() comes after
+                let pause = if lines > 0 {
+                    Self::CLEAR_FLASH
+                } else {
+                    Self::LOCK_PAUSE
+                };
+                self.locked_until = Some(self.clock + pause);
+                true
+            }
+            Outcome::GameOver => {
+                self.changed = true;
+                true
+            }
+        }
+    }
+
+    // What the game says happened goes to the crash log and the tally, and line
+    // clears to the label
+    fn follow_events(&mut self) {
+        for event in self.game.take_events() {
+            match event {
+                GameEvent::PieceSpawned { shape, from_hold } => {
+                    crash::record(format!("spawned {shape}"));
+                    if !from_hold {
+                        self.dealt.push(shape);
+                    }
+                }
+                GameEvent::PieceLocked { shape, cells } => {
+                    crash::record(format!("locked {shape}"));
+                    crash::set_board(self.game.board().to_text());
+                    self.tally.placements.push(Placement {
+                        ms: self.tick_count(),
+                        shape,
+                        cells,
+                    });
+                }
+                GameEvent::PieceSlid { cells } => {
+                    crash::set_board(self.game.board().to_text());
+                    if let Some(placement) = self.tally.placements.last_mut() {
+                        placement.cells = cells;
+                    }
+                }
+                GameEvent::LinesCleared { clear, .. } => {
+                    self.splits.extend(iter::repeat_n(self.clock, clear.lines));
+                    self.show_clear(clear);
+                }
+                // A T-spin is worth mentioning without lines too
+                GameEvent::SpinWithoutLines { clear } => self.show_clear(clear),
+                GameEvent::LevelUp { level } => {
+                    crash::record(format!("level {level}"));
+                    self.pace_soft_drop();
+                }
+                GameEvent::GameOver { completed } => crash::record(String::from(if completed {
+                    "completed"
+                } else {
+                    "topped out"
+                })),
+            }
+        }
+    }
+
+    // Counted, and shown when it has a name
+    fn show_clear(&mut self, clear: LineClear) {
+        self.tally.count(clear);
+        if let Some(name) = clear.name() {
+            self.clear_shown = Some((name, self.clock));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tetris::{
+        game::TetrominosBag,
+        state::{GameMode, Ruleset, SoftDropLock},
+    };
+
+    fn session() -> Session {
+        Session::new(
+            Game::with_bag(10, 20, TetrominosBag::new()),
+            RepeatSettings::default(),
+        )
+    }
+
+    fn advance_by(session: &mut Session, millis: u64) {
+        session.advance(session.clock() + Duration::from_millis(millis));
+    }
+
+    fn advance_to(session: &mut Session, millis: u64) {
+        session.advance(Duration::from_millis(millis));
+    }
+
+    fn lowest_row(session: &Session) -> usize {
+        let body = session.game().tetromino().body();
+        body.iter().map(|(_, y)| *y).max().unwrap()
+    }
+
+    fn advance_to_the_next_spawn(session: &mut Session) {
+        let spawned_at = session.spawned_at;
+        while session.spawned_at == spawned_at {
+            assert!(session.clock() < Duration::from_secs(10));
+            advance_by(session, 1);
+        }
+    }
+
+    #[test]
+    fn the_key_held_through_a_lock_waits_before_moving_the_next_tetromino() {
+        let mut session = session();
+        // Held with releases reported, or it would stop repeating during the lock
+        session.key(Action::MoveRight, KeyEventKind::Press);
+        session.key(Action::MoveRight, KeyEventKind::Repeat);
+        advance_by(&mut session, 300);
+        session.key(Action::HardDrop, KeyEventKind::Press);
+        advance_to_the_next_spawn(&mut session);
+        let body = session.game().tetromino().body();
+        advance_by(&mut session, 45);
+        assert_eq!(session.game().tetromino().body(), body);
+        advance_by(&mut session, 100);
+        assert_ne!(session.game().tetromino().body(), body);
+    }
+
+    #[test]
+    fn other_held_keys_go_on_repeating_right_after_a_spawn() {
+        let mut session = session();
+        session.key(Action::MoveLeft, KeyEventKind::Press);
+        session.key(Action::MoveLeft, KeyEventKind::Release);
+        session.key(Action::SoftDrop, KeyEventKind::Press);
+        // Tapped while the soft drop is held, so it is the last movement at the lock
+        session.key(Action::MoveLeft, KeyEventKind::Press);
+        session.key(Action::MoveLeft, KeyEventKind::Release);
+        advance_to_the_next_spawn(&mut session);
+        let body = session.game().tetromino().body();
+        advance_by(&mut session, 40);
+        assert_ne!(session.game().tetromino().body(), body);
+    }
+
+    // The first O in an unshuffled bag clears the two rows, the I is next
+    fn clearing_session(rules: Ruleset) -> Session {
+        let mut game = Game::with_rules(10, 20, TetrominosBag::new(), rules);
+        game.load_stack("GGGG..GGGG\nGGGG..GGGG").unwrap();
+        let mut session = Session::new(game, RepeatSettings::default());
+        session.key(Action::HardDrop, KeyEventKind::Press);
+        assert!(session.game().is_clearing());
+        session
+    }
+
+    fn buffering() -> Ruleset {
+        Ruleset {
+            clear_buffer: true,
+            ..GameMode::Marathon.ruleset()
+        }
+    }
+
+    #[test]
+    fn a_rotation_pressed_while_lines_clear_turns_the_next_tetromino_as_it_spawns() {
+        let mut session = clearing_session(buffering());
+        advance_by(&mut session, 50);
+        session.key(Action::RotateCw, KeyEventKind::Press);
+        advance_to_the_next_spawn(&mut session);
+        let body = session.game().tetromino().body();
+        assert_eq!(session.game().tetromino().shape(), 'I');
+        assert!(body.iter().all(|&(x, _)| x == body[0].0));
+
+        let mut session = clearing_session(GameMode::Marathon.ruleset());
+        session.key(Action::RotateCw, KeyEventKind::Press);
+        advance_to_the_next_spawn(&mut session);
+        let body = session.game().tetromino().body();
+        assert!(body.iter().all(|&(_, y)| y == body[0].1));
+    }
+
+    #[test]
+    fn a_hold_pressed_while_lines_clear_holds_the_next_tetromino() {
+        let mut session = clearing_session(buffering());
+        session.key(Action::Hold, KeyEventKind::Press);
+        advance_to_the_next_spawn(&mut session);
+        assert_eq!(session.game().held().unwrap().shape(), 'I');
+        assert_eq!(session.game().tetromino().shape(), 'J');
+    }
+
+    #[test]
+    fn a_movement_key_pressed_while_lines_clear_is_charged_by_the_spawn() {
+        let moved_right_after_the_spawn = |rules| {
+            let mut session = clearing_session(rules);
+            advance_by(&mut session, 10);
+            session.key(Action::MoveRight, KeyEventKind::Press);
+            session.key(Action::MoveRight, KeyEventKind::Repeat);
+            advance_to_the_next_spawn(&mut session);
+            let body = session.game().tetromino().body();
+            // Pressed 140ms before the spawn, the 170ms repeat delay is 30ms away
+            advance_by(&mut session, 35);
+            session.game().tetromino().body() != body
+        };
+        assert!(moved_right_after_the_spawn(buffering()));
+        assert!(!moved_right_after_the_spawn(GameMode::Marathon.ruleset()));
+    }
+
+    #[test]
+    fn with_zangi_the_clearing_tetromino_slides_and_only_its_lines_count() {
+        let rules = Ruleset {
+            zangi: true,
+            ..GameMode::Marathon.ruleset()
+        };
+        let mut session = clearing_session(rules);
+        advance_by(&mut session, 50);
+        session.key(Action::MoveRight, KeyEventKind::Press);
+        assert!(session.game().clearing_rows().is_empty());
+        advance_to_the_next_spawn(&mut session);
+        assert_eq!(session.game().score().lines, 0);
+        assert_eq!(
+            session.tally().placements[0].cells,
+            [(5, 16), (5, 17), (6, 16), (6, 17)]
+        );
+    }
+
+    #[test]
+    fn every_line_cleared_is_a_split() {
+        let mut session = clearing_session(GameMode::Marathon.ruleset());
+        assert_eq!(session.splits(), Splits::default());
+        advance_to_the_next_spawn(&mut session);
+        // They go once the clear flash is over
+        let at = Duration::from_millis(150);
+        assert_eq!(session.splits(), Splits::new(vec![at, at]));
+    }
+
+    #[test]
+    fn gravity_moves_the_tetromino_once_every_interval() {
+        let mut session = session();
+        let start = lowest_row(&session);
+        advance_to(&mut session, 999);
+        assert_eq!(lowest_row(&session), start);
+        advance_to(&mut session, 1000);
+        assert_eq!(lowest_row(&session), start + 1);
+        advance_to(&mut session, 1999);
+        assert_eq!(lowest_row(&session), start + 1);
+        advance_to(&mut session, 2000);
+        assert_eq!(lowest_row(&session), start + 2);
+    }
+
+    #[test]
+    fn a_game_left_alone_pauses_itself_until_the_pause_key() {
+        let mut session = session();
+        session.set_idle_pause(Some(Duration::from_secs(5)));
+        advance_to(&mut session, 3000);
+        session.key(Action::MoveLeft, KeyEventKind::Press);
+        session.key(Action::MoveLeft, KeyEventKind::Release);
+        // Gravity moving the tetromino isn't the player
+        advance_to(&mut session, 7999);
+        assert!(!session.game().is_paused());
+        assert_eq!(session.next_deadline(), Some(Duration::from_millis(8000)));
+        advance_to(&mut session, 20_000);
+        assert_eq!(session.clock(), Duration::from_millis(8000));
+        assert!(session.is_idle_paused());
+        assert_eq!(session.tally().idle, Duration::from_secs(5));
+        // Other keys are blocked like in any pause, the pause key resumes the game
+        let before = session.game().state_hash();
+        session.key(Action::MoveRight, KeyEventKind::Press);
+        assert_eq!(session.game().state_hash(), before);
+        session.key(Action::Pause, KeyEventKind::Press);
+        assert!(!session.game().is_paused() && !session.is_idle_paused());
+        advance_to(&mut session, 12_999);
+        assert!(!session.game().is_paused());
+        advance_to(&mut session, 20_000);
+        assert!(session.is_idle_paused());
+        assert_eq!(session.tally().idle, Duration::from_secs(10));
+        // Without it a game waits for the player as long as it takes
+        let mut session = self::session();
+        advance_to(&mut session, 60_000);
+        assert!(!session.game().is_paused());
+    }
+
+    #[test]
+    fn a_soft_drop_right_before_gravity_is_not_a_double_step() {
+        let mut session = session();
+        let start = lowest_row(&session);
+        advance_to(&mut session, 999);
+        session.key(Action::SoftDrop, KeyEventKind::Press);
+        session.key(Action::SoftDrop, KeyEventKind::Release);
+        assert_eq!(lowest_row(&session), start + 1);
+        // Gravity starts over from the soft drop
+        advance_to(&mut session, 1998);
+        assert_eq!(lowest_row(&session), start + 1);
+        advance_to(&mut session, 1999);
+        assert_eq!(lowest_row(&session), start + 2);
+    }
+
+    #[test]
+    fn a_key_on_the_gravity_tick_goes_before_gravity() {
+        let mut session = session();
+        let start = lowest_row(&session);
+        let left = session.game().tetromino().body()[0].0;
+        // Applied on tick 999 while gravity is due on the next one
+        advance_to(&mut session, 999);
+        session.key(Action::MoveLeft, KeyEventKind::Press);
+        assert_eq!(session.game().tetromino().body()[0], (left - 1, start - 1));
+        advance_to(&mut session, 1000);
+        assert_eq!(lowest_row(&session), start + 1);
+    }
+
+    // The O lands on the floor with 18 soft drops on tick 0
+    fn grounded_session() -> Session {
+        let mut session = session();
+        for _ in 0..18 {
+            session.key(Action::SoftDrop, KeyEventKind::Press);
+            session.key(Action::SoftDrop, KeyEventKind::Release);
+        }
+        assert_eq!(lowest_row(&session), 19);
+        session
+    }
+
+    #[test]
+    fn a_grounded_tetromino_locks_once_the_lock_delay_is_over() {
+        let mut session = grounded_session();
+        advance_to(&mut session, 499);
+        assert_eq!(session.game().tetromino().shape(), 'O');
+        advance_to(&mut session, 500);
+        assert_eq!(session.game().tetromino().shape(), 'I');
+        // Gravity ignores the grounded O, and waits out the pause after the lock
+        let spawned = lowest_row(&session);
+        advance_to(&mut session, 1599);
+        assert_eq!(lowest_row(&session), spawned);
+        advance_to(&mut session, 1600);
+        assert_eq!(lowest_row(&session), spawned + 1);
+    }
+
+    #[test]
+    fn rotating_near_the_ground_restarts_the_lock_delay() {
+        let mut session = grounded_session();
+        advance_to(&mut session, 400);
+        session.key(Action::RotateCw, KeyEventKind::Press);
+        advance_to(&mut session, 899);
+        assert_eq!(session.game().tetromino().shape(), 'O');
+        advance_to(&mut session, 900);
+        assert_eq!(session.game().tetromino().shape(), 'I');
+    }
+
+    fn tap(session: &mut Session, action: Action) {
+        session.key(action, KeyEventKind::Press);
+        session.key(action, KeyEventKind::Release);
+    }
+
+    // The I out of hold, a few rows down and `lefts` columns to the left, then the
+    // keys on their ticks
+    fn two_keys(lefts: usize, keys: [(u64, Action); 2]) -> [(usize, usize); 4] {
+        let mut session = session();
+        tap(&mut session, Action::Hold);
+        for action in [Action::SoftDrop; 5]
+            .into_iter()
+            .chain([Action::MoveLeft; 3].into_iter().take(lefts))
+        {
+            tap(&mut session, action);
+        }
+        for (tick, action) in keys {
+            advance_to(&mut session, tick);
+            tap(&mut session, action);
+        }
+        advance_to(&mut session, 400);
+        session.game().tetromino().body()
+    }
+
+    // The same inputs straight into the game, in the given order
+    fn in_order(lefts: usize, inputs: [Input; 2]) -> [(usize, usize); 4] {
+        let mut game = Game::with_bag(10, 20, TetrominosBag::new());
+        game.apply(Input::Hold);
+        for input in [Input::SoftDrop; 5]
+            .into_iter()
+            .chain([Input::MoveLeft; 3].into_iter().take(lefts))
+        {
+            game.apply(input);
+        }
+        for input in inputs {
+            game.apply(input);
+        }
+        game.tetromino().body()
+    }
+
+    #[test]
+    fn a_move_and_a_rotation_in_the_open_land_the_same_either_way() {
+        let body = in_order(0, [Input::MoveLeft, Input::RotateCw]);
+        assert_eq!(in_order(0, [Input::RotateCw, Input::MoveLeft]), body);
+        for keys in [
+            [(300, Action::MoveLeft), (302, Action::RotateCw)],
+            [(300, Action::RotateCw), (302, Action::MoveLeft)],
+            [(300, Action::MoveLeft), (300, Action::RotateCw)],
+            [(300, Action::RotateCw), (300, Action::MoveLeft)],
+        ] {
+            assert_eq!(two_keys(0, keys), body, "{keys:?}");
+        }
+    }
+
+    #[test]
+    fn a_move_and_a_rotation_against_the_wall_go_in_the_order_they_came_in() {
+        let move_first = in_order(3, [Input::MoveLeft, Input::RotateCw]);
+        let rotate_first = in_order(3, [Input::RotateCw, Input::MoveLeft]);
+        assert_ne!(move_first, rotate_first);
+        for tick in [300, 302] {
+            assert_eq!(
+                two_keys(3, [(300, Action::MoveLeft), (tick, Action::RotateCw)]),
+                move_first
+            );
+            assert_eq!(
+                two_keys(3, [(300, Action::RotateCw), (tick, Action::MoveLeft)]),
+                rotate_first
+            );
+        }
+    }
+
+    fn with_soft_drop_factor(factor: SoftDropFactor) -> Session {
+        Session::new(
+            Game::with_bag(10, 20, TetrominosBag::new()),
+            RepeatSettings {
+                soft_drop_factor: Some(factor),
+                ..RepeatSettings::default()
+            },
+        )
+    }
+
+    #[test]
+    fn a_soft_drop_factor_is_a_multiple_of_gravity() {
+        let mut session = with_soft_drop_factor(SoftDropFactor::Times(4));
+        let start = lowest_row(&session);
+        // Held with releases reported, or it would only repeat with the terminal
+        session.key(Action::SoftDrop, KeyEventKind::Press);
+        session.key(Action::SoftDrop, KeyEventKind::Repeat);
+        assert_eq!(lowest_row(&session), start + 1);
+        // A second at level 1, so a row every 250ms while held
+        advance_to(&mut session, 249);
+        assert_eq!(lowest_row(&session), start + 1);
+        advance_to(&mut session, 250);
+        assert_eq!(lowest_row(&session), start + 2);
+        session.key(Action::SoftDrop, KeyEventKind::Release);
+        advance_to(&mut session, 999);
+        assert_eq!(lowest_row(&session), start + 2);
+    }
+
+    #[test]
+    fn an_infinite_soft_drop_goes_to_the_stack_without_locking() {
+        let mut session = with_soft_drop_factor(SoftDropFactor::Infinite);
+        session.key(Action::SoftDrop, KeyEventKind::Press);
+        session.key(Action::SoftDrop, KeyEventKind::Repeat);
+        assert_eq!(lowest_row(&session), 19);
+        // A point for each of the 18 rows it went down
+        assert_eq!(session.game().score().points, 18);
+        advance_to(&mut session, 100);
+        assert_eq!(session.game().tetromino().shape(), 'O');
+        assert_eq!(session.game().score().points, 18);
+        // Still held when the lock delay runs out, the next one goes down too once the
+        // spawn debounce is over
+        advance_to(&mut session, 500);
+        advance_to_the_next_spawn(&mut session);
+        assert_eq!(lowest_row(&session), 0);
+        advance_by(&mut session, 50);
+        assert_eq!(session.game().tetromino().shape(), 'I');
+        // Flat on top of the O
+        assert_eq!(lowest_row(&session), 17);
+        // Let go, the one after that stays up top
+        session.key(Action::SoftDrop, KeyEventKind::Release);
+        tap(&mut session, Action::HardDrop);
+        advance_to_the_next_spawn(&mut session);
+        advance_by(&mut session, 50);
+        assert_eq!(lowest_row(&session), 1);
+    }
+
+    #[test]
+    fn a_held_soft_drop_lands_without_locking_but_a_fresh_press_locks() {
+        let mut session = with_soft_drop_factor(SoftDropFactor::Times(20));
+        session.key(Action::SoftDrop, KeyEventKind::Press);
+        session.key(Action::SoftDrop, KeyEventKind::Repeat);
+        // 50ms a row, on the floor after 18 of them and still held there
+        advance_to(&mut session, 1300);
+        assert_eq!(lowest_row(&session), 19);
+        assert_eq!(session.game().tetromino().shape(), 'O');
+        session.key(Action::SoftDrop, KeyEventKind::Release);
+        tap(&mut session, Action::SoftDrop);
+        assert_eq!(session.game().tetromino().shape(), 'I');
+        // With hold the held key locks it the moment it lands
+        let mut session = Session::new(
+            Game::with_bag(10, 20, TetrominosBag::new()),
+            RepeatSettings {
+                soft_drop_factor: Some(SoftDropFactor::Times(20)),
+                soft_drop_lock: SoftDropLock::Hold,
+                ..RepeatSettings::default()
+            },
+        );
+        session.key(Action::SoftDrop, KeyEventKind::Press);
+        session.key(Action::SoftDrop, KeyEventKind::Repeat);
+        advance_to(&mut session, 950);
+        assert_eq!(session.game().tetromino().shape(), 'I');
+    }
+
+    #[test]
+    fn kicks_are_shown_for_a_moment_after_a_rotation() {
+        let mut quiet = session();
+        tap(&mut quiet, Action::RotateCw);
+        assert!(quiet.kicks_shown().is_none());
+
+        let mut session = session();
+        session.set_debug_kicks(true);
+        advance_to(&mut session, 20);
+        // The O turns in place, its one kick always fits
+        tap(&mut session, Action::RotateCw);
+        let kicks = session.kicks_shown().unwrap();
+        assert_eq!((kicks.len(), kicks[0].failure), (1, None));
+        assert_eq!(session.next_deadline(), Some(Duration::from_millis(220)));
+        advance_to(&mut session, 219);
+        assert!(session.kicks_shown().is_some());
+        session.take_changed();
+        advance_to(&mut session, 220);
+        assert!(session.kicks_shown().is_none());
+        assert!(session.take_changed());
+        // Locking takes them away with the tetromino they were tried around
+        tap(&mut session, Action::RotateCw);
+        tap(&mut session, Action::HardDrop);
+        assert!(session.kicks_shown().is_none());
+    }
+}
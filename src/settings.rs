@@ -0,0 +1,525 @@
+// The Settings entry of the menu. Whatever is changed goes to a draft of the config,
+// the game only sees it once it's applied:
+//
+//     up, down       picks a setting
+//     left, right    changes it, the handling by 10 ms and the idle pause by 10 s
+//     enter          types a number in, or binds the next key pressed to the action
+//                    on top of its keys, or applies or cancels on those two
+//     backspace      takes every key off the action
+//     a              applies
+//     esc, q         cancels
+//
+// Applying validates the whole draft first, see Config::validate(). The first setting
+// that doesn't pass is marked and its error shown, and nothing is applied until it's
+// fixed. Once it passes the draft takes the place of the live config, and main
+// configures the game from it and writes it to settings.toml.
+
+use std::{io, sync::mpsc, time::Duration, time::Instant};
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind};
+use tui::{backend::Backend, widgets::BorderType, widgets::ListState, Terminal};
+
+use crate::{
+    config::{self, Config},
+    input::Action,
+    ui::{self, Playfield},
+};
+
+const FRAMES: [BorderType; 4] = [
+    BorderType::Plain,
+    BorderType::Rounded,
+    BorderType::Double,
+    BorderType::Thick,
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Setting {
+    Das,
+    Arr,
+    SoftDrop,
+    Ghost,
+    Hold,
+    IdlePause,
+    Frame,
+    Title,
+    HoldHint,
+    Skin,
+    Key(Action),
+    Apply,
+    Cancel,
+}
+
+impl Setting {
+    fn all() -> Vec<Setting> {
+        let mut all = vec![
+            Setting::Das,
+            Setting::Arr,
+            Setting::SoftDrop,
+            Setting::Ghost,
+            Setting::Hold,
+            Setting::IdlePause,
+            Setting::Frame,
+            Setting::Title,
+            Setting::HoldHint,
+            Setting::Skin,
+        ];
+        all.extend(Action::ALL.map(Setting::Key));
+        all.extend([Setting::Apply, Setting::Cancel]);
+        all
+    }
+
+    // As the config file has it, and so the errors of Config::validate()
+    fn name(self) -> String {
+        match self {
+            Setting::Das => "handling.das".to_string(),
+            Setting::Arr => "handling.arr".to_string(),
+            Setting::SoftDrop => "handling.soft_drop".to_string(),
+            Setting::Ghost => "game.ghost".to_string(),
+            Setting::Hold => "game.hold".to_string(),
+            Setting::IdlePause => "game.idle_pause".to_string(),
+            Setting::Frame => "display.frame".to_string(),
+            Setting::Title => "display.title".to_string(),
+            Setting::HoldHint => "display.hold_hint".to_string(),
+            Setting::Skin => "display.skin".to_string(),
+            Setting::Key(action) => format!("keys.{}", action.name()),
+            Setting::Apply => "apply".to_string(),
+            Setting::Cancel => "cancel".to_string(),
+        }
+    }
+
+    fn label(self) -> String {
+        match self {
+            Setting::Das => "DAS".to_string(),
+            Setting::Arr => "ARR".to_string(),
+            Setting::SoftDrop => "soft drop".to_string(),
+            Setting::Ghost => "ghost".to_string(),
+            Setting::Hold => "hold".to_string(),
+            Setting::IdlePause => "idle pause".to_string(),
+            Setting::Frame => "frame".to_string(),
+            Setting::Title => "window title".to_string(),
+            Setting::HoldHint => "hold hint".to_string(),
+            Setting::Skin => "skin".to_string(),
+            Setting::Key(action) => format!("{} key", action.name().replace('_', " ")),
+            Setting::Apply => "Apply".to_string(),
+            Setting::Cancel => "Cancel".to_string(),
+        }
+    }
+
+    fn value(self, config: &Config) -> String {
+        let millis = |duration: Duration| format!("{} ms", duration.as_millis());
+        let on = |enabled: bool| String::from(if enabled { "on" } else { "off" });
+        match self {
+            Setting::Das => millis(config.repeat.delay),
+            Setting::Arr => millis(config.repeat.interval),
+            Setting::SoftDrop => millis(config.repeat.soft_drop_interval),
+            Setting::Ghost => on(config.ghost),
+            Setting::Hold => on(config.hold),
+            Setting::IdlePause => match config.idle_pause {
+                Some(after) => format!("{} s", after.as_secs()),
+                None => "off".to_string(),
+            },
+            Setting::Frame => ui::border_type_name(config.border_type).to_string(),
+            Setting::Title => on(config.title),
+            Setting::HoldHint => on(config.hold_hint),
+            Setting::Skin => config.skin_dir.clone().unwrap_or("none".to_string()),
+            Setting::Key(action) => {
+                let keys = config.keymap.keys(action);
+                if keys.is_empty() {
+                    return "none".to_string();
+                }
+                let names: Vec<String> = keys.into_iter().map(config::key_name).collect();
+                names.join(", ")
+            }
+            Setting::Apply | Setting::Cancel => String::new(),
+        }
+    }
+}
+
+// What came of the screen
+#[derive(Debug)]
+pub enum Done {
+    Applied(Box<Config>),
+    Cancelled,
+}
+
+pub struct Settings {
+    draft: Config,
+    settings: Vec<Setting>,
+    selected: usize,
+    // The digits typed so far for a number
+    typed: Option<String>,
+    // Waiting for the key to bind
+    binding: bool,
+    // The setting that didn't pass the last apply, and why
+    invalid: Option<(usize, String)>,
+    // The directories a skin can be picked from
+    skins: Vec<String>,
+}
+
+impl Settings {
+    pub fn new(config: &Config, skins: Vec<String>) -> Self {
+        Self {
+            draft: config.clone(),
+            settings: Setting::all(),
+            selected: 0,
+            typed: None,
+            binding: false,
+            invalid: None,
+            skins,
+        }
+    }
+
+    fn setting(&self) -> Setting {
+        self.settings[self.selected]
+    }
+
+    // One line of the list for each setting
+    pub fn lines(&self) -> Vec<String> {
+        self.settings
+            .iter()
+            .enumerate()
+            .map(|(index, setting)| {
+                let value = match (&self.typed, self.binding) {
+                    (Some(typed), _) if index == self.selected => format!("{typed}_"),
+                    (_, true) if index == self.selected => "press a key".to_string(),
+                    _ => setting.value(&self.draft),
+                };
+                let mark = if self.invalid_index() == Some(index) {
+                    '!'
+                } else {
+                    ' '
+                };
+                format!("{mark}{:<16}{value:>14}", setting.label())
+            })
+            .collect()
+    }
+
+    pub fn invalid_index(&self) -> Option<usize> {
+        self.invalid.as_ref().map(|(index, _)| *index)
+    }
+
+    pub fn status(&self) -> String {
+        if let Some((_, message)) = &self.invalid {
+            return message.clone();
+        }
+        match (&self.typed, self.binding) {
+            (Some(_), _) => "type a number, enter to keep it, esc to leave it".to_string(),
+            (_, true) => "press the key to add, esc to leave it".to_string(),
+            _ => "\u{2190}\u{2192} change  enter set  a apply  esc cancel".to_string(),
+        }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn key(&mut self, key: KeyEvent) -> Option<Done> {
+        if self.binding {
+            self.binding = false;
+            if key.code != KeyCode::Esc {
+                if let Setting::Key(action) = self.setting() {
+                    self.draft.keymap.add(action, key.code);
+                    self.invalid = None;
+                }
+            }
+            return None;
+        }
+        if let Some(typed) = &mut self.typed {
+            match key.code {
+                KeyCode::Char(digit @ '0'..='9') if typed.len() < 9 => typed.push(digit),
+                KeyCode::Backspace => {
+                    typed.pop();
+                }
+                KeyCode::Enter => {
+                    if let Ok(number) = typed.parse() {
+                        self.set_number(number);
+                    }
+                    self.typed = None;
+                }
+                KeyCode::Esc => self.typed = None,
+                _ => {}
+            }
+            return None;
+        }
+        let last = self.settings.len() - 1;
+        match key.code {
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(last),
+            KeyCode::Left => self.change(-1),
+            KeyCode::Right => self.change(1),
+            KeyCode::Backspace | KeyCode::Delete => {
+                if let Setting::Key(action) = self.setting() {
+                    self.draft.keymap.bind(action, Vec::new());
+                    self.invalid = None;
+                }
+            }
+            KeyCode::Enter | KeyCode::Char(' ') => match self.setting() {
+                Setting::Apply => return self.apply(),
+                Setting::Cancel => return Some(Done::Cancelled),
+                Setting::Das | Setting::Arr | Setting::SoftDrop | Setting::IdlePause => {
+                    self.typed = Some(String::new());
+                }
+                Setting::Key(_) => self.binding = true,
+                _ => self.change(1),
+            },
+            KeyCode::Char('a') => return self.apply(),
+            KeyCode::Esc | KeyCode::Char('q') => return Some(Done::Cancelled),
+            _ => {}
+        }
+        None
+    }
+
+    // Milliseconds for the handling, seconds for the idle pause
+    fn set_number(&mut self, number: u64) {
+        let setting = self.setting();
+        let repeat = &mut self.draft.repeat;
+        match setting {
+            Setting::Das => repeat.delay = Duration::from_millis(number),
+            Setting::Arr => repeat.interval = Duration::from_millis(number),
+            Setting::SoftDrop => repeat.soft_drop_interval = Duration::from_millis(number),
+            Setting::IdlePause => {
+                self.draft.idle_pause = (number > 0).then(|| Duration::from_secs(number));
+            }
+            _ => return,
+        }
+        self.invalid = None;
+    }
+
+    // A step along, one way or the other
+    fn change(&mut self, by: i64) {
+        let step = |duration: Duration, unit: u64| {
+            (duration.as_millis() as u64 / unit).saturating_add_signed(10 * by)
+        };
+        let setting = self.setting();
+        let repeat = self.draft.repeat;
+        let number = match setting {
+            Setting::Das => Some(step(repeat.delay, 1)),
+            Setting::Arr => Some(step(repeat.interval, 1)),
+            Setting::SoftDrop => Some(step(repeat.soft_drop_interval, 1)),
+            Setting::IdlePause => Some(step(self.draft.idle_pause.unwrap_or_default(), 1000)),
+            _ => None,
+        };
+        if let Some(number) = number {
+            self.set_number(number);
+            return;
+        }
+        let draft = &mut self.draft;
+        match setting {
+            Setting::Das | Setting::Arr | Setting::SoftDrop | Setting::IdlePause => return,
+            Setting::Ghost => draft.ghost = !draft.ghost,
+            Setting::Hold => draft.hold = !draft.hold,
+            Setting::Title => draft.title = !draft.title,
+            Setting::HoldHint => draft.hold_hint = !draft.hold_hint,
+            Setting::Frame => {
+                let at = FRAMES.iter().position(|frame| *frame == draft.border_type);
+                draft.border_type = FRAMES[cycle(at, FRAMES.len(), by)];
+            }
+            Setting::Skin => {
+                // None first, then the directories
+                let at = match &draft.skin_dir {
+                    Some(dir) => self
+                        .skins
+                        .iter()
+                        .position(|skin| skin == dir)
+                        .map(|at| at + 1),
+                    None => Some(0),
+                };
+                draft.skin_dir = match cycle(at, self.skins.len() + 1, by) {
+                    0 => None,
+                    at => Some(self.skins[at - 1].clone()),
+                };
+            }
+            Setting::Key(_) | Setting::Apply | Setting::Cancel => return,
+        }
+        self.invalid = None;
+    }
+
+    fn apply(&mut self) -> Option<Done> {
+        let mut draft = self.draft.clone();
+        match draft.validate() {
+            Ok(()) => Some(Done::Applied(Box::new(draft))),
+            Err(err) => {
+                let name = err.setting();
+                let index = self
+                    .settings
+                    .iter()
+                    .position(|setting| Some(setting.name()) == name)
+                    .unwrap_or(self.selected);
+                self.selected = index;
+                self.invalid = Some((index, err.to_string()));
+                None
+            }
+        }
+    }
+}
+
+// The index `by` steps on from `at`, round the end, from the start when it's unknown
+fn cycle(at: Option<usize>, len: usize, by: i64) -> usize {
+    let at = at.unwrap_or(0) as i64;
+    (at + by).rem_euclid(len as i64) as usize
+}
+
+pub fn run<B: Backend>(
+    terminal: &mut Terminal<B>,
+    playfield: &mut Playfield,
+    config: &Config,
+    rx_input: &mpsc::Receiver<(Event, Instant)>,
+) -> io::Result<Done> {
+    let mut settings = Settings::new(config, config::skin_dirs());
+    let mut state = ListState::default();
+    loop {
+        state.select(Some(settings.selected()));
+        playfield.draw_settings(
+            terminal,
+            &settings.lines(),
+            &mut state,
+            &settings.status(),
+            settings.invalid_index(),
+        );
+        let Ok((event, _)) = rx_input.recv() else {
+            return Ok(Done::Cancelled);
+        };
+        let key = match event {
+            Event::Key(key) if key.kind != KeyEventKind::Release => key,
+            Event::Resize(width, height) => {
+                crate::resize(terminal, playfield, width, height)?;
+                continue;
+            }
+            _ => continue,
+        };
+        if let Some(done) = settings.key(key) {
+            return Ok(done);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::KeyModifiers;
+
+    use super::*;
+
+    fn press(settings: &mut Settings, codes: &[KeyCode]) -> Option<Done> {
+        codes
+            .iter()
+            .map(|&code| settings.key(KeyEvent::new(code, KeyModifiers::NONE)))
+            .last()
+            .flatten()
+    }
+
+    fn settings() -> Settings {
+        Settings::new(&Config::default(), vec!["green".to_string()])
+    }
+
+    #[test]
+    fn changes_stay_in_the_draft_until_applied() {
+        let live = Config::default();
+        let mut settings = Settings::new(&live, Vec::new());
+        // DAS down twice, then typed in for ARR
+        press(&mut settings, &[KeyCode::Left, KeyCode::Left]);
+        let typed = [
+            KeyCode::Down,
+            KeyCode::Enter,
+            KeyCode::Char('2'),
+            KeyCode::Enter,
+        ];
+        press(&mut settings, &typed);
+        assert_eq!(settings.draft.repeat.delay, Duration::from_millis(150));
+        assert_eq!(settings.draft.repeat.interval, Duration::from_millis(2));
+        assert_eq!(live.repeat.delay, Duration::from_millis(170));
+        assert!(settings.lines()[0].ends_with("150 ms"));
+        let Some(Done::Applied(applied)) = press(&mut settings, &[KeyCode::Char('a')]) else {
+            panic!("the draft is valid");
+        };
+        assert_eq!(applied.repeat.interval, Duration::from_millis(2));
+        let mut settings = Settings::new(&live, Vec::new());
+        press(&mut settings, &[KeyCode::Right]);
+        assert!(matches!(
+            press(&mut settings, &[KeyCode::Esc]),
+            Some(Done::Cancelled)
+        ));
+    }
+
+    #[test]
+    fn an_invalid_draft_is_marked_and_not_applied() {
+        let mut settings = settings();
+        let typed = [
+            KeyCode::Down,
+            KeyCode::Down,
+            KeyCode::Enter,
+            KeyCode::Char('5'),
+            KeyCode::Char('0'),
+            KeyCode::Char('0'),
+            KeyCode::Char('0'),
+            KeyCode::Enter,
+            KeyCode::Up,
+            KeyCode::Up,
+        ];
+        press(&mut settings, &typed);
+        assert!(press(&mut settings, &[KeyCode::Char('a')]).is_none());
+        let soft_drop = 2;
+        assert_eq!(settings.selected(), soft_drop);
+        assert_eq!(settings.invalid_index(), Some(soft_drop));
+        assert!(settings.lines()[soft_drop].starts_with('!'));
+        assert_eq!(
+            settings.status(),
+            "handling.soft_drop must be 1000 milliseconds or less"
+        );
+        // Fixing it clears the mark
+        press(
+            &mut settings,
+            &[KeyCode::Enter, KeyCode::Char('9'), KeyCode::Enter],
+        );
+        assert_eq!(settings.invalid_index(), None);
+        assert!(press(&mut settings, &[KeyCode::Char('a')]).is_some());
+    }
+
+    #[test]
+    fn a_key_already_bound_elsewhere_is_a_conflict() {
+        let mut settings = settings();
+        let hold = Setting::all()
+            .iter()
+            .position(|setting| *setting == Setting::Key(Action::Hold))
+            .unwrap();
+        settings.selected = hold;
+        // Space is the hard drop's
+        press(&mut settings, &[KeyCode::Enter, KeyCode::Char(' ')]);
+        assert!(settings.lines()[hold].ends_with("c, Space"));
+        assert!(press(&mut settings, &[KeyCode::Char('a')]).is_none());
+        assert_eq!(
+            settings.status(),
+            "Space is bound to both hard_drop and hold"
+        );
+        press(
+            &mut settings,
+            &[KeyCode::Backspace, KeyCode::Enter, KeyCode::Char('v')],
+        );
+        assert!(settings.lines()[hold].ends_with("v"));
+        let Some(Done::Applied(applied)) = press(&mut settings, &[KeyCode::Char('a')]) else {
+            panic!("v isn't bound to anything");
+        };
+        assert_eq!(applied.keymap.keys(Action::Hold), [KeyCode::Char('v')]);
+        assert_eq!(applied.keymap.conflict(), None);
+    }
+
+    #[test]
+    fn toggles_and_choices_go_round() {
+        let mut settings = settings();
+        settings.selected = Setting::all()
+            .iter()
+            .position(|setting| *setting == Setting::Frame)
+            .unwrap();
+        press(&mut settings, &[KeyCode::Left]);
+        assert_eq!(settings.draft.border_type, BorderType::Thick);
+        press(
+            &mut settings,
+            &[KeyCode::Down, KeyCode::Enter, KeyCode::Down],
+        );
+        assert!(!settings.draft.title);
+        // Past the hold hint the skins, none and then the directory
+        press(&mut settings, &[KeyCode::Down, KeyCode::Right]);
+        assert_eq!(settings.draft.skin_dir.as_deref(), Some("green"));
+        press(&mut settings, &[KeyCode::Right]);
+        assert_eq!(settings.draft.skin_dir, None);
+    }
+}
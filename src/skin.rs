@@ -0,0 +1,268 @@
+// A skin is a directory holding a theme.toml, picked with `skin = "green"` in the
+// [display] table of the config. A relative path is taken from the config directory,
+// and skins/ in this repository has one to start from. Every key is optional, what the
+// theme leaves out is drawn the built-in way:
+//
+//     [colors]
+//     t = "magenta"
+//     l = 208
+//     i = "#00ffff"
+//     garbage = "darkgray"
+//
+//     [glyphs]
+//     block = "▓"
+//     ghost = "·"
+//     clearing = "="
+//
+//     [frame]
+//     style = "rounded"
+//
+// Colors are one of the 16 basic color names, a number out of the 256 color palette
+// or "#rrggbb", which only shows as such on true color terminals. Without a block
+// glyph cells are filled with their color, with one they're drawn with it in their
+// color. The frame style is the one of --frame, which still goes over it.
+//
+// There's no sound in the game, a skin is only what it looks like.
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use tui::{style::Color, widgets::BorderType};
+
+use crate::ui::{self, Glyphs, Palette};
+
+#[derive(Clone, Default, Debug)]
+pub struct Skin {
+    // None for every color the theme leaves to the palette, in the palette's order
+    pieces: [Option<Color>; 7],
+    garbage: Option<Color>,
+    clearing: Option<Color>,
+    floor: Option<Color>,
+    border: Option<Color>,
+    pub glyphs: Glyphs,
+    pub border_type: Option<BorderType>,
+}
+
+#[derive(Debug)]
+pub enum SkinError {
+    Read(PathBuf, io::Error),
+    Syntax(PathBuf, toml::de::Error),
+    // The path of the theme and the key in it
+    UnknownSetting(PathBuf, String),
+    UnknownColor(PathBuf, String, String),
+    NotAGlyph(PathBuf, String),
+    UnknownFrame(PathBuf, String),
+}
+
+impl fmt::Display for SkinError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SkinError::Read(path, err) => write!(f, "{}: can't be read: {err}", path.display()),
+            SkinError::Syntax(path, err) => write!(f, "{}: {err}", path.display()),
+            SkinError::UnknownSetting(path, key) => {
+                write!(f, "{}: unknown setting '{key}'", path.display())
+            }
+            SkinError::UnknownColor(path, key, color) => write!(
+                f,
+                "{}: {key}: unknown color '{color}' (expected a name like \"green\", \
+                 a number up to 255 or \"#rrggbb\")",
+                path.display()
+            ),
+            SkinError::NotAGlyph(path, key) => {
+                write!(f, "{}: {key} must be a single character", path.display())
+            }
+            SkinError::UnknownFrame(path, frame) => write!(
+                f,
+                "{}: frame.style: unknown frame '{frame}' (expected plain, rounded, double or thick)",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SkinError {}
+
+impl Skin {
+    // `dir` is the skin's directory, theme.toml in it is read right away
+    pub fn load(dir: PathBuf) -> Result<Skin, SkinError> {
+        let path = dir.join("theme.toml");
+        let text = fs::read_to_string(&path).map_err(|err| SkinError::Read(path.clone(), err))?;
+        Self::parse(&text, &path)
+    }
+
+    // `path` is only there for the errors
+    fn parse(text: &str, path: &Path) -> Result<Skin, SkinError> {
+        let at = || path.to_path_buf();
+        let table: toml::Table = text.parse().map_err(|err| SkinError::Syntax(at(), err))?;
+        let mut skin = Skin::default();
+        for (name, value) in table {
+            let toml::Value::Table(settings) = value else {
+                return Err(SkinError::UnknownSetting(at(), name));
+            };
+            for (setting, value) in settings {
+                let key = format!("{name}.{setting}");
+                match name.as_str() {
+                    "colors" => {
+                        let Some(color) = parse_color(&value) else {
+                            let color = shown(&value);
+                            return Err(SkinError::UnknownColor(at(), key, color));
+                        };
+                        let slot = match setting.as_str() {
+                            "garbage" => &mut skin.garbage,
+                            "clearing" => &mut skin.clearing,
+                            "floor" => &mut skin.floor,
+                            "border" => &mut skin.border,
+                            shape => match "oijlszt".find(shape).filter(|_| shape.len() == 1) {
+                                Some(index) => &mut skin.pieces[index],
+                                None => return Err(SkinError::UnknownSetting(at(), key)),
+                            },
+                        };
+                        *slot = Some(color);
+                    }
+                    "glyphs" => {
+                        let glyph = match value.as_str().map(|text| text.chars()) {
+                            Some(mut chars) => match (chars.next(), chars.next()) {
+                                (Some(glyph), None) => glyph,
+                                _ => return Err(SkinError::NotAGlyph(at(), key)),
+                            },
+                            None => return Err(SkinError::NotAGlyph(at(), key)),
+                        };
+                        match setting.as_str() {
+                            "block" => skin.glyphs.block = Some(glyph),
+                            "ghost" => skin.glyphs.ghost = glyph,
+                            "clearing" => skin.glyphs.clearing = glyph,
+                            _ => return Err(SkinError::UnknownSetting(at(), key)),
+                        }
+                    }
+                    "frame" if setting == "style" => {
+                        let style = shown(&value);
+                        skin.border_type = Some(
+                            ui::parse_border_type(&style)
+                                .ok_or(SkinError::UnknownFrame(at(), style))?,
+                        );
+                    }
+                    _ => return Err(SkinError::UnknownSetting(at(), key)),
+                }
+            }
+        }
+        Ok(skin)
+    }
+
+    // The theme's colors over the ones picked for the terminal
+    pub fn palette(&self, mut palette: Palette) -> Palette {
+        for (color, skin) in palette.pieces.iter_mut().zip(self.pieces) {
+            *color = skin.unwrap_or(*color);
+        }
+        palette.garbage = self.garbage.unwrap_or(palette.garbage);
+        palette.clearing = self.clearing.unwrap_or(palette.clearing);
+        palette.floor = self.floor.unwrap_or(palette.floor);
+        palette.border = self.border.unwrap_or(palette.border);
+        palette
+    }
+}
+
+// As it's written in the theme, near enough for an error
+fn shown(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(text) => text.clone(),
+        toml::Value::Integer(number) => number.to_string(),
+        other => other.type_str().to_string(),
+    }
+}
+
+fn parse_color(value: &toml::Value) -> Option<Color> {
+    let name = match value {
+        toml::Value::Integer(index) => return u8::try_from(*index).ok().map(Color::Indexed),
+        toml::Value::String(name) => name,
+        _ => return None,
+    };
+    if let Some(hex) = name.strip_prefix('#').filter(|hex| hex.len() == 6) {
+        let channel = |at| u8::from_str_radix(hex.get(at..at + 2)?, 16).ok();
+        return Some(Color::Rgb(channel(0)?, channel(2)?, channel(4)?));
+    }
+    Some(match name.to_lowercase().as_str() {
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" => Color::Gray,
+        "darkgray" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        "reset" => Color::Reset,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_green_skin_loads() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("skins/green");
+        let skin = Skin::load(dir).unwrap();
+        let palette = skin.palette(Palette::default());
+        assert!("OIJLSZT"
+            .chars()
+            .all(|shape| palette.piece(shape) == Color::Green));
+        assert_eq!(skin.glyphs.block, Some('\u{2593}'));
+        assert_eq!(skin.border_type, Some(BorderType::Plain));
+    }
+
+    #[test]
+    fn what_the_theme_leaves_out_stays_built_in() {
+        let skin = Skin::parse("[colors]\nt = 93\n", Path::new("theme.toml")).unwrap();
+        let palette = skin.palette(Palette::default());
+        assert_eq!(palette.piece('T'), Color::Indexed(93));
+        assert_eq!(palette.piece('O'), Palette::default().piece('O'));
+        assert_eq!(skin.glyphs, Glyphs::default());
+        assert_eq!(skin.border_type, None);
+    }
+
+    #[test]
+    fn errors_point_at_the_file_and_the_key() {
+        let dir = std::env::temp_dir().join(format!("tetrs-skin-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let error = |text: &str| {
+            fs::write(dir.join("theme.toml"), text).unwrap();
+            Skin::load(dir.clone()).unwrap_err().to_string()
+        };
+        let theme = dir.join("theme.toml").display().to_string();
+        assert_eq!(
+            error("[colors]\nt = \"purpel\"\n"),
+            format!(
+                "{theme}: colors.t: unknown color 'purpel' \
+                 (expected a name like \"green\", a number up to 255 or \"#rrggbb\")"
+            )
+        );
+        assert_eq!(
+            error("[glyphs]\nghost = \"..\"\n"),
+            format!("{theme}: glyphs.ghost must be a single character")
+        );
+        assert_eq!(
+            error("[colors]\nq = \"red\"\n"),
+            format!("{theme}: unknown setting 'colors.q'")
+        );
+        assert_eq!(
+            error("[frame]\nstyle = \"wavy\"\n"),
+            format!(
+                "{theme}: frame.style: unknown frame 'wavy' \
+                 (expected plain, rounded, double or thick)"
+            )
+        );
+        fs::remove_dir_all(&dir).unwrap();
+        let missing = Skin::load(dir).unwrap_err().to_string();
+        assert!(missing.starts_with(&format!("{theme}: can't be read")));
+    }
+}
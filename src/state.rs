@@ -0,0 +1,896 @@
+// The game as a state machine: inputs go in through apply(), and the board, the
+// pieces and the score can be read back out. There's no terminal or clock in here,
+// the caller decides when gravity ticks and tells elapse() how much time went by.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game::{
+        self, Board, BoardTextError, Direction, KickAttempt, ShiftError, TSpin, Tetromino,
+        TetrominosBag,
+    },
+    score::{GravityCurve, LineClear, Score},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Input {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    // All the way down to the stack at once, a point per row, without locking
+    SoftDropToStack,
+    // The two above repeated by a held key rather than pressed, see SoftDropLock
+    SoftDropRepeat,
+    SoftDropToStackRepeat,
+    HardDrop,
+    RotateCw,
+    RotateCcw,
+    Hold,
+    // One gravity step
+    Tick,
+}
+
+// What applying an input did to the game
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    Moved,
+    // Nothing changed, the move didn't fit or isn't allowed right now
+    Blocked,
+    // The tetromino locked. With lines to clear the game waits for finish_clear(),
+    // otherwise the next tetromino has already spawned.
+    Locked { lines: usize },
+    // The next tetromino had no room to spawn, every input is ignored from now on
+    GameOver,
+}
+
+// Something that happened in the game, for whatever wants to follow along without
+// being wired into the loop that drives it. Each occurrence is queued exactly once,
+// right after the state it describes has changed, and take_events() hands them out
+// in this order:
+//
+//     PieceLocked, then once its rows are off the board LinesCleared and LevelUp,
+//     then PieceSpawned for the next tetromino or GameOver
+//
+// A T-spin that cleared nothing has a SpinWithoutLines where LinesCleared would be.
+//
+// With the ruleset's zangi a PieceSlid can come between PieceLocked and the rest.
+//
+// A hold queues PieceSpawned for the tetromino that comes out, and the first
+// tetromino of a game is queued from the start. There's no GarbageReceived, nothing
+// sends garbage.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum GameEvent {
+    PieceSpawned {
+        shape: char,
+        // It came out of hold rather than out of the bag
+        from_hold: bool,
+    },
+    // Where the tetromino became part of the stack
+    PieceLocked {
+        shape: char,
+        cells: [(usize, usize); 4],
+    },
+    // The tetromino that locked moved along the stack before its lines went, these are
+    // its cells now
+    PieceSlid {
+        cells: [(usize, usize); 4],
+    },
+    // The rows as they were numbered before they went, from top to bottom. Only when
+    // there were any, a lock without lines is just a PieceLocked.
+    LinesCleared {
+        rows: Vec<usize>,
+        clear: LineClear,
+    },
+    // A T-spin that cleared no lines, in place of LinesCleared
+    SpinWithoutLines {
+        clear: LineClear,
+    },
+    LevelUp {
+        level: u32,
+    },
+    GameOver {
+        // The mode's goal was reached, rather than topping out
+        completed: bool,
+    },
+}
+
+// What a game is played for, and what ends it besides topping out. The built-in
+// modes, a custom one from the config file is only known by its Ruleset.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameMode {
+    // Endless, the level and the speed go up every 10 lines
+    #[default]
+    Marathon,
+    // 40 lines as fast as possible
+    Sprint,
+    // As many points as possible in 2 minutes
+    Ultra,
+    // A marathon whose speed follows how well it's going, see Ruleset::adaptive
+    Adaptive,
+    // Defined in the config file
+    Custom,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 4] = [
+        GameMode::Marathon,
+        GameMode::Sprint,
+        GameMode::Ultra,
+        GameMode::Adaptive,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GameMode::Marathon => "Marathon",
+            GameMode::Sprint => "Sprint",
+            GameMode::Ultra => "Ultra",
+            GameMode::Adaptive => "Adaptive",
+            GameMode::Custom => "Custom",
+        }
+    }
+
+    // As the mode is known in files and on the command line
+    pub fn id(self) -> &'static str {
+        match self {
+            GameMode::Marathon => "marathon",
+            GameMode::Sprint => "sprint",
+            GameMode::Ultra => "ultra",
+            GameMode::Adaptive => "adaptive",
+            GameMode::Custom => "custom",
+        }
+    }
+
+    // A custom mode has no rules of its own, it gets a marathon's
+    pub fn ruleset(self) -> Ruleset {
+        let marathon = Ruleset {
+            id: self.id().to_string(),
+            name: self.name().to_string(),
+            description: "endless, faster every 10 lines".to_string(),
+            line_goal: None,
+            time_limit: None,
+            level_up: true,
+            hold: Hold::Standard,
+            ghost: true,
+            scoring: true,
+            clear_buffer: false,
+            zangi: false,
+            gravity: GravityCurve::Stepped,
+            adaptive: false,
+            spin_b2b: false,
+        };
+        match self {
+            GameMode::Marathon | GameMode::Custom => marathon,
+            GameMode::Sprint => Ruleset {
+                description: "40 lines as fast as you can".to_string(),
+                line_goal: Some(40),
+                level_up: false,
+                ..marathon
+            },
+            GameMode::Ultra => Ruleset {
+                description: "most points in 2 minutes".to_string(),
+                time_limit: Some(120),
+                level_up: false,
+                ..marathon
+            },
+            GameMode::Adaptive => Ruleset {
+                description: "a marathon that keeps pace with you".to_string(),
+                adaptive: true,
+                ..marathon
+            },
+        }
+    }
+}
+
+// Everything a mode decides about a game, the game itself only looks at this
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Ruleset {
+    // One of GameMode's ids, or the name of the mode's table in the config file
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    // Lines that finish the game
+    pub line_goal: Option<u32>,
+    // Seconds the game lasts
+    pub time_limit: Option<u64>,
+    // The level, and the speed with it, goes up every 10 lines. Otherwise the game is
+    // played at level 1 throughout.
+    pub level_up: bool,
+    pub hold: Hold,
+    pub ghost: bool,
+    // Without, lines are counted but never worth any points
+    pub scoring: bool,
+    // Keys pressed while lines are being cleared aren't lost: movement keys charge
+    // their repeat, and a rotation or a hold is applied to the next tetromino as it
+    // spawns. Up to the session, the game itself doesn't see those keys.
+    #[serde(default)]
+    pub clear_buffer: bool,
+    // The tetromino that cleared lines can still be moved sideways along the stack
+    // until they go, as in a few classic games. The lines cleared are the ones it
+    // fills once it's done moving.
+    #[serde(default)]
+    pub zangi: bool,
+    #[serde(default)]
+    pub gravity: GravityCurve,
+    // The speed goes up a level faster for clean play and eases off while the stack is
+    // near the top, see Adaptive. The level itself still goes by the lines.
+    #[serde(default)]
+    pub adaptive: bool,
+    // A T-spin without lines counts towards the back-to-back chain, starting it or
+    // keeping it going. Otherwise it leaves the chain as it was.
+    #[serde(default)]
+    pub spin_b2b: bool,
+}
+
+// How much holding a ruleset allows. Kept as true, false or the number of holds, the
+// way the config file has it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(from = "HoldSetting", into = "HoldSetting")]
+pub enum Hold {
+    Off,
+    // Once per tetromino
+    Standard,
+    // Once per tetromino, and no more than that many times in the game
+    Limited(u32),
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(untagged)]
+enum HoldSetting {
+    Allowed(bool),
+    Limited(u32),
+}
+
+impl From<HoldSetting> for Hold {
+    fn from(setting: HoldSetting) -> Self {
+        match setting {
+            HoldSetting::Allowed(false) => Hold::Off,
+            HoldSetting::Allowed(true) => Hold::Standard,
+            HoldSetting::Limited(holds) => Hold::Limited(holds),
+        }
+    }
+}
+
+impl From<Hold> for HoldSetting {
+    fn from(hold: Hold) -> Self {
+        match hold {
+            Hold::Off => HoldSetting::Allowed(false),
+            Hold::Standard => HoldSetting::Allowed(true),
+            Hold::Limited(holds) => HoldSetting::Limited(holds),
+        }
+    }
+}
+
+impl Ruleset {
+    pub fn mode(&self) -> GameMode {
+        GameMode::ALL
+            .into_iter()
+            .find(|mode| mode.id() == self.id)
+            .unwrap_or(GameMode::Custom)
+    }
+
+    pub fn time_limit(&self) -> Option<Duration> {
+        self.time_limit.map(Duration::from_secs)
+    }
+
+    // Whether the clock is what the game is about
+    pub fn is_timed(&self) -> bool {
+        self.line_goal.is_some() || self.time_limit.is_some()
+    }
+}
+
+// What a soft drop does to a tetromino that's already on the stack. A matter of
+// handling rather than of the rules, a new game starts out with Never.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SoftDropLock {
+    // A fresh press locks it, holding the key down never does
+    Tap,
+    // Pressed or held, it locks it
+    Hold,
+    // Only the lock delay or a hard drop lock it
+    Never,
+}
+
+impl SoftDropLock {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "tap" => Some(Self::Tap),
+            "hold" => Some(Self::Hold),
+            "never" => Some(Self::Never),
+            _ => None,
+        }
+    }
+}
+
+// What the adaptive rules' speed goes by, read after every lock. A run of CLEAN_RUN
+// locks that leave no new hole, with lines cleared along the way, takes the speed a
+// level over the one played on. A stack higher than DANGER_HEIGHT for longer than
+// DANGER_TIME eases it a level, and again after every DANGER_TIME more, until the
+// stack is back down. Either way the speed is never more than MAX_EASE levels under
+// the level or MAX_RAMP over it.
+#[derive(Clone, Default)]
+struct Adaptive {
+    holes: usize,
+    clean_locks: u32,
+    cleared: bool,
+    // Levels gained by clean play, kept for the rest of the game
+    ramp: u32,
+    // Since when the stack has been too high, while it is
+    danger_since: Option<Duration>,
+    // Levels eased since then
+    eased: u32,
+}
+
+impl Adaptive {
+    const CLEAN_RUN: u32 = 10;
+    const DANGER_HEIGHT: u8 = 14;
+    const DANGER_TIME: Duration = Duration::from_secs(10);
+    const MAX_EASE: u32 = 2;
+    const MAX_RAMP: u32 = 3;
+
+    // Levels the speed is off the level, for the board as it is once the lines of the
+    // lock are cleared
+    fn after_lock(&mut self, board: &Board, lines: usize, played: Duration) -> i32 {
+        let holes = board.hole_count();
+        if holes > self.holes {
+            self.clean_locks = 0;
+            self.cleared = false;
+        } else {
+            self.clean_locks += 1;
+            self.cleared |= lines > 0;
+        }
+        self.holes = holes;
+        if self.clean_locks >= Self::CLEAN_RUN && self.cleared {
+            self.ramp = (self.ramp + 1).min(Self::MAX_RAMP);
+            self.clean_locks = 0;
+            self.cleared = false;
+        }
+        if board.stack_height() > Self::DANGER_HEIGHT {
+            let since = *self.danger_since.get_or_insert(played);
+            if played - since > Self::DANGER_TIME {
+                self.eased = (self.eased + 1).min(self.ramp + Self::MAX_EASE);
+                self.danger_since = Some(played);
+            }
+        } else {
+            self.danger_since = None;
+            self.eased = 0;
+        }
+        self.ramp as i32 - self.eased as i32
+    }
+}
+
+#[derive(Clone)]
+pub struct Game {
+    rules: Ruleset,
+    board: Board,
+    tetromino: Tetromino,
+    bag: TetrominosBag,
+    // Only the shape, whatever was held comes back out as a fresh tetromino
+    held: Option<char>,
+    // Hold can be used once per tetromino, it becomes available again when one locks
+    can_hold: bool,
+    // Holds made so far, for a limited hold
+    holds: u32,
+    score: Score,
+    over: bool,
+    paused: bool,
+    // How long the tetromino has been resting on the stack, None while it can still fall
+    grounded_for: Option<Duration>,
+    lock_resets: u32,
+    soft_drop_lock: SoftDropLock,
+    adaptive: Adaptive,
+    // Full rows still on the board, waiting for finish_clear()
+    clearing: Vec<usize>,
+    awaiting_clear: bool,
+    // Kick used by the last successful move if it was a rotation, T-spins need one
+    last_kick: Option<usize>,
+    // Every kick the last rotation tried, whether or not one fit
+    last_rotation: Vec<KickAttempt>,
+    // T-spin found when the tetromino locked, scored once its lines are cleared
+    spin: Option<TSpin>,
+    last_clear: Option<LineClear>,
+    // Time the game has been running for, pauses aside
+    played: Duration,
+    // The game ended by reaching the mode's goal rather than by topping out
+    completed: bool,
+    // Waiting for take_events()
+    events: Vec<GameEvent>,
+}
+
+impl Game {
+    const LOCK_DELAY: Duration = Duration::from_millis(500);
+    // Past this many resets a grounded tetromino locks no matter what the player does
+    const MAX_LOCK_RESETS: u32 = 15;
+
+    // A marathon out of bags shuffled from the seed
+    pub fn new(width: usize, height: usize, seed: u64) -> Self {
+        Self::with_bag(width, height, TetrominosBag::shuffled(seed))
+    }
+
+    // The bag is used as given, it's up to the caller to shuffle it
+    pub fn with_bag(width: usize, height: usize, bag: TetrominosBag) -> Self {
+        Self::with_mode(width, height, bag, GameMode::Marathon)
+    }
+
+    pub fn with_mode(width: usize, height: usize, bag: TetrominosBag, mode: GameMode) -> Self {
+        Self::with_rules(width, height, bag, mode.ruleset())
+    }
+
+    pub fn with_rules(width: usize, height: usize, mut bag: TetrominosBag, rules: Ruleset) -> Self {
+        let mut board = Board::new(width, height);
+        let mut tetromino = bag.get().expect("a bag deals at least one tetromino");
+        // Nothing can block the first piece on an empty board
+        let _ = tetromino.spawn(&mut board);
+        let mut score = Score::new();
+        score.fixed_level = !rules.level_up;
+        score.gravity = rules.gravity;
+        score.spin_b2b = rules.spin_b2b;
+        Self {
+            rules,
+            board,
+            tetromino,
+            bag,
+            held: None,
+            can_hold: true,
+            holds: 0,
+            score,
+            over: false,
+            paused: false,
+            grounded_for: None,
+            lock_resets: 0,
+            soft_drop_lock: SoftDropLock::Never,
+            adaptive: Adaptive::default(),
+            clearing: Vec::new(),
+            awaiting_clear: false,
+            last_kick: None,
+            last_rotation: Vec::new(),
+            spin: None,
+            last_clear: None,
+            played: Duration::ZERO,
+            completed: false,
+            events: vec![GameEvent::PieceSpawned {
+                shape: tetromino.shape(),
+                from_hold: false,
+            }],
+        }
+    }
+
+    // Landing doesn't lock the tetromino, elapse() does once the lock delay runs out
+    pub fn apply(&mut self, input: Input) -> Outcome {
+        if self.over {
+            return Outcome::GameOver;
+        }
+        if self.paused {
+            return Outcome::Blocked;
+        }
+        if self.is_clearing() {
+            return match input {
+                Input::MoveLeft if self.rules.zangi => self.slide(Direction::Left),
+                Input::MoveRight if self.rules.zangi => self.slide(Direction::Right),
+                _ => Outcome::Blocked,
+            };
+        }
+        let shifted = match input {
+            Input::MoveLeft => self.tetromino.shift(&mut self.board, Direction::Left),
+            Input::MoveRight => self.tetromino.shift(&mut self.board, Direction::Right),
+            Input::SoftDrop | Input::SoftDropRepeat => {
+                let shifted = self.tetromino.shift(&mut self.board, Direction::Down);
+                if shifted.is_ok() && self.rules.scoring {
+                    self.score.soft_drop(1);
+                }
+                shifted
+            }
+            Input::SoftDropToStack | Input::SoftDropToStackRepeat => {
+                let distance = self.tetromino.hard_drop(&mut self.board);
+                if distance == 0 {
+                    if self.soft_drop_locks(input) {
+                        return self.lock();
+                    }
+                    self.grounded_for.get_or_insert(Duration::ZERO);
+                    return Outcome::Blocked;
+                }
+                if self.rules.scoring {
+                    self.score.soft_drop(distance);
+                }
+                self.last_kick = None;
+                self.moved(false);
+                return Outcome::Moved;
+            }
+            Input::Tick => self.tetromino.shift(&mut self.board, Direction::Down),
+            Input::RotateCw | Input::RotateCcw => {
+                let clockwise = input == Input::RotateCw;
+                self.last_rotation = self.tetromino.rotate_traced(&mut self.board, clockwise);
+                let Some(kick) = self
+                    .last_rotation
+                    .last()
+                    .filter(|attempt| attempt.failure.is_none())
+                    .map(|attempt| attempt.index)
+                else {
+                    return Outcome::Blocked;
+                };
+                self.last_kick = Some(kick);
+                self.moved(true);
+                return Outcome::Moved;
+            }
+            Input::HardDrop => {
+                let distance = self.tetromino.hard_drop(&mut self.board);
+                if self.rules.scoring {
+                    self.score.hard_drop(distance);
+                }
+                if distance > 0 {
+                    self.last_kick = None;
+                }
+                return self.lock();
+            }
+            Input::Hold => return self.hold(),
+        };
+        match shifted {
+            Ok(()) => {
+                self.last_kick = None;
+                self.moved(matches!(input, Input::MoveLeft | Input::MoveRight));
+                Outcome::Moved
+            }
+            Err(ShiftError::BottomCollision) => {
+                if self.soft_drop_locks(input) {
+                    return self.lock();
+                }
+                self.grounded_for.get_or_insert(Duration::ZERO);
+                Outcome::Blocked
+            }
+            Err(ShiftError::BorderCollision) => Outcome::Blocked,
+        }
+    }
+
+    // Whether a soft drop the stack stopped locks the tetromino there and then
+    fn soft_drop_locks(&self, input: Input) -> bool {
+        let pressed = matches!(input, Input::SoftDrop | Input::SoftDropToStack);
+        let held = matches!(input, Input::SoftDropRepeat | Input::SoftDropToStackRepeat);
+        match self.soft_drop_lock {
+            SoftDropLock::Tap => pressed,
+            SoftDropLock::Hold => pressed || held,
+            SoftDropLock::Never => false,
+        }
+    }
+
+    // Advances the game time and the lock delay, locking the tetromino once it has been
+    // grounded for long enough. Returns Blocked when nothing happened. The game time
+    // keeps going while lines are being cleared, and a game with a time limit ends
+    // once they're off the board.
+    pub fn elapse(&mut self, elapsed: Duration) -> Outcome {
+        if self.over {
+            return Outcome::GameOver;
+        }
+        if self.paused {
+            return Outcome::Blocked;
+        }
+        self.played += elapsed;
+        if self.is_clearing() {
+            return Outcome::Blocked;
+        }
+        if let Some(limit) = self.rules.time_limit() {
+            if self.played >= limit {
+                self.played = limit;
+                return self.complete();
+            }
+        }
+        let Some(grounded_for) = &mut self.grounded_for else {
+            return Outcome::Blocked;
+        };
+        *grounded_for += elapsed;
+        if *grounded_for >= Self::LOCK_DELAY || self.lock_resets >= Self::MAX_LOCK_RESETS {
+            return self.lock();
+        }
+        Outcome::Blocked
+    }
+
+    // Time until elapse() would lock the tetromino, None while it isn't grounded
+    pub fn lock_delay_left(&self) -> Option<Duration> {
+        if self.lock_resets >= Self::MAX_LOCK_RESETS {
+            return self.grounded_for.map(|_| Duration::ZERO);
+        }
+        self.grounded_for
+            .map(|grounded_for| Self::LOCK_DELAY.saturating_sub(grounded_for))
+    }
+
+    // To be called after every successful move. Shifting or rotating a grounded
+    // tetromino restarts its lock delay, a limited number of times.
+    fn moved(&mut self, can_reset: bool) {
+        if !self.is_grounded() {
+            self.grounded_for = None;
+        } else if self.grounded_for.is_none() {
+            self.grounded_for = Some(Duration::ZERO);
+        } else if can_reset && self.lock_resets < Self::MAX_LOCK_RESETS {
+            self.lock_resets += 1;
+            self.grounded_for = Some(Duration::ZERO);
+        }
+    }
+
+    fn is_grounded(&self) -> bool {
+        self.tetromino.ghost_body(&self.board) == self.tetromino.body()
+    }
+
+    fn lock(&mut self) -> Outcome {
+        let rows = self.tetromino.rows();
+        self.tetromino.place_in_playfield(&mut self.board);
+        self.events.push(GameEvent::PieceLocked {
+            shape: self.tetromino.shape(),
+            cells: self.tetromino.body(),
+        });
+        self.grounded_for = None;
+        self.spin = self
+            .last_kick
+            .and_then(|kick| self.tetromino.t_spin(&self.board, kick));
+        self.clearing = self.board.full_rows(&rows);
+        if !self.clearing.is_empty() {
+            self.awaiting_clear = true;
+            return Outcome::Locked {
+                lines: self.clearing.len(),
+            };
+        }
+        self.finish_clear()
+    }
+
+    // Lifts the tetromino that just locked back off the stack and moves it a column
+    // over, climbing the stack if it has to, then lets it fall to rest again. The rows
+    // it fills are worked out anew, and a move after the rotation means it's no T-spin
+    // any more.
+    fn slide(&mut self, direction: Direction) -> Outcome {
+        self.tetromino.lift_from_playfield(&mut self.board);
+        let mut climbed = 0;
+        let shifted = loop {
+            if self.tetromino.shift(&mut self.board, direction).is_ok() {
+                break true;
+            }
+            if self
+                .tetromino
+                .shift(&mut self.board, Direction::Up)
+                .is_err()
+            {
+                break false;
+            }
+            climbed += 1;
+        };
+        if shifted {
+            self.tetromino.hard_drop(&mut self.board);
+            self.spin = None;
+        } else {
+            for _ in 0..climbed {
+                let _ = self.tetromino.shift(&mut self.board, Direction::Down);
+            }
+        }
+        self.tetromino.place_in_playfield(&mut self.board);
+        if !shifted {
+            return Outcome::Blocked;
+        }
+        self.clearing = self.board.full_rows(&self.tetromino.rows());
+        self.events.push(GameEvent::PieceSlid {
+            cells: self.tetromino.body(),
+        });
+        Outcome::Moved
+    }
+
+    // Takes the full rows off the board and spawns the next tetromino. Until then
+    // every input is blocked, so the rows can be shown before they go.
+    pub fn finish_clear(&mut self) -> Outcome {
+        self.awaiting_clear = false;
+        let rows = std::mem::take(&mut self.clearing);
+        let lines = self.board.clear_lines(&rows).len();
+        let level = self.score.level;
+        let mut clear = self.score.line_clear(lines, self.spin.take());
+        if !self.rules.scoring {
+            self.score.points -= clear.points;
+            clear.points = 0;
+        }
+        if self.rules.adaptive {
+            self.score.speed_offset = self.adaptive.after_lock(&self.board, lines, self.played);
+        }
+        self.last_clear = Some(clear);
+        if lines > 0 {
+            self.events.push(GameEvent::LinesCleared { rows, clear });
+        } else if clear.spin.is_some() {
+            self.events.push(GameEvent::SpinWithoutLines { clear });
+        }
+        if self.score.level > level {
+            self.events.push(GameEvent::LevelUp {
+                level: self.score.level,
+            });
+        }
+        if let Some(goal) = self.rules.line_goal {
+            if self.score.lines >= goal {
+                return self.complete();
+            }
+        }
+        self.can_hold = true;
+        // Past the end of a scripted sequence the held tetromino is the last one left
+        let (next, from_hold) = match self.bag.get() {
+            Some(next) => (next, false),
+            None => match self.held.take().and_then(game::template) {
+                Some(held) => (held, true),
+                None => return self.complete(),
+            },
+        };
+        self.tetromino = next;
+        self.spawn(Outcome::Locked { lines }, from_hold)
+    }
+
+    fn complete(&mut self) -> Outcome {
+        self.over = true;
+        self.completed = true;
+        self.events.push(GameEvent::GameOver { completed: true });
+        Outcome::GameOver
+    }
+
+    // Still so after a slide that leaves no full row, the next tetromino comes out of
+    // finish_clear() all the same
+    pub fn is_clearing(&self) -> bool {
+        self.awaiting_clear
+    }
+
+    // The rows finish_clear() is going to take off, from top to bottom
+    pub fn clearing_rows(&self) -> &[usize] {
+        &self.clearing
+    }
+
+    fn hold(&mut self) -> Outcome {
+        if !self.can_hold() {
+            return Outcome::Blocked;
+        }
+        self.can_hold = false;
+        self.holds += 1;
+        self.tetromino.remove_from_playfield(&mut self.board);
+        let held = self.held.replace(self.tetromino.shape());
+        let from_hold = held.is_some();
+        self.tetromino = held
+            .and_then(game::template)
+            .or_else(|| self.bag.get())
+            .unwrap();
+        self.spawn(Outcome::Moved, from_hold)
+    }
+
+    // Whether Input::Hold would hold the tetromino now
+    pub fn can_hold(&self) -> bool {
+        // Nothing to swap in after the last tetromino of a sequence
+        let swap = self.held.is_some() || self.bag.peek().is_some();
+        self.can_hold && swap && self.holds_left() != Some(0)
+    }
+
+    // How many more times the ruleset lets the player hold, None when there's no limit
+    pub fn holds_left(&self) -> Option<u32> {
+        match self.rules.hold {
+            Hold::Off => Some(0),
+            Hold::Standard => None,
+            Hold::Limited(holds) => Some(holds.saturating_sub(self.holds)),
+        }
+    }
+
+    fn spawn(&mut self, outcome: Outcome, from_hold: bool) -> Outcome {
+        self.grounded_for = None;
+        self.last_kick = None;
+        self.lock_resets = 0;
+        if self.tetromino.spawn(&mut self.board).is_err() {
+            self.over = true;
+            self.events.push(GameEvent::GameOver { completed: false });
+            return Outcome::GameOver;
+        }
+        self.events.push(GameEvent::PieceSpawned {
+            shape: self.tetromino.shape(),
+            from_hold,
+        });
+        outcome
+    }
+
+    // Everything that happened since the last call, oldest first. Events pile up
+    // until they're taken.
+    pub fn take_events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    // Replaces the stack with the one in the text, see Board::load_text, for setting
+    // up a position. The falling tetromino stays where it is and has to be clear of it.
+    pub fn load_stack(&mut self, text: &str) -> Result<(), BoardTextError> {
+        self.board.load_text(text)?;
+        let body = self.tetromino.body();
+        self.tetromino.change_position(&body, &mut self.board);
+        Ok(())
+    }
+
+    // Locked cells plus the falling tetromino as active cells
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn tetromino(&self) -> &Tetromino {
+        &self.tetromino
+    }
+
+    pub fn ghost(&self) -> Tetromino {
+        self.tetromino.ghost(&self.board)
+    }
+
+    // None after the last tetromino of a scripted sequence that ends
+    pub fn next(&self) -> Option<Tetromino> {
+        self.bag.peek()
+    }
+
+    // In its spawn rotation, however it was turned when it went into hold
+    pub fn held(&self) -> Option<Tetromino> {
+        self.held.and_then(game::template)
+    }
+
+    pub fn score(&self) -> Score {
+        self.score
+    }
+
+    // The kicks tried by the last rotation, for showing them while debugging
+    pub fn last_rotation(&self) -> &[KickAttempt] {
+        &self.last_rotation
+    }
+
+    // What the last tetromino to lock was worth, once its lines are cleared
+    pub fn last_clear(&self) -> Option<LineClear> {
+        self.last_clear
+    }
+
+    // How every bag so far was shuffled, see TetrominosBag::decisions
+    pub fn bag_decisions(&self) -> &[String] {
+        self.bag.decisions()
+    }
+
+    // Changes with the board and anything that decides which tetromino comes next,
+    // which is where a desync shows up first. Timers and the score aren't part of it.
+    pub fn state_hash(&self) -> u64 {
+        game::state_hash(
+            &self.board,
+            &self.tetromino,
+            self.held,
+            self.can_hold,
+            &self.bag,
+        )
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.over
+    }
+
+    // Whether the game ended by reaching its goal, a sprint's 40 lines, the end of an
+    // ultra's time or the last tetromino of a sequence that ends. Topping out never
+    // completes a game.
+    pub fn is_completed(&self) -> bool {
+        self.completed
+    }
+
+    pub fn mode(&self) -> GameMode {
+        self.rules.mode()
+    }
+
+    pub fn rules(&self) -> &Ruleset {
+        &self.rules
+    }
+
+    pub fn played(&self) -> Duration {
+        self.played
+    }
+
+    // None in modes without a time limit
+    pub fn time_left(&self) -> Option<Duration> {
+        self.rules
+            .time_limit()
+            .map(|limit| limit.saturating_sub(self.played))
+    }
+
+    pub fn set_soft_drop_lock(&mut self, lock: SoftDropLock) {
+        self.soft_drop_lock = lock;
+    }
+
+    // While paused every input is blocked and the lock delay stands still
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused && !self.over;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+}
@@ -0,0 +1,221 @@
+// The game state for external overlays, served with --state-socket PATH as one JSON
+// snapshot per line:
+//
+//     {"mode": "sprint", "board": "..........\n...", "piece": "T",
+//      "piece_cells": [[4, 0], [3, 1], [4, 1], [5, 1]], "hold": "I", "next": ["L"],
+//      "score": {"points": 100, "lines": 1, "level": 1, "back_to_back": false, ...},
+//      "played_ms": 5230, "paused": false, "over": false}
+//
+// The board is in the text format of Board::to_text(), which leaves the falling piece
+// out, that one is in piece_cells. next is empty once a scripted sequence that ends
+// has nothing left. Any number of readers can connect at any time.
+// The game only ever writes: a reader that falls behind misses snapshots rather than
+// slowing the game down, and one that goes away is dropped. It's a Unix domain
+// socket, or a TCP address like 127.0.0.1:7878 on Windows. See
+// examples/state_reader.py for a reader.
+
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use tetris::{
+    score::Score,
+    state::{Game, GameMode},
+};
+
+#[cfg(not(unix))]
+use std::net::{TcpListener as Listener, TcpStream as Connection};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener as Listener, UnixStream as Connection};
+
+#[derive(Serialize)]
+struct Snapshot {
+    mode: GameMode,
+    board: String,
+    piece: char,
+    piece_cells: [(usize, usize); 4],
+    hold: Option<char>,
+    next: Vec<char>,
+    score: Score,
+    played_ms: u64,
+    paused: bool,
+    over: bool,
+}
+
+impl Snapshot {
+    fn of(game: &Game) -> Snapshot {
+        Snapshot {
+            mode: game.mode(),
+            board: game.board().to_text(),
+            piece: game.tetromino().shape(),
+            piece_cells: game.tetromino().body(),
+            hold: game.held().map(|held| held.shape()),
+            next: game.next().iter().map(|next| next.shape()).collect(),
+            score: game.score(),
+            played_ms: game.played().as_millis() as u64,
+            paused: game.is_paused(),
+            over: game.is_over(),
+        }
+    }
+}
+
+struct Reader {
+    connection: Connection,
+    // What's left of the last line, it goes out before anything new does so that
+    // lines never get cut
+    unsent: Vec<u8>,
+}
+
+impl Reader {
+    // Returns whether the reader is still there
+    fn flush(&mut self) -> bool {
+        while !self.unsent.is_empty() {
+            match self.connection.write(&self.unsent) {
+                Ok(0) => return false,
+                Ok(written) => {
+                    self.unsent.drain(..written);
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return true,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) => return false,
+            }
+        }
+        true
+    }
+}
+
+pub struct StateStream {
+    listener: Listener,
+    path: PathBuf,
+    readers: Vec<Reader>,
+    interval: Duration,
+    last_sent: Option<Instant>,
+}
+
+impl StateStream {
+    // A socket left behind by an earlier game is replaced
+    pub fn bind(path: &Path, rate: u32) -> io::Result<StateStream> {
+        #[cfg(unix)]
+        let listener = {
+            let _ = std::fs::remove_file(path);
+            Listener::bind(path)?
+        };
+        #[cfg(not(unix))]
+        let listener = Listener::bind(path.to_string_lossy().as_ref())?;
+        listener.set_nonblocking(true)?;
+        Ok(StateStream {
+            listener,
+            path: path.to_path_buf(),
+            readers: Vec::new(),
+            interval: Duration::from_secs(1) / rate.max(1),
+            last_sent: None,
+        })
+    }
+
+    // When the next snapshot is due
+    pub fn deadline(&self) -> Option<Instant> {
+        Some(self.last_sent? + self.interval)
+    }
+
+    // Sends a snapshot to every reader unless the last one went out less than an
+    // interval ago, the one of a game that just ended always goes. Never waits on a
+    // reader.
+    pub fn publish(&mut self, game: &Game, now: Instant) {
+        if !game.is_over() && self.deadline().is_some_and(|deadline| now < deadline) {
+            return;
+        }
+        self.last_sent = Some(now);
+        while let Ok((connection, _)) = self.listener.accept() {
+            if connection.set_nonblocking(true).is_ok() {
+                self.readers.push(Reader {
+                    connection,
+                    unsent: Vec::new(),
+                });
+            }
+        }
+        let mut line = serde_json::to_vec(&Snapshot::of(game)).unwrap();
+        line.push(b'\n');
+        self.readers.retain_mut(|reader| {
+            // Still busy with an older snapshot, this one is skipped
+            if !reader.flush() {
+                return false;
+            }
+            if !reader.unsent.is_empty() {
+                return true;
+            }
+            reader.unsent.extend_from_slice(&line);
+            reader.flush()
+        });
+    }
+}
+
+impl Drop for StateStream {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::io::{BufRead, BufReader};
+
+    use tetris::game::TetrominosBag;
+
+    use super::*;
+
+    fn socket(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("tetrs-{name}-{}.sock", std::process::id()))
+    }
+
+    #[test]
+    fn readers_get_a_snapshot_per_line() {
+        let path = socket("snapshot");
+        let mut stream = StateStream::bind(&path, 10).unwrap();
+        let reader = Connection::connect(&path).unwrap();
+        let mut game = Game::with_bag(10, 20, TetrominosBag::new());
+        game.apply(tetris::state::Input::HardDrop);
+        let start = Instant::now();
+        stream.publish(&game, start);
+        // Too soon for another one
+        stream.publish(&game, start + Duration::from_millis(50));
+        assert_eq!(stream.deadline(), Some(start + Duration::from_millis(100)));
+        stream.publish(&game, start + Duration::from_millis(100));
+        drop(stream);
+        assert!(!path.exists());
+        let lines: Vec<String> = BufReader::new(reader).lines().map(Result::unwrap).collect();
+        assert_eq!(lines.len(), 2);
+        let snapshot: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(snapshot["mode"], "marathon");
+        assert_eq!(snapshot["piece"], "I");
+        assert_eq!(snapshot["next"], serde_json::json!(["J"]));
+        assert_eq!(snapshot["hold"], serde_json::Value::Null);
+        assert_eq!(snapshot["score"]["back_to_back"], false);
+        let board = snapshot["board"].as_str().unwrap();
+        assert_eq!(board.lines().count(), 20);
+        assert_eq!(board.lines().last(), Some("....OO...."));
+    }
+
+    #[test]
+    fn a_reader_that_never_reads_doesnt_hold_the_game_up() {
+        let path = socket("stalled");
+        let mut stream = StateStream::bind(&path, 1000).unwrap();
+        let stalled = Connection::connect(&path).unwrap();
+        let game = Game::with_bag(10, 20, TetrominosBag::new());
+        let start = Instant::now();
+        // Far more than a socket buffer holds
+        for frame in 0..20_000 {
+            stream.publish(&game, start + Duration::from_millis(frame));
+        }
+        assert!(start.elapsed() < Duration::from_secs(10));
+        // Whatever made it through is whole lines
+        drop(stream);
+        for line in BufReader::new(stalled).lines() {
+            serde_json::from_str::<serde_json::Value>(&line.unwrap()).unwrap();
+        }
+    }
+}
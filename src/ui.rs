@@ -1,30 +1,461 @@
 // Here will be contained all the graphics-related stuff.
 
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+use crossterm::{execute, style::available_color_count, terminal::SetTitle};
+
+use tetris::{
+    game::{KickAttempt, Tetromino},
+    highscores::{HighScores, Ranking},
+    state::{Game, Hold, Ruleset},
+};
+
 use tui::{
     backend::Backend,
-    buffer::Buffer,
-    layout::{Rect, Layout, Direction, Constraint},
-    style::Color,
-    widgets::{Block, Borders},
-    Terminal,
+    buffer::{Buffer, Cell},
+    layout::{Alignment, Margin, Rect},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans, Text},
+    widgets::{
+        Block, BorderType, Borders, Clear, List, ListItem, ListState, Paragraph, Widget, Wrap,
+    },
+    Frame, Terminal,
 };
 
+use crate::{pace::Splits, results::Results};
+
+// What the game over overlay shows besides the final score
+pub struct GameOver<'a> {
+    // The seed in there plays the same tetrominos again with --seed
+    pub results: &'a Results,
+    // For --challenge, to pass on, None for a scripted sequence
+    pub challenge: Option<String>,
+    // Played from a challenge code made with other handling
+    pub other_handling: bool,
+    pub high_scores: &'a HighScores,
+    // For each entry, whether its replay is missing or isn't the one it was
+    pub unverified: Vec<bool>,
+    // This run's place in the table, highlighted
+    pub rank: Option<usize>,
+    // The name typed so far while the run qualifies and hasn't been entered yet
+    pub name: Option<&'a str>,
+    // What went wrong saving the table, once the run is entered
+    pub save_error: Option<&'a str>,
+    // None when there's no replay to save, a replay being watched is saved already
+    pub replay_save: Option<&'a ReplaySave>,
+}
+
+// How far the game over screen has got with saving the game's replay
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ReplaySave {
+    Ask,
+    // The name typed so far
+    Naming(String),
+    Saved,
+    // What went wrong
+    Failed(String),
+}
+
+// A column beside the board filling up towards a line goal, with a mark where the
+// best run was at the same time
+struct PaceBar {
+    goal: u32,
+    lines: u32,
+    best: Option<f64>,
+    palette: Palette,
+    glyphs_only: bool,
+}
+
+impl Widget for PaceBar {
+    fn render(self, area: Rect, buffer: &mut Buffer) {
+        let (track, done, mark) = if self.glyphs_only {
+            ('|', '#', '>')
+        } else {
+            ('\u{2502}', '\u{2588}', '\u{25b8}')
+        };
+        let height = f64::from(area.height);
+        let goal = f64::from(self.goal.max(1));
+        // Counted in rows from the bottom. The best run's mark shows from its first line
+        // on, and over the filled part when this run is ahead.
+        let filled = (f64::from(self.lines) / goal * height).round() as u16;
+        let best = self
+            .best
+            .filter(|&lines| lines > 0.0)
+            .map(|lines| ((lines / goal * height).round() as u16).clamp(1, area.height));
+        for row in 1..=area.height {
+            let cell = buffer.get_mut(area.x, area.bottom() - row);
+            if best == Some(row) {
+                cell.set_char(mark).set_fg(self.palette.clearing);
+                if row <= filled && !self.glyphs_only {
+                    cell.set_bg(self.palette.piece('I'));
+                }
+            } else if row <= filled {
+                cell.set_char(done).set_fg(self.palette.piece('I'));
+            } else {
+                cell.set_char(track).set_fg(self.palette.border);
+            }
+        }
+    }
+}
+
+// A list over the whole of the playfield, for draw_list()
+struct ListScreen<'a> {
+    title: &'a str,
+    // Shown instead when there's nothing to list
+    empty: &'a str,
+    lines: &'a [String],
+    marked: Option<usize>,
+}
+
+// Where and how a game gets drawn, the game itself is handed over on every draw
+#[derive(Clone)]
 pub struct Playfield {
     pub rect: Rect,
-    pub tiles: Vec<Vec<Option<Playcell>>>,
+    // Size of the terminal the playfield is centered in
+    frame: (u16, u16),
+    // Draw piece letters instead of colors, for terminals without color support
+    pub glyphs_only: bool,
+    pub ghost: bool,
+    // The held tetromino's letter over the preview while it can be swapped in
+    pub hold_hint: bool,
+    pub palette: Palette,
+    pub glyphs: Glyphs,
+    pub border_type: BorderType,
+    pub hud: Hud,
+    // The splits of the best run of the mode, for the pace bar
+    pub pace: Option<Splits>,
+    // The game paused itself, nobody was playing
+    pub idle_paused: bool,
+    framing: Framing,
     x_scaling: u16,
     y_scaling: u16,
 }
 
-#[derive(Clone, Copy)]
-pub struct Playcell {
-    pub is_active: bool,
-    color: Color,
+// Extra room inside the playfield's border, it changes the playfield's size
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Framing {
+    // An empty column on either side of the board
+    pub padding: bool,
+    // A row under the board drawn unlike the border, so the floor reads clearly
+    pub floor: bool,
+}
+
+impl Framing {
+    fn padding(self) -> u16 {
+        u16::from(self.padding)
+    }
+
+    fn floor(self) -> u16 {
+        u16::from(self.floor)
+    }
+}
+
+// A box beside the board, named as in the hud setting
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Panel {
+    Next,
+    Hold,
+    Score,
+    Level,
+    Lines,
+    // Only filled in the modes played against the clock
+    Time,
+    // The name of the last line clear worth mentioning
+    Clear,
+}
+
+impl Panel {
+    pub const ALL: [Panel; 7] = [
+        Panel::Next,
+        Panel::Hold,
+        Panel::Score,
+        Panel::Level,
+        Panel::Lines,
+        Panel::Time,
+        Panel::Clear,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Panel::Next => "next",
+            Panel::Hold => "hold",
+            Panel::Score => "score",
+            Panel::Level => "level",
+            Panel::Lines => "lines",
+            Panel::Time => "time",
+            Panel::Clear => "clear",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Panel> {
+        Panel::ALL.into_iter().find(|panel| panel.name() == name)
+    }
+
+    fn is_stat(self) -> bool {
+        matches!(self, Panel::Score | Panel::Level | Panel::Lines)
+    }
+}
+
+// Where the panels go: hold and the clear name on the left of the board and the rest
+// on the right, or all of them on one side
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum Sidebar {
+    #[default]
+    Both,
+    Left,
+    Right,
+}
+
+impl Sidebar {
+    pub fn from_name(name: &str) -> Option<Sidebar> {
+        match name {
+            "both" => Some(Sidebar::Both),
+            "left" => Some(Sidebar::Left),
+            "right" => Some(Sidebar::Right),
+            _ => None,
+        }
+    }
+}
+
+// The panels shown, from the top of their side and in the order they're given up in
+// when the terminal is too small for all of them: the last one goes first
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Hud {
+    pub panels: Vec<Panel>,
+    pub sidebar: Sidebar,
+}
+
+impl Default for Hud {
+    fn default() -> Self {
+        Self {
+            panels: vec![
+                Panel::Next,
+                Panel::Hold,
+                Panel::Score,
+                Panel::Level,
+                Panel::Lines,
+                Panel::Time,
+                Panel::Clear,
+            ],
+            sidebar: Sidebar::Both,
+        }
+    }
+}
+
+impl Hud {
+    // Without the hold box when the rules don't let the player hold
+    pub fn for_rules(&self, rules: &Ruleset) -> Hud {
+        let mut hud = self.clone();
+        if rules.hold == Hold::Off {
+            hud.panels.retain(|&panel| panel != Panel::Hold);
+        }
+        hud
+    }
+}
+
+// A box of the hud and the panels in it, more than one for score, level and lines
+struct PanelBox {
+    panels: Vec<Panel>,
+    rect: Rect,
+}
+
+pub fn parse_border_type(name: &str) -> Option<BorderType> {
+    match name {
+        "plain" => Some(BorderType::Plain),
+        "rounded" => Some(BorderType::Rounded),
+        "double" => Some(BorderType::Double),
+        "thick" => Some(BorderType::Thick),
+        _ => None,
+    }
+}
+
+pub fn border_type_name(border_type: BorderType) -> &'static str {
+    match border_type {
+        BorderType::Plain => "plain",
+        BorderType::Rounded => "rounded",
+        BorderType::Double => "double",
+        BorderType::Thick => "thick",
+    }
+}
+
+// NO_COLOR is honoured, and dumb or unknown terminals are assumed to be monochrome
+pub fn color_supported() -> bool {
+    if std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+        return false;
+    }
+    !matches!(
+        std::env::var("TERM").as_deref(),
+        Err(_) | Ok("") | Ok("dumb")
+    )
+}
+
+// The terminal's background, which decides the palette the game is drawn with
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum Background {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl Background {
+    pub fn from_name(name: &str) -> Option<Background> {
+        match name {
+            "dark" => Some(Background::Dark),
+            "light" => Some(Background::Light),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Background::Dark => "dark",
+            Background::Light => "light",
+        }
+    }
+
+    // COLORFGBG is set by rxvt, Konsole and a few others as "fg;bg", sometimes with a
+    // field in between. Terminals that don't set it are assumed to be dark.
+    pub fn detect() -> Option<Background> {
+        Self::from_colorfgbg(&std::env::var("COLORFGBG").ok()?)
+    }
+
+    // The background is one of the 16 basic colors, 7 and up are the light ones
+    // except for the bright black at 8
+    fn from_colorfgbg(value: &str) -> Option<Background> {
+        match value.rsplit(';').next()?.parse::<u8>().ok()? {
+            7 | 9..=15 => Some(Background::Light),
+            0..=6 | 8 => Some(Background::Dark),
+            _ => None,
+        }
+    }
+
+    // Light backgrounds get darker pieces, a gray that shows up and black borders.
+    // The T is purple where the terminal has true color, magenta elsewhere. There's no
+    // orange among the basic colors, so the L takes the one of the 256 color palette
+    // and light red where even that isn't there.
+    pub fn palette(self, depth: ColorDepth) -> Palette {
+        let pick = |true_color, indexed, basic| match depth {
+            ColorDepth::TrueColor => true_color,
+            ColorDepth::Indexed => indexed,
+            ColorDepth::Basic => basic,
+        };
+        let rgb = |r, g, b, fallback| pick(Color::Rgb(r, g, b), fallback, fallback);
+        let t = rgb(160, 32, 240, Color::Magenta);
+        match self {
+            Background::Dark => Palette {
+                pieces: [
+                    Color::Yellow,
+                    Color::Cyan,
+                    Color::Blue,
+                    pick(
+                        Color::Rgb(255, 165, 0),
+                        Color::Indexed(208),
+                        Color::LightRed,
+                    ),
+                    Color::Green,
+                    Color::Red,
+                    t,
+                ],
+                garbage: Color::DarkGray,
+                clearing: Color::White,
+                floor: Color::DarkGray,
+                border: Color::Reset,
+            },
+            Background::Light => Palette {
+                pieces: [
+                    rgb(204, 153, 0, Color::Yellow),
+                    rgb(0, 139, 139, Color::Cyan),
+                    Color::Blue,
+                    pick(
+                        Color::Rgb(230, 115, 0),
+                        Color::Indexed(166),
+                        Color::LightRed,
+                    ),
+                    rgb(0, 128, 0, Color::Green),
+                    Color::Red,
+                    t,
+                ],
+                garbage: Color::Gray,
+                clearing: Color::Black,
+                floor: Color::Gray,
+                border: Color::Black,
+            },
+        }
+    }
+}
+
+// How many colors the terminal can show, from the basic 16 up to 24 bit
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ColorDepth {
+    Basic,
+    Indexed,
+    TrueColor,
+}
+
+impl ColorDepth {
+    // COLORTERM is how terminals announce 24 bit color, TERM tells about 256 colors
+    pub fn detect() -> ColorDepth {
+        if matches!(
+            std::env::var("COLORTERM").as_deref(),
+            Ok("truecolor" | "24bit")
+        ) {
+            ColorDepth::TrueColor
+        } else if available_color_count() >= 256 {
+            ColorDepth::Indexed
+        } else {
+            ColorDepth::Basic
+        }
+    }
+}
+
+// Every color the playfield is drawn with
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Palette {
+    // In the order O, I, J, L, S, Z, T
+    pub pieces: [Color; 7],
+    pub garbage: Color,
+    // Rows about to be cleared
+    pub clearing: Color,
+    pub floor: Color,
+    pub border: Color,
+}
+
+impl Palette {
+    pub fn piece(&self, shape: char) -> Color {
+        match "OIJLSZT".find(shape) {
+            Some(index) => self.pieces[index],
+            None => self.garbage,
+        }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Background::Dark.palette(ColorDepth::Basic)
+    }
+}
+
+// The characters cells are drawn with where color alone won't do
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Glyphs {
+    // Drawn in the piece's color, None fills the cell with the color instead
+    pub block: Option<char>,
+    pub ghost: char,
+    // Rows about to be cleared, without colors
+    pub clearing: char,
 }
 
-impl Playcell {
-    pub fn new(is_active: bool, color: Color) -> Self {
-        Self { is_active, color }
+impl Default for Glyphs {
+    fn default() -> Self {
+        Self {
+            block: None,
+            ghost: '\u{2591}',
+            clearing: '=',
+        }
     }
 }
 
@@ -36,65 +467,1667 @@ impl Playfield {
         height: u16,
         x_scaling: u16,
         y_scaling: u16,
+        framing: Framing,
     ) -> Self {
         const BORDER_PIXELS: u16 = 2;
-        // They are centered
-        let orig_x = (frame_width - width * x_scaling + 2) / 2;
-        let orig_y = (frame_height - height * y_scaling + 2) / 2;
+        let inner_width = width * x_scaling + 2 * framing.padding();
+        let inner_height = height * y_scaling + framing.floor();
+        // They are centered, or pushed against the top left corner when they don't fit
+        let orig_x = (frame_width + 2).saturating_sub(inner_width) / 2;
+        let orig_y = (frame_height + 2).saturating_sub(inner_height) / 2;
         let rect = Rect::new(
             orig_x,
             orig_y,
-            width * x_scaling + BORDER_PIXELS,
-            height * y_scaling + BORDER_PIXELS,
+            inner_width + BORDER_PIXELS,
+            inner_height + BORDER_PIXELS,
         );
-        let mut tiles: Vec<Vec<Option<Playcell>>> = Vec::with_capacity(height.into());
-        for _ in 0..height {
-            tiles.push(vec![None; width.into()]);
-        }
         Self {
             rect,
-            tiles,
+            frame: (frame_width, frame_height),
+            glyphs_only: false,
+            ghost: true,
+            hold_hint: false,
+            palette: Palette::default(),
+            glyphs: Glyphs::default(),
+            hud: Hud::default(),
+            pace: None,
+            idle_paused: false,
+            border_type: BorderType::Plain,
+            framing,
             x_scaling,
             y_scaling,
         }
     }
 
-    pub fn get_x_midpoint(&self) -> usize {
-        ((self.rect.width - 2) / self.x_scaling / 2).into()
+    // Smallest terminal a playfield of this size fits in with every panel of the hud
+    pub fn required_terminal_size(
+        width: u16,
+        height: u16,
+        x_scaling: u16,
+        y_scaling: u16,
+        framing: Framing,
+        hud: &Hud,
+    ) -> (u16, u16) {
+        Self::smallest_frame(width, height, x_scaling, y_scaling, framing, |playfield| {
+            playfield.board_fits() && playfield.stack(&hud.panels).is_some()
+        })
+    }
+
+    fn smallest_frame(
+        width: u16,
+        height: u16,
+        x_scaling: u16,
+        y_scaling: u16,
+        framing: Framing,
+        fits: impl Fn(&Playfield) -> bool,
+    ) -> (u16, u16) {
+        let fits = |frame_width, frame_height| {
+            fits(&Self::new(
+                frame_width,
+                frame_height,
+                width,
+                height,
+                x_scaling,
+                y_scaling,
+                framing,
+            ))
+        };
+        let frame_width = (width * x_scaling..)
+            .find(|w| fits(*w, u16::MAX / 2))
+            .unwrap();
+        let frame_height = (height * y_scaling..)
+            .find(|h| fits(u16::MAX / 2, *h))
+            .unwrap();
+        (frame_width, frame_height)
+    }
+
+    // Recenters the playfield in a terminal of the new size
+    pub fn resize(&mut self, frame_width: u16, frame_height: u16) {
+        let (width, height) = self.board_size();
+        *self = Self {
+            glyphs_only: self.glyphs_only,
+            ghost: self.ghost,
+            palette: self.palette,
+            glyphs: self.glyphs,
+            border_type: self.border_type,
+            hud: self.hud.clone(),
+            pace: self.pace.clone(),
+            ..Self::new(
+                frame_width,
+                frame_height,
+                width,
+                height,
+                self.x_scaling,
+                self.y_scaling,
+                self.framing,
+            )
+        };
+    }
+
+    // The column left of the board, as tall as the board's rows
+    fn pace_rect(&self) -> Option<Rect> {
+        let (_, rows) = self.board_size();
+        Some(Rect::new(
+            self.rect.x.checked_sub(1)?,
+            self.rect.y + 1,
+            1,
+            rows * self.y_scaling,
+        ))
+    }
+
+    // Whether the board fits in the terminal, nothing but a warning is drawn otherwise.
+    // The hud makes do with the room there is, see layout().
+    pub fn fits(&self) -> bool {
+        self.board_fits()
+    }
+
+    // The size of the terminal
+    pub fn frame(&self) -> (u16, u16) {
+        self.frame
+    }
+
+    fn board_fits(&self) -> bool {
+        self.rect.right() <= self.frame.0 && self.rect.bottom() <= self.frame.1
+    }
+
+    fn required_size(&self) -> (u16, u16) {
+        let (width, height) = self.board_size();
+        Self::smallest_frame(
+            width,
+            height,
+            self.x_scaling,
+            self.y_scaling,
+            self.framing,
+            Self::board_fits,
+        )
+    }
+
+    // In cells, not terminal columns and rows
+    fn board_size(&self) -> (u16, u16) {
+        (
+            (self.rect.width - 2 - 2 * self.framing.padding()) / self.x_scaling,
+            (self.rect.height - 2 - self.framing.floor()) / self.y_scaling,
+        )
+    }
+
+    // Terminal position of the board's top left cell
+    fn board_origin(&self) -> (u16, u16) {
+        (self.rect.x + 1 + self.framing.padding(), self.rect.y + 1)
+    }
+
+    // The boxes of the hud in a column beside the board, from the top, as long as
+    // they all fit in the terminal
+    fn stack(&self, panels: &[Panel]) -> Option<Vec<PanelBox>> {
+        let width = 5 * self.x_scaling;
+        // With a gap on the terminal's side too
+        let left = self
+            .rect
+            .x
+            .checked_sub(2 * self.x_scaling + width)
+            .map(|x| x + self.x_scaling);
+        let right = self.rect.right() + self.x_scaling;
+        let mut boxes: Vec<PanelBox> = Vec::new();
+        let mut tops = [self.rect.y; 2];
+        for &panel in panels {
+            let on_left = match self.hud.sidebar {
+                Sidebar::Left => true,
+                Sidebar::Right => false,
+                Sidebar::Both => matches!(panel, Panel::Hold | Panel::Clear),
+            };
+            let x = if on_left { left? } else { right };
+            let top = &mut tops[usize::from(on_left)];
+            // Score, level and lines right after one another share a box
+            if let Some(last) = boxes.last_mut().filter(|last| last.rect.x == x) {
+                if panel.is_stat() && last.panels.iter().all(|panel| panel.is_stat()) {
+                    last.panels.push(panel);
+                    last.rect.height += 2;
+                    *top += 2;
+                    continue;
+                }
+            }
+            let height = match panel {
+                Panel::Next | Panel::Hold => 5 * self.y_scaling,
+                Panel::Score | Panel::Level | Panel::Lines | Panel::Time | Panel::Clear => 4,
+            };
+            let rect = Rect {
+                x,
+                y: *top,
+                width,
+                height,
+            };
+            *top = rect.bottom() + self.y_scaling;
+            boxes.push(PanelBox {
+                panels: vec![panel],
+                rect,
+            });
+        }
+        let (frame_width, frame_height) = self.frame;
+        boxes
+            .iter()
+            .all(|panel| panel.rect.right() <= frame_width && panel.rect.bottom() <= frame_height)
+            .then_some(boxes)
+    }
+
+    // Where the hud goes. In a terminal too small for all of it, each panel in turn
+    // is kept if it still fits with the ones before it, so the last ones go first.
+    fn layout(&self) -> Vec<PanelBox> {
+        let mut shown = Vec::new();
+        for &panel in &self.hud.panels {
+            shown.push(panel);
+            if self.stack(&shown).is_none() {
+                shown.pop();
+            }
+        }
+        self.stack(&shown).unwrap_or_default()
+    }
+
+    // None when the panel is hidden, or left out for lack of room
+    fn panel_rect(&self, panel: Panel) -> Option<Rect> {
+        self.layout()
+            .into_iter()
+            .find(|panels| panels.panels.contains(&panel))
+            .map(|panels| panels.rect)
+    }
+
+    fn preview_rect(&self) -> Option<Rect> {
+        self.panel_rect(Panel::Next)
+    }
+
+    fn hold_rect(&self) -> Option<Rect> {
+        self.panel_rect(Panel::Hold)
+    }
+
+    // Centered on the board, wider than it when needed to fit the high score table
+    fn overlay_rect(&self, lines: u16) -> Rect {
+        let width = self.bounds().width.min(34).max(self.rect.width - 2);
+        let height = (self.rect.height - 2).min(lines + 2);
+        Rect {
+            x: (self.rect.x + self.rect.width / 2).saturating_sub(width / 2),
+            y: self.rect.y + (self.rect.height - height) / 2,
+            width,
+            height,
+        }
+    }
+
+    // Area taken by the playfield and everything drawn around it
+    pub fn bounds(&self) -> Rect {
+        self.layout()
+            .iter()
+            .fold(self.rect, |bounds, panels| bounds.union(panels.rect))
+    }
+
+    // Every box gets the same border
+    fn block(&self) -> Block<'static> {
+        Block::default()
+            .borders(Borders::ALL)
+            .border_type(self.border_type)
+            .border_style(Style::default().fg(self.palette.border))
+    }
+
+    // The held tetromino's letter, dimmed in the middle of the preview's top border,
+    // for what holding would bring in instead of the next one
+    fn draw_hold_hint(&self, buffer: &mut Buffer, preview: Rect, game: &Game) {
+        let Some(held) = game.held() else {
+            return;
+        };
+        let mut style = Style::default().add_modifier(Modifier::DIM);
+        if !self.glyphs_only {
+            style = style.fg(self.palette.piece(held.shape()));
+        }
+        buffer
+            .get_mut(preview.x + preview.width / 2, preview.y)
+            .set_char(held.shape())
+            .set_style(style);
+    }
+
+    // Draws an unspawned tetromino centered inside the borders of `area`
+    fn draw_tetromino(&self, buffer: &mut Buffer, area: Rect, tetromino: &Tetromino) {
+        let body = tetromino.body();
+        let width = body.iter().map(|(x, _)| x + 1).max().unwrap_or(0) as u16;
+        let height = body.iter().map(|(_, y)| y + 1).max().unwrap_or(0) as u16;
+        let x_offset = area.x + 1 + (area.width - 2).saturating_sub(width * self.x_scaling) / 2;
+        let y_offset = area.y + 1 + (area.height - 2).saturating_sub(height * self.y_scaling) / 2;
+        for (x, y) in body {
+            for dx in 0..self.x_scaling {
+                for dy in 0..self.y_scaling {
+                    let cell = buffer.get_mut(
+                        x_offset + x as u16 * self.x_scaling + dx,
+                        y_offset + y as u16 * self.y_scaling + dy,
+                    );
+                    if self.glyphs_only {
+                        cell.set_char(tetromino.shape());
+                    } else {
+                        self.fill(cell, self.palette.piece(tetromino.shape()));
+                    }
+                }
+            }
+        }
+    }
+
+    // With the skin's block glyph in the color if it has one
+    fn fill(&self, cell: &mut Cell, color: Color) {
+        match self.glyphs.block {
+            Some(block) => cell.set_char(block).set_fg(color),
+            None => cell.set_bg(color),
+        };
+    }
+
+    // Only drawn on free cells, so it never covers the tetromino it belongs to
+    fn draw_ghost(&self, buffer: &mut Buffer, game: &Game) {
+        let ghost = game.ghost();
+        let (left, top) = self.board_origin();
+        for (x, y) in ghost.body() {
+            if game.board().tiles[y][x].is_some() {
+                continue;
+            }
+            for dx in 0..self.x_scaling {
+                for dy in 0..self.y_scaling {
+                    let cell = buffer.get_mut(
+                        left + x as u16 * self.x_scaling + dx,
+                        top + y as u16 * self.y_scaling + dy,
+                    );
+                    cell.set_char(self.glyphs.ghost);
+                    if !self.glyphs_only {
+                        cell.set_fg(self.palette.piece(ghost.shape()));
+                    }
+                }
+            }
+        }
     }
-    pub fn draw<B: Backend>(&self, terminal: &mut Terminal<B>) {
-        const _BLOCK: char = '\u{2588}';
-        let playcells = &self.tiles;
 
-        let mut buffer = Buffer::empty(self.rect);
-        for y in 0..playcells.len() * usize::from(self.y_scaling) {
-            for x in 0..playcells[0].len() * usize::from(self.x_scaling) {
-                let cell = buffer.get_mut(x as u16 + 1 + self.rect.x, y as u16 + 1 + self.rect.y);
-                if let Some(color) =
-                    &playcells[y / usize::from(self.y_scaling)][x / usize::from(self.x_scaling)]
-                {
-                    cell.set_bg(color.color);
+    // Kicks that didn't fit are outlined in red where they're on the board, the one
+    // taken turns green, and its number goes on the top border
+    fn draw_kicks(&self, buffer: &mut Buffer, kicks: &[KickAttempt]) {
+        const OUTLINE: [char; 2] = ['[', ']'];
+        let (left, top) = self.board_origin();
+        let (width, height) = self.board_size();
+        let on_board = |(x, y): (i32, i32)| {
+            let x = u16::try_from(x).ok().filter(|x| *x < width)?;
+            let y = u16::try_from(y).ok().filter(|y| *y < height)?;
+            Some((left + x * self.x_scaling, top + y * self.y_scaling))
+        };
+        for attempt in kicks {
+            for (x, y) in attempt.cells.into_iter().filter_map(on_board) {
+                for dx in 0..self.x_scaling {
+                    for dy in 0..self.y_scaling {
+                        let cell = buffer.get_mut(x + dx, y + dy);
+                        match attempt.failure {
+                            Some(_) => {
+                                let edge = usize::from(dx > 0 && dx + 1 == self.x_scaling);
+                                cell.set_char(OUTLINE[edge]);
+                                if !self.glyphs_only {
+                                    cell.set_fg(Color::Red);
+                                }
+                            }
+                            None if !self.glyphs_only => {
+                                cell.set_bg(Color::Green);
+                            }
+                            None => {}
+                        }
+                    }
                 }
             }
         }
+        let label = match kicks.last() {
+            Some(attempt) if attempt.failure.is_none() => format!(" kick {} ", attempt.index + 1),
+            _ => String::from(" no kick "),
+        };
+        let x = self.rect.right().saturating_sub(label.len() as u16 + 1);
+        buffer.set_string(x, self.rect.y, label, Style::default());
+    }
 
-        terminal.current_buffer_mut().merge(&buffer);
+    fn draw_too_small<B: Backend>(&self, terminal: &mut Terminal<B>) {
+        let (min_width, min_height) = self.required_size();
         terminal
             .draw(|f| {
-                // let chunks = Layout::default()
-                //     .direction(Direction::Horizontal)
-                //     .constraints([
-                //                  Constraint::Length(5),
-                //                  Constraint::Min(0),
-                //     ]
-                //     .as_ref(),
-                //     ).split(Rect { x: self.rect.x, y: self.rect.y, width: self.rect.width *2 , height: self.rect.height});
-                //
-                let block = Block::default().borders(Borders::ALL);
-                f.render_widget(block, self.rect);
-                let block2 = Block::default().borders(Borders::ALL);
-                f.render_widget(block2, Rect { x: self.rect.x + self.rect.width + self.x_scaling, y: self.rect.y, width: 5 * self.x_scaling, height: 5 * self.y_scaling })
+                let size = f.size();
+                let message = Paragraph::new(format!(
+                    "terminal too small, need at least {min_width}x{min_height}"
+                ))
+                .alignment(Alignment::Center)
+                .wrap(Wrap { trim: true });
+                let area = Rect {
+                    y: size.height.saturating_sub(1) / 2,
+                    height: size.height.min(2),
+                    ..size
+                };
+                f.render_widget(message, area);
             })
             .unwrap();
     }
+
+    pub fn widget<'a>(
+        &'a self,
+        game: &'a Game,
+        kicks: Option<&'a [KickAttempt]>,
+    ) -> PlayfieldWidget<'a> {
+        PlayfieldWidget {
+            playfield: self,
+            game,
+            kicks,
+        }
+    }
+
+    pub fn draw<B: Backend>(
+        &self,
+        terminal: &mut Terminal<B>,
+        game: &Game,
+        // Name of the last line clear worth mentioning, while it's still shown
+        clear: Option<&str>,
+        // The kicks of the last rotation, with --debug-kicks
+        kicks: Option<&[KickAttempt]>,
+        game_over: Option<&GameOver>,
+        overlay: Option<&FrameStats>,
+    ) {
+        if !self.fits() {
+            self.draw_too_small(terminal);
+            return;
+        }
+        terminal
+            .draw(|f| self.render(f, game, clear, kicks, game_over, overlay))
+            .unwrap();
+    }
+
+    // What draw() and draw_scrub() have in common
+    fn render<B: Backend>(
+        &self,
+        f: &mut Frame<B>,
+        game: &Game,
+        clear: Option<&str>,
+        kicks: Option<&[KickAttempt]>,
+        game_over: Option<&GameOver>,
+        overlay: Option<&FrameStats>,
+    ) {
+        f.render_widget(self.widget(game, kicks), self.bounds());
+        let score = game.score();
+        let lines = match game.rules().line_goal {
+            Some(goal) => format!("{}/{goal}", score.lines),
+            None => score.lines.to_string(),
+        };
+        // A sprint counts up, an ultra counts down
+        let clock = match game.time_left() {
+            Some(left) => Some(format_countdown(left)),
+            None if game.rules().line_goal.is_some() => Some(format_time(game.played())),
+            None => None,
+        };
+        for PanelBox { panels, rect } in self.layout() {
+            match panels[0] {
+                // Drawn by the widget
+                Panel::Next | Panel::Hold => {}
+                Panel::Time => {
+                    if let Some(clock) = &clock {
+                        f.render_widget(
+                            Paragraph::new(format!("Time\n{clock}")).block(self.block()),
+                            rect,
+                        );
+                    }
+                }
+                Panel::Clear => {
+                    if let Some(clear) = clear {
+                        f.render_widget(
+                            Paragraph::new(clear.replace(' ', "\n")).alignment(Alignment::Center),
+                            rect,
+                        );
+                    }
+                }
+                Panel::Score | Panel::Level | Panel::Lines => {
+                    let text: Vec<String> = panels
+                        .iter()
+                        .map(|panel| match panel {
+                            Panel::Score => format!("Score\n{}", score.points),
+                            // With the adaptive rules, how far the speed is off it
+                            Panel::Level => match score.speed_level() as i64 - score.level as i64 {
+                                0 => format!("Level\n{}", score.level),
+                                off => format!("Level\n{}{off:+}", score.level),
+                            },
+                            _ => format!("Lines\n{lines}"),
+                        })
+                        .collect();
+                    f.render_widget(Paragraph::new(text.join("\n")).block(self.block()), rect);
+                }
+            }
+        }
+        if let (Some(goal), Some(rect)) = (game.rules().line_goal, self.pace_rect()) {
+            let pace = PaceBar {
+                goal,
+                lines: score.lines,
+                best: self.pace.as_ref().map(|best| best.lines_at(game.played())),
+                palette: self.palette,
+                glyphs_only: self.glyphs_only,
+            };
+            f.render_widget(pace, rect);
+        }
+        if game.is_paused() {
+            // The whole board goes, it can't be studied while the clock is stopped
+            let board = self.rect.inner(&Margin {
+                horizontal: 1,
+                vertical: 1,
+            });
+            f.render_widget(Clear, board);
+            let message = Rect {
+                y: board.y + board.height / 2,
+                height: 2,
+                ..board
+            };
+            let text = if self.idle_paused {
+                "PAUSED\nauto-paused (idle)"
+            } else {
+                "PAUSED"
+            };
+            f.render_widget(Paragraph::new(text).alignment(Alignment::Center), message);
+        }
+        if let Some(game_over) = game_over {
+            let text = Self::game_over_text(game, game_over);
+            let overlay = self.overlay_rect(text.height() as u16);
+            f.render_widget(Clear, overlay);
+            f.render_widget(
+                Paragraph::new(text)
+                    .alignment(Alignment::Center)
+                    .block(self.block()),
+                overlay,
+            );
+        }
+        if let Some(stats) = overlay {
+            f.render_widget(
+                stats.widget(),
+                Rect::new(0, 0, 20, 3).intersection(f.size()),
+            );
+        }
+    }
+
+    // The game as --scrub has got to, with where along the replay in the top row and
+    // the timeline of scrub::Scrubber, with a ^ under the current input, in the bottom two
+    pub fn draw_scrub<B: Backend>(
+        &self,
+        terminal: &mut Terminal<B>,
+        game: &Game,
+        status: &str,
+        (timeline, cursor): (String, usize),
+    ) {
+        if !self.fits() {
+            self.draw_too_small(terminal);
+            return;
+        }
+        terminal
+            .draw(|f| {
+                self.render(f, game, None, None, None, None);
+                let size = f.size();
+                let row = |y| Rect::new(1, y, size.width.saturating_sub(2), 1);
+                f.render_widget(Paragraph::new(status), row(0));
+                let bottom = size.height.saturating_sub(2);
+                f.render_widget(Paragraph::new(timeline), row(bottom));
+                let cursor = format!("{:>1$}", "^", cursor + 1);
+                f.render_widget(Paragraph::new(cursor), row(bottom + 1));
+            })
+            .unwrap();
+    }
+
+    // The modes to choose from before a game, the built-in ones and then those from
+    // the config file, with the selected one highlighted. Replays and Settings come
+    // after them, Replays is selected when `selected` is the number of modes. The banner goes along the
+    // bottom row of the terminal, for a warning the player should see once.
+    pub fn draw_menu<B: Backend>(
+        &self,
+        terminal: &mut Terminal<B>,
+        modes: &[Ruleset],
+        selected: usize,
+        banner: Option<&str>,
+    ) {
+        if !self.fits() {
+            self.draw_too_small(terminal);
+            return;
+        }
+        let mut lines = vec![Spans::from("TETRS"), Spans::from("")];
+        let names = modes
+            .iter()
+            .map(|mode| mode.name.as_str())
+            .chain(["Replays", "Settings"]);
+        for (index, name) in names.enumerate() {
+            let line = format!("{name:<12}");
+            if index == selected {
+                let style = Style::default().add_modifier(Modifier::REVERSED);
+                lines.push(Spans::from(Span::styled(line, style)));
+            } else {
+                lines.push(Spans::from(line));
+            }
+        }
+        lines.push(Spans::from(""));
+        lines.push(Spans::from(match modes.get(selected) {
+            Some(mode) => mode.description.as_str(),
+            None if selected == modes.len() => "Watch a saved replay again",
+            None => "Handling, keys and looks",
+        }));
+        lines.push(Spans::from(""));
+        lines.push(Spans::from("enter  play   q  quit"));
+        let text = Text::from(lines);
+        terminal
+            .draw(|f| {
+                f.render_widget(self.block(), self.rect);
+                let menu = self.overlay_rect(text.height() as u16);
+                f.render_widget(Clear, menu);
+                f.render_widget(
+                    Paragraph::new(text)
+                        .alignment(Alignment::Center)
+                        .block(self.block()),
+                    menu,
+                );
+                if let Some(banner) = banner {
+                    let size = f.size();
+                    let row = Rect::new(0, size.height.saturating_sub(1), size.width, 1);
+                    let style = Style::default().add_modifier(Modifier::BOLD);
+                    f.render_widget(
+                        Paragraph::new(Span::styled(banner, style)).alignment(Alignment::Center),
+                        row,
+                    );
+                }
+            })
+            .unwrap();
+    }
+
+    // The saved replays, see browser.rs, over the whole of the playfield with what
+    // the keys do under them. The state scrolls the list to keep the selected one
+    // in view.
+    pub fn draw_replays<B: Backend>(
+        &self,
+        terminal: &mut Terminal<B>,
+        lines: &[String],
+        state: &mut ListState,
+        status: &str,
+    ) {
+        let list = ListScreen {
+            title: "Replays",
+            empty: "no saved replays yet",
+            lines,
+            marked: None,
+        };
+        self.draw_list(terminal, list, state, status);
+    }
+
+    // The settings screen, see settings.rs, drawn like the replays. The marked one
+    // didn't pass the last apply, the status says why.
+    pub fn draw_settings<B: Backend>(
+        &self,
+        terminal: &mut Terminal<B>,
+        lines: &[String],
+        state: &mut ListState,
+        status: &str,
+        marked: Option<usize>,
+    ) {
+        let list = ListScreen {
+            title: "Settings",
+            empty: "",
+            lines,
+            marked,
+        };
+        self.draw_list(terminal, list, state, status);
+    }
+
+    fn draw_list<B: Backend>(
+        &self,
+        terminal: &mut Terminal<B>,
+        list: ListScreen,
+        state: &mut ListState,
+        status: &str,
+    ) {
+        let ListScreen {
+            title,
+            empty,
+            lines,
+            marked,
+        } = list;
+        if !self.fits() {
+            self.draw_too_small(terminal);
+            return;
+        }
+        let bounds = self.bounds();
+        // Free to go past the box on the right
+        let status_row = Rect {
+            y: bounds.bottom().saturating_sub(1),
+            width: self.frame.0.saturating_sub(bounds.x),
+            height: 1,
+            ..bounds
+        };
+        let area = Rect {
+            height: bounds.height.saturating_sub(1),
+            ..bounds
+        };
+        terminal
+            .draw(|f| {
+                let block = self.block().title(title);
+                if lines.is_empty() {
+                    let message = Paragraph::new(empty)
+                        .alignment(Alignment::Center)
+                        .block(block);
+                    f.render_widget(message, area);
+                } else {
+                    // Bold as well, for terminals without color
+                    let marked_style = Style::default().fg(Color::Red).add_modifier(Modifier::BOLD);
+                    let items: Vec<ListItem> = lines
+                        .iter()
+                        .enumerate()
+                        .map(|(index, line)| match marked {
+                            Some(marked) if marked == index => {
+                                ListItem::new(line.as_str()).style(marked_style)
+                            }
+                            _ => ListItem::new(line.as_str()),
+                        })
+                        .collect();
+                    let list = List::new(items)
+                        .block(block)
+                        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+                    f.render_stateful_widget(list, area, state);
+                }
+                f.render_widget(Paragraph::new(status), status_row);
+            })
+            .unwrap();
+    }
+
+    fn game_over_text<'a>(game: &Game, game_over: &GameOver<'a>) -> Text<'a> {
+        let results = game_over.results;
+        let score = results.score;
+        let time = Duration::from_millis(results.time_ms);
+        let rules = game.rules();
+        let (heading, result) = match (rules.line_goal, results.completed) {
+            // Whatever the mode, a sequence that ends can run out before its goal
+            (_, true) if game.next().is_none() => {
+                ("SEQUENCE COMPLETE", format!("Score {}", score.points))
+            }
+            (Some(_), true) => ("FINISHED", format!("Time {}", format_time(time))),
+            (None, true) => ("TIME'S UP", format!("Score {}", score.points)),
+            (Some(goal), false) => ("GAME OVER", format!("Lines {}/{goal}", score.lines)),
+            (None, false) => ("GAME OVER", format!("Score {}", score.points)),
+        };
+        let mut lines = vec![
+            Spans::from(heading),
+            Spans::from(""),
+            Spans::from(result),
+            Spans::from(format!("{} pieces, {:.2} PPS", results.pieces, results.pps)),
+            Spans::from(format!(
+                "Gravity {:.2} rows/s",
+                game.score().gravity_speed()
+            )),
+            Spans::from(format!("Seed {}", results.seed)),
+        ];
+        if let Some(challenge) = &game_over.challenge {
+            lines.push(Spans::from(format!("Challenge {challenge}")));
+        }
+        if game_over.other_handling {
+            lines.push(Spans::from("(with other handling than the challenge)"));
+        }
+        lines.push(Spans::from(""));
+        let ranking = game_over.high_scores.ranking();
+        let entries = game_over.high_scores.entries();
+        if !entries.is_empty() {
+            lines.push(Spans::from(match ranking {
+                Ranking::Points => format!(
+                    "{:>2} {:<12} {:>7} {:>5} {:>2}",
+                    "", "name", "score", "lines", "lv"
+                ),
+                Ranking::Time => format!("{:>2} {:<12} {:>7} {:>7}", "", "name", "time", "score"),
+            }));
+        }
+        for (rank, entry) in entries.iter().enumerate() {
+            let name = if entry.name.is_empty() {
+                "-"
+            } else {
+                &entry.name
+            };
+            // In the space after the rank, there's no room for more
+            let mark = match game_over.unverified.get(rank) {
+                Some(true) => '?',
+                _ => ' ',
+            };
+            let line = match ranking {
+                Ranking::Points => format!(
+                    "{:>2}{mark}{:<12} {:>7} {:>5} {:>2}",
+                    rank + 1,
+                    name,
+                    entry.points,
+                    entry.lines,
+                    entry.level
+                ),
+                Ranking::Time => format!(
+                    "{:>2}{mark}{:<12} {:>7} {:>7}",
+                    rank + 1,
+                    name,
+                    format_time(entry.time),
+                    entry.points
+                ),
+            };
+            if game_over.rank == Some(rank) {
+                let style = Style::default().add_modifier(Modifier::REVERSED);
+                lines.push(Spans::from(Span::styled(line, style)));
+            } else {
+                lines.push(Spans::from(line));
+            }
+        }
+        if game_over.unverified.iter().any(|&unverified| unverified) {
+            lines.push(Spans::from("? replay missing or altered"));
+        }
+        if !entries.is_empty() {
+            lines.push(Spans::from(""));
+        }
+        match game_over.name {
+            Some(name) => {
+                lines.push(Spans::from("New high score!"));
+                lines.push(Spans::from(format!("name: {name}_")));
+            }
+            None => {
+                if let Some(err) = game_over.save_error {
+                    lines.push(Spans::from("the table couldn't be saved:"));
+                    lines.push(Spans::from(err));
+                }
+                match game_over.replay_save {
+                    Some(ReplaySave::Ask) => {
+                        lines.push(Spans::from("Save replay? [y/n]"));
+                        return Text::from(lines);
+                    }
+                    Some(ReplaySave::Naming(name)) => {
+                        lines.push(Spans::from("replay name (optional):"));
+                        lines.push(Spans::from(format!("{name}_")));
+                        return Text::from(lines);
+                    }
+                    Some(ReplaySave::Saved) => lines.push(Spans::from("replay saved")),
+                    Some(ReplaySave::Failed(err)) => {
+                        lines.push(Spans::from("the replay couldn't be saved:"));
+                        lines.push(Spans::from(err.as_str()));
+                    }
+                    None => {}
+                }
+                lines.push(Spans::from("n  new game   q  quit"));
+            }
+        }
+        Text::from(lines)
+    }
+}
+
+// The board with its stack, ghost and tetromino, and the preview and hold boxes around
+// it. Everything is rendered again on every frame, tui compares the result with the
+// last frame and only writes the cells that changed, the borders only go out again
+// after a resize clears the screen. Drawn at the playfield's own position whatever
+// the area, which has to hold bounds().
+pub struct PlayfieldWidget<'a> {
+    playfield: &'a Playfield,
+    game: &'a Game,
+    kicks: Option<&'a [KickAttempt]>,
+}
+
+impl Widget for PlayfieldWidget<'_> {
+    fn render(self, _area: Rect, buffer: &mut Buffer) {
+        const FLOOR: char = '\u{2580}';
+        let Self {
+            playfield,
+            game,
+            kicks,
+        } = self;
+        playfield.block().render(playfield.rect, buffer);
+        if let Some(preview) = playfield.preview_rect() {
+            match game.next() {
+                Some(next) => {
+                    playfield.block().render(preview, buffer);
+                    playfield.draw_tetromino(buffer, preview, &next);
+                    if playfield.hold_hint && game.can_hold() {
+                        playfield.draw_hold_hint(buffer, preview, game);
+                    }
+                }
+                // The sequence is over, the preview is greyed out rather than left blank
+                None => playfield
+                    .block()
+                    .border_style(Style::default().add_modifier(Modifier::DIM))
+                    .render(preview, buffer),
+            }
+        }
+        if let Some(hold) = playfield.hold_rect() {
+            let block = match game.holds_left() {
+                Some(left) => playfield.block().title(format!("{left} left")),
+                None => playfield.block(),
+            };
+            block.render(hold, buffer);
+            if let Some(held) = &game.held() {
+                playfield.draw_tetromino(buffer, hold, held);
+            }
+        }
+        // Nothing is going to land once the game is over
+        if playfield.ghost && game.rules().ghost && !game.is_over() {
+            playfield.draw_ghost(buffer, game);
+        }
+        let playcells = &game.board().tiles;
+        let (x_scaling, y_scaling) = (playfield.x_scaling, playfield.y_scaling);
+        let (left, top) = playfield.board_origin();
+        if playfield.framing.floor {
+            let y = top + playcells.len() as u16 * y_scaling;
+            for x in left..left + playcells[0].len() as u16 * x_scaling {
+                let cell = buffer.get_mut(x, y).set_char(FLOOR);
+                if !playfield.glyphs_only {
+                    cell.set_fg(playfield.palette.floor);
+                }
+            }
+        }
+        for y in 0..playcells.len() * usize::from(y_scaling) {
+            for x in 0..playcells[0].len() * usize::from(x_scaling) {
+                let cell = buffer.get_mut(x as u16 + left, y as u16 + top);
+                let row = y / usize::from(y_scaling);
+                if let Some(color) = &playcells[row][x / usize::from(x_scaling)] {
+                    // Full rows flash before they're cleared
+                    if game.clearing_rows().contains(&row) {
+                        if playfield.glyphs_only {
+                            cell.set_char(playfield.glyphs.clearing);
+                        } else {
+                            playfield.fill(cell, playfield.palette.clearing);
+                        }
+                    } else if playfield.glyphs_only {
+                        cell.set_char(color.glyph());
+                    } else {
+                        playfield.fill(cell, playfield.palette.piece(color.shape));
+                    }
+                }
+            }
+        }
+        if let Some(kicks) = kicks {
+            playfield.draw_kicks(buffer, kicks);
+        }
+    }
+}
+
+// Minutes, seconds and hundredths, as in 1:23.45
+pub fn format_time(time: Duration) -> String {
+    let hundredths = time.as_millis() / 10;
+    format!(
+        "{}:{:02}.{:02}",
+        hundredths / 6000,
+        hundredths / 100 % 60,
+        hundredths % 100
+    )
+}
+
+// Whole seconds rounded up, so that 0:00 only shows once the time is up
+fn format_countdown(left: Duration) -> String {
+    let seconds = left.as_millis().div_ceil(1000);
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+// Debug overlay for --show-frametime, events are counted over the last second
+pub struct FrameStats {
+    render_time: Duration,
+    input_latency: Option<Duration>,
+    events: VecDeque<Instant>,
+}
+
+impl FrameStats {
+    const WINDOW: Duration = Duration::from_secs(1);
+
+    pub fn new() -> Self {
+        Self {
+            render_time: Duration::ZERO,
+            input_latency: None,
+            events: VecDeque::new(),
+        }
+    }
+
+    // To be called right after the frame that began at `started` got drawn
+    pub fn record_frame(&mut self, started: Instant, input_received: Option<Instant>) {
+        self.render_time = started.elapsed();
+        if let Some(received) = input_received {
+            self.input_latency = Some(received.elapsed());
+        }
+    }
+
+    pub fn record_event(&mut self, received: Instant) {
+        self.events.push_back(received);
+        while let Some(oldest) = self.events.front() {
+            if oldest.elapsed() <= Self::WINDOW {
+                break;
+            }
+            self.events.pop_front();
+        }
+    }
+
+    fn widget(&self) -> Paragraph<'static> {
+        let events = self
+            .events
+            .iter()
+            .filter(|event| event.elapsed() <= Self::WINDOW)
+            .count();
+        let latency = match self.input_latency {
+            Some(latency) => format!("{:.2}ms", latency.as_secs_f64() * 1000.0),
+            None => String::from("-"),
+        };
+        Paragraph::new(format!(
+            "frame  {:.2}ms\ninput  {}\nevents {}/s",
+            self.render_time.as_secs_f64() * 1000.0,
+            latency,
+            events
+        ))
+    }
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The window title for a game in progress: the level of a marathon, the lines of a
+// sprint and the time left in an ultra, and the same for custom modes with a goal
+pub fn window_title(game: &Game) -> String {
+    let score = game.score();
+    let name = game.rules().name.to_lowercase();
+    let mut title = match (game.rules().line_goal, game.time_left()) {
+        (Some(goal), _) => format!("tetrs - {name} {}/{goal} lines", score.lines.min(goal)),
+        (None, Some(left)) => format!("tetrs - {name} {} left", format_countdown(left)),
+        (None, None) => format!("tetrs - level {}", score.level),
+    };
+    if game.is_paused() {
+        title.push_str(" - paused");
+    }
+    title
+}
+
+// Keeps the terminal window title in sync with the game, changes are written at most
+// once per second. A change that comes in sooner is held back until the second is up,
+// so the last one always makes it. Disabled instances never write anything, for
+// terminals that show the escape sequence instead.
+pub struct TitleUpdater {
+    enabled: bool,
+    current: String,
+    pending: Option<String>,
+    last_update: Option<Instant>,
+}
+
+impl TitleUpdater {
+    const MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            current: String::new(),
+            pending: None,
+            last_update: None,
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn update<W: Write>(&mut self, out: &mut W, title: &str, now: Instant) -> io::Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        self.pending = (self.current != title).then(|| title.to_string());
+        self.flush(out, now)
+    }
+
+    // Writes the title held back, if its time has come
+    pub fn flush<W: Write>(&mut self, out: &mut W, now: Instant) -> io::Result<()> {
+        if self.deadline().is_some_and(|deadline| now < deadline) {
+            return Ok(());
+        }
+        let Some(title) = self.pending.take() else {
+            return Ok(());
+        };
+        execute!(out, SetTitle(&title))?;
+        self.current = title;
+        self.last_update = Some(now);
+        Ok(())
+    }
+
+    // When the title held back can be written, None when there's nothing to write
+    pub fn deadline(&self) -> Option<Instant> {
+        self.pending.as_ref()?;
+        Some(self.last_update? + Self::MIN_INTERVAL)
+    }
+
+    // The original title can't be read back from the terminal, so it's cleared instead
+    pub fn restore<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        if self.enabled && self.last_update.is_some() {
+            execute!(out, SetTitle(""))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tetris::{
+        game::{KickFailure, Playcell, SequenceEnd, TetrominosBag},
+        state::{Game, GameMode, Hold, Input},
+    };
+    use tui::backend::TestBackend;
+
+    use super::*;
+
+    // The O on the floor, the I in hold and the J falling with the L up next
+    fn game() -> Game {
+        let mut game = Game::with_bag(10, 20, TetrominosBag::new());
+        game.apply(Input::HardDrop);
+        game.apply(Input::Hold);
+        game
+    }
+
+    fn draw(playfield: &Playfield, game: &Game) -> Buffer {
+        let (width, height) = playfield.frame;
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        playfield.draw(&mut terminal, game, None, None, None, None);
+        terminal.backend().buffer().clone()
+    }
+
+    fn text(buffer: &Buffer, area: Rect) -> Vec<String> {
+        (area.top()..area.bottom())
+            .map(|y| {
+                (area.left()..area.right())
+                    .map(|x| buffer.get(x, y).symbol.as_str())
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn inside(area: Rect) -> Rect {
+        area.inner(&Margin {
+            horizontal: 1,
+            vertical: 1,
+        })
+    }
+
+    #[test]
+    fn monochrome_terminals_get_the_piece_letters() {
+        let mut playfield = Playfield::new(80, 30, 10, 20, 2, 1, Framing::default());
+        playfield.glyphs_only = true;
+        let buffer = draw(&playfield, &game());
+        let mut board = vec!["                    "; 20];
+        board[0] = "        JJ          ";
+        board[1] = "        JJJJJJ      ";
+        board[16] = "        \u{2591}\u{2591}          ";
+        board[17] = "        \u{2591}\u{2591}\u{2591}\u{2591}\u{2591}\u{2591}      ";
+        board[18] = "        OOOO        ";
+        board[19] = "        OOOO        ";
+        assert_eq!(text(&buffer, inside(playfield.rect)), board);
+        assert_eq!(
+            text(&buffer, inside(playfield.preview_rect().unwrap())),
+            ["     LL ", " LLLLLL ", "        "]
+        );
+        assert_eq!(
+            text(&buffer, inside(playfield.hold_rect().unwrap())),
+            ["        ", "IIIIIIII", "        "]
+        );
+        assert_eq!(Playcell::new(false, 'G').glyph(), '#');
+    }
+
+    #[test]
+    fn the_hold_hint_shows_the_held_letter_over_the_preview_while_it_can_be_swapped() {
+        let mut playfield = Playfield::new(80, 30, 10, 20, 2, 1, Framing::default());
+        playfield.glyphs_only = true;
+        let preview = playfield.preview_rect().unwrap();
+        // The middle of the preview's top border
+        let hint = |playfield: &Playfield, game: &Game| {
+            let buffer = draw(playfield, game);
+            buffer.get(preview.x + preview.width / 2, preview.y).clone()
+        };
+        // The J locked, the I it held can come back out
+        let mut game = game();
+        game.apply(Input::HardDrop);
+        assert_eq!(hint(&playfield, &game).symbol, "\u{2500}");
+        playfield.hold_hint = true;
+        let cell = hint(&playfield, &game);
+        assert_eq!(cell.symbol, "I");
+        assert!(cell.modifier.contains(Modifier::DIM));
+        // Gone as soon as hold is used, it can't be again before the next lock
+        game.apply(Input::Hold);
+        assert_eq!(hint(&playfield, &game).symbol, "\u{2500}");
+        // And there's nothing to hint with nothing held yet
+        let fresh = Game::with_bag(10, 20, TetrominosBag::new());
+        assert_eq!(hint(&playfield, &fresh).symbol, "\u{2500}");
+    }
+
+    #[test]
+    fn the_menu_has_the_banner_along_the_bottom() {
+        let playfield = Playfield::new(80, 30, 10, 20, 2, 1, Framing::default());
+        let modes = [GameMode::Marathon.ruleset()];
+        let mut terminal = Terminal::new(TestBackend::new(80, 30)).unwrap();
+        let bottom = Rect::new(0, 29, 80, 1);
+        playfield.draw_menu(&mut terminal, &modes, 0, None);
+        assert_eq!(text(terminal.backend().buffer(), bottom)[0].trim(), "");
+        let banner = "running without persistence: --no-persist";
+        playfield.draw_menu(&mut terminal, &modes, 0, Some(banner));
+        assert_eq!(text(terminal.backend().buffer(), bottom)[0].trim(), banner);
+    }
+
+    #[test]
+    fn color_terminals_get_colored_cells_without_letters() {
+        let playfield = Playfield::new(80, 30, 10, 20, 2, 1, Framing::default());
+        let buffer = draw(&playfield, &game());
+        let board = inside(playfield.rect);
+        assert!(text(&buffer, board)
+            .concat()
+            .chars()
+            .all(|cell| cell == ' ' || cell == '\u{2591}'));
+        let cell = |x, y| buffer.get(board.x + x, board.y + y);
+        assert_eq!(cell(8, 19).bg, Color::Yellow);
+        assert_eq!(cell(13, 1).bg, Color::Blue);
+        assert_eq!(cell(8, 17).fg, Color::Blue);
+        assert_eq!(cell(0, 19).bg, Color::Reset);
+    }
+
+    #[test]
+    fn every_box_gets_the_border_style() {
+        for (border_type, glyphs) in [
+            (
+                BorderType::Plain,
+                "\u{250c}\u{2500}\u{2510}\u{2502}\u{2514}\u{2518}",
+            ),
+            (
+                BorderType::Rounded,
+                "\u{256d}\u{2500}\u{256e}\u{2502}\u{2570}\u{256f}",
+            ),
+            (
+                BorderType::Double,
+                "\u{2554}\u{2550}\u{2557}\u{2551}\u{255a}\u{255d}",
+            ),
+            (
+                BorderType::Thick,
+                "\u{250f}\u{2501}\u{2513}\u{2503}\u{2517}\u{251b}",
+            ),
+        ] {
+            let mut playfield = Playfield::new(80, 30, 10, 20, 2, 1, Framing::default());
+            playfield.border_type = border_type;
+            let buffer = draw(&playfield, &game());
+            let glyphs: Vec<String> = glyphs.chars().map(String::from).collect();
+            for area in [
+                playfield.rect,
+                playfield.preview_rect().unwrap(),
+                playfield.hold_rect().unwrap(),
+                playfield.panel_rect(Panel::Score).unwrap(),
+            ] {
+                let symbol = |x, y| buffer.get(x, y).symbol.clone();
+                let (right, bottom) = (area.right() - 1, area.bottom() - 1);
+                let found = [
+                    symbol(area.x, area.y),
+                    symbol(area.x + 1, area.y),
+                    symbol(right, area.y),
+                    symbol(area.x, area.y + 1),
+                    symbol(area.x, bottom),
+                    symbol(right, bottom),
+                ];
+                assert_eq!(found[..], glyphs[..], "{area:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn padding_keeps_a_blank_column_on_either_side_of_the_board() {
+        let plain = Playfield::new(80, 30, 10, 20, 2, 1, Framing::default());
+        let framing = Framing {
+            padding: true,
+            floor: false,
+        };
+        let padded = Playfield::new(80, 30, 10, 20, 2, 1, framing);
+        assert_eq!(padded.rect.width, plain.rect.width + 2);
+        assert_eq!(padded.board_size(), plain.board_size());
+        let text_of = |playfield: &Playfield| {
+            let mut playfield = playfield.clone();
+            playfield.glyphs_only = true;
+            text(&draw(&playfield, &game()), inside(playfield.rect))
+        };
+        let padded_rows: Vec<String> = text_of(&plain)
+            .iter()
+            .map(|row| format!(" {row} "))
+            .collect();
+        assert_eq!(text_of(&padded), padded_rows);
+    }
+
+    #[test]
+    fn the_floor_is_a_row_of_its_own_under_the_board() {
+        let plain = Playfield::new(80, 30, 10, 20, 2, 1, Framing::default());
+        let framing = Framing {
+            padding: false,
+            floor: true,
+        };
+        let floored = Playfield::new(80, 30, 10, 20, 2, 1, framing);
+        assert_eq!(floored.rect.height, plain.rect.height + 1);
+        assert_eq!(floored.board_size(), plain.board_size());
+        let buffer = draw(&floored, &game());
+        let rows = text(&buffer, inside(floored.rect));
+        assert_eq!(
+            rows[..20],
+            text(&draw(&plain, &game()), inside(plain.rect))[..]
+        );
+        assert_eq!(rows[20], "\u{2580}".repeat(20));
+        let floor = inside(floored.rect).bottom() - 1;
+        assert_eq!(buffer.get(floored.rect.x + 1, floor).fg, Color::DarkGray);
+        // The taller frame needs a taller terminal
+        let (_, plain_height) =
+            Playfield::required_terminal_size(10, 20, 2, 1, Framing::default(), &Hud::default());
+        let (_, height) = Playfield::required_terminal_size(10, 20, 2, 1, framing, &Hud::default());
+        assert_eq!(height, plain_height + 1);
+    }
+
+    #[test]
+    fn the_preview_is_greyed_out_once_a_sequence_has_nothing_left() {
+        let playfield = Playfield::new(80, 30, 10, 20, 2, 1, Framing::default());
+        let bag = TetrominosBag::scripted("OI", SequenceEnd::End).unwrap();
+        let mut game = Game::with_bag(10, 20, bag);
+        let preview = playfield.preview_rect().unwrap();
+        let corner = |buffer: &Buffer| buffer.get(preview.x, preview.y).clone();
+        let buffer = draw(&playfield, &game);
+        assert!(!corner(&buffer).modifier.contains(Modifier::DIM));
+        game.apply(Input::HardDrop);
+        let buffer = draw(&playfield, &game);
+        assert!(corner(&buffer).modifier.contains(Modifier::DIM));
+        assert!(text(&buffer, inside(preview))
+            .iter()
+            .all(|row| row.trim().is_empty()));
+    }
+
+    #[test]
+    fn the_ghost_can_be_turned_off() {
+        let mut playfield = Playfield::new(80, 30, 10, 20, 2, 1, Framing::default());
+        playfield.glyphs_only = true;
+        playfield.ghost = false;
+        let buffer = draw(&playfield, &game());
+        let board = text(&buffer, inside(playfield.rect));
+        assert!(board.iter().all(|row| !row.contains('\u{2591}')));
+        assert_eq!(board[18], "        OOOO        ");
+    }
+
+    #[test]
+    fn a_skin_block_is_drawn_in_the_pieces_color() {
+        let mut playfield = Playfield::new(80, 30, 10, 20, 2, 1, Framing::default());
+        playfield.glyphs.block = Some('\u{2593}');
+        playfield.glyphs.ghost = '.';
+        let buffer = draw(&playfield, &game());
+        let board = inside(playfield.rect);
+        let rows = text(&buffer, board);
+        assert_eq!(rows[16], "        ..          ");
+        assert_eq!(rows[19], "        \u{2593}\u{2593}\u{2593}\u{2593}        ");
+        let cell = buffer.get(board.x + 8, board.y + 19);
+        assert_eq!(cell.fg, playfield.palette.piece('O'));
+        assert_eq!(cell.bg, Color::Reset);
+    }
+
+    #[test]
+    fn the_default_hud_keeps_hold_and_clear_on_the_left() {
+        let playfield = Playfield::new(80, 30, 10, 20, 2, 1, Framing::default());
+        let rect = |panel| playfield.panel_rect(panel).unwrap();
+        assert_eq!(rect(Panel::Next), Rect::new(55, 6, 10, 5));
+        assert_eq!(rect(Panel::Hold), Rect::new(19, 6, 10, 5));
+        // Score, level and lines share a box
+        assert_eq!(rect(Panel::Score), Rect::new(55, 12, 10, 8));
+        assert_eq!(rect(Panel::Lines), rect(Panel::Score));
+        assert_eq!(rect(Panel::Time), Rect::new(55, 21, 10, 4));
+        assert_eq!(rect(Panel::Clear), Rect::new(19, 12, 10, 4));
+        assert_eq!(
+            Playfield::required_terminal_size(10, 20, 2, 1, Framing::default(), &Hud::default()),
+            (49, 25)
+        );
+    }
+
+    #[test]
+    fn the_hud_shows_its_panels_in_order_on_one_side() {
+        let mut playfield = Playfield::new(80, 30, 10, 20, 2, 1, Framing::default());
+        playfield.hud = Hud {
+            panels: vec![Panel::Hold, Panel::Lines, Panel::Next],
+            sidebar: Sidebar::Right,
+        };
+        assert_eq!(playfield.hold_rect(), Some(Rect::new(55, 6, 10, 5)));
+        assert_eq!(
+            playfield.panel_rect(Panel::Lines),
+            Some(Rect::new(55, 12, 10, 4))
+        );
+        assert_eq!(playfield.preview_rect(), Some(Rect::new(55, 17, 10, 5)));
+        assert_eq!(playfield.panel_rect(Panel::Score), None);
+        let buffer = draw(&playfield, &game());
+        assert_eq!(
+            text(&buffer, inside(Rect::new(55, 12, 10, 4))),
+            ["Lines   ", "0       "]
+        );
+        // Nothing is drawn on the left
+        assert!(text(&buffer, Rect::new(0, 6, 31, 20))
+            .iter()
+            .all(|row| row.trim().is_empty()));
+    }
+
+    #[test]
+    fn a_small_terminal_leaves_out_the_panels_that_dont_fit() {
+        let mut playfield = Playfield::new(47, 30, 10, 20, 2, 1, Framing::default());
+        assert!(playfield.fits());
+        // There's only room on the left
+        assert_eq!(playfield.preview_rect(), None);
+        assert_eq!(playfield.panel_rect(Panel::Score), None);
+        assert!(playfield.hold_rect().is_some());
+        assert!(playfield.panel_rect(Panel::Clear).is_some());
+        // All on the left, a terminal a row taller only leaves out the clear
+        playfield.hud.sidebar = Sidebar::Left;
+        playfield.resize(47, 31);
+        assert!(playfield.preview_rect().is_some());
+        assert!(playfield.panel_rect(Panel::Time).is_some());
+        assert_eq!(playfield.panel_rect(Panel::Clear), None);
+        playfield.resize(30, 30);
+        assert!(playfield.fits());
+        assert!(playfield.layout().is_empty());
+        playfield.resize(20, 30);
+        assert!(!playfield.fits());
+    }
+
+    #[test]
+    fn a_sprint_has_a_pace_bar_with_the_best_run_on_it() {
+        let mut playfield = Playfield::new(80, 30, 10, 20, 2, 1, Framing::default());
+        playfield.glyphs_only = true;
+        let bar = Rect::new(playfield.rect.x - 1, playfield.rect.y + 1, 1, 20);
+        let mut sprint = Game::with_mode(10, 20, TetrominosBag::new(), GameMode::Sprint);
+        sprint.load_stack("GGGG..GGGG\nGGGG..GGGG").unwrap();
+        sprint.apply(Input::HardDrop);
+        sprint.finish_clear();
+        sprint.elapse(Duration::from_secs(1));
+        let column =
+            |playfield: &Playfield, game: &Game| text(&draw(playfield, game), bar).concat();
+        // Without a best run only the lines so far, 2 of 40 make a row
+        assert_eq!(column(&playfield, &sprint), format!("{}#", "|".repeat(19)));
+        // The best run had 20 lines after a second, halfway up
+        let times = (1..=40)
+            .map(|line| Duration::from_millis(50 * line))
+            .collect();
+        playfield.pace = Some(Splits::new(times));
+        assert_eq!(
+            column(&playfield, &sprint),
+            format!("{}>{}#", "|".repeat(10), "|".repeat(8))
+        );
+        assert_eq!(column(&playfield, &game()), " ".repeat(20));
+    }
+
+    #[test]
+    fn the_hold_box_goes_with_hold_and_counts_down_a_limited_one() {
+        let rules = |hold| Ruleset {
+            hold,
+            ..GameMode::Marathon.ruleset()
+        };
+        let mut playfield = Playfield::new(80, 30, 10, 20, 2, 1, Framing::default());
+        playfield.hud = Hud::default().for_rules(&rules(Hold::Off));
+        assert_eq!(playfield.hold_rect(), None);
+        let buffer = draw(
+            &playfield,
+            &Game::with_rules(10, 20, TetrominosBag::new(), rules(Hold::Off)),
+        );
+        assert!(text(&buffer, Rect::new(19, 6, 10, 5))
+            .iter()
+            .all(|row| row.trim().is_empty()));
+
+        playfield.hud = Hud::default().for_rules(&rules(Hold::Limited(3)));
+        let mut limited = Game::with_rules(10, 20, TetrominosBag::new(), rules(Hold::Limited(3)));
+        limited.apply(Input::Hold);
+        let hold = playfield.hold_rect().unwrap();
+        let top = Rect { height: 1, ..hold };
+        assert!(text(&draw(&playfield, &limited), top)[0].contains("2 left"));
+        playfield.hud = Hud::default();
+        assert!(!text(&draw(&playfield, &game()), top)[0].contains("left"));
+    }
+
+    #[test]
+    fn a_pause_the_game_made_by_itself_says_so() {
+        let mut playfield = Playfield::new(80, 30, 10, 20, 2, 1, Framing::default());
+        let mut paused = game();
+        paused.toggle_pause();
+        let board = inside(playfield.rect);
+        let lines = |playfield: &Playfield| -> Vec<String> {
+            text(&draw(playfield, &paused), board)
+                .into_iter()
+                .map(|line| line.trim().to_string())
+                .filter(|line| !line.is_empty())
+                .collect()
+        };
+        assert_eq!(lines(&playfield), ["PAUSED"]);
+        playfield.idle_paused = true;
+        assert_eq!(lines(&playfield), ["PAUSED", "auto-paused (idle)"]);
+    }
+
+    #[test]
+    fn the_title_shows_how_far_the_game_got() {
+        let mut marathon = game();
+        assert_eq!(window_title(&marathon), "tetrs - level 1");
+        marathon.toggle_pause();
+        assert_eq!(window_title(&marathon), "tetrs - level 1 - paused");
+        let sprint = Game::with_mode(10, 20, TetrominosBag::new(), GameMode::Sprint);
+        assert_eq!(window_title(&sprint), "tetrs - sprint 0/40 lines");
+        let mut ultra = Game::with_mode(10, 20, TetrominosBag::new(), GameMode::Ultra);
+        ultra.elapse(Duration::from_millis(36_500));
+        assert_eq!(window_title(&ultra), "tetrs - ultra 1:24 left");
+    }
+
+    #[test]
+    fn titles_that_come_too_soon_are_written_once_the_second_is_up() {
+        let start = Instant::now();
+        let mut out = Vec::new();
+        let mut title = TitleUpdater::new(true);
+        title.update(&mut out, "one", start).unwrap();
+        assert_eq!(title.deadline(), None);
+        let written = out.len();
+        title
+            .update(&mut out, "two", start + Duration::from_millis(300))
+            .unwrap();
+        title
+            .update(&mut out, "three", start + Duration::from_millis(600))
+            .unwrap();
+        assert_eq!(out.len(), written);
+        assert_eq!(title.deadline(), Some(start + Duration::from_secs(1)));
+        title
+            .flush(&mut out, start + Duration::from_millis(999))
+            .unwrap();
+        assert_eq!(out.len(), written);
+        // Only the latest one is written
+        title
+            .flush(&mut out, start + Duration::from_secs(1))
+            .unwrap();
+        let written = String::from_utf8(out[written..].to_vec()).unwrap();
+        assert!(written.contains("three") && !written.contains("two"));
+        assert_eq!(title.deadline(), None);
+    }
+
+    #[test]
+    fn going_back_to_the_title_shown_cancels_the_one_held_back() {
+        let start = Instant::now();
+        let mut out = Vec::new();
+        let mut title = TitleUpdater::new(true);
+        title.update(&mut out, "one", start).unwrap();
+        title
+            .update(&mut out, "two", start + Duration::from_millis(300))
+            .unwrap();
+        title
+            .update(&mut out, "one", start + Duration::from_millis(600))
+            .unwrap();
+        assert_eq!(title.deadline(), None);
+        let mut disabled = TitleUpdater::new(false);
+        disabled.update(&mut out, "two", start).unwrap();
+        disabled.restore(&mut out).unwrap();
+        let one = String::from_utf8(out).unwrap();
+        assert_eq!(one.matches("one").count(), 1);
+        assert!(!one.contains("two"));
+    }
+
+    #[test]
+    fn each_background_gets_its_own_palette() {
+        for (background, o, j, l, border) in [
+            (
+                Background::Dark,
+                Color::Yellow,
+                Color::Blue,
+                Color::Rgb(255, 165, 0),
+                Color::Reset,
+            ),
+            (
+                Background::Light,
+                Color::Rgb(204, 153, 0),
+                Color::Blue,
+                Color::Rgb(230, 115, 0),
+                Color::Black,
+            ),
+        ] {
+            let mut playfield = Playfield::new(80, 30, 10, 20, 2, 1, Framing::default());
+            playfield.palette = background.palette(ColorDepth::TrueColor);
+            let buffer = draw(&playfield, &game());
+            let board = inside(playfield.rect);
+            let cell = |x, y| buffer.get(board.x + x, board.y + y);
+            assert_eq!(cell(8, 19).bg, o, "{background:?}");
+            assert_eq!(cell(13, 1).bg, j, "{background:?}");
+            assert_eq!(cell(8, 17).fg, j, "{background:?}");
+            let preview = inside(playfield.preview_rect().unwrap());
+            assert_eq!(buffer.get(preview.x + 6, preview.y).bg, l, "{background:?}");
+            for area in [
+                playfield.rect,
+                playfield.hold_rect().unwrap(),
+                playfield.panel_rect(Panel::Score).unwrap(),
+            ] {
+                assert_eq!(buffer.get(area.x, area.y).fg, border, "{background:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn the_t_is_purple_on_either_background() {
+        for background in [Background::Dark, Background::Light] {
+            assert_eq!(
+                background.palette(ColorDepth::TrueColor).piece('T'),
+                Color::Rgb(160, 32, 240)
+            );
+            for depth in [ColorDepth::Indexed, ColorDepth::Basic] {
+                assert_eq!(background.palette(depth).piece('T'), Color::Magenta);
+            }
+        }
+        assert_eq!(
+            Background::Light.palette(ColorDepth::TrueColor).piece('G'),
+            Color::Gray
+        );
+    }
+
+    #[test]
+    fn the_l_stays_orange_without_true_color() {
+        for (background, indexed) in [
+            (Background::Dark, Color::Indexed(208)),
+            (Background::Light, Color::Indexed(166)),
+        ] {
+            assert_eq!(background.palette(ColorDepth::Indexed).piece('L'), indexed);
+            // Only light red is left among the basic colors, red is the Z's
+            let basic = background.palette(ColorDepth::Basic);
+            assert_eq!(basic.piece('L'), Color::LightRed);
+            assert_ne!(basic.piece('L'), basic.piece('Z'));
+        }
+    }
+
+    #[test]
+    fn colorfgbg_tells_light_backgrounds_from_dark_ones() {
+        assert_eq!(Background::from_colorfgbg("0;15"), Some(Background::Light));
+        assert_eq!(Background::from_colorfgbg("0;7"), Some(Background::Light));
+        assert_eq!(
+            Background::from_colorfgbg("15;default;0"),
+            Some(Background::Dark)
+        );
+        assert_eq!(Background::from_colorfgbg("7;8"), Some(Background::Dark));
+        assert_eq!(Background::from_colorfgbg("default"), None);
+    }
+
+    #[test]
+    fn kicks_are_outlined_and_the_one_taken_is_green() {
+        let kicks = [
+            KickAttempt {
+                index: 0,
+                cells: [(-1, 5), (0, 5), (1, 5), (1, 4)],
+                failure: Some(KickFailure::OutOfBounds),
+            },
+            KickAttempt {
+                index: 1,
+                cells: [(0, 6), (1, 6), (2, 6), (2, 5)],
+                failure: None,
+            },
+        ];
+        for glyphs_only in [false, true] {
+            let mut playfield = Playfield::new(80, 30, 10, 20, 2, 1, Framing::default());
+            playfield.glyphs_only = glyphs_only;
+            let (width, height) = playfield.frame;
+            let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+            playfield.draw(&mut terminal, &game(), None, Some(&kicks), None, None);
+            let buffer = terminal.backend().buffer().clone();
+            let board = text(&buffer, inside(playfield.rect));
+            // The cell off the board is left out
+            assert_eq!(board[4], "  []                ");
+            assert_eq!(board[5], "[][]                ");
+            let cell = |x, y| buffer.get(playfield.rect.x + 1 + x, playfield.rect.y + 1 + y);
+            let top = text(&buffer, playfield.rect)[0].clone();
+            assert!(top.ends_with(" kick 2 \u{2510}"), "{top}");
+            if glyphs_only {
+                assert_eq!(cell(0, 6).bg, Color::Reset);
+            } else {
+                assert_eq!(cell(0, 5).fg, Color::Red);
+                assert_eq!(cell(0, 6).bg, Color::Green);
+                assert_eq!(cell(5, 5).bg, Color::Green);
+            }
+        }
+        let blocked = [KickAttempt {
+            failure: Some(KickFailure::Blocked),
+            ..kicks[1]
+        }];
+        let playfield = Playfield::new(80, 30, 10, 20, 2, 1, Framing::default());
+        let (width, height) = playfield.frame;
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        playfield.draw(&mut terminal, &game(), None, Some(&blocked), None, None);
+        let top = text(terminal.backend().buffer(), playfield.rect)[0].clone();
+        assert!(top.ends_with(" no kick \u{2510}"), "{top}");
+    }
 }
@@ -0,0 +1,108 @@
+// `tetris verify <replay>`: plays a replay back without a terminal, as fast as it goes,
+// and checks that it comes to the result written in it. Every input is checked
+// against its state hash on the way, and once they're all in the game runs on by
+// itself until it ends, the way it did when it was played. Whatever doesn't match is
+// an error, and so is a replay without a result, like that of a game that was quit.
+
+use std::{error::Error, path::Path, time::Duration};
+
+use crate::{
+    replay::{Claim, Replay, ReplayError},
+    session::Session,
+};
+
+// Left to itself a game ends long before this, gravity tops it out
+const RUN_ON: Duration = Duration::from_secs(60 * 60);
+
+pub fn run() -> Result<(), Box<dyn Error>> {
+    let path = std::env::args()
+        .nth(2)
+        .ok_or("usage: tetris verify <replay>")?;
+    let replay =
+        Replay::load(Path::new(&path)).map_err(|err| format!("can't verify {path}: {err}"))?;
+    let claim = replay.result.ok_or(format!(
+        "{path} has no result to verify, its game didn't end"
+    ))?;
+    let played = play(&replay).map_err(|err| format!("{path}: {err}"))?;
+    if played != claim {
+        return Err(format!("{path} claims {claim}, but plays back to {played}").into());
+    }
+    println!("{path}: verified, {played}");
+    Ok(())
+}
+
+// What the replay comes to when played back
+pub fn play(replay: &Replay) -> Result<Claim, ReplayError> {
+    let mut session = Session::new(replay.game(), replay.repeat());
+    for (index, input) in replay.inputs.iter().enumerate() {
+        session.advance(Duration::from_millis(input.tick));
+        session.key(input.action, input.kind.into());
+        input.check(index, session.game().state_hash())?;
+    }
+    let end = session.clock() + RUN_ON;
+    while !session.game().is_over() && session.clock() < end {
+        session.advance(session.clock() + Duration::from_secs(1));
+    }
+    Ok(Claim::of(session.game()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::KeyEventKind;
+    use tetris::state::GameMode;
+
+    use super::*;
+    use crate::input::{Action, RepeatSettings};
+
+    // Hard drops where the tetrominos spawn until the stack tops out, the way main
+    // records a game and notes its result
+    fn recorded() -> Replay {
+        let repeat = RepeatSettings::default();
+        let mut replay = Replay::new(9, 1, GameMode::Marathon.ruleset(), repeat);
+        let mut session = Session::new(replay.game(), repeat);
+        let mut tick = 0;
+        while !session.game().is_over() {
+            tick += 100;
+            session.advance(Duration::from_millis(tick));
+            session.key(Action::HardDrop, KeyEventKind::Press);
+            replay.record(
+                tick,
+                Action::HardDrop,
+                KeyEventKind::Press,
+                session.game().state_hash(),
+            );
+        }
+        replay.note_result(session.game());
+        replay
+    }
+
+    #[test]
+    fn a_recorded_game_plays_back_to_its_result() {
+        let replay = recorded();
+        assert_eq!(play(&replay).unwrap(), replay.result.unwrap());
+    }
+
+    #[test]
+    fn a_game_left_to_gravity_runs_on_to_its_end() {
+        let mut replay = recorded();
+        // Without the last drops the stack still tops out, only later
+        replay.inputs.truncate(3);
+        let played = play(&replay).unwrap();
+        assert!(!played.completed);
+        assert!(played.time_ms > replay.result.unwrap().time_ms);
+    }
+
+    #[test]
+    fn a_result_that_was_edited_doesnt_verify() {
+        let mut replay = recorded();
+        let claim = replay.result.as_mut().unwrap();
+        claim.points += 1000;
+        assert_ne!(play(&replay).unwrap(), replay.result.unwrap());
+        let mut replay = recorded();
+        replay.inputs[1].hash = Some(0);
+        assert!(matches!(
+            play(&replay),
+            Err(ReplayError::Desync { input: 1, .. })
+        ));
+    }
+}
@@ -0,0 +1,77 @@
+// The engine for the browser through wasm-bindgen, see examples/web. Keys are given by
+// KeyboardEvent.key and time in milliseconds, the page does the drawing.
+
+use std::time::Duration;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    engine::Engine,
+    state::{GameMode, Input},
+};
+
+const COLUMNS: usize = 10;
+const ROWS: usize = 20;
+
+#[wasm_bindgen]
+pub struct WebGame {
+    engine: Engine,
+}
+
+#[wasm_bindgen]
+impl WebGame {
+    // A marathon, JavaScript numbers only hold so much of a seed
+    #[wasm_bindgen(constructor)]
+    pub fn new(seed: u32) -> WebGame {
+        WebGame {
+            engine: Engine::new(COLUMNS, ROWS, seed.into(), GameMode::Marathon),
+        }
+    }
+
+    // Returns whether the key means anything to the game, the page can leave the
+    // others to the browser
+    pub fn key(&mut self, key: &str) -> bool {
+        let input = match key {
+            "ArrowLeft" => Input::MoveLeft,
+            "ArrowRight" => Input::MoveRight,
+            "ArrowDown" => Input::SoftDrop,
+            " " => Input::HardDrop,
+            "ArrowUp" | "x" => Input::RotateCw,
+            "z" => Input::RotateCcw,
+            "c" => Input::Hold,
+            _ => return false,
+        };
+        self.engine.apply(input);
+        true
+    }
+
+    pub fn tick(&mut self, ms: u32) {
+        self.engine.tick(Duration::from_millis(ms.into()));
+    }
+
+    // A line of text per row, as Engine::cells() has them
+    pub fn board(&self) -> String {
+        self.engine
+            .cells()
+            .chunks(COLUMNS)
+            .map(|row| String::from_utf8_lossy(row).into_owned())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn points(&self) -> u32 {
+        self.engine.game().score().points
+    }
+
+    pub fn lines(&self) -> u32 {
+        self.engine.game().score().lines
+    }
+
+    pub fn level(&self) -> u32 {
+        self.engine.game().score().level
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.engine.game().is_over()
+    }
+}
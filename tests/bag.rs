@@ -0,0 +1,88 @@
+use tetris::game::TetrominosBag;
+
+fn sorted_shapes(bag: &mut TetrominosBag, count: usize) -> String {
+    let mut shapes: Vec<char> = (0..count).map(|_| bag.get().unwrap().shape()).collect();
+    shapes.sort_unstable();
+    shapes.into_iter().collect()
+}
+
+#[test]
+fn every_bag_holds_each_tetromino_once() {
+    let mut bag = TetrominosBag::new();
+    bag.shuffle();
+    for _ in 0..10 {
+        assert_eq!(sorted_shapes(&mut bag, 7), "IJLOSTZ");
+    }
+}
+
+#[test]
+fn double_bag_holds_each_tetromino_twice() {
+    let mut bag = TetrominosBag::with_bag_count(2);
+    bag.shuffle();
+    for _ in 0..5 {
+        assert_eq!(sorted_shapes(&mut bag, 14), "IIJJLLOOSSTTZZ");
+    }
+}
+
+#[test]
+fn peek_matches_the_next_tetromino_across_bags() {
+    let mut bag = TetrominosBag::new();
+    bag.shuffle();
+    for _ in 0..30 {
+        let peeked = bag.peek().unwrap().shape();
+        assert_eq!(bag.get().unwrap().shape(), peeked);
+    }
+}
+
+#[test]
+fn shuffling_changes_the_order() {
+    // 20 identical orders out of 5040 possible ones won't happen by chance
+    let orders: Vec<String> = (0..20)
+        .map(|seed| {
+            let mut bag = TetrominosBag::shuffled(seed);
+            (0..7).map(|_| bag.get().unwrap().shape()).collect()
+        })
+        .collect();
+    assert!(orders.iter().any(|order| *order != orders[0]));
+}
+
+#[test]
+fn the_same_seed_deals_the_same_tetrominos() {
+    let deal = |seed| {
+        let mut bag = TetrominosBag::new();
+        bag.reseed(seed);
+        bag.shuffle();
+        (0..30)
+            .map(|_| bag.get().unwrap().shape())
+            .collect::<String>()
+    };
+    assert_eq!(deal(42), deal(42));
+    assert_ne!(deal(42), deal(43));
+}
+
+fn dealt(bag: &mut TetrominosBag, count: usize) -> String {
+    (0..count).map(|_| bag.get().unwrap().shape()).collect()
+}
+
+#[test]
+fn every_shuffle_is_kept_as_a_decision() {
+    let mut bag = TetrominosBag::shuffled(5);
+    let shapes = dealt(&mut bag, 15);
+    // The bag after the one dealing is already shuffled
+    assert_eq!(bag.decisions().len(), 4);
+    assert_eq!(bag.decisions().concat()[..15], shapes);
+}
+
+#[test]
+fn recorded_decisions_go_before_the_seed() {
+    let mut bag = TetrominosBag::new();
+    bag.reseed(5);
+    let decisions = ["TSZOJLI".to_string(), "IIIIIII".to_string()];
+    assert!(bag.follow(&decisions));
+    bag.shuffle();
+    assert_eq!(dealt(&mut bag, 14), "TSZOJLIIIIIIII");
+    // Then it's back to shuffling, from the seed
+    assert_eq!(sorted_shapes(&mut bag, 7), "IJLOSTZ");
+    assert_eq!(bag.decisions()[..2], decisions);
+    assert!(!bag.follow(&["TSZOJLQ".to_string()]));
+}
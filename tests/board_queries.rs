@@ -0,0 +1,55 @@
+use tetris::game::{Board, Playcell};
+
+// A T with a hole under its left arm, an overhang over column 0 and a falling I
+fn board() -> Board {
+    let mut board = Board::from_text(
+        "......\n\
+         GG....\n\
+         ...T..\n\
+         .GTTT.\n\
+         GG.GGG",
+    )
+    .unwrap();
+    for x in 2..6 {
+        board.tiles[0][x] = Some(Playcell::new(true, 'I'));
+    }
+    board
+}
+
+#[test]
+fn only_locked_cells_and_everything_outside_are_occupied() {
+    let board = board();
+    assert!(board.is_occupied(3, 2));
+    assert!(!board.is_occupied(2, 4));
+    // The falling I
+    assert!(!board.is_occupied(2, 0));
+    // Walls and floor
+    assert!(board.is_occupied(6, 0));
+    assert!(board.is_occupied(0, 5));
+    assert!(board.is_occupied(usize::MAX, 0));
+}
+
+#[test]
+fn column_heights_count_from_the_floor_to_the_highest_locked_cell() {
+    assert_eq!(board().column_heights(), [4, 4, 2, 3, 2, 1]);
+    assert_eq!(Board::new(3, 4).column_heights(), [0, 0, 0]);
+}
+
+#[test]
+fn holes_are_empty_cells_under_locked_ones() {
+    // Two under the overhang in column 0, one in each of columns 1 and 2
+    assert_eq!(board().hole_count(), 4);
+    assert_eq!(Board::new(3, 4).hole_count(), 0);
+    assert_eq!(Board::from_text("GGG\nGGG").unwrap().hole_count(), 0);
+}
+
+#[test]
+fn row_fill_counts_skip_the_falling_tetromino() {
+    assert_eq!(board().row_fill_counts(), [0, 2, 1, 4, 5]);
+}
+
+#[test]
+fn stack_height_is_the_highest_column() {
+    assert_eq!(board().stack_height(), 4);
+    assert_eq!(Board::new(3, 4).stack_height(), 0);
+}
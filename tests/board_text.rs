@@ -0,0 +1,72 @@
+use tetris::{
+    game::{Board, TetrominosBag},
+    state::{Game, Input},
+};
+
+#[test]
+fn text_round_trips() {
+    let text = "..........\n....T.....\nI..TTT..GG\nIOOJJJLSSG\nIOOZZJLLSS";
+    let board = Board::from_text(text).unwrap();
+    assert_eq!(board.to_text(), text);
+    assert_eq!(Board::from_text(&board.to_text()).unwrap(), board);
+    assert_eq!(board.row_fill_counts(), [0, 1, 6, 10, 10]);
+}
+
+#[test]
+fn a_played_board_round_trips_without_the_falling_tetromino() {
+    let mut game = Game::with_bag(10, 20, TetrominosBag::new());
+    for input in [
+        Input::MoveLeft,
+        Input::HardDrop,
+        Input::RotateCw,
+        Input::HardDrop,
+    ] {
+        game.apply(input);
+    }
+    let mut locked = game.board().clone();
+    game.tetromino().remove_from_playfield(&mut locked);
+    assert_eq!(Board::from_text(&game.board().to_text()).unwrap(), locked);
+}
+
+#[test]
+fn loaded_text_sits_on_the_floor() {
+    let mut board = Board::new(4, 3);
+    board.load_text("T...\nTTGG").unwrap();
+    assert_eq!(board.to_text(), "....\nT...\nTTGG");
+    board.load_text("").unwrap();
+    assert_eq!(board, Board::new(4, 3));
+}
+
+#[test]
+fn malformed_text_is_refused_with_the_reason() {
+    for (text, message) in [
+        ("", "board has no cells"),
+        ("....\n...", "row 1 is 3 cells wide, expected 4"),
+        (
+            "..X.",
+            "unknown cell 'X' at row 0, column 2 (expected '.', 'G' or a piece letter)",
+        ),
+    ] {
+        let error = Board::from_text(text).unwrap_err();
+        assert_eq!(error.to_string(), message, "{text:?}");
+    }
+}
+
+#[test]
+fn loading_refuses_text_that_doesnt_fit_and_keeps_the_board() {
+    let mut board = Board::new(4, 2);
+    board.load_text("GG..").unwrap();
+    let before = board.clone();
+    for (text, message) in [
+        ("....\n....\n....", "board has 3 rows but at most 2 fit"),
+        (".....", "row 0 is 5 cells wide, expected 4"),
+        (
+            "....\n.Q..",
+            "unknown cell 'Q' at row 1, column 1 (expected '.', 'G' or a piece letter)",
+        ),
+    ] {
+        let error = board.load_text(text).unwrap_err();
+        assert_eq!(error.to_string(), message, "{text:?}");
+        assert_eq!(board, before);
+    }
+}
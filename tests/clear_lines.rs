@@ -0,0 +1,94 @@
+use tetris::game::Board;
+
+// Board text is bottom-aligned, see Board::load_text
+fn board(text: &str) -> Board {
+    let mut board = Board::new(10, 20);
+    board.load_text(text).unwrap();
+    board
+}
+
+fn bottom_rows(board: &Board, count: usize) -> Vec<String> {
+    let text = board.to_text();
+    let rows: Vec<&str> = text.lines().collect();
+    rows[rows.len() - count..]
+        .iter()
+        .map(|row| row.to_string())
+        .collect()
+}
+
+#[test]
+fn single() {
+    let mut board = board("....T.....\nGGGGGGGGGG");
+    assert_eq!(board.clear_lines(&[18, 19]), [19]);
+    assert_eq!(bottom_rows(&board, 2), ["..........", "....T....."]);
+}
+
+#[test]
+fn stacked_double() {
+    let mut board = board("..T.......\nGGGGGGGGGG\nGGGGGGGGGG");
+    assert_eq!(board.clear_lines(&[17, 18, 19]), [18, 19]);
+    assert_eq!(
+        bottom_rows(&board, 3),
+        ["..........", "..........", "..T......."]
+    );
+}
+
+#[test]
+fn split_double() {
+    let mut board = board("...I......\nGGGGGGGGGG\nG.G.G.G.G.\nGGGGGGGGGG");
+    assert_eq!(board.clear_lines(&[17, 18, 19]), [17, 19]);
+    assert_eq!(
+        bottom_rows(&board, 3),
+        ["..........", "...I......", "G.G.G.G.G."]
+    );
+}
+
+#[test]
+fn tetris() {
+    let mut board = board(".........L\nIIIIIIIIIG\nIIIIIIIIIG\nIIIIIIIIIG\nIIIIIIIIIG");
+    assert_eq!(board.clear_lines(&[16, 17, 18, 19]), [16, 17, 18, 19]);
+    assert_eq!(bottom_rows(&board, 2), ["..........", ".........L"]);
+}
+
+#[test]
+fn only_the_given_rows_are_checked() {
+    let mut board = board("GGGGGGGGGG\nGGGGGGGGGG");
+    assert_eq!(board.clear_lines(&[18]), [18]);
+    assert_eq!(bottom_rows(&board, 2), ["..........", "GGGGGGGGGG"]);
+}
+
+#[test]
+fn row_counters_follow_the_cleared_rows() {
+    let mut board = board("G.........\nGGGGGGGGGG\nGG........\nGGGGGGGGGG");
+    board.clear_lines(&[16, 17, 18, 19]);
+    let fill = board.row_fill_counts();
+    assert_eq!(fill[18..], [1, 2]);
+    assert!(fill[..18].iter().all(|count| *count == 0));
+}
+
+#[test]
+fn split_double_given_bottom_up() {
+    let mut board = board("..O.......\nGGGGGGGGGG\nGGGG.GGGGG\nGGGGGGGGGG\nG.........");
+    assert_eq!(board.clear_lines(&[18, 17, 16, 15]), [16, 18]);
+    assert_eq!(
+        bottom_rows(&board, 4),
+        ["..........", "..O.......", "GGGG.GGGGG", "G........."]
+    );
+}
+
+#[test]
+fn tetris_in_the_middle_of_the_stack() {
+    let mut board = board("...T......\nGGGGGGGGGG\nGGGGGGGGGG\nGGGGGGGGGG\nGGGGGGGGGG\nGG.GGGGGGG");
+    assert_eq!(board.clear_lines(&[15, 16, 17, 18]), [15, 16, 17, 18]);
+    assert_eq!(bottom_rows(&board, 2), ["...T......", "GG.GGGGGGG"]);
+    assert!(bottom_rows(&board, 6)[..4]
+        .iter()
+        .all(|row| row == ".........."));
+}
+
+#[test]
+fn nothing_full() {
+    let mut board = board("GGGGGGGGG.");
+    assert!(board.clear_lines(&[19]).is_empty());
+    assert_eq!(bottom_rows(&board, 1), ["GGGGGGGGG."]);
+}
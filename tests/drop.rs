@@ -0,0 +1,78 @@
+use tetris::{
+    game::{template, Board, Direction, TetrominosBag},
+    state::{Game, Input, Outcome},
+};
+
+fn lowest_row(body: &[(usize, usize)]) -> usize {
+    body.iter().map(|(_, y)| *y).max().unwrap()
+}
+
+#[test]
+fn hard_drop_distance_on_an_empty_board() {
+    let mut board = Board::new(10, 20);
+    let mut tetromino = template('O').unwrap();
+    assert!(tetromino.spawn(&mut board).is_ok());
+    let start = lowest_row(&tetromino.body());
+    assert_eq!(tetromino.hard_drop(&mut board), 19 - start);
+    assert_eq!(lowest_row(&tetromino.body()), 19);
+}
+
+#[test]
+fn hard_drop_distance_onto_the_stack() {
+    let mut board = Board::new(10, 20);
+    board.load_text("GGGG.GGGGG\nGGGG.GGGGG").unwrap();
+    let mut tetromino = template('I').unwrap();
+    assert!(tetromino.spawn(&mut board).is_ok());
+    let start = lowest_row(&tetromino.body());
+    // Lies flat on top of the two rows, the gap under it doesn't matter
+    assert_eq!(tetromino.hard_drop(&mut board), 17 - start);
+    assert_eq!(lowest_row(&tetromino.body()), 17);
+}
+
+#[test]
+fn hard_drop_on_the_floor_goes_nowhere() {
+    let mut board = Board::new(10, 20);
+    let mut tetromino = template('T').unwrap();
+    assert!(tetromino.spawn(&mut board).is_ok());
+    while tetromino.shift(&mut board, Direction::Down).is_ok() {}
+    assert_eq!(lowest_row(&tetromino.body()), 19);
+    assert_eq!(tetromino.hard_drop(&mut board), 0);
+}
+
+#[test]
+fn soft_drop_to_the_floor_scores_every_row_and_grounds_without_locking() {
+    // An unshuffled bag starts with an O, spawned in the top two rows
+    let mut game = Game::with_bag(10, 20, TetrominosBag::new());
+    for _ in 0..18 {
+        assert_eq!(game.apply(Input::SoftDrop), Outcome::Moved);
+    }
+    assert_eq!(game.apply(Input::SoftDrop), Outcome::Blocked);
+    assert_eq!(game.score().points, 18);
+    assert_eq!(game.tetromino().shape(), 'O');
+    assert!(game.lock_delay_left().is_some());
+    // Nothing left to fall, so the hard drop only locks it
+    assert_eq!(game.apply(Input::HardDrop), Outcome::Locked { lines: 0 });
+    assert_eq!(game.score().points, 18);
+}
+
+#[test]
+fn hard_drop_scores_two_points_per_row() {
+    let mut game = Game::with_bag(10, 20, TetrominosBag::new());
+    for _ in 0..5 {
+        game.apply(Input::Tick);
+    }
+    assert_eq!(game.apply(Input::HardDrop), Outcome::Locked { lines: 0 });
+    assert_eq!(game.score().points, 2 * 13);
+}
+
+#[test]
+fn a_soft_drop_to_the_stack_scores_each_row_and_leaves_the_locking_to_the_delay() {
+    let mut game = Game::with_bag(10, 20, TetrominosBag::new());
+    assert_eq!(game.apply(Input::SoftDropToStack), Outcome::Moved);
+    assert_eq!(lowest_row(&game.tetromino().body()), 19);
+    assert_eq!(game.score().points, 18);
+    assert_eq!(game.apply(Input::SoftDropToStack), Outcome::Blocked);
+    assert_eq!(game.score().points, 18);
+    assert_eq!(game.tetromino().shape(), 'O');
+    assert!(game.lock_delay_left().is_some());
+}
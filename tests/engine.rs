@@ -0,0 +1,87 @@
+use std::time::Duration;
+
+use tetris::{
+    engine::Engine,
+    game::TetrominosBag,
+    state::{Game, GameMode, Input, Outcome},
+};
+
+fn engine() -> Engine {
+    Engine::new(10, 20, 1, GameMode::Marathon)
+}
+
+fn falling_rows(engine: &Engine) -> Vec<usize> {
+    engine.game().tetromino().rows()
+}
+
+#[test]
+fn gravity_moves_the_tetromino_on_the_clock() {
+    let mut engine = engine();
+    let rows = falling_rows(&engine);
+    // A second a row at level 1
+    engine.tick(Duration::from_millis(999));
+    assert_eq!(falling_rows(&engine), rows);
+    assert_eq!(engine.tick(Duration::from_millis(1)), Outcome::Moved);
+    assert_eq!(falling_rows(&engine)[0], rows[0] + 1);
+}
+
+#[test]
+fn a_grounded_tetromino_locks_once_the_delay_runs_out() {
+    let mut engine = engine();
+    engine.apply(Input::SoftDropToStack);
+    let shape = engine.game().tetromino().shape();
+    assert_eq!(
+        engine.tick(Duration::from_secs(1)),
+        Outcome::Locked { lines: 0 }
+    );
+    let last_row = &engine.cells()[190..];
+    assert!(last_row.contains(&(shape as u8)));
+}
+
+#[test]
+fn the_same_seed_deals_the_same_tetrominos() {
+    let shapes = |seed| {
+        let mut engine = Engine::new(10, 20, seed, GameMode::Marathon);
+        (0..14)
+            .map(|_| {
+                let shape = engine.game().tetromino().shape();
+                engine.apply(Input::HardDrop);
+                shape
+            })
+            .collect::<String>()
+    };
+    assert_eq!(shapes(7), shapes(7));
+    assert_ne!(shapes(7), shapes(8));
+}
+
+#[test]
+fn cells_tell_the_falling_tetromino_from_the_stack() {
+    let mut engine = engine();
+    let first = engine.game().tetromino().shape();
+    engine.apply(Input::HardDrop);
+    let second = engine.game().tetromino().shape();
+    let cells = engine.cells();
+    assert_eq!(cells.len(), 200);
+    assert_eq!(
+        cells
+            .iter()
+            .filter(|&&cell| cell == second.to_ascii_lowercase() as u8)
+            .count(),
+        4
+    );
+    assert_eq!(cells.iter().filter(|&&cell| cell == first as u8).count(), 4);
+    assert_eq!(cells.iter().filter(|&&cell| cell == b'.').count(), 192);
+}
+
+#[test]
+fn full_rows_go_right_away() {
+    // An unshuffled bag deals an O first, right into the gap
+    let mut game = Game::with_bag(10, 20, TetrominosBag::new());
+    game.load_stack("GGGG..GGGG\nGGGG..GGGG").unwrap();
+    let mut engine = Engine::with_game(game);
+    assert_eq!(engine.apply(Input::HardDrop), Outcome::Locked { lines: 2 });
+    assert!(!engine.game().is_clearing());
+    assert_eq!(engine.game().score().lines, 2);
+    assert_eq!(engine.game().tetromino().shape(), 'I');
+    assert!(engine.cells()[180..].iter().all(|&cell| cell == b'.'));
+}
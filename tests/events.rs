@@ -0,0 +1,138 @@
+use tetris::{
+    game::TetrominosBag,
+    state::{Game, GameEvent, Input, Outcome},
+};
+
+// An unshuffled bag deals O, I, J, L, S, Z, T
+fn game() -> Game {
+    Game::with_bag(10, 20, TetrominosBag::new())
+}
+
+fn spawned(shape: char, from_hold: bool) -> GameEvent {
+    GameEvent::PieceSpawned { shape, from_hold }
+}
+
+#[test]
+fn a_game_starts_with_its_first_spawn() {
+    let mut game = game();
+    assert_eq!(game.take_events(), [spawned('O', false)]);
+    assert_eq!(game.take_events(), []);
+}
+
+#[test]
+fn a_lock_without_lines_is_followed_by_the_next_spawn() {
+    let mut game = game();
+    game.take_events();
+    game.apply(Input::MoveLeft);
+    assert_eq!(game.take_events(), []);
+    game.apply(Input::HardDrop);
+    assert_eq!(
+        game.take_events(),
+        [
+            GameEvent::PieceLocked {
+                shape: 'O',
+                cells: [(3, 18), (3, 19), (4, 18), (4, 19)],
+            },
+            spawned('I', false),
+        ]
+    );
+}
+
+#[test]
+fn lines_are_cleared_before_the_next_tetromino_spawns() {
+    let mut game = game();
+    game.load_stack("GGGG..GGGG").unwrap();
+    game.take_events();
+    assert_eq!(game.apply(Input::HardDrop), Outcome::Locked { lines: 1 });
+    assert!(matches!(
+        game.take_events()[..],
+        [GameEvent::PieceLocked { shape: 'O', .. }]
+    ));
+    game.finish_clear();
+    let events = game.take_events();
+    let [GameEvent::LinesCleared { rows, clear }, next] = &events[..] else {
+        panic!("{events:?}");
+    };
+    assert_eq!((&rows[..], clear.lines), (&[19][..], 1));
+    assert_eq!(*next, spawned('I', false));
+}
+
+#[test]
+fn a_hold_spawns_what_comes_out_of_it() {
+    let mut game = game();
+    game.take_events();
+    game.apply(Input::Hold);
+    assert_eq!(game.take_events(), [spawned('I', false)]);
+    game.apply(Input::HardDrop);
+    game.take_events();
+    game.apply(Input::Hold);
+    assert_eq!(game.take_events(), [spawned('O', true)]);
+    // Not allowed again, nothing happened
+    game.apply(Input::Hold);
+    assert_eq!(game.take_events(), []);
+}
+
+#[test]
+fn topping_out_is_the_last_event() {
+    let mut game = game();
+    while game.apply(Input::HardDrop) != Outcome::GameOver {}
+    let events = game.take_events();
+    assert!(matches!(
+        events[events.len() - 2..],
+        [
+            GameEvent::PieceLocked { .. },
+            GameEvent::GameOver { completed: false }
+        ]
+    ));
+    game.apply(Input::HardDrop);
+    game.elapse(std::time::Duration::from_secs(1));
+    assert_eq!(game.take_events(), []);
+}
+
+// A bottom row that's full except where the falling tetromino would land in it, so
+// that dropping it clears that row
+fn fill_around_the_landing(game: &mut Game) {
+    game.load_stack("").unwrap();
+    let ghost = game.ghost().body();
+    let row: String = (0..10)
+        .map(|x| if ghost.contains(&(x, 19)) { '.' } else { 'G' })
+        .collect();
+    game.load_stack(&row).unwrap();
+}
+
+#[test]
+fn every_lock_comes_with_its_clear_level_up_and_spawn_in_order() {
+    let mut game = game();
+    let mut events = game.take_events();
+    let mut locks = 0;
+    while game.score().level < 3 {
+        fill_around_the_landing(&mut game);
+        assert!(matches!(
+            game.apply(Input::HardDrop),
+            Outcome::Locked { lines: 1.. }
+        ));
+        game.finish_clear();
+        locks += 1;
+        events.extend(game.take_events());
+    }
+    let mut level_ups = 0;
+    let mut rest = &events[1..];
+    for _ in 0..locks {
+        let [GameEvent::PieceLocked { .. }, GameEvent::LinesCleared { .. }, after @ ..] = rest
+        else {
+            panic!("no lock and clear at {rest:?}");
+        };
+        rest = after;
+        if let [GameEvent::LevelUp { level }, after @ ..] = rest {
+            level_ups += 1;
+            assert_eq!(*level, level_ups + 1);
+            rest = after;
+        }
+        let [GameEvent::PieceSpawned { .. }, after @ ..] = rest else {
+            panic!("no spawn at {rest:?}");
+        };
+        rest = after;
+    }
+    assert!(rest.is_empty());
+    assert_eq!(level_ups, 2);
+}
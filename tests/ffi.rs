@@ -0,0 +1,52 @@
+#![cfg(feature = "ffi")]
+
+use std::ptr;
+
+use tetris::ffi::{self, TetrsConfig};
+
+extern "C" {
+    // tests/ffi/roundtrip.c, built by build.rs
+    fn tetrs_roundtrip() -> i32;
+}
+
+#[test]
+fn a_c_program_plays_through_the_abi() {
+    // Taken here as well so the linker keeps them for the C side
+    let abi: [*const (); 7] = [
+        ffi::tetrs_game_new as *const (),
+        ffi::tetrs_game_input as *const (),
+        ffi::tetrs_game_tick as *const (),
+        ffi::tetrs_game_board as *const (),
+        ffi::tetrs_game_width as *const (),
+        ffi::tetrs_game_points as *const (),
+        ffi::tetrs_game_free as *const (),
+    ];
+    assert!(abi.iter().all(|function| !function.is_null()));
+    assert_eq!(unsafe { tetrs_roundtrip() }, 0);
+}
+
+#[test]
+fn the_same_seed_plays_the_same_board() {
+    let config = TetrsConfig {
+        width: 10,
+        height: 20,
+        seed: 7,
+        mode: ffi::TETRS_SPRINT,
+    };
+    let boards: Vec<Vec<u8>> = (0..2)
+        .map(|_| unsafe {
+            let game = ffi::tetrs_game_new(&config);
+            for action in [ffi::TETRS_ROTATE_CW, ffi::TETRS_HARD_DROP, ffi::TETRS_HOLD] {
+                ffi::tetrs_game_input(game, action);
+            }
+            let mut board = vec![0; ffi::tetrs_game_board(game, ptr::null_mut(), 0)];
+            // Too small, nothing is copied
+            assert_eq!(ffi::tetrs_game_board(game, board.as_mut_ptr(), 10), 200);
+            assert!(board.iter().all(|&cell| cell == 0));
+            ffi::tetrs_game_board(game, board.as_mut_ptr(), board.len());
+            ffi::tetrs_game_free(game);
+            board
+        })
+        .collect();
+    assert_eq!(boards[0], boards[1]);
+}
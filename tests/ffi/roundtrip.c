@@ -0,0 +1,69 @@
+/* A few moves through the C ABI the way a C frontend would make them, run by
+ * tests/ffi.rs. Returns 0 when everything came back as expected, otherwise the
+ * number of the check that failed. */
+
+#include <string.h>
+
+#include "tetrs.h"
+
+int tetrs_roundtrip(void) {
+    TetrsConfig config = {.width = 10, .height = 20, .seed = 42, .mode = TETRS_MARATHON};
+    TetrsGame *game = tetrs_game_new(&config);
+    if (game == NULL) {
+        return 1;
+    }
+    size_t size = tetrs_game_board(game, NULL, 0);
+    if (size != 200 || tetrs_game_width(game) != 10) {
+        return 2;
+    }
+    unsigned char board[200];
+    tetrs_game_board(game, board, sizeof board);
+    /* Only the falling tetromino is on the board, in lower case */
+    int falling = 0;
+    for (size_t i = 0; i < size; i++) {
+        if (board[i] >= 'a' && board[i] <= 'z') {
+            falling++;
+        } else if (board[i] != '.') {
+            return 3;
+        }
+    }
+    if (falling != 4) {
+        return 4;
+    }
+    if (tetrs_game_input(game, TETRS_MOVE_LEFT) != TETRS_MOVED) {
+        return 5;
+    }
+    if (tetrs_game_input(game, 99) != TETRS_UNKNOWN_ACTION) {
+        return 6;
+    }
+    if (tetrs_game_input(game, TETRS_HARD_DROP) != TETRS_LOCKED) {
+        return 7;
+    }
+    /* The locked tetromino is in upper case on the bottom row */
+    tetrs_game_board(game, board, sizeof board);
+    int locked = 0;
+    for (size_t i = 0; i < size; i++) {
+        locked += board[i] >= 'A' && board[i] <= 'Z';
+    }
+    if (locked != 4 || memchr(board + 190, '.', 10) == NULL || tetrs_game_points(game) == 0) {
+        return 8;
+    }
+    /* Gravity moves the next one down on its own, then it locks */
+    if (tetrs_game_tick(game, 1000) != TETRS_MOVED) {
+        return 9;
+    }
+    int outcome = TETRS_MOVED;
+    for (int ms = 0; ms < 30000 && outcome != TETRS_LOCKED; ms += 100) {
+        outcome = tetrs_game_tick(game, 100);
+    }
+    if (outcome != TETRS_LOCKED) {
+        return 10;
+    }
+    tetrs_game_free(game);
+    tetrs_game_free(NULL);
+    config.width = 2;
+    if (tetrs_game_new(&config) != NULL || tetrs_game_new(NULL) != NULL) {
+        return 11;
+    }
+    return 0;
+}
@@ -0,0 +1,242 @@
+use std::time::Duration;
+
+use tetris::{
+    game::{state_hash, template, Board, TetrominosBag},
+    state::{Game, GameMode, Hold, Input, Outcome, Ruleset},
+};
+
+// An unshuffled bag deals O, I, J, L, S, Z, T
+fn game() -> Game {
+    Game::with_bag(10, 20, TetrominosBag::new())
+}
+
+#[test]
+fn hard_drop_locks_and_spawns_the_next_tetromino() {
+    let mut game = game();
+    assert_eq!(game.tetromino().shape(), 'O');
+    assert_eq!(game.next().unwrap().shape(), 'I');
+    assert_eq!(game.apply(Input::HardDrop), Outcome::Locked { lines: 0 });
+    assert_eq!(game.tetromino().shape(), 'I');
+    let text = game.board().to_text();
+    assert_eq!(text.lines().last(), Some("....OO...."));
+    // Two points per cell, from the top two rows down to the floor
+    assert_eq!(game.score().points, 2 * 18);
+}
+
+#[test]
+fn soft_drop_scores_a_point_per_row() {
+    let mut game = game();
+    for _ in 0..5 {
+        assert_eq!(game.apply(Input::SoftDrop), Outcome::Moved);
+    }
+    assert_eq!(game.score().points, 5);
+    game.apply(Input::Tick);
+    assert_eq!(game.score().points, 5);
+}
+
+#[test]
+fn hold_is_only_available_once_per_tetromino() {
+    let mut game = game();
+    assert_eq!(game.apply(Input::Hold), Outcome::Moved);
+    assert_eq!(game.held().map(|held| held.shape()), Some('O'));
+    assert_eq!(game.tetromino().shape(), 'I');
+    assert!(!game.can_hold());
+    assert_eq!(game.apply(Input::Hold), Outcome::Blocked);
+    game.apply(Input::HardDrop);
+    assert!(game.can_hold());
+    assert_eq!(game.apply(Input::Hold), Outcome::Moved);
+    assert_eq!(game.tetromino().shape(), 'O');
+}
+
+fn with_hold(hold: Hold) -> Game {
+    let rules = Ruleset {
+        hold,
+        ..GameMode::Marathon.ruleset()
+    };
+    Game::with_rules(10, 20, TetrominosBag::new(), rules)
+}
+
+#[test]
+fn without_hold_a_hold_changes_nothing() {
+    let mut game = with_hold(Hold::Off);
+    let hash = game.state_hash();
+    game.take_events();
+    assert_eq!(game.apply(Input::Hold), Outcome::Blocked);
+    assert_eq!(game.state_hash(), hash);
+    assert!(game.held().is_none());
+    assert_eq!(game.take_events(), []);
+    assert_eq!(game.holds_left(), Some(0));
+    assert!(!game.can_hold());
+}
+
+#[test]
+fn a_limited_hold_runs_out() {
+    let mut game = with_hold(Hold::Limited(2));
+    assert_eq!(game.holds_left(), Some(2));
+    assert_eq!(game.apply(Input::Hold), Outcome::Moved);
+    // Once per tetromino still, a blocked hold doesn't count
+    assert_eq!(game.apply(Input::Hold), Outcome::Blocked);
+    assert_eq!(game.holds_left(), Some(1));
+    game.apply(Input::HardDrop);
+    assert_eq!(game.apply(Input::Hold), Outcome::Moved);
+    assert_eq!(game.holds_left(), Some(0));
+    game.apply(Input::HardDrop);
+    let hash = game.state_hash();
+    assert_eq!(game.apply(Input::Hold), Outcome::Blocked);
+    assert_eq!(game.state_hash(), hash);
+    assert_eq!(with_hold(Hold::Standard).holds_left(), None);
+}
+
+#[test]
+fn a_held_tetromino_comes_back_as_a_fresh_one() {
+    let mut fresh = game();
+    fresh.apply(Input::HardDrop);
+    let fresh = *fresh.tetromino();
+    let mut game = game();
+    game.apply(Input::HardDrop);
+    game.apply(Input::RotateCw);
+    game.apply(Input::RotateCw);
+    game.apply(Input::MoveLeft);
+    game.apply(Input::SoftDrop);
+    assert_eq!(game.apply(Input::Hold), Outcome::Moved);
+    for _ in 0..3 {
+        assert_eq!(game.apply(Input::HardDrop), Outcome::Locked { lines: 0 });
+    }
+    assert_eq!(game.apply(Input::Hold), Outcome::Moved);
+    assert_eq!(game.tetromino().shape(), 'I');
+    assert_eq!(game.tetromino().body(), fresh.body());
+    assert_eq!(game.tetromino().quarter_turns(), 0);
+}
+
+#[test]
+fn topping_out_ends_the_game() {
+    let mut game = game();
+    let mut outcome = Outcome::Moved;
+    for _ in 0..100 {
+        outcome = game.apply(Input::HardDrop);
+        if outcome == Outcome::GameOver {
+            break;
+        }
+    }
+    assert_eq!(outcome, Outcome::GameOver);
+    assert!(game.is_over());
+    let board = game.board().to_text();
+    assert_eq!(game.apply(Input::MoveLeft), Outcome::GameOver);
+    assert_eq!(game.board().to_text(), board);
+}
+
+#[test]
+fn the_ghost_of_a_blocked_spawn_stays_where_it_is() {
+    let mut game = game();
+    // Everything against the left wall, until a spawn runs into the stack
+    while !game.is_over() {
+        while game.apply(Input::MoveLeft) == Outcome::Moved {}
+        game.apply(Input::HardDrop);
+    }
+    assert_eq!(game.ghost().body(), game.tetromino().body());
+}
+
+#[test]
+fn pausing_freezes_the_game() {
+    let mut game = game();
+    for _ in 0..18 {
+        game.apply(Input::Tick);
+    }
+    game.toggle_pause();
+    assert!(game.is_paused());
+    for input in [
+        Input::MoveLeft,
+        Input::RotateCw,
+        Input::HardDrop,
+        Input::Hold,
+    ] {
+        assert_eq!(game.apply(input), Outcome::Blocked);
+    }
+    assert_eq!(game.elapse(Duration::from_secs(10)), Outcome::Blocked);
+    assert_eq!(game.tetromino().shape(), 'O');
+    game.toggle_pause();
+    assert_eq!(game.apply(Input::MoveLeft), Outcome::Moved);
+}
+
+// Shifts the tetromino until its leftmost cell is in the given column
+fn move_to(game: &mut Game, column: usize) {
+    loop {
+        let left = game
+            .tetromino()
+            .body()
+            .iter()
+            .map(|(x, _)| *x)
+            .min()
+            .unwrap();
+        let input = match left.cmp(&column) {
+            std::cmp::Ordering::Less => Input::MoveRight,
+            std::cmp::Ordering::Greater => Input::MoveLeft,
+            std::cmp::Ordering::Equal => return,
+        };
+        assert_eq!(game.apply(input), Outcome::Moved);
+    }
+}
+
+#[test]
+fn full_rows_wait_for_finish_clear() {
+    let mut game = game();
+    // O, I and J leave a gap in the bottom row that the foot of the L fills
+    for (column, rotate) in [(0, false), (6, false), (2, false), (4, true)] {
+        if rotate {
+            game.apply(Input::RotateCcw);
+        }
+        move_to(&mut game, column);
+        if game.tetromino().shape() != 'L' {
+            assert_eq!(game.apply(Input::HardDrop), Outcome::Locked { lines: 0 });
+        }
+    }
+    assert_eq!(game.apply(Input::HardDrop), Outcome::Locked { lines: 1 });
+    assert_eq!(game.clearing_rows(), [19]);
+    assert_eq!(game.board().to_text().lines().last(), Some("OOJJJLIIII"));
+    assert_eq!(game.apply(Input::MoveLeft), Outcome::Blocked);
+    assert_eq!(game.elapse(Duration::from_secs(1)), Outcome::Blocked);
+    assert_eq!(game.score().lines, 0);
+
+    assert_eq!(game.finish_clear(), Outcome::Locked { lines: 1 });
+    assert!(!game.is_clearing());
+    assert_eq!(game.score().lines, 1);
+    assert_eq!(game.tetromino().shape(), 'S');
+    let text = game.board().to_text();
+    let rows: Vec<&str> = text.lines().collect();
+    assert_eq!(rows[18..], ["....LL....", "OOJ..L...."]);
+}
+
+#[test]
+fn the_state_hash_follows_the_game() {
+    let (mut game, mut same) = (game(), game());
+    assert_eq!(game.state_hash(), same.state_hash());
+    let start = game.state_hash();
+    game.apply(Input::MoveLeft);
+    assert_ne!(game.state_hash(), start);
+    game.apply(Input::MoveRight);
+    assert_eq!(game.state_hash(), start);
+    game.apply(Input::HardDrop);
+    same.apply(Input::HardDrop);
+    assert_eq!(game.state_hash(), same.state_hash());
+}
+
+#[test]
+fn holding_changes_the_state_hash() {
+    let mut game = game();
+    let before = game.state_hash();
+    assert_eq!(game.apply(Input::Hold), Outcome::Moved);
+    assert_ne!(game.state_hash(), before);
+
+    // Only the held tetromino differs, or whether hold can be used
+    let (board, bag) = (Board::new(10, 20), TetrominosBag::new());
+    let falling = template('T').unwrap();
+    let hashes = [
+        state_hash(&board, &falling, None, true, &bag),
+        state_hash(&board, &falling, Some('I'), true, &bag),
+        state_hash(&board, &falling, Some('O'), true, &bag),
+        state_hash(&board, &falling, Some('O'), false, &bag),
+    ];
+    for (i, hash) in hashes.iter().enumerate() {
+        assert!(!hashes[i + 1..].contains(hash), "{i}");
+    }
+}
@@ -0,0 +1,162 @@
+use std::{env, fs, path::PathBuf, process, time::Duration};
+
+use tetris::highscores::{replay_hash, Entry, HighScores, Ranking, ReplayRef};
+
+fn entry(points: u32, name: &str) -> Entry {
+    Entry {
+        points,
+        lines: points / 100,
+        level: 1,
+        time: Duration::from_secs(u64::from(points)),
+        timestamp: 1_700_000_000,
+        name: name.to_string(),
+        replay: None,
+    }
+}
+
+// A directory of its own for every test, removed up front in case an earlier run left it
+fn scratch_dir(test: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("tetrs-{test}-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn entries_are_kept_best_first_and_ties_go_to_the_older_one() {
+    let mut high_scores = HighScores::default();
+    assert_eq!(high_scores.insert(entry(500, "a")), Some(0));
+    assert_eq!(high_scores.insert(entry(900, "b")), Some(0));
+    assert_eq!(high_scores.insert(entry(500, "c")), Some(2));
+    let names: Vec<&str> = high_scores
+        .entries()
+        .iter()
+        .map(|entry| entry.name.as_str())
+        .collect();
+    assert_eq!(names, ["b", "a", "c"]);
+}
+
+#[test]
+fn only_the_top_ten_are_kept() {
+    let mut high_scores = HighScores::default();
+    for points in 1..=10 {
+        high_scores.insert(entry(points * 100, ""));
+    }
+    assert!(!high_scores.qualifies(&entry(100, "")));
+    assert_eq!(high_scores.insert(entry(100, "")), None);
+    assert!(high_scores.qualifies(&entry(150, "")));
+    assert_eq!(high_scores.insert(entry(150, "")), Some(9));
+    assert_eq!(high_scores.entries().len(), HighScores::MAX_ENTRIES);
+    assert_eq!(high_scores.entries().last().unwrap().points, 150);
+}
+
+#[test]
+fn a_scoreless_run_never_qualifies() {
+    assert!(!HighScores::default().qualifies(&entry(0, "")));
+}
+
+#[test]
+fn text_round_trips() {
+    let mut high_scores = HighScores::default();
+    high_scores.insert(entry(1200, "with spaces"));
+    high_scores.insert(entry(300, ""));
+    let parsed = HighScores::parse(&high_scores.to_text(), Ranking::Points).unwrap();
+    assert_eq!(parsed.entries(), high_scores.entries());
+}
+
+#[test]
+fn timed_tables_are_fastest_first() {
+    let mut high_scores = HighScores::new(Ranking::Time);
+    assert_eq!(high_scores.insert(entry(900, "slow")), Some(0));
+    assert_eq!(high_scores.insert(entry(300, "fast")), Some(0));
+    let parsed = HighScores::parse(&high_scores.to_text(), Ranking::Time).unwrap();
+    assert_eq!(parsed.entries()[0].name, "fast");
+    assert_eq!(parsed.entries()[1].time, Duration::from_secs(900));
+}
+
+#[test]
+fn tables_from_before_the_time_column_still_load() {
+    let text = "tetrs highscores 1\n1200\t12\t2\t1700000000\told\n";
+    let high_scores = HighScores::parse(text, Ranking::Points).unwrap();
+    let old = &high_scores.entries()[0];
+    assert_eq!((old.points, old.time), (1200, Duration::ZERO));
+    assert_eq!(old.name, "old");
+}
+
+#[test]
+fn tables_from_before_the_replay_columns_still_load() {
+    let text = "tetrs highscores 2\n1200\t12\t2\t61250\t1700000000\told\n";
+    let high_scores = HighScores::parse(text, Ranking::Points).unwrap();
+    let old = &high_scores.entries()[0];
+    assert_eq!(
+        (old.time, old.replay.clone()),
+        (Duration::from_millis(61250), None)
+    );
+    assert_eq!(old.name, "old");
+}
+
+#[test]
+fn an_entry_is_verified_only_while_its_replay_is_unchanged() {
+    let dir = scratch_dir("verified");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("run.json"), "{\"version\": 3}").unwrap();
+    let mut kept = entry(800, "me");
+    kept.replay = Some(ReplayRef {
+        file: "run.json".to_string(),
+        hash: replay_hash(b"{\"version\": 3}"),
+    });
+    let mut high_scores = HighScores::default();
+    high_scores.insert(kept.clone());
+    high_scores.insert(entry(400, "old"));
+    let parsed = HighScores::parse(&high_scores.to_text(), Ranking::Points).unwrap();
+    assert_eq!(parsed.entries()[0].replay, kept.replay);
+    assert!(kept.verified(&dir));
+    assert!(!entry(400, "old").verified(&dir));
+    fs::write(dir.join("run.json"), "{\"version\": 4}").unwrap();
+    assert!(!kept.verified(&dir));
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(!kept.verified(&dir));
+}
+
+#[test]
+fn names_lose_control_characters_and_get_cut_short() {
+    let entry = Entry::new(
+        Default::default(),
+        Duration::ZERO,
+        "tab\there\nand a very long name",
+    );
+    assert_eq!(entry.name, "tabhereand a");
+}
+
+#[test]
+fn corrupt_files_start_a_fresh_table() {
+    assert!(HighScores::parse("not a high score table", Ranking::Points).is_none());
+    let text = "tetrs highscores 2\n100\tten\t1\t0\t0\tname\n";
+    assert!(HighScores::parse(text, Ranking::Points).is_none());
+    let dir = scratch_dir("corrupt");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("highscores");
+    fs::write(&path, [0xff, 0xfe, 0x00]).unwrap();
+    assert!(HighScores::load(&path, Ranking::Points)
+        .entries()
+        .is_empty());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn saving_creates_the_directory_and_leaves_no_temporary_file() {
+    let dir = scratch_dir("save");
+    let path = dir.join("nested").join("highscores");
+    assert!(HighScores::load(&path, Ranking::Points)
+        .entries()
+        .is_empty());
+    let mut high_scores = HighScores::default();
+    high_scores.insert(entry(800, "me"));
+    high_scores.save(&path).unwrap();
+    assert_eq!(
+        HighScores::load(&path, Ranking::Points).entries(),
+        high_scores.entries()
+    );
+    let files = fs::read_dir(dir.join("nested")).unwrap().count();
+    assert_eq!(files, 1);
+    fs::remove_dir_all(&dir).unwrap();
+}
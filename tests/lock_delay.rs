@@ -0,0 +1,164 @@
+use std::time::Duration;
+
+use tetris::{
+    game::TetrominosBag,
+    state::{Game, Input, Outcome, SoftDropLock},
+};
+
+// The first tetromino is an O, sitting on the floor after 18 rows
+fn grounded_game() -> Game {
+    let mut game = Game::with_bag(10, 20, TetrominosBag::new());
+    for _ in 0..18 {
+        assert_eq!(game.apply(Input::Tick), Outcome::Moved);
+    }
+    game
+}
+
+#[test]
+fn landing_waits_for_the_lock_delay() {
+    let mut game = grounded_game();
+    assert_eq!(game.apply(Input::Tick), Outcome::Blocked);
+    assert_eq!(game.apply(Input::SoftDrop), Outcome::Blocked);
+    assert_eq!(game.elapse(Duration::from_millis(499)), Outcome::Blocked);
+    assert_eq!(game.tetromino().shape(), 'O');
+    assert_eq!(
+        game.elapse(Duration::from_millis(1)),
+        Outcome::Locked { lines: 0 }
+    );
+    assert_eq!(game.tetromino().shape(), 'I');
+}
+
+#[test]
+fn airborne_tetrominos_never_lock() {
+    let mut game = Game::with_bag(10, 20, TetrominosBag::new());
+    assert_eq!(game.lock_delay_left(), None);
+    assert_eq!(game.elapse(Duration::from_secs(10)), Outcome::Blocked);
+    assert_eq!(game.tetromino().shape(), 'O');
+}
+
+#[test]
+fn moving_restarts_the_lock_delay() {
+    let mut game = grounded_game();
+    game.elapse(Duration::from_millis(400));
+    assert_eq!(game.apply(Input::MoveLeft), Outcome::Moved);
+    assert_eq!(game.lock_delay_left(), Some(Duration::from_millis(500)));
+    assert_eq!(game.elapse(Duration::from_millis(400)), Outcome::Blocked);
+    assert_eq!(game.apply(Input::RotateCw), Outcome::Moved);
+    assert_eq!(game.elapse(Duration::from_millis(400)), Outcome::Blocked);
+}
+
+#[test]
+fn resets_run_out() {
+    let mut game = grounded_game();
+    game.elapse(Duration::ZERO);
+    for _ in 0..14 {
+        assert_eq!(game.apply(Input::RotateCw), Outcome::Moved);
+        assert_eq!(game.elapse(Duration::from_millis(100)), Outcome::Blocked);
+    }
+    // The 15th and last reset, the next grounded frame locks however much delay is left
+    assert_eq!(game.apply(Input::RotateCw), Outcome::Moved);
+    assert_eq!(game.elapse(Duration::ZERO), Outcome::Locked { lines: 0 });
+}
+
+#[test]
+fn hard_drop_skips_the_lock_delay() {
+    let mut game = Game::with_bag(10, 20, TetrominosBag::new());
+    assert_eq!(game.apply(Input::HardDrop), Outcome::Locked { lines: 0 });
+}
+
+fn grounded_with(lock: SoftDropLock) -> Game {
+    let mut game = grounded_game();
+    game.set_soft_drop_lock(lock);
+    game
+}
+
+#[test]
+fn with_tap_only_a_fresh_soft_drop_locks() {
+    let mut game = grounded_with(SoftDropLock::Tap);
+    assert_eq!(game.apply(Input::SoftDropRepeat), Outcome::Blocked);
+    assert_eq!(game.apply(Input::SoftDropToStackRepeat), Outcome::Blocked);
+    assert_eq!(game.elapse(Duration::from_millis(400)), Outcome::Blocked);
+    assert_eq!(game.apply(Input::SoftDrop), Outcome::Locked { lines: 0 });
+    // The next one is held on its way down, and waits out the lock delay on the stack
+    for _ in 0..30 {
+        game.apply(Input::SoftDropRepeat);
+    }
+    assert_eq!(game.tetromino().shape(), 'I');
+    assert_eq!(game.elapse(Duration::from_millis(499)), Outcome::Blocked);
+    assert_eq!(
+        game.elapse(Duration::from_millis(1)),
+        Outcome::Locked { lines: 0 }
+    );
+}
+
+#[test]
+fn with_hold_a_held_soft_drop_locks_too() {
+    let mut game = grounded_with(SoftDropLock::Hold);
+    assert_eq!(
+        game.apply(Input::SoftDropRepeat),
+        Outcome::Locked { lines: 0 }
+    );
+    assert_eq!(game.tetromino().shape(), 'I');
+    // Still in the air it only goes down
+    assert_eq!(game.apply(Input::SoftDropRepeat), Outcome::Moved);
+}
+
+#[test]
+fn with_never_soft_drops_leave_it_to_the_lock_delay() {
+    let mut game = grounded_with(SoftDropLock::Never);
+    for input in [
+        Input::SoftDrop,
+        Input::SoftDropRepeat,
+        Input::SoftDropToStack,
+        Input::SoftDropToStackRepeat,
+    ] {
+        assert_eq!(game.apply(input), Outcome::Blocked);
+    }
+    assert_eq!(game.elapse(Duration::from_millis(499)), Outcome::Blocked);
+    assert_eq!(
+        game.elapse(Duration::from_millis(1)),
+        Outcome::Locked { lines: 0 }
+    );
+}
+
+// With an infinite soft drop factor the first soft drop takes the tetromino all the
+// way down, only one that finds it already there can lock it
+#[test]
+fn an_infinite_soft_drop_only_locks_once_on_the_stack() {
+    for lock in [SoftDropLock::Tap, SoftDropLock::Hold] {
+        let mut game = Game::with_bag(10, 20, TetrominosBag::new());
+        game.set_soft_drop_lock(lock);
+        assert_eq!(game.apply(Input::SoftDropToStack), Outcome::Moved);
+        assert_eq!(game.lock_delay_left(), Some(Duration::from_millis(500)));
+        let held = game.apply(Input::SoftDropToStackRepeat);
+        match lock {
+            SoftDropLock::Tap => {
+                assert_eq!(held, Outcome::Blocked);
+                assert_eq!(
+                    game.apply(Input::SoftDropToStack),
+                    Outcome::Locked { lines: 0 }
+                );
+            }
+            _ => assert_eq!(held, Outcome::Locked { lines: 0 }),
+        }
+    }
+}
+
+// At 20G the tetromino is on the stack the moment it spawns, a soft drop held from one
+// tetromino to the next mustn't lock every one of them straight away
+#[test]
+fn at_20g_a_held_soft_drop_waits_for_the_lock_delay_with_tap() {
+    let mut game = grounded_with(SoftDropLock::Tap);
+    assert_eq!(game.apply(Input::HardDrop), Outcome::Locked { lines: 0 });
+    while game.apply(Input::Tick) == Outcome::Moved {}
+    assert_eq!(game.apply(Input::SoftDropRepeat), Outcome::Blocked);
+    assert_eq!(game.elapse(Duration::from_millis(250)), Outcome::Blocked);
+    assert_eq!(game.apply(Input::MoveLeft), Outcome::Moved);
+    assert_eq!(game.apply(Input::SoftDropRepeat), Outcome::Blocked);
+    assert_eq!(game.lock_delay_left(), Some(Duration::from_millis(500)));
+    game.set_soft_drop_lock(SoftDropLock::Hold);
+    assert_eq!(
+        game.apply(Input::SoftDropRepeat),
+        Outcome::Locked { lines: 0 }
+    );
+}
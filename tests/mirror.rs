@@ -0,0 +1,83 @@
+use tetris::game::{mirrored_shape, template, Board, Playcell, TetrominosBag};
+
+const SHAPES: [char; 7] = ['O', 'I', 'J', 'L', 'S', 'Z', 'T'];
+
+#[test]
+fn mirrored_shapes_swap_the_chiral_pairs() {
+    let mirrored: String = SHAPES.iter().map(|&shape| mirrored_shape(shape)).collect();
+    assert_eq!(mirrored, "OILJZST");
+    for shape in SHAPES.into_iter().chain(['G', '.']) {
+        assert_eq!(mirrored_shape(mirrored_shape(shape)), shape);
+    }
+    assert_eq!(mirrored_shape('G'), 'G');
+}
+
+#[test]
+fn templates_match_what_the_bag_deals() {
+    let mut bag = TetrominosBag::new();
+    for shape in SHAPES {
+        let dealt = bag.get().unwrap();
+        let template = template(shape).unwrap();
+        assert_eq!(dealt.shape(), shape);
+        assert_eq!(template.shape(), shape);
+        assert_eq!(template.body(), dealt.body());
+    }
+    assert!(template('G').is_none());
+    assert!(template('X').is_none());
+}
+
+#[test]
+fn mirroring_flips_the_rows_and_the_pieces() {
+    let board = Board::from_text("J..S\nJJJG\nZZ.T").unwrap();
+    let mirrored = board.mirrored();
+    assert_eq!(mirrored.to_text(), "Z..L\nGLLL\nT.SS");
+    assert_eq!(mirrored.row_fill_counts(), board.row_fill_counts());
+    assert_eq!(mirrored.mirrored(), board);
+}
+
+#[test]
+fn mirroring_keeps_the_falling_tetromino_active() {
+    let mut board = Board::new(4, 2);
+    board.tiles[0][0] = Some(Playcell::new(true, 'S'));
+    let mirrored = board.mirrored();
+    let cell = mirrored.tiles[0][3].unwrap();
+    assert!(cell.is_active);
+    assert_eq!(cell.shape, 'Z');
+    assert!(mirrored.tiles[0][0].is_none());
+}
+
+#[test]
+fn templates_match_on_locked_cells_whatever_the_piece() {
+    let board = Board::from_text("....\nT...\nTTGG").unwrap();
+    let template = Board::from_text("....\nG...\nGGGG").unwrap();
+    assert!(board.matches_template(&template, &[]));
+    assert!(board.matches_template(&board, &[]));
+
+    let mut with_piece = board.clone();
+    with_piece.tiles[0][3] = Some(Playcell::new(true, 'I'));
+    assert!(with_piece.matches_template(&template, &[]));
+}
+
+#[test]
+fn templates_only_ignore_masked_cells() {
+    let board = Board::from_text("....\nT...\nTTGG").unwrap();
+    let template = Board::from_text("....\n....\nGG.G").unwrap();
+    assert!(!board.matches_template(&template, &[]));
+    let mut mask = vec![vec![false; 4]; 3];
+    mask[1][0] = true;
+    assert!(!board.matches_template(&template, &mask));
+    mask[2][2] = true;
+    assert!(board.matches_template(&template, &mask));
+    // Cells the mask doesn't reach are compared
+    assert!(!board.matches_template(&template, &mask[..2]));
+}
+
+#[test]
+fn templates_of_another_size_never_match() {
+    let board = Board::from_text("..\nGG").unwrap();
+    for other in ["..\n..\nGG", "...\nGG."] {
+        let template = Board::from_text(other).unwrap();
+        let mask = vec![vec![true; 3]; 3];
+        assert!(!board.matches_template(&template, &mask), "{other:?}");
+    }
+}
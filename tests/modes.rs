@@ -0,0 +1,211 @@
+use std::time::Duration;
+
+use tetris::{
+    game::{SequenceEnd, TetrominosBag},
+    score::Score,
+    state::{Game, GameEvent, GameMode, Hold, Input, Outcome, Ruleset},
+};
+
+fn game(mode: GameMode) -> Game {
+    Game::with_mode(10, 20, TetrominosBag::new(), mode)
+}
+
+#[test]
+fn ultra_ends_when_the_time_is_up() {
+    let mut game = game(GameMode::Ultra);
+    let limit = GameMode::Ultra.ruleset().time_limit().unwrap();
+    assert_eq!(
+        game.elapse(limit - Duration::from_millis(1)),
+        Outcome::Blocked
+    );
+    assert_eq!(game.time_left(), Some(Duration::from_millis(1)));
+    assert_eq!(game.elapse(Duration::from_millis(5)), Outcome::GameOver);
+    assert!(game.is_over());
+    assert!(game.is_completed());
+    assert_eq!(game.played(), limit);
+    assert_eq!(game.time_left(), Some(Duration::ZERO));
+}
+
+#[test]
+fn a_paused_game_stops_the_clock() {
+    let mut game = game(GameMode::Ultra);
+    game.elapse(Duration::from_secs(10));
+    game.toggle_pause();
+    assert_eq!(game.elapse(Duration::from_secs(200)), Outcome::Blocked);
+    assert_eq!(game.played(), Duration::from_secs(10));
+    assert!(!game.is_over());
+}
+
+#[test]
+fn only_ultra_has_a_time_limit() {
+    for mode in [GameMode::Marathon, GameMode::Sprint] {
+        let mut game = game(mode);
+        assert_eq!(game.time_left(), None);
+        game.elapse(Duration::from_secs(3600));
+        assert!(!game.is_over());
+        assert_eq!(game.played(), Duration::from_secs(3600));
+    }
+}
+
+#[test]
+fn topping_out_never_completes_a_game() {
+    let mut game = game(GameMode::Sprint);
+    while game.apply(Input::HardDrop) != Outcome::GameOver {}
+    assert!(game.is_over());
+    assert!(!game.is_completed());
+}
+
+#[test]
+fn only_a_marathon_levels_up() {
+    assert_eq!(game(GameMode::Marathon).score().level, 1);
+    let mut score = game(GameMode::Sprint).score();
+    for _ in 0..5 {
+        score.line_clear(4, None);
+    }
+    assert_eq!(score.level, 1);
+    let mut marathon = Score::new();
+    for _ in 0..5 {
+        marathon.line_clear(4, None);
+    }
+    assert_eq!(marathon.level, 3);
+}
+
+#[test]
+fn a_custom_ruleset_decides_the_game() {
+    let rules = Ruleset {
+        id: "drill".to_string(),
+        name: "Drill".to_string(),
+        line_goal: Some(2),
+        hold: Hold::Off,
+        scoring: false,
+        ..GameMode::Marathon.ruleset()
+    };
+    let mut game = Game::with_rules(10, 20, TetrominosBag::new(), rules);
+    assert_eq!(game.mode(), GameMode::Custom);
+    // The unshuffled bag deals an O first, right into the gap
+    game.load_stack("GGGG..GGGG\nGGGG..GGGG").unwrap();
+    assert_eq!(game.apply(Input::Hold), Outcome::Blocked);
+    assert_eq!(game.apply(Input::HardDrop), Outcome::Locked { lines: 2 });
+    assert_eq!(game.finish_clear(), Outcome::GameOver);
+    assert!(game.is_completed());
+    assert_eq!(game.score().lines, 2);
+    assert_eq!(game.score().points, 0);
+}
+
+#[test]
+fn the_built_in_modes_are_rulesets_too() {
+    for mode in GameMode::ALL {
+        let rules = mode.ruleset();
+        assert_eq!(rules.mode(), mode);
+        assert_eq!(game(mode).rules(), &rules);
+    }
+    assert_eq!(GameMode::Sprint.ruleset().line_goal, Some(40));
+    assert!(GameMode::Marathon.ruleset().level_up);
+}
+
+// An unshuffled bag deals an O first, right into the gap
+fn clearing(rules: Ruleset) -> Game {
+    let mut game = Game::with_rules(10, 20, TetrominosBag::new(), rules);
+    game.load_stack("GGGG..GGGG\nGGGG..GGGG").unwrap();
+    assert_eq!(game.apply(Input::HardDrop), Outcome::Locked { lines: 2 });
+    game.take_events();
+    game
+}
+
+#[test]
+fn with_zangi_a_slide_decides_which_lines_clear() {
+    let rules = Ruleset {
+        zangi: true,
+        ..GameMode::Marathon.ruleset()
+    };
+    let mut game = clearing(rules);
+    // Over the stack on the right, where it fills no row
+    assert_eq!(game.apply(Input::MoveRight), Outcome::Moved);
+    assert_eq!(
+        game.take_events(),
+        [GameEvent::PieceSlid {
+            cells: [(5, 16), (5, 17), (6, 16), (6, 17)],
+        }]
+    );
+    assert!(game.clearing_rows().is_empty());
+    assert!(game.is_clearing());
+    let points = game.score().points;
+    assert_eq!(game.finish_clear(), Outcome::Locked { lines: 0 });
+    assert_eq!(game.score().lines, 0);
+    assert_eq!(game.score().points, points);
+}
+
+#[test]
+fn without_zangi_the_clearing_tetromino_stays_put() {
+    let mut game = clearing(GameMode::Marathon.ruleset());
+    assert_eq!(game.apply(Input::MoveRight), Outcome::Blocked);
+    assert_eq!(game.take_events(), []);
+    assert_eq!(game.clearing_rows(), [18, 19]);
+    assert_eq!(game.finish_clear(), Outcome::Locked { lines: 2 });
+    assert_eq!(game.score().lines, 2);
+    assert_eq!(game.last_clear().unwrap().name().as_deref(), Some("DOUBLE"));
+}
+
+fn adaptive(shapes: &str) -> Game {
+    let bag = TetrominosBag::scripted(shapes, SequenceEnd::Loop).unwrap();
+    Game::with_rules(10, 20, bag, GameMode::Adaptive.ruleset())
+}
+
+// Moves the tetromino `columns` over, negative to the left, and hard drops it
+fn drop_at(game: &mut Game, columns: i32) -> Outcome {
+    let input = if columns < 0 {
+        Input::MoveLeft
+    } else {
+        Input::MoveRight
+    };
+    for _ in 0..columns.abs() {
+        game.apply(input);
+    }
+    match game.apply(Input::HardDrop) {
+        Outcome::Locked { lines } if lines > 0 => game.finish_clear(),
+        outcome => outcome,
+    }
+}
+
+#[test]
+fn adaptive_speeds_up_for_clean_clears() {
+    let mut game = adaptive("O");
+    // Five Os side by side clear two lines without a hole, twice
+    for _ in 0..2 {
+        assert_eq!(game.score().speed_level(), 1);
+        for columns in [-4, -2, 0, 2] {
+            assert_eq!(drop_at(&mut game, columns), Outcome::Locked { lines: 0 });
+        }
+        assert_eq!(drop_at(&mut game, 4), Outcome::Locked { lines: 2 });
+    }
+    assert_eq!(game.score().level, 1);
+    assert_eq!(game.score().speed_level(), 2);
+    // The same pace without adaptive stays at the level
+    let mut marathon = Game::with_bag(
+        10,
+        20,
+        TetrominosBag::scripted("O", SequenceEnd::Loop).unwrap(),
+    );
+    for columns in [-4, -2, 0, 2, 4, -4, -2, 0, 2, 4] {
+        drop_at(&mut marathon, columns);
+    }
+    assert_eq!(marathon.score().speed_level(), 1);
+}
+
+#[test]
+fn adaptive_eases_off_while_the_stack_is_high_and_no_further_than_two_levels() {
+    let mut game = adaptive("OOOOI");
+    game.load_stack(&["GGGGGGGGG."; 13].join("\n")).unwrap();
+    // Up to 15 rows, past the danger line, on and on every 11 seconds
+    for (columns, offset) in [(0, 0), (-4, -1), (-2, -2), (2, -2)] {
+        assert_eq!(drop_at(&mut game, columns), Outcome::Locked { lines: 0 });
+        assert_eq!(game.score().speed_offset, offset);
+        game.elapse(Duration::from_secs(11));
+    }
+    // An I down the well takes four rows off, and the easing with them
+    game.apply(Input::RotateCw);
+    let column = game.tetromino().body()[0].0 as i32;
+    assert_eq!(drop_at(&mut game, 9 - column), Outcome::Locked { lines: 4 });
+    assert_eq!(game.board().stack_height(), 11);
+    assert_eq!(game.score().speed_offset, 0);
+}
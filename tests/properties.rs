@@ -0,0 +1,144 @@
+use std::time::Duration;
+
+use proptest::prelude::*;
+use tetris::{
+    game::{template, Board, Direction, TetrominosBag},
+    state::{Game, Input, Outcome},
+};
+
+const SHAPES: [char; 7] = ['O', 'I', 'J', 'L', 'S', 'Z', 'T'];
+
+#[derive(Clone, Copy, Debug)]
+enum Step {
+    Apply(Input),
+    Elapse(u64),
+}
+
+fn step() -> impl Strategy<Value = Step> {
+    // Plenty of hard drops, so that narrow boards fill up and clear lines
+    prop_oneof![
+        2 => Just(Step::Apply(Input::HardDrop)),
+        6 => prop::sample::select(vec![
+            Input::MoveLeft,
+            Input::MoveRight,
+            Input::SoftDrop,
+            Input::HardDrop,
+            Input::RotateCw,
+            Input::RotateCcw,
+            Input::Hold,
+            Input::Tick,
+        ])
+        .prop_map(Step::Apply),
+        1 => (0..600u64).prop_map(Step::Elapse),
+    ]
+}
+
+// Rows of garbage with random gaps, under a few empty rows to spawn in
+fn stack(width: usize) -> impl Strategy<Value = Board> {
+    prop::collection::vec(prop::collection::vec(any::<bool>(), width), 0..16).prop_map(
+        move |rows| {
+            let mut board = Board::new(width, 20);
+            let text: Vec<String> = rows
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|&full| if full { 'G' } else { '.' })
+                        .collect()
+                })
+                .collect();
+            board.load_text(&text.join("\n")).unwrap();
+            board
+        },
+    )
+}
+
+fn locked_cells(board: &Board) -> usize {
+    board
+        .row_fill_counts()
+        .iter()
+        .map(|&count| usize::from(count))
+        .sum()
+}
+
+fn active_cells(board: &Board) -> Vec<(usize, usize)> {
+    let mut cells = Vec::new();
+    for (y, row) in board.tiles.iter().enumerate() {
+        for (x, cell) in row.iter().enumerate() {
+            if cell.is_some_and(|cell| cell.is_active) {
+                cells.push((x, y));
+            }
+        }
+    }
+    cells
+}
+
+proptest! {
+    #[test]
+    fn shifts_never_move_into_a_locked_cell(
+        board in stack(10),
+        shape in prop::sample::select(SHAPES.to_vec()),
+        moves in prop::collection::vec(0..4usize, 1..40),
+    ) {
+        let mut board = board;
+        let mut tetromino = template(shape).unwrap();
+        prop_assume!(tetromino.spawn(&mut board).is_ok());
+        for direction in moves {
+            let direction = [Direction::Left, Direction::Right, Direction::Down, Direction::Up][direction];
+            let before = board.clone();
+            if tetromino.shift(&mut board, direction).is_ok() {
+                for (x, y) in tetromino.body() {
+                    prop_assert!(!before.is_occupied(x, y), "moved into ({x}, {y})");
+                }
+            } else {
+                prop_assert_eq!(&board, &before);
+            }
+        }
+    }
+
+    // Whatever the player does the falling tetromino is four active cells on the board,
+    // every lock adds four locked cells and every cleared line takes a row's worth away
+    #[test]
+    fn games_keep_their_cells_in_check(
+        seed in any::<u64>(),
+        width in 4..=10usize,
+        steps in prop::collection::vec(step(), 1..300),
+    ) {
+        let mut bag = TetrominosBag::new();
+        bag.reseed(seed);
+        bag.shuffle();
+        let mut game = Game::with_bag(width, 20, bag);
+        for step in steps {
+            let locked = locked_cells(game.board());
+            let lines = game.score().lines;
+            let mut outcome = match step {
+                Step::Apply(input) => game.apply(input),
+                Step::Elapse(millis) => game.elapse(Duration::from_millis(millis)),
+            };
+            let mut cleared = 0;
+            if game.is_clearing() {
+                cleared = game.clearing_rows().len();
+                prop_assert_eq!(outcome, Outcome::Locked { lines: cleared });
+                outcome = game.finish_clear();
+            }
+            if game.is_over() {
+                break;
+            }
+
+            let board = game.board();
+            let mut body = game.tetromino().body().to_vec();
+            body.sort_by_key(|&(x, y)| (y, x));
+            prop_assert_eq!(active_cells(board), body);
+            prop_assert!(board.row_fill_counts().iter().all(|&count| usize::from(count) < width));
+
+            prop_assert_eq!(game.score().lines, lines + cleared as u32);
+            let added = match outcome {
+                Outcome::Locked { lines } => {
+                    prop_assert_eq!(lines, cleared);
+                    4
+                }
+                _ => 0,
+            };
+            prop_assert_eq!(locked_cells(board) + width * cleared, locked + added);
+        }
+    }
+}
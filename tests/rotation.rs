@@ -0,0 +1,198 @@
+use tetris::game::{template, Board, Direction, KickFailure, Tetromino};
+
+fn spawned(shape: char, board: &mut Board) -> Tetromino {
+    let mut tetromino = template(shape).unwrap();
+    assert!(tetromino.spawn(board).is_ok());
+    tetromino
+}
+
+fn active_cells(board: &Board) -> usize {
+    board
+        .tiles
+        .iter()
+        .flatten()
+        .filter(|cell| cell.is_some_and(|cell| cell.is_active))
+        .count()
+}
+
+#[test]
+fn rotating_against_either_wall_stays_on_the_board() {
+    for direction in [Direction::Left, Direction::Right] {
+        for clockwise in [true, false] {
+            let mut board = Board::new(10, 20);
+            let mut tetromino = spawned('T', &mut board);
+            for _ in 0..3 {
+                assert!(tetromino.shift(&mut board, Direction::Down).is_ok());
+            }
+            while tetromino.shift(&mut board, direction).is_ok() {}
+            for _ in 0..4 {
+                tetromino.rotate(&mut board, clockwise);
+                assert!(tetromino.body().iter().all(|(x, y)| *x < 10 && *y < 20));
+                assert_eq!(active_cells(&board), 4);
+            }
+        }
+    }
+}
+
+#[test]
+fn i_rotates_back_flat_when_flush_against_the_wall() {
+    for direction in [Direction::Left, Direction::Right] {
+        let mut board = Board::new(10, 20);
+        let mut tetromino = spawned('I', &mut board);
+        for _ in 0..3 {
+            assert!(tetromino.shift(&mut board, Direction::Down).is_ok());
+        }
+        assert!(tetromino.rotate(&mut board, true).is_some());
+        while tetromino.shift(&mut board, direction).is_ok() {}
+        assert!(tetromino.rotate(&mut board, false).is_some());
+        let rows: Vec<usize> = tetromino.body().iter().map(|(_, y)| *y).collect();
+        assert!(rows.iter().all(|y| *y == rows[0]));
+        assert_eq!(active_cells(&board), 4);
+    }
+}
+
+#[test]
+fn blocked_rotation_changes_nothing() {
+    let mut board = Board::new(10, 20);
+    board.load_text(&["GGGGGGGGGG"; 19].join("\n")).unwrap();
+    let mut tetromino = spawned('I', &mut board);
+    let body = tetromino.body();
+    assert!(tetromino.rotate(&mut board, true).is_none());
+    assert!(tetromino.rotate(&mut board, false).is_none());
+    assert_eq!(tetromino.body(), body);
+    assert_eq!(active_cells(&board), 4);
+}
+
+#[test]
+fn o_rotates_in_place() {
+    let mut board = Board::new(10, 20);
+    let mut tetromino = spawned('O', &mut board);
+    let mut body = tetromino.body();
+    body.sort_unstable();
+    for clockwise in [true, true, false] {
+        assert!(tetromino.rotate(&mut board, clockwise).is_some());
+        let mut rotated = tetromino.body();
+        rotated.sort_unstable();
+        assert_eq!(rotated, body);
+    }
+}
+
+fn sorted_body(tetromino: &Tetromino) -> Vec<(usize, usize)> {
+    let mut body = tetromino.body().to_vec();
+    body.sort_unstable();
+    body
+}
+
+#[test]
+fn i_in_a_one_wide_well() {
+    let mut board = Board::new(10, 20);
+    board.load_text(&[".GGGGGGGGG"; 4].join("\n")).unwrap();
+    let mut tetromino = spawned('I', &mut board);
+    assert!(tetromino.rotate(&mut board, true).is_some());
+    while tetromino.shift(&mut board, Direction::Left).is_ok() {}
+    tetromino.hard_drop(&mut board);
+    assert_eq!(
+        sorted_body(&tetromino),
+        [(0, 16), (0, 17), (0, 18), (0, 19)]
+    );
+    // None of the kicks lifts it far enough out of the well to lie flat
+    for clockwise in [true, false] {
+        assert!(tetromino.rotate(&mut board, clockwise).is_none());
+        assert_eq!(
+            sorted_body(&tetromino),
+            [(0, 16), (0, 17), (0, 18), (0, 19)]
+        );
+    }
+}
+
+#[test]
+fn t_kicks_off_the_right_wall() {
+    let mut board = Board::new(10, 20);
+    let mut tetromino = spawned('T', &mut board);
+    for _ in 0..5 {
+        assert!(tetromino.shift(&mut board, Direction::Down).is_ok());
+    }
+    // Pointing left, with its flat side against the wall
+    assert!(tetromino.rotate(&mut board, false).is_some());
+    while tetromino.shift(&mut board, Direction::Right).is_ok() {}
+    assert!(tetromino.body().iter().any(|(x, _)| *x == 9));
+    let y = tetromino.body()[0].1;
+    // Turning back flat would poke out of the board, the second test moves it left
+    assert!(tetromino.rotate(&mut board, true).is_some());
+    assert_eq!(
+        sorted_body(&tetromino),
+        [(7, y), (8, y - 1), (8, y), (9, y)]
+    );
+}
+
+#[test]
+fn kicks_past_the_top_left_corner_are_skipped() {
+    let mut board = Board::new(10, 20);
+    let mut tetromino = spawned('I', &mut board);
+    assert!(tetromino.rotate(&mut board, true).is_some());
+    while tetromino.shift(&mut board, Direction::Left).is_ok() {}
+    while tetromino.shift(&mut board, Direction::Up).is_ok() {}
+    for clockwise in [true, false, false, true] {
+        tetromino.rotate(&mut board, clockwise);
+        assert_eq!(active_cells(&board), 4);
+    }
+}
+
+#[test]
+fn a_traced_rotation_reports_every_kick_it_tried() {
+    let mut board = Board::new(10, 20);
+    let mut tetromino = spawned('T', &mut board);
+    for _ in 0..5 {
+        assert!(tetromino.shift(&mut board, Direction::Down).is_ok());
+    }
+    assert!(tetromino.rotate(&mut board, false).is_some());
+    while tetromino.shift(&mut board, Direction::Right).is_ok() {}
+    let attempts = tetromino.rotate_traced(&mut board, true);
+    assert_eq!(attempts.len(), 2);
+    assert_eq!(attempts[0].index, 0);
+    assert_eq!(attempts[0].failure, Some(KickFailure::OutOfBounds));
+    assert!(attempts[0].cells.iter().any(|(x, _)| *x == 10));
+    assert_eq!((attempts[1].index, attempts[1].failure), (1, None));
+    let mut taken: Vec<_> = attempts[1]
+        .cells
+        .iter()
+        .map(|&(x, y)| (x as usize, y as usize))
+        .collect();
+    taken.sort_unstable();
+    assert_eq!(taken, sorted_body(&tetromino));
+}
+
+#[test]
+fn a_traced_rotation_that_fails_reports_all_the_kicks() {
+    use KickFailure::{Blocked, OutOfBounds};
+    let mut board = Board::new(10, 20);
+    board.load_text(&[".GGGGGGGGG"; 4].join("\n")).unwrap();
+    let mut tetromino = spawned('I', &mut board);
+    assert!(tetromino.rotate(&mut board, true).is_some());
+    while tetromino.shift(&mut board, Direction::Left).is_ok() {}
+    tetromino.hard_drop(&mut board);
+    let attempts = tetromino.rotate_traced(&mut board, true);
+    let failures: Vec<_> = attempts.iter().map(|attempt| attempt.failure).collect();
+    assert_eq!(
+        attempts
+            .iter()
+            .map(|attempt| attempt.index)
+            .collect::<Vec<_>>(),
+        [0, 1, 2, 3, 4]
+    );
+    // Lying flat it pokes out past the wall or the floor, or into the stack
+    assert_eq!(
+        failures,
+        [
+            Some(OutOfBounds),
+            Some(OutOfBounds),
+            Some(Blocked),
+            Some(OutOfBounds),
+            Some(Blocked)
+        ]
+    );
+    assert_eq!(
+        sorted_body(&tetromino),
+        [(0, 16), (0, 17), (0, 18), (0, 19)]
+    );
+}
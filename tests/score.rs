@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+use tetris::score::{GravityCurve, Score};
+
+fn at_level(level: u32) -> Score {
+    Score {
+        level,
+        ..Score::new()
+    }
+}
+
+#[test]
+fn gravity_speeds_up_with_the_level() {
+    assert_eq!(at_level(1).gravity_interval(), Duration::from_secs(1));
+    assert!(at_level(2).gravity_interval() < at_level(1).gravity_interval());
+    assert!(at_level(15).gravity_interval() < at_level(10).gravity_interval());
+}
+
+#[test]
+fn gravity_stops_speeding_up_past_the_last_speed_level() {
+    let fastest = at_level(Score::MAX_SPEED_LEVEL).gravity_interval();
+    assert!(fastest > Duration::ZERO);
+    // 1150 lines into a marathon the formula itself would go negative
+    for level in [Score::MAX_SPEED_LEVEL + 1, 116, 1000, u32::MAX] {
+        assert_eq!(at_level(level).gravity_interval(), fastest);
+    }
+}
+
+#[test]
+fn a_default_score_falls_like_level_one() {
+    assert_eq!(
+        Score::default().gravity_interval(),
+        at_level(1).gravity_interval()
+    );
+}
+
+fn with_curve(gravity: GravityCurve, lines: u32) -> Score {
+    Score {
+        lines,
+        level: 1 + lines / 10,
+        gravity,
+        ..Score::new()
+    }
+}
+
+#[test]
+fn every_curve_keeps_to_the_table_at_the_level_ups() {
+    for level in 1..=Score::MAX_SPEED_LEVEL + 2 {
+        let lines = (level - 1) * 10;
+        let stepped = with_curve(GravityCurve::Stepped, lines).gravity_interval();
+        assert_eq!(stepped, at_level(level).gravity_interval());
+        for curve in [GravityCurve::Linear, GravityCurve::Exponential] {
+            assert_eq!(with_curve(curve, lines).gravity_interval(), stepped);
+        }
+    }
+}
+
+#[test]
+fn smooth_curves_speed_up_with_every_line() {
+    for curve in [GravityCurve::Linear, GravityCurve::Exponential] {
+        let intervals: Vec<Duration> = (10..=20)
+            .map(|lines| with_curve(curve, lines).gravity_interval())
+            .collect();
+        assert!(intervals.windows(2).all(|pair| pair[1] < pair[0]));
+    }
+    // Stepped, it waits for the level up
+    assert_eq!(
+        with_curve(GravityCurve::Stepped, 19).gravity_interval(),
+        with_curve(GravityCurve::Stepped, 10).gravity_interval()
+    );
+}
+
+#[test]
+fn halfway_a_linear_curve_is_halfway_in_time() {
+    let half = with_curve(GravityCurve::Linear, 5).gravity_interval();
+    let sum = at_level(1).gravity_interval() + at_level(2).gravity_interval();
+    assert!(half.abs_diff(sum / 2) < Duration::from_micros(1));
+    let exponential = with_curve(GravityCurve::Exponential, 5).gravity_interval();
+    assert!(exponential < half);
+}
+
+#[test]
+fn a_fixed_level_keeps_its_speed() {
+    let score = Score {
+        fixed_level: true,
+        ..with_curve(GravityCurve::Linear, 7)
+    };
+    assert_eq!(score.gravity_interval(), at_level(1).gravity_interval());
+}
@@ -0,0 +1,104 @@
+use tetris::{
+    game::{SequenceEnd, TetrominosBag},
+    state::{Game, Input, Outcome},
+};
+
+fn deal(bag: &mut TetrominosBag, count: usize) -> String {
+    (0..count)
+        .map(|_| bag.get().map_or('-', |tetromino| tetromino.shape()))
+        .collect()
+}
+
+fn scripted(shapes: &str, end: SequenceEnd) -> Game {
+    Game::with_bag(10, 20, TetrominosBag::scripted(shapes, end).unwrap())
+}
+
+#[test]
+fn only_tetrominos_make_a_sequence() {
+    assert!(TetrominosBag::scripted("", SequenceEnd::Loop).is_none());
+    assert!(TetrominosBag::scripted("TSX", SequenceEnd::Loop).is_none());
+    assert!(TetrominosBag::scripted("G", SequenceEnd::Loop).is_none());
+}
+
+#[test]
+fn a_looping_sequence_starts_over_and_is_never_shuffled() {
+    let mut bag = TetrominosBag::scripted("SZT", SequenceEnd::Loop).unwrap();
+    bag.shuffle();
+    assert_eq!(deal(&mut bag, 3), "SZT");
+    assert_eq!(bag.peek().unwrap().shape(), 'S');
+    assert_eq!(deal(&mut bag, 5), "SZTSZ");
+}
+
+#[test]
+fn a_sequence_falling_back_to_bags_goes_on_with_all_seven() {
+    let mut bag = TetrominosBag::scripted("II", SequenceEnd::Bag).unwrap();
+    bag.reseed(1);
+    bag.shuffle();
+    assert_eq!(deal(&mut bag, 2), "II");
+    // The preview already sees into the first bag
+    let next = bag.peek().unwrap().shape();
+    for _ in 0..3 {
+        let mut shapes: Vec<char> = deal(&mut bag, 7).chars().collect();
+        assert!(!shapes.contains(&'-'));
+        if next != '-' {
+            assert!(shapes.contains(&next));
+        }
+        shapes.sort_unstable();
+        assert_eq!(shapes.into_iter().collect::<String>(), "IJLOSTZ");
+    }
+}
+
+#[test]
+fn a_sequence_that_ends_runs_out() {
+    let mut bag = TetrominosBag::scripted("OI", SequenceEnd::End).unwrap();
+    assert_eq!(bag.peek().unwrap().shape(), 'O');
+    assert_eq!(deal(&mut bag, 4), "OI--");
+    assert!(bag.peek().is_none());
+}
+
+#[test]
+fn the_game_is_complete_once_the_last_tetromino_locks() {
+    let mut game = scripted("OI", SequenceEnd::End);
+    assert_eq!(game.next().unwrap().shape(), 'I');
+    game.apply(Input::HardDrop);
+    assert_eq!(game.tetromino().shape(), 'I');
+    assert!(game.next().is_none());
+    assert_eq!(game.apply(Input::HardDrop), Outcome::GameOver);
+    assert!(game.is_over());
+    assert!(game.is_completed());
+}
+
+#[test]
+fn the_last_tetromino_can_only_be_swapped_with_a_held_one() {
+    let mut game = scripted("OI", SequenceEnd::End);
+    // Nothing in hold and nothing left to deal
+    game.apply(Input::HardDrop);
+    assert_eq!(game.apply(Input::Hold), Outcome::Blocked);
+    assert_eq!(game.tetromino().shape(), 'I');
+    assert!(game.held().is_none());
+
+    let mut game = scripted("OIT", SequenceEnd::End);
+    assert_eq!(game.apply(Input::Hold), Outcome::Moved);
+    game.apply(Input::HardDrop);
+    // The T is the last out of the sequence, the O comes back out of hold for it
+    assert_eq!(game.tetromino().shape(), 'T');
+    assert_eq!(game.apply(Input::Hold), Outcome::Moved);
+    assert_eq!(game.tetromino().shape(), 'O');
+    game.apply(Input::HardDrop);
+    // Then the T out of hold, and that's the end of it
+    assert_eq!(game.tetromino().shape(), 'T');
+    assert!(game.held().is_none());
+    assert_eq!(game.apply(Input::HardDrop), Outcome::GameOver);
+    assert!(game.is_completed());
+}
+
+#[test]
+fn a_looping_sequence_keeps_hold_going() {
+    let mut game = scripted("S", SequenceEnd::Loop);
+    for _ in 0..5 {
+        assert_eq!(game.apply(Input::Hold), Outcome::Moved);
+        game.apply(Input::HardDrop);
+        assert_eq!(game.tetromino().shape(), 'S');
+    }
+    assert!(!game.is_over());
+}
@@ -0,0 +1,41 @@
+use tetris::game::{template, Board, Playcell};
+
+// A stack reaching up into the two rows tetrominos spawn in
+fn topped_out() -> Board {
+    let mut board = Board::new(10, 20);
+    let mut text = vec![".........."; 20];
+    text[0] = "....GG....";
+    text[1] = "...GGGG...";
+    board.load_text(&text.join("\n")).unwrap();
+    board
+}
+
+#[test]
+fn a_blocked_spawn_leaves_the_board_untouched() {
+    for shape in ['O', 'I', 'J', 'L', 'S', 'Z', 'T'] {
+        let mut board = topped_out();
+        // Active cells left over from another tetromino must survive too
+        for x in [0, 9] {
+            board.tiles[0][x] = Some(Playcell::new(true, 'I'));
+        }
+        let before = board.clone();
+        let mut tetromino = template(shape).unwrap();
+        let body = tetromino.body();
+        assert!(tetromino.spawn(&mut board).is_err(), "{shape} spawned");
+        assert_eq!(board, before, "{shape} changed the board");
+        assert_eq!(tetromino.body(), body, "{shape} moved");
+    }
+}
+
+#[test]
+fn a_free_spawn_writes_the_tetromino_as_active_cells() {
+    let mut board = Board::new(10, 20);
+    let mut tetromino = template('T').unwrap();
+    assert!(tetromino.spawn(&mut board).is_ok());
+    for (x, y) in tetromino.body() {
+        assert_eq!(
+            board.tiles[y][x].map(|cell| (cell.is_active, cell.shape)),
+            Some((true, 'T'))
+        );
+    }
+}
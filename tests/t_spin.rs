@@ -0,0 +1,231 @@
+use tetris::{
+    game::{template, Board, Direction, SequenceEnd, TSpin, Tetromino, TetrominosBag},
+    score::Score,
+    state::{Game, GameEvent, GameMode, Input, Outcome, Ruleset},
+};
+
+// A T turned clockwise `turns` times on an empty board, then moved so that its center
+// is at `center`. The stack is loaded afterwards, only its shape matters to t_spin().
+fn t_at(turns: usize, center: (usize, usize), stack: &str) -> (Tetromino, Board) {
+    let mut board = Board::new(10, 20);
+    let mut tetromino = template('T').unwrap();
+    assert!(tetromino.spawn(&mut board).is_ok());
+    while tetromino.body()[0].1 < 2 {
+        assert!(tetromino.shift(&mut board, Direction::Down).is_ok());
+    }
+    for _ in 0..turns {
+        assert_eq!(tetromino.rotate(&mut board, true), Some(0));
+    }
+    move_center(&mut tetromino, &mut board, center);
+    board.load_text(stack).unwrap();
+    (tetromino, board)
+}
+
+fn move_center(tetromino: &mut Tetromino, board: &mut Board, (x, y): (usize, usize)) {
+    while tetromino.body()[0].0 < x {
+        assert!(tetromino.shift(board, Direction::Right).is_ok());
+    }
+    while tetromino.body()[0].0 > x {
+        assert!(tetromino.shift(board, Direction::Left).is_ok());
+    }
+    while tetromino.body()[0].1 < y {
+        assert!(tetromino.shift(board, Direction::Down).is_ok());
+    }
+}
+
+#[test]
+fn three_corners_with_both_front_ones_is_a_full_t_spin() {
+    // Pointing down into the slot, the overhang is a back corner
+    let (tetromino, board) = t_at(2, (3, 18), "GGG.......\nGG...GGGGG\nGGG.GGGGGG");
+    assert_eq!(tetromino.t_spin(&board, 0), Some(TSpin::Full));
+}
+
+#[test]
+fn one_front_corner_is_a_mini() {
+    // Pointing up against the left wall, the floor takes both back corners
+    let (tetromino, board) = t_at(0, (1, 19), "G.........\n...GGGGGGG");
+    assert_eq!(tetromino.t_spin(&board, 0), Some(TSpin::Mini));
+}
+
+#[test]
+fn the_last_kick_makes_a_mini_a_full_t_spin() {
+    let (tetromino, board) = t_at(0, (1, 19), "G.........\n...GGGGGGG");
+    assert_eq!(tetromino.t_spin(&board, 4), Some(TSpin::Full));
+}
+
+#[test]
+fn walls_count_as_taken_corners() {
+    // Pointing right against the left wall, only one corner of the stack is needed
+    let (tetromino, board) = t_at(1, (0, 18), ".G........\n..........\n..........");
+    assert_eq!(tetromino.t_spin(&board, 0), Some(TSpin::Mini));
+}
+
+#[test]
+fn two_corners_is_no_t_spin() {
+    // Flat on the floor in the open
+    let (tetromino, board) = t_at(0, (4, 19), "GG........");
+    assert_eq!(tetromino.t_spin(&board, 0), None);
+}
+
+#[test]
+fn only_a_t_can_spin() {
+    let mut board = Board::new(10, 20);
+    let mut tetromino = template('S').unwrap();
+    assert!(tetromino.spawn(&mut board).is_ok());
+    while tetromino.shift(&mut board, Direction::Down).is_ok() {}
+    board.load_text("GGGGGGGGGG\nGGGGGGGGGG").unwrap();
+    assert_eq!(tetromino.t_spin(&board, 0), None);
+}
+
+#[test]
+fn rotating_into_a_t_spin_double_slot() {
+    let mut board = Board::new(10, 20);
+    board
+        .load_text("GGG.......\nGG...GGGGG\nGGG.GGGGGG")
+        .unwrap();
+    let mut tetromino = template('T').unwrap();
+    assert!(tetromino.spawn(&mut board).is_ok());
+    tetromino.shift(&mut board, Direction::Down).ok();
+    assert_eq!(tetromino.rotate(&mut board, true), Some(0));
+    move_center(&mut tetromino, &mut board, (3, 18));
+    assert!(tetromino.shift(&mut board, Direction::Down).is_err());
+    let kick = tetromino.rotate(&mut board, true).unwrap();
+    let mut body = tetromino.body().to_vec();
+    body.sort_unstable();
+    assert_eq!(body, [(2, 18), (3, 18), (3, 19), (4, 18)]);
+    assert_eq!(tetromino.t_spin(&board, kick), Some(TSpin::Full));
+}
+
+#[test]
+fn t_spin_clears_score_higher() {
+    let mut score = Score::new();
+    assert_eq!(score.line_clear(2, Some(TSpin::Full)).points, 1200);
+    assert_eq!(score.line_clear(1, Some(TSpin::Mini)).points, 200 * 3 / 2);
+    assert_eq!(score.line_clear(0, Some(TSpin::Full)).points, 400);
+}
+
+#[test]
+fn back_to_back_needs_difficult_clears_in_a_row() {
+    let mut score = Score::new();
+    let first = score.line_clear(4, None);
+    assert!(!first.back_to_back);
+    assert_eq!(first.points, 800);
+    // A T-spin without lines keeps the streak going
+    score.line_clear(0, Some(TSpin::Mini));
+    let second = score.line_clear(4, None);
+    assert!(second.back_to_back);
+    assert_eq!(second.points, 1200);
+    score.line_clear(1, None);
+    let broken = score.line_clear(2, Some(TSpin::Full));
+    assert!(!broken.back_to_back);
+    assert_eq!(broken.points, 1200);
+}
+
+#[test]
+fn any_other_clear_breaks_the_back_to_back_chain() {
+    for lines in 1..=3 {
+        let mut score = Score::new();
+        score.line_clear(2, Some(TSpin::Full));
+        assert!(score.back_to_back);
+        // Locking without a clear leaves the chain alone
+        score.line_clear(0, None);
+        assert!(score.back_to_back);
+        let breaking = score.line_clear(lines, None);
+        assert!(!breaking.back_to_back, "{lines} lines");
+        assert!(!breaking.name().unwrap().starts_with("B2B"));
+        assert!(!score.back_to_back, "{lines} lines");
+        // The next difficult clear starts a new chain, without the bonus
+        let tetris = score.line_clear(4, None);
+        assert!(!tetris.back_to_back, "{lines} lines");
+        assert_eq!(tetris.points, 800);
+        assert!(score.back_to_back);
+    }
+}
+
+#[test]
+fn clear_names() {
+    let mut score = Score::new();
+    assert_eq!(score.line_clear(0, None).name(), None);
+    assert_eq!(
+        score.line_clear(0, Some(TSpin::Full)).name().as_deref(),
+        Some("T-SPIN")
+    );
+    assert_eq!(score.line_clear(4, None).name().as_deref(), Some("TETRIS"));
+    assert_eq!(
+        score.line_clear(1, Some(TSpin::Mini)).name().as_deref(),
+        Some("B2B MINI T-SPIN SINGLE")
+    );
+    assert_eq!(score.line_clear(3, None).name().as_deref(), Some("TRIPLE"));
+}
+
+// The T-spin double slot without its rows ever filling, the last column stays open
+const NO_LINES_SLOT: &str = "GGG.......\nGG...GGGG.\nGGG.GGGG..";
+
+// Spins a T into NO_LINES_SLOT through the game's own inputs: pointing right it goes
+// down beside the overhang, and one more turn points it down into the slot. What it's
+// worth is what the hard drop that locks it scores, the soft drop on the way gave a
+// point a row already.
+fn spin_into_slot(game: &mut Game) -> (Outcome, u32) {
+    game.load_stack(NO_LINES_SLOT).unwrap();
+    assert_eq!(game.apply(Input::RotateCw), Outcome::Moved);
+    while game.tetromino().body()[0].0 > 3 {
+        assert_eq!(game.apply(Input::MoveLeft), Outcome::Moved);
+    }
+    while game.tetromino().body()[0].1 < 18 {
+        assert_eq!(game.apply(Input::SoftDrop), Outcome::Moved);
+    }
+    assert_eq!(game.apply(Input::RotateCw), Outcome::Moved);
+    let mut body = game.tetromino().body().to_vec();
+    body.sort_unstable();
+    assert_eq!(body, [(2, 18), (3, 18), (3, 19), (4, 18)]);
+    game.take_events();
+    let before = game.score().points;
+    let outcome = game.apply(Input::HardDrop);
+    (outcome, game.score().points - before)
+}
+
+fn t_game(rules: Ruleset) -> Game {
+    let bag = TetrominosBag::scripted("TI", SequenceEnd::Loop).unwrap();
+    Game::with_rules(10, 20, bag, rules)
+}
+
+#[test]
+fn a_t_spun_into_a_slot_without_lines_scores_and_keeps_the_chain() {
+    let mut game = t_game(GameMode::Marathon.ruleset());
+    assert_eq!(
+        spin_into_slot(&mut game),
+        (Outcome::Locked { lines: 0 }, 400)
+    );
+    let score = game.score();
+    assert_eq!(score.lines, 0);
+    assert!(!score.back_to_back);
+    let clear = game.last_clear().unwrap();
+    assert_eq!(clear.spin, Some(TSpin::Full));
+    assert_eq!(clear.name().as_deref(), Some("T-SPIN"));
+    let events = game.take_events();
+    assert!(events.contains(&GameEvent::SpinWithoutLines { clear }));
+    assert!(!events
+        .iter()
+        .any(|event| matches!(event, GameEvent::LinesCleared { .. })));
+}
+
+#[test]
+fn with_spin_b2b_a_spin_without_lines_starts_the_chain() {
+    let rules = Ruleset {
+        spin_b2b: true,
+        ..GameMode::Marathon.ruleset()
+    };
+    let mut game = t_game(rules);
+    assert_eq!(
+        spin_into_slot(&mut game),
+        (Outcome::Locked { lines: 0 }, 400)
+    );
+    assert!(game.score().back_to_back);
+    // A mini starts it as well, the tetris after it gets the bonus
+    let mut score = Score {
+        spin_b2b: true,
+        ..Score::new()
+    };
+    assert!(!score.line_clear(0, Some(TSpin::Mini)).back_to_back);
+    assert!(score.line_clear(4, None).back_to_back);
+}